@@ -0,0 +1,144 @@
+use crate::{cache::TtlCache, BoxError, BoxFuture, Denied, Gate, GateContext};
+use core::future::Future;
+use futures::{future, FutureExt, TryFutureExt};
+use http::Extensions;
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Status of account token was issued for, as reported by [`UserStatusStore`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AccountStatus {
+    Active,
+    Disabled,
+    Locked,
+    Deleted,
+}
+
+/// Source of truth for account status, see [`CheckAccount`].
+pub trait UserStatusStore: Send + Sync + 'static {
+    /// Returns status of account identified by token `sub`
+    fn status(&self, sub: &str) -> BoxFuture<AccountStatus, BoxError>;
+}
+
+impl<F, Fut> UserStatusStore for F
+where
+    F: Fn(&str) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<AccountStatus, BoxError>> + Send + 'static,
+{
+    fn status(&self, sub: &str) -> BoxFuture<AccountStatus, BoxError> {
+        Box::pin(self(sub))
+    }
+}
+
+/// [`Gate`] denying requests of disabled, locked or deleted accounts, even though their
+/// tokens are still valid.
+///
+/// Statuses are cached for configured time (thirty seconds by default), which bounds how long
+/// it takes for account changes to take effect. Requests of accounts which aren't
+/// [active][AccountStatus::Active] are denied with [`Denied::Account`], which is meant to
+/// be rendered as `403 Forbidden`, as are tokens without `sub`.
+///
+/// ```rust
+/// # use tower_jwt::AccountStatus;
+/// # async fn lookup(sub: &str) -> Result<AccountStatus, tower_jwt::BoxError> { todo!() }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{CheckAccount, Layer};
+///
+/// let layer = Layer::new(decoder).gate(CheckAccount::new(|sub: &str| {
+///     let sub = sub.to_owned();
+///     async move { lookup(&sub).await }
+/// }));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CheckAccount {
+    store: Arc<dyn UserStatusStore>,
+    cache: TtlCache<AccountStatus>,
+}
+
+impl CheckAccount {
+    pub fn new<S: UserStatusStore>(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            cache: TtlCache::new(Duration::from_secs(30)),
+        }
+    }
+
+    /// How long statuses are cached for, zero disables caching
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.cache = TtlCache::new(ttl);
+        self
+    }
+}
+
+fn verdict(status: AccountStatus) -> Result<Extensions, Denied> {
+    match status {
+        AccountStatus::Active => Ok(Extensions::new()),
+        status => Err(Denied::Account(Some(status))),
+    }
+}
+
+impl Gate for CheckAccount {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let sub = match cx.payload().and_then(|payload| payload.str("sub")) {
+            Some(sub) => sub.to_owned(),
+            None => return Box::pin(future::ready(Err(Denied::Account(None)))),
+        };
+        if let Some(status) = self.cache.get(&sub) {
+            return Box::pin(future::ready(verdict(status)));
+        }
+
+        let cache = self.cache.clone();
+        self.store
+            .status(&sub)
+            .map_err(Denied::Other)
+            .map(move |status| {
+                let status = status?;
+                cache.insert(sub, status);
+                verdict(status)
+            })
+            .boxed()
+    }
+}
+
+impl fmt::Debug for CheckAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CheckAccount")
+            .field("ttl", &self.cache.ttl())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AccountStatus, CheckAccount};
+    use crate::{util, BoxError, Denied, Gate, GateContext};
+    use http::Request;
+
+    #[tokio::test]
+    async fn check_account() {
+        let gate = CheckAccount::new(|sub: &str| {
+            let status = match sub {
+                "active" => AccountStatus::Active,
+                _ => AccountStatus::Locked,
+            };
+            async move { Ok::<_, BoxError>(status) }
+        });
+        let (parts, _) = Request::new(()).into_parts();
+
+        let token = util::token(&serde_json::json!({ "sub": "active" }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+
+        let token = util::token(&serde_json::json!({ "sub": "locked" }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(
+            outcome,
+            Err(Denied::Account(Some(AccountStatus::Locked)))
+        ));
+
+        let token = util::token(&serde_json::json!({ "iss": "issuer" }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Account(None))));
+    }
+}
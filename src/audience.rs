@@ -0,0 +1,267 @@
+use crate::{BoxFuture, Denied, Gate, GateContext};
+use futures::future;
+use http::Extensions;
+use std::{
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::SystemTime,
+};
+
+/// Audience token was accepted for, set on request extensions by [`MatchAudience`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MatchedAudience(String);
+
+impl MatchedAudience {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+/// [`Gate`] for gateways accepting multiple audiences.
+///
+/// Records first of configured audiences present in token `aud` claim as [`MatchedAudience`],
+/// so downstream routing can branch on it. Requests with tokens matching none of the audiences
+/// are denied.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, MatchAudience};
+///
+/// let layer = Layer::new(decoder).gate(MatchAudience::new(["api://orders", "api://billing"]));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MatchAudience {
+    audiences: Vec<String>,
+}
+
+impl MatchAudience {
+    pub fn new<I, A>(audiences: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<String>,
+    {
+        Self {
+            audiences: audiences.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    fn matched(&self, cx: &GateContext<'_>) -> Option<MatchedAudience> {
+        let accepted = cx.payload()?.audiences();
+        self.audiences
+            .iter()
+            .find(|audience| accepted.contains(&audience.as_str()))
+            .cloned()
+            .map(MatchedAudience)
+    }
+}
+
+impl Gate for MatchAudience {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let outcome = match self.matched(cx) {
+            Some(matched) => {
+                let mut extensions = Extensions::new();
+                extensions.insert(matched);
+                Ok(extensions)
+            }
+            None => Err(Denied::Audience),
+        };
+        Box::pin(future::ready(outcome))
+    }
+}
+
+/// When audience token was accepted for stops being accepted, set on request extensions
+/// by [`VersionedAudience`] next to [`MatchedAudience`], e.g. to advertise it in `Sunset` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudienceSunset(pub SystemTime);
+
+#[derive(Debug, Clone)]
+struct Version {
+    audience: String,
+    sunset: Option<SystemTime>,
+}
+
+/// Handle to audiences accepted by [`VersionedAudience`], changes apply to subsequent requests
+#[derive(Debug, Clone, Default)]
+pub struct AudienceHandle(Arc<RwLock<Vec<Version>>>);
+
+impl AudienceHandle {
+    fn read(&self) -> RwLockReadGuard<'_, Vec<Version>> {
+        self.0
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Vec<Version>> {
+        self.0
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn set(&self, audience: String, sunset: Option<SystemTime>) {
+        let mut versions = self.write();
+        match versions
+            .iter_mut()
+            .find(|version| version.audience == audience)
+        {
+            Some(version) => version.sunset = sunset,
+            None => versions.push(Version { audience, sunset }),
+        }
+    }
+
+    /// Accept `audience` with no end date
+    pub fn accept(&self, audience: impl Into<String>) {
+        self.set(audience.into(), None);
+    }
+
+    /// Accept `audience` until `at`
+    pub fn sunset(&self, audience: impl Into<String>, at: SystemTime) {
+        self.set(audience.into(), Some(at));
+    }
+
+    /// Stop accepting `audience` right away
+    pub fn remove(&self, audience: &str) {
+        self.write().retain(|version| version.audience != audience);
+    }
+
+    /// Currently configured audiences, along with their sunsets
+    pub fn audiences(&self) -> Vec<(String, Option<SystemTime>)> {
+        self.read()
+            .iter()
+            .map(|version| (version.audience.clone(), version.sunset))
+            .collect()
+    }
+}
+
+/// [`Gate`] accepting several versions of API audience during migration windows.
+///
+/// Works like [`MatchAudience`], except that audiences may have a sunset, past which tokens
+/// issued only for them are denied, and that audiences can be changed at runtime through
+/// [`AudienceHandle`]. Tokens accepted for an audience with a sunset get [`AudienceSunset`]
+/// on request extensions.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D, end_of_v1: std::time::SystemTime) {
+/// use tower_jwt::{Layer, VersionedAudience};
+///
+/// let audiences = VersionedAudience::new()
+///     .accept("api://v2")
+///     .sunset("api://v1", end_of_v1);
+/// let handle = audiences.handle();
+/// let layer = Layer::new(decoder).gate(audiences);
+///
+/// // later on, e.g. when config changes
+/// handle.remove("api://v1");
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VersionedAudience {
+    handle: AudienceHandle,
+}
+
+impl VersionedAudience {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept `audience` with no end date
+    pub fn accept(self, audience: impl Into<String>) -> Self {
+        self.handle.accept(audience);
+        self
+    }
+
+    /// Accept `audience` until `at`
+    pub fn sunset(self, audience: impl Into<String>, at: SystemTime) -> Self {
+        self.handle.sunset(audience, at);
+        self
+    }
+
+    /// Handle to change accepted audiences at runtime
+    pub fn handle(&self) -> AudienceHandle {
+        self.handle.clone()
+    }
+
+    fn matched(&self, cx: &GateContext<'_>, now: SystemTime) -> Option<Extensions> {
+        let accepted = cx.payload()?.audiences();
+        let versions = self.handle.read();
+        let version = versions.iter().find(|version| {
+            accepted.contains(&version.audience.as_str())
+                && version.sunset.is_none_or(|sunset| now < sunset)
+        })?;
+        let mut extensions = Extensions::new();
+        extensions.insert(MatchedAudience(version.audience.clone()));
+        if let Some(sunset) = version.sunset {
+            extensions.insert(AudienceSunset(sunset));
+        }
+        Some(extensions)
+    }
+}
+
+impl Gate for VersionedAudience {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let outcome = self.matched(cx, SystemTime::now()).ok_or(Denied::Audience);
+        Box::pin(future::ready(outcome))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AudienceSunset, MatchAudience, MatchedAudience, VersionedAudience};
+    use crate::{util, Denied, Gate, GateContext};
+    use http::Request;
+    use std::time::{Duration, SystemTime};
+
+    #[tokio::test]
+    async fn match_audience() {
+        let gate = MatchAudience::new(["api://v2", "api://v1"]);
+        let (parts, _) = Request::new(()).into_parts();
+
+        let token = util::token(&serde_json::json!({ "aud": ["api://v1", "api://v2"] }));
+        let extensions = gate
+            .check(&GateContext::new(&parts, Some(&token)))
+            .await
+            .expect("Token audience is accepted");
+        let matched = extensions
+            .get::<MatchedAudience>()
+            .map(MatchedAudience::as_str);
+        assert_eq!(matched, Some("api://v2"));
+
+        let token = util::token(&serde_json::json!({ "aud": "api://v3" }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Audience)));
+    }
+
+    #[tokio::test]
+    async fn versioned_audience() {
+        let later = SystemTime::now() + Duration::from_secs(3600);
+        let gate = VersionedAudience::new()
+            .accept("api://v2")
+            .sunset("api://v1", later);
+        let handle = gate.handle();
+        let (parts, _) = Request::new(()).into_parts();
+        let token = util::token(&serde_json::json!({ "aud": "api://v1" }));
+
+        let extensions = gate
+            .check(&GateContext::new(&parts, Some(&token)))
+            .await
+            .expect("Old audience is accepted until sunset");
+        assert_eq!(
+            extensions.get::<AudienceSunset>(),
+            Some(&AudienceSunset(later))
+        );
+
+        handle.sunset("api://v1", SystemTime::now() - Duration::from_secs(1));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Audience)));
+
+        let token = util::token(&serde_json::json!({ "aud": "api://v2" }));
+        let extensions = gate
+            .check(&GateContext::new(&parts, Some(&token)))
+            .await
+            .expect("New audience is accepted");
+        assert!(extensions.get::<AudienceSunset>().is_none());
+    }
+}
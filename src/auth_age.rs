@@ -0,0 +1,121 @@
+use crate::{path::under, BoxFuture, Denied, Gate, GateContext, StepUpChallenge};
+use futures::future;
+use http::Extensions;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// [`Gate`] requiring that user authenticated interactively within given time,
+/// as reported by `auth_time` claim.
+///
+/// Unlike token expiry, which is reset every time token is refreshed, `auth_time` tracks
+/// when user last presented credentials, so sensitive routes can demand a recent login.
+/// Routes can tighten maximum age by path prefix, longest prefix wins. Tokens without
+/// `auth_time`, or authenticated too long ago, are denied with [`Denied::StepUp`].
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use std::time::Duration;
+/// use tower_jwt::{AuthAge, Layer};
+///
+/// let layer = Layer::new(decoder).gate(
+///     AuthAge::new(Duration::from_secs(12 * 60 * 60))
+///         .route("/transfers", Duration::from_secs(5 * 60)),
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AuthAge {
+    max_age: Duration,
+    routes: Vec<(String, Duration)>,
+}
+
+impl AuthAge {
+    /// Maximum time since authentication on every route
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Maximum time since authentication on `prefix` and paths beneath it, whole segments
+    /// compared once paths are normalized, so `/transfers` covers `//Transfers/new` but not
+    /// `/transfersx`
+    pub fn route(mut self, prefix: impl Into<String>, max_age: Duration) -> Self {
+        self.routes.push((prefix.into(), max_age));
+        self
+    }
+
+    fn max_age(&self, path: &str) -> Duration {
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| under(prefix, path))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.max_age, |(_, max_age)| *max_age)
+    }
+
+    fn verify(&self, cx: &GateContext<'_>, now: u64) -> Result<(), StepUpChallenge> {
+        let max_age = self.max_age(cx.uri().path());
+        let auth_time = cx
+            .payload()
+            .and_then(|payload| payload.i64("auth_time"))
+            .and_then(|auth_time| u64::try_from(auth_time).ok());
+        match auth_time {
+            Some(auth_time) if now.saturating_sub(auth_time) <= max_age.as_secs() => Ok(()),
+            _ => Err(StepUpChallenge::new().with_max_age(max_age)),
+        }
+    }
+}
+
+impl Gate for AuthAge {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let outcome = self
+            .verify(cx, now)
+            .map(|_| Extensions::new())
+            .map_err(Denied::StepUp);
+        Box::pin(future::ready(outcome))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AuthAge;
+    use crate::{util, GateContext};
+    use http::Request;
+    use std::time::Duration;
+
+    #[test]
+    fn auth_age_per_route() {
+        let gate =
+            AuthAge::new(Duration::from_secs(3600)).route("/transfers", Duration::from_secs(300));
+        let now = 1_000_000;
+        let token = util::token(&serde_json::json!({ "auth_time": now - 600 }));
+
+        let (parts, _) = Request::get("/accounts").body(()).unwrap().into_parts();
+        assert!(gate
+            .verify(&GateContext::new(&parts, Some(&token)), now)
+            .is_ok());
+
+        for path in [
+            "/transfers",
+            "/transfers/new",
+            "//Transfers/new",
+            "/a/../transfers",
+        ] {
+            let (parts, _) = Request::get(path).body(()).unwrap().into_parts();
+            let challenge = gate
+                .verify(&GateContext::new(&parts, Some(&token)), now)
+                .expect_err("Accepted stale authentication");
+            assert_eq!(challenge.max_age(), Some(Duration::from_secs(300)));
+        }
+
+        let token = util::token(&serde_json::json!({ "sub": "user" }));
+        let (parts, _) = Request::get("/accounts").body(()).unwrap().into_parts();
+        assert!(gate
+            .verify(&GateContext::new(&parts, Some(&token)), now)
+            .is_err());
+    }
+}
@@ -0,0 +1,602 @@
+use crate::{
+    cache::{KeyCache, Lookup},
+    fingerprint::sha256_hex,
+    quota::civil_from_days,
+    BoxError, BoxFuture, DecodeFailure, DecodeStats, Decoder, KeySource, RefreshKeys,
+    SyncBoxFuture, TokenEndpoint, ValidationProfile,
+};
+use core::future::Future;
+use futures::TryFutureExt;
+use http::{header::AUTHORIZATION, Method, Request, Uri};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use ring::hmac;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    env, fmt,
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+/// IAM credentials requests are signed with
+#[derive(Clone)]
+pub struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+        }
+    }
+
+    /// Session token of temporary credentials, e.g. of assumed role
+    pub fn with_session_token(mut self, session_token: impl Into<String>) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+
+    /// Credentials from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`
+    /// environment variables, as set for Lambda functions. ECS tasks and EC2 instances get
+    /// theirs from metadata endpoints instead, see [`ProvideCredentials`].
+    pub fn from_env() -> Option<Self> {
+        let credentials = Self::new(
+            env::var("AWS_ACCESS_KEY_ID").ok()?,
+            env::var("AWS_SECRET_ACCESS_KEY").ok()?,
+        );
+        Some(match env::var("AWS_SESSION_TOKEN") {
+            Ok(session_token) => credentials.with_session_token(session_token),
+            Err(_) => credentials,
+        })
+    }
+
+    pub fn access_key_id(&self) -> &str {
+        &self.access_key_id
+    }
+}
+
+impl fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Supplies [`AwsCredentials`] for every key refresh, see [`AwsKeys`].
+///
+/// Static credentials supply themselves, any
+/// `Fn() -> impl Future<Output = Result<AwsCredentials, BoxError>>` supplies rotating ones.
+/// There is no built-in credential chain: instance metadata (IMDS), ECS container credentials,
+/// web identity and shared config profiles are all left to such closure, e.g. one wrapping
+/// provider of the AWS SDK.
+pub trait ProvideCredentials: Send + Sync + 'static {
+    fn credentials(&self) -> BoxFuture<AwsCredentials, BoxError>;
+}
+
+impl ProvideCredentials for AwsCredentials {
+    fn credentials(&self) -> BoxFuture<AwsCredentials, BoxError> {
+        Box::pin(futures::future::ready(Ok(self.clone())))
+    }
+}
+
+impl<F, Fut> ProvideCredentials for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<AwsCredentials, BoxError>> + Send + 'static,
+{
+    fn credentials(&self) -> BoxFuture<AwsCredentials, BoxError> {
+        Box::pin(self())
+    }
+}
+
+/// Where [`AwsKeys`] finds verification keys, PEM encoded public key or JWK set.
+///
+/// Requests go to public regional endpoint (`https://{service}.{region}.amazonaws.com`)
+/// unless [`endpoint`][Self::endpoint] is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwsSecret {
+    region: String,
+    service: &'static str,
+    target: &'static str,
+    body: String,
+    endpoint: Option<Uri>,
+}
+
+impl AwsSecret {
+    /// `SecretString` of Secrets Manager secret, named by its name or ARN
+    pub fn secrets_manager(region: impl Into<String>, secret_id: &str) -> Self {
+        Self {
+            region: region.into(),
+            service: "secretsmanager",
+            target: "secretsmanager.GetSecretValue",
+            body: json!({ "SecretId": secret_id }).to_string(),
+            endpoint: None,
+        }
+    }
+
+    /// Value of SSM Parameter Store parameter, decrypted if it's `SecureString`
+    pub fn parameter(region: impl Into<String>, name: &str) -> Self {
+        Self {
+            region: region.into(),
+            service: "ssm",
+            target: "AmazonSSM.GetParameter",
+            body: json!({ "Name": name, "WithDecryption": true }).to_string(),
+            endpoint: None,
+        }
+    }
+
+    /// Send requests to `endpoint` (e.g. VPC endpoint, FIPS or other partition endpoint, or
+    /// local emulator) instead, its scheme and authority are kept, path is ignored
+    pub fn endpoint(mut self, endpoint: Uri) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    /// Scheme and host (with port, if any) requests are sent to
+    fn origin(&self) -> (&str, String) {
+        let endpoint = self
+            .endpoint
+            .as_ref()
+            .and_then(|endpoint| Some((endpoint.scheme_str()?, endpoint.authority()?)));
+        match endpoint {
+            Some((scheme, authority)) => (scheme, authority.to_string()),
+            None => (
+                "https",
+                format!("{}.{}.amazonaws.com", self.service, self.region),
+            ),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AwsError {
+    #[error("Failed to decode token")]
+    Decode(#[source] jsonwebtoken::errors::Error),
+
+    #[error("Token is signed by unknown key")]
+    UnknownKey,
+
+    #[error("AWS responded with {code}")]
+    Aws {
+        code: String,
+        message: Option<String>,
+    },
+
+    #[error("Failed to parse AWS response")]
+    Response(#[source] serde_json::Error),
+
+    #[error("Failed to fetch keys")]
+    Fetch(#[source] BoxError),
+}
+
+/// `YYYYMMDDTHHMMSSZ` timestamp of `time`
+fn amz_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// SigV4 signing key for `date` (`YYYYMMDD`), `region` and `service`
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> hmac::Key {
+    let sign = |key: &[u8], data: &str| {
+        hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes())
+    };
+    let key = sign(format!("AWS4{}", secret).as_bytes(), date);
+    let key = sign(key.as_ref(), region);
+    let key = sign(key.as_ref(), service);
+    let key = sign(key.as_ref(), "aws4_request");
+    hmac::Key::new(hmac::HMAC_SHA256, key.as_ref())
+}
+
+/// Request fetching `secret`, signed with
+/// [SigV4](https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html)
+fn signed_request(
+    secret: &AwsSecret,
+    credentials: &AwsCredentials,
+    now: SystemTime,
+) -> Result<Request<String>, http::Error> {
+    let (scheme, host) = secret.origin();
+    let amz_date = amz_date(now);
+    let date = &amz_date[..8];
+    let mut headers = vec![
+        ("content-type", "application/x-amz-json-1.1"),
+        ("host", host.as_str()),
+        ("x-amz-date", amz_date.as_str()),
+    ];
+    if let Some(session_token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", session_token));
+    }
+    headers.push(("x-amz-target", secret.target));
+
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let canonical_request = format!(
+        "POST\n/\n\n{}\n{}\n{}",
+        canonical_headers,
+        signed_headers,
+        sha256_hex(secret.body.as_bytes())
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, secret.region, secret.service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+    let key = signing_key(
+        &credentials.secret_access_key,
+        date,
+        &secret.region,
+        secret.service,
+    );
+    let signature: String = hmac::sign(&key, string_to_sign.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri(format!("{}://{}/", scheme, host))
+        .header(
+            AUTHORIZATION,
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                credentials.access_key_id, scope, signed_headers, signature
+            ),
+        );
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    request.body(secret.body.clone())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SecretValue {
+    secret_string: Option<String>,
+    parameter: Option<Parameter>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Parameter {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct AwsErrorBody {
+    #[serde(rename = "__type")]
+    code: Option<String>,
+    #[serde(alias = "Message")]
+    message: Option<String>,
+}
+
+/// Key material found in secret value
+fn parse_keys(value: &str, algorithm: Algorithm) -> Result<HashMap<String, DecodingKey>, AwsError> {
+    if value.trim_start().starts_with('{') {
+        let keys: JwkSet = serde_json::from_str(value).map_err(AwsError::Response)?;
+        return Ok(keys
+            .keys
+            .iter()
+            .filter_map(|jwk| {
+                let kid = jwk.common.key_id.clone()?;
+                Some((kid, DecodingKey::from_jwk(jwk).ok()?))
+            })
+            .collect());
+    }
+
+    let pem = value.as_bytes();
+    let key = match algorithm {
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => Ok(DecodingKey::from_secret(pem)),
+        Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(pem),
+        Algorithm::EdDSA => DecodingKey::from_ed_pem(pem),
+        _ => DecodingKey::from_rsa_pem(pem),
+    };
+    // Single key verifies tokens regardless of their `kid`
+    Ok(HashMap::from([(
+        String::from(ANY_KID),
+        key.map_err(AwsError::Decode)?,
+    )]))
+}
+
+const ANY_KID: &str = "*";
+
+/// [`Decoder`] verifying tokens with keys kept in
+/// [AWS Secrets Manager](https://docs.aws.amazon.com/secretsmanager/) or
+/// [SSM Parameter Store](https://docs.aws.amazon.com/systems-manager/latest/userguide/systems-manager-parameter-store.html).
+///
+/// Secret holds either PEM encoded public key (or shared secret of HMAC algorithms), which
+/// verifies every token, or JWK set, whose keys verify tokens with matching `kid`. Keys are
+/// fetched with IAM credentials supplied by [`ProvideCredentials`], through
+/// [`TokenEndpoint`] transport, and refreshed every 5 minutes, or sooner when token is signed
+/// by unknown key. Such refreshes happen at most once per 30 seconds, as every fetch is
+/// a billed and throttled AWS API call.
+///
+/// ```rust
+/// # fn example<T: tower_jwt::TokenEndpoint>(transport: T) {
+/// use jsonwebtoken::{Algorithm, Validation};
+/// use serde_json::Value;
+/// use std::time::Duration;
+/// use tower_jwt::{AwsCredentials, AwsKeys, AwsSecret};
+///
+/// let secret = AwsSecret::secrets_manager("eu-west-1", "prod/api/jwt-keys");
+/// let credentials = AwsCredentials::from_env().expect("Credentials are set");
+/// let decoder = AwsKeys::<Value>::new(secret, credentials, transport, Validation::new(Algorithm::RS256))
+///     .refresh(Duration::from_secs(60));
+/// # }
+/// ```
+pub struct AwsKeys<C> {
+    secret: Arc<AwsSecret>,
+    credentials: Arc<dyn ProvideCredentials>,
+    transport: Arc<dyn TokenEndpoint>,
+    validation: Validation,
+    profile: Option<ValidationProfile>,
+    cache: KeyCache,
+    _claim: PhantomData<fn() -> C>,
+}
+
+impl<C> Clone for AwsKeys<C> {
+    fn clone(&self) -> Self {
+        Self {
+            secret: self.secret.clone(),
+            credentials: self.credentials.clone(),
+            transport: self.transport.clone(),
+            validation: self.validation.clone(),
+            profile: self.profile,
+            cache: self.cache.clone(),
+            _claim: PhantomData,
+        }
+    }
+}
+
+impl<C> AwsKeys<C> {
+    pub fn new<P: ProvideCredentials, T: TokenEndpoint>(
+        secret: AwsSecret,
+        credentials: P,
+        transport: T,
+        validation: Validation,
+    ) -> Self {
+        Self {
+            secret: Arc::new(secret),
+            credentials: Arc::new(credentials),
+            transport: Arc::new(transport),
+            validation,
+            profile: None,
+            cache: KeyCache::new(Duration::from_secs(300)),
+            _claim: PhantomData,
+        }
+    }
+
+    /// How often keys are fetched again
+    pub fn refresh(mut self, every: Duration) -> Self {
+        self.cache = KeyCache::new(every);
+        self
+    }
+
+    /// Apply [`ValidationProfile`] to decoder's validation
+    pub fn with_profile(mut self, profile: ValidationProfile) -> Self {
+        profile.apply(&mut self.validation);
+        self.profile = Some(profile);
+        self
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, DecodingKey>, AwsError> {
+        let credentials = self
+            .credentials
+            .credentials()
+            .await
+            .map_err(AwsError::Fetch)?;
+        let request = signed_request(&self.secret, &credentials, SystemTime::now())
+            .map_err(|err| AwsError::Fetch(err.into()))?;
+        let response = self
+            .transport
+            .send(request)
+            .await
+            .map_err(AwsError::Fetch)?;
+        if !response.status().is_success() {
+            let body: AwsErrorBody =
+                serde_json::from_slice(response.body()).map_err(AwsError::Response)?;
+            return Err(AwsError::Aws {
+                code: body.code.unwrap_or_else(|| response.status().to_string()),
+                message: body.message,
+            });
+        }
+
+        let value: SecretValue =
+            serde_json::from_slice(response.body()).map_err(AwsError::Response)?;
+        let value = match (value.secret_string, value.parameter) {
+            (Some(value), _) | (None, Some(Parameter { value })) => value,
+            (None, None) => return Err(AwsError::UnknownKey),
+        };
+        let algorithm = self
+            .validation
+            .algorithms
+            .first()
+            .copied()
+            .unwrap_or_default();
+        parse_keys(&value, algorithm)
+    }
+}
+
+impl<C: 'static> AwsKeys<C> {
+    /// Fetch of keys shared through [`KeyCache`]
+    fn fetch_keys(&self) -> BoxFuture<HashMap<String, DecodingKey>, BoxError> {
+        let this = self.clone();
+        Box::pin(async move { Ok(this.fetch().await?) })
+    }
+}
+
+impl<C> fmt::Debug for AwsKeys<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsKeys")
+            .field("secret", &self.secret)
+            .field("refresh", &self.cache.ttl())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: 'static> RefreshKeys for AwsKeys<C> {
+    fn refresh_keys(&self, token: &str) -> Option<BoxFuture<(), BoxError>> {
+        let kid = jsonwebtoken::decode_header(token).ok()?.kid;
+        let refresh = self.cache.refresh(kid.as_deref(), || self.fetch_keys())?;
+        Some(Box::pin(refresh.map_ok(|_| ()).map_err(BoxError::from)))
+    }
+}
+
+impl<C> Decoder for AwsKeys<C>
+where
+    C: DeserializeOwned + Send + 'static,
+{
+    type Error = AwsError;
+    type Claim = C;
+    type Future = SyncBoxFuture<Self::Claim, Self::Error>;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        tracing::trace!("AwsKeys::entered");
+        let this = self.clone();
+        let token = token.to_owned();
+        let stats = DecodeStats::current();
+        SyncBoxFuture::new(Box::pin(async move {
+            let header = jsonwebtoken::decode_header(&token).map_err(AwsError::Decode)?;
+            let kids = match header.kid.as_deref() {
+                Some(kid) => vec![kid, ANY_KID],
+                None => vec![ANY_KID],
+            };
+            let key = match this.cache.lookup(&kids, || this.fetch_keys()) {
+                Lookup::Hit(key) => {
+                    tracing::Span::current().record("cache", "hit");
+                    if let Some(stats) = &stats {
+                        stats.key_source(KeySource::Cached);
+                    }
+                    key
+                }
+                Lookup::Unknown => return Err(AwsError::UnknownKey),
+                Lookup::Failed(err) => return Err(AwsError::Fetch(err.into())),
+                Lookup::Fetch(fetch) => {
+                    tracing::Span::current().record("cache", "miss");
+                    if let Some(stats) = &stats {
+                        stats.key_source(KeySource::Fetched);
+                    }
+                    let keys = fetch.await.map_err(|err| AwsError::Fetch(err.into()))?;
+                    kids.iter()
+                        .find_map(|kid| keys.get(*kid))
+                        .cloned()
+                        .ok_or(AwsError::UnknownKey)?
+                }
+            };
+            let claims = jsonwebtoken::decode(&token, &key, &this.validation)
+                .map_err(AwsError::Decode)?
+                .claims;
+            if let Some(profile) = this.profile {
+                profile.check(&token).map_err(AwsError::Decode)?;
+            }
+            Ok(claims)
+        }))
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        match err {
+            AwsError::UnknownKey => Some(DecodeFailure::Key),
+            err => Some(DecodeFailure::of(err)),
+        }
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{amz_date, signed_request, signing_key, AwsCredentials, AwsKeys, AwsSecret};
+    use crate::{util, BoxError, Decoder};
+    use http::{Request, Response, Uri};
+    use jsonwebtoken::{Algorithm, Validation};
+    use ring::hmac;
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[tokio::test]
+    async fn aws_keys() {
+        // https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv-create-signed-request.html
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        let expected = hmac::Key::new(
+            hmac::HMAC_SHA256,
+            &[
+                0xf4, 0x78, 0x0e, 0x2d, 0x9f, 0x65, 0xfa, 0x89, 0x5f, 0x9c, 0x67, 0xb3, 0x2c, 0xe1,
+                0xba, 0xf0, 0xb0, 0xd8, 0xa4, 0x35, 0x05, 0xa0, 0x00, 0xa1, 0xa9, 0xe0, 0x90, 0xd4,
+                0x14, 0xdb, 0x40, 0x4d,
+            ],
+        );
+        assert_eq!(
+            hmac::sign(&key, b"payload").as_ref(),
+            hmac::sign(&expected, b"payload").as_ref()
+        );
+        let time = UNIX_EPOCH + Duration::from_secs(1329264000 + 3723);
+        assert_eq!(amz_date(time), "20120215T010203Z");
+
+        let transport = |req: Request<String>| async move {
+            assert_eq!(req.uri(), "https://ssm.eu-west-1.amazonaws.com/");
+            assert_eq!(req.headers()["x-amz-target"], "AmazonSSM.GetParameter");
+            assert!(req.headers()["authorization"]
+                .to_str()?
+                .starts_with("AWS4-HMAC-SHA256 Credential=AKID/"));
+            let body = json!({ "Parameter": { "Value": util::PUBLIC_KEY } });
+            Ok::<_, BoxError>(Response::new(serde_json::to_vec(&body)?))
+        };
+        let decoder = AwsKeys::<util::Claim>::new(
+            AwsSecret::parameter("eu-west-1", "/api/jwt-key"),
+            AwsCredentials::new("AKID", "secret"),
+            transport,
+            Validation::new(Algorithm::EdDSA),
+        );
+        let claim = util::claim(Some(100));
+        let decoded = decoder
+            .decode(&util::token(&claim))
+            .await
+            .expect("Valid token");
+        assert_eq!(decoded, claim);
+
+        let secret = AwsSecret::secrets_manager("eu-west-1", "prod/api/jwt-keys")
+            .endpoint(Uri::from_static("http://localhost:4566"));
+        let req = signed_request(&secret, &AwsCredentials::new("AKID", "secret"), time)
+            .expect("Valid request");
+        assert_eq!(req.uri(), "http://localhost:4566/");
+        assert_eq!(req.headers()["host"], "localhost:4566");
+    }
+}
@@ -0,0 +1,127 @@
+use crate::Payload;
+use http::HeaderValue;
+use serde_json::Value;
+use std::fmt::{self, Write};
+
+/// Selected claims of accepted token, set on request extensions when baggage is enabled
+/// on [`Layer`][crate::Layer] or [`Middleware`][crate::Middleware].
+///
+/// Inner service is also called (and polled) within `jwt` [span][tracing::Span] carrying
+/// baggage as `baggage` field, so identity propagates across async boundaries without
+/// being threaded through call graphs. [`Baggage::header_value`] renders it as
+/// [W3C baggage](https://www.w3.org/TR/baggage/) for outgoing requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baggage(Vec<(String, String)>);
+
+impl Baggage {
+    /// Copies `claims` present in `payload`, non-string claims are kept as JSON
+    pub fn from_payload(payload: &Payload, claims: &[String]) -> Self {
+        let entries = claims
+            .iter()
+            .filter_map(|claim| {
+                let value = match payload.get(claim)? {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                Some((claim.clone(), value))
+            })
+            .collect();
+        Self(entries)
+    }
+
+    pub fn get(&self, claim: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == claim)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `baggage` header value, `None` when there are no entries
+    pub fn header_value(&self) -> Option<HeaderValue> {
+        match self.is_empty() {
+            true => None,
+            false => HeaderValue::from_str(&self.to_string()).ok(),
+        }
+    }
+
+    pub(crate) fn span(&self) -> tracing::Span {
+        tracing::info_span!("jwt", baggage = %self)
+    }
+}
+
+/// Formats as W3C baggage list, values are percent-encoded
+impl fmt::Display for Baggage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (key, value)) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_char(',')?;
+            }
+            write!(f, "{}=", key)?;
+            for byte in value.bytes() {
+                match byte {
+                    0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e
+                        if byte != b'%' =>
+                    {
+                        f.write_char(byte as char)?
+                    }
+                    _ => write!(f, "%{:02X}", byte)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Baggage;
+    use crate::{util, Middleware, Payload};
+    use http::{header::AUTHORIZATION, Request};
+    use tower::{service_fn, ServiceExt};
+
+    #[test]
+    fn baggage_header() {
+        let token = util::token(&serde_json::json!({
+            "sub": "user 1",
+            "tenant": "acme,inc",
+            "tier": 3,
+        }));
+        let payload = Payload::from_token(&token).expect("Valid token");
+        let claims = ["sub", "tier", "tenant", "missing"].map(String::from);
+        let baggage = Baggage::from_payload(&payload, &claims);
+
+        assert_eq!(baggage.get("tier"), Some("3"));
+        assert_eq!(
+            baggage.header_value().expect("Baggage is not empty"),
+            "sub=user%201,tier=3,tenant=acme%2Cinc"
+        );
+        assert_eq!(Baggage::default().header_value(), None);
+    }
+
+    #[tokio::test]
+    async fn baggage_extension() {
+        let svc = service_fn(|req: Request<()>| async move {
+            let baggage = req.extensions().get::<Baggage>().cloned();
+            Ok::<_, ()>(baggage.and_then(|baggage| baggage.get("sub").map(String::from)))
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).baggage(["sub"]);
+
+        let token = util::token(&util::claim(Some(100)));
+        let req = Request::builder()
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .body(())
+            .expect("Valid request");
+        let sub = middleware.oneshot(req).await.expect("Valid token");
+        assert_eq!(sub.as_deref(), Some("sub"));
+    }
+}
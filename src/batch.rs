@@ -0,0 +1,225 @@
+use crate::{BoxError, BoxFuture, Decoder};
+use core::future::Future;
+use futures::{
+    future::{FutureExt, Shared},
+    ready,
+};
+use serde::de::DeserializeOwned;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use thiserror::Error;
+
+/// Backend verifying many tokens per request, e.g. KMS offering bulk verification,
+/// see [`Batched`]
+pub trait BatchVerifier: Send + Sync + 'static {
+    type Claim: DeserializeOwned + Send + 'static;
+    type Error: Send + 'static;
+
+    /// Verifies `tokens`, returning outcome per token in the same order
+    fn verify(&self, tokens: Vec<String>) -> BoxFuture<Vec<Outcome<Self>>, BoxError>;
+}
+
+type Slots<V> = Arc<Mutex<Vec<Option<Outcome<V>>>>>;
+type Outcome<V> = Result<<V as BatchVerifier>::Claim, <V as BatchVerifier>::Error>;
+type Round<V> = Shared<BoxFuture<Slots<V>, Arc<BoxError>>>;
+
+/// Batch accepting tokens until it's first polled
+struct Open<V: BatchVerifier> {
+    tokens: Arc<Mutex<Option<Vec<String>>>>,
+    round: Round<V>,
+}
+
+/// [`Decoder`] coalescing concurrent verifications into batched backend requests, for
+/// backends where per-token round trips dominate latency.
+///
+/// Tokens join current batch until it's first polled, at which point batch is sealed and
+/// sent to [`BatchVerifier`], or until it holds maximum number of tokens (64 by default).
+/// Concurrent requests thus share a single round trip without any added delay.
+///
+/// ```rust
+/// # use tower_jwt::{BatchVerifier, BoxError, BoxFuture};
+/// # struct Kms;
+/// # impl BatchVerifier for Kms {
+/// # type Claim = (); type Error = std::io::Error;
+/// # fn verify(&self, tokens: Vec<String>) -> BoxFuture<Vec<Result<(), std::io::Error>>, BoxError> { todo!() }
+/// # }
+/// use tower_jwt::Batched;
+///
+/// let decoder = Batched::new(Kms).max_batch(16);
+/// ```
+pub struct Batched<V: BatchVerifier> {
+    verifier: Arc<V>,
+    open: Arc<Mutex<Option<Open<V>>>>,
+    max_batch: usize,
+}
+
+impl<V: BatchVerifier> Clone for Batched<V> {
+    fn clone(&self) -> Self {
+        Self {
+            verifier: self.verifier.clone(),
+            open: self.open.clone(),
+            max_batch: self.max_batch,
+        }
+    }
+}
+
+impl<V: BatchVerifier> Batched<V> {
+    pub fn new(verifier: V) -> Self {
+        Self {
+            verifier: Arc::new(verifier),
+            open: Arc::new(Mutex::new(None)),
+            max_batch: 64,
+        }
+    }
+
+    /// Maximum number of tokens per backend request
+    pub fn max_batch(mut self, max_batch: usize) -> Self {
+        self.max_batch = max_batch.max(1);
+        self
+    }
+
+    fn round(&self) -> Open<V> {
+        let tokens = Arc::new(Mutex::new(Some(Vec::new())));
+        let sealed = tokens.clone();
+        let verifier = self.verifier.clone();
+        let round: BoxFuture<Slots<V>, Arc<BoxError>> = Box::pin(async move {
+            let tokens = lock(&sealed).take().unwrap_or_default();
+            tracing::trace!(tokens = tokens.len(), "Batched::sealed");
+            let outcomes = verifier.verify(tokens).await.map_err(Arc::new)?;
+            Ok(Arc::new(Mutex::new(
+                outcomes.into_iter().map(Some).collect(),
+            )))
+        });
+        Open {
+            tokens,
+            round: round.shared(),
+        }
+    }
+
+    /// Adds `token` to open batch, returning the batch and token index in it
+    fn join(&self, token: &str) -> (Round<V>, usize) {
+        let mut open = lock(&self.open);
+        loop {
+            if let Some(batch) = open.as_ref() {
+                if let Some(tokens) = lock(&batch.tokens).as_mut() {
+                    if tokens.len() < self.max_batch {
+                        tokens.push(token.to_owned());
+                        return (batch.round.clone(), tokens.len() - 1);
+                    }
+                }
+            }
+            *open = Some(self.round());
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl<V: BatchVerifier> fmt::Debug for Batched<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Batched")
+            .field("max_batch", &self.max_batch)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BatchError<E> {
+    #[error("Token was rejected")]
+    Verifier(#[source] E),
+
+    #[error("Batch verification failed: {0}")]
+    Backend(Arc<BoxError>),
+
+    #[error("Batch verifier returned no outcome for token")]
+    Incomplete,
+}
+
+impl<V: BatchVerifier> Decoder for Batched<V> {
+    type Error = BatchError<V::Error>;
+    type Claim = V::Claim;
+    type Future = BatchFuture<V>;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        tracing::trace!("Batched::entered");
+        let (round, index) = self.join(token);
+        BatchFuture { round, index }
+    }
+}
+
+pub struct BatchFuture<V: BatchVerifier> {
+    round: Round<V>,
+    index: usize,
+}
+
+impl<V: BatchVerifier> Future for BatchFuture<V> {
+    type Output = Result<V::Claim, BatchError<V::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let index = self.index;
+        let slots = ready!(self.round.poll_unpin(cx)).map_err(BatchError::Backend)?;
+        let outcome = lock(&slots).get_mut(index).and_then(Option::take);
+        Poll::Ready(match outcome {
+            Some(outcome) => outcome.map_err(BatchError::Verifier),
+            None => Err(BatchError::Incomplete),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BatchError, BatchVerifier, Batched};
+    use crate::{BoxError, BoxFuture, Decoder};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Backend {
+        batches: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl BatchVerifier for Backend {
+        type Claim = String;
+        type Error = String;
+
+        fn verify(&self, tokens: Vec<String>) -> BoxFuture<Vec<Result<String, String>>, BoxError> {
+            self.batches
+                .lock()
+                .expect("Not poisoned")
+                .push(tokens.len());
+            let outcomes = tokens
+                .into_iter()
+                .map(|token| match token.starts_with("valid") {
+                    true => Ok(token),
+                    false => Err(token),
+                })
+                .collect();
+            Box::pin(async move { Ok(outcomes) })
+        }
+    }
+
+    #[tokio::test]
+    async fn batched() {
+        let backend = Backend::default();
+        let batches = backend.batches.clone();
+        let decoder = Batched::new(backend).max_batch(2);
+
+        let first = decoder.decode("valid-1");
+        let second = decoder.decode("forged");
+        let third = decoder.decode("valid-3");
+        let (first, second, third) = futures::join!(first, second, third);
+
+        assert_eq!(first.expect("Valid token"), "valid-1");
+        assert!(matches!(second, Err(BatchError::Verifier(token)) if token == "forged"));
+        assert_eq!(third.expect("Valid token"), "valid-3");
+        assert_eq!(*batches.lock().expect("Not poisoned"), vec![2, 1]);
+    }
+}
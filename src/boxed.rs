@@ -0,0 +1,102 @@
+use core::future::Future;
+use core::task::{Context, Poll};
+use std::pin::Pin;
+use tower::Service;
+
+/// Type erased response future of [`Boxed`] services.
+///
+/// Also returned by lookups gates and decoders delegate to, e.g. [`UserStatusStore`],
+/// [`SubjectResolver`], [`WatermarkStore`], [`FeatureFlagResolver`], [`SignatureKeys`] or
+/// [`EntitlementSource`]. Each of them is implemented for any `Fn` taking the arguments of its
+/// method and returning `impl Future` of the same output, so async closures and functions
+/// plug in as they are.
+///
+/// [`UserStatusStore`]: crate::UserStatusStore
+/// [`SubjectResolver`]: crate::SubjectResolver
+/// [`WatermarkStore`]: crate::WatermarkStore
+/// [`FeatureFlagResolver`]: crate::FeatureFlagResolver
+/// [`SignatureKeys`]: crate::SignatureKeys
+/// [`EntitlementSource`]: crate::EntitlementSource
+pub type BoxFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'static>>;
+
+/// Boxes response futures of wrapped [service][tower::Service] (or of services produced by wrapped [layer][tower::Layer]).
+///
+/// [`MiddlewareFuture`][crate::MiddlewareFuture] embeds inner service, request, decoder future and inner future,
+/// which can noticeably bloat futures of enclosing services. [`Boxed`] trades one allocation per request for
+/// pointer-sized response future.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::Layer;
+///
+/// let layer = Layer::new(decoder).boxed();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Boxed<T> {
+    inner: T,
+}
+
+impl<T> Boxed<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<L, S> tower::Layer<S> for Boxed<L>
+where
+    L: tower::Layer<S>,
+{
+    type Service = Boxed<L::Service>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Boxed::new(self.inner.layer(inner))
+    }
+}
+
+impl<T, R> Service<R> for Boxed<T>
+where
+    T: Service<R>,
+    T::Future: Send + 'static,
+{
+    type Response = T::Response;
+    type Error = T::Error;
+    type Future = BoxFuture<T::Response, T::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        Box::pin(self.inner.call(req))
+    }
+}
+
+/// [`BoxFuture`] which is also `Sync`, as required of [`Decoder`][crate::Decoder] futures
+/// by [`Middleware`][crate::Middleware].
+///
+/// Future is only ever accessed through `&mut`, so the mutex is never actually locked.
+pub struct SyncBoxFuture<T, E>(std::sync::Mutex<BoxFuture<T, E>>);
+
+impl<T, E> SyncBoxFuture<T, E> {
+    pub fn new(future: BoxFuture<T, E>) -> Self {
+        Self(std::sync::Mutex::new(future))
+    }
+}
+
+impl<T, E> Future for SyncBoxFuture<T, E> {
+    type Output = Result<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self
+            .get_mut()
+            .0
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner());
+        future.as_mut().poll(cx)
+    }
+}
@@ -0,0 +1,234 @@
+//! Builder methods shared by [`Layer`][crate::Layer] and [`Middleware`][crate::Middleware],
+//! expanded into both so the two can't drift apart.
+
+/// Methods configuring [`DefaultExtractor`][crate::DefaultExtractor], expects `extractor` field
+macro_rules! extractor_builders {
+    () => {
+        /// Read token from header `name` (e.g. `X-Api-Token`) instead of `Authorization`.
+        ///
+        /// Header value is either bare token or `Bearer` credentials.
+        pub fn header(mut self, name: HeaderName) -> Self {
+            self.extractor.header(name);
+            self
+        }
+
+        /// Expect credentials of authentication `scheme` (e.g. `Token`) instead of `Bearer`
+        pub fn scheme(mut self, scheme: impl Into<String>) -> Self {
+            self.extractor.scheme(scheme.into());
+            self
+        }
+
+        /// Expect bare token, with no authentication scheme
+        pub fn bare_token(mut self) -> Self {
+            self.extractor.bare_token();
+            self
+        }
+
+        /// Tolerate padding and tabs around `Bearer` scheme and token, as sent by some clients
+        /// in the wild, rather than rejecting such headers. Scheme is matched case-insensitively
+        /// either way.
+        pub fn lenient_scheme(mut self) -> Self {
+            self.extractor.lenient_scheme();
+            self
+        }
+
+        /// Take token from password of `Basic` credentials, for clients only capable of basic auth.
+        ///
+        /// Username is ignored.
+        pub fn basic_password(mut self) -> Self {
+            self.extractor.basic_password();
+            self
+        }
+
+        /// Also accept token from query parameter `name` (e.g. `access_token`), as described in
+        /// [RFC 6750 §2.3](https://www.rfc-editor.org/rfc/rfc6750#section-2.3).
+        ///
+        /// Meant for clients which can't set headers, such as `EventSource` or download links.
+        /// `Authorization` header takes precedence when both are present.
+        pub fn query_param(mut self, name: impl Into<String>) -> Self {
+            self.extractor.query_param(name.into());
+            self
+        }
+    };
+}
+
+/// Methods configuring `Options`, expects `options` field
+macro_rules! options_builders {
+    () => {
+        /// Run [`Gate`] on every request with accepted token
+        pub fn gate<G: Gate>(mut self, gate: G) -> Self {
+            self.options.gates.push(gate);
+            self
+        }
+
+        /// Insert `project(&claim)` on request extensions next to the claim, so handlers can depend
+        /// on narrow types (user id, tenant, ..) rather than the whole claim.
+        ///
+        /// Projections run as [gates][Gate], any number of them can be registered.
+        pub fn project<C, T, F>(self, project: F) -> Self
+        where
+            C: Send + Sync + 'static,
+            T: Send + Sync + 'static,
+            F: Fn(&C) -> T + Send + Sync + 'static,
+        {
+            self.gate(claims::projection(project))
+        }
+
+        /// Set [`AuthTiming`] on extensions of every request with accepted token
+        pub fn timing(mut self) -> Self {
+            self.options.timing = true;
+            self
+        }
+
+        /// Append `Server-Timing` entry to responses, see [`ServerTiming`]
+        pub fn server_timing(self) -> ServerTiming<Self> {
+            ServerTiming::new(self.timing())
+        }
+
+        /// Copy `claims` of accepted token into [`Baggage`] and tracing span inner service runs in
+        pub fn baggage<I, C>(mut self, claims: I) -> Self
+        where
+            I: IntoIterator<Item = C>,
+            C: Into<String>,
+        {
+            self.options.baggage = Some(claims.into_iter().map(Into::into).collect());
+            self
+        }
+
+        /// Remove credentials (`Authorization` header, token cookie, query parameter, ..) from
+        /// requests once they were authenticated, so the raw token doesn't leak into upstream
+        /// services and logs.
+        ///
+        /// What's removed is up to [extractor][TokenExtractor::strip], gates still see them.
+        pub fn strip_token(mut self) -> Self {
+            self.options.strip = true;
+            self
+        }
+
+        /// Reject tokens longer than `max` bytes before they reach decoder
+        pub fn max_token_len(mut self, max: usize) -> Self {
+            self.options.max_token_len = Some(max);
+            self
+        }
+
+        /// Let requests to paths matching `pattern` (e.g. `/healthz` or `/assets/**`) through
+        /// without looking for token, with no claim on extensions.
+        ///
+        /// `*` in the pattern stands for anything within one path segment, `**` for anything at all.
+        /// Any number of patterns can be registered. Paths with dot segments, repeated slashes,
+        /// backslashes or percent-encoded dots and slashes never match, as router could resolve
+        /// them outside of the pattern.
+        pub fn skip_path(mut self, pattern: impl Into<String>) -> Self {
+            self.options.exempt.path(pattern.into());
+            self
+        }
+
+        /// Let requests of `method` (e.g. `OPTIONS`) through without looking for token, with no
+        /// claim on extensions.
+        ///
+        /// Unlike [`skip_preflight`][Self::skip_preflight], which only lets CORS preflight requests
+        /// through, all requests of the method are. Any number of methods can be registered.
+        pub fn skip_method(mut self, method: Method) -> Self {
+            self.options.exempt.method(method);
+            self
+        }
+
+        /// Let requests without token through to inner service, with no claim on extensions.
+        ///
+        /// Requests presenting a token still have it decoded and checked, and are rejected if it's
+        /// invalid. Handlers tell the two apart with [`claims`] returning `None`.
+        pub fn optional(mut self) -> Self {
+            self.options.optional = true;
+            self
+        }
+
+        /// Let requests with token that failed to decode through to inner service, with
+        /// [`AuthFailure`] on extensions in place of the claim, e.g. to canary a new issuer
+        /// without breaking traffic.
+        ///
+        /// [Gates][Gate] are not run for such requests.
+        pub fn soft_fail(mut self) -> Self {
+            self.options.soft_fail = true;
+            self
+        }
+
+        /// Apply `route` overrides to requests to paths matching `pattern` (e.g. `/admin/**`),
+        /// see [`Route`].
+        ///
+        /// Patterns are matched as in [`skip_path`][Self::skip_path], first matching route applies.
+        /// `**` suffix doesn't match bare prefix: `/admin/**` covers `/admin/` and anything beneath,
+        /// but not `/admin`, register both to cover both.
+        pub fn route(mut self, pattern: impl Into<String>, route: Route) -> Self {
+            self.options.routes.push(pattern.into(), route);
+            self
+        }
+
+        /// Let CORS preflight requests (`OPTIONS` with `Access-Control-Request-Method`) through
+        /// without authentication, browsers never attach credentials to those
+        pub fn skip_preflight(mut self) -> Self {
+            self.options.preflight = true;
+            self
+        }
+
+        /// Split and decode token once, reusing its header and payload across decoders peeking
+        /// at them (e.g. [`AlgorithmGuard`], [`Offload`]), [gates][Gate] and [`Baggage`], rather than
+        /// having each one decode the token again.
+        ///
+        /// Meant for gateways stacking several such steps, peeks made by decoders outside of
+        /// [`Decoder::decode`] itself (i.e. in returned futures) aren't covered. Neither is the
+        /// final verification: `jsonwebtoken` decodes both segments once more while checking
+        /// signature and claims, as key family checks it makes aren't available outside of it.
+        pub fn fast_path(mut self) -> Self {
+            self.options.fast_path = true;
+            self
+        }
+
+        /// Report every rejected request to `observer`, along with its method, path and
+        /// remote address, see [`RejectionObserver`]
+        pub fn observe_rejections<O: RejectionObserver>(mut self, observer: O) -> Self {
+            self.options.observer = Some(Observer::new(observer));
+            self
+        }
+
+        /// Request handling settings recommended for APIs: tokens over [`MAX_TOKEN_LEN`] rejected,
+        /// preflight requests let through and credentials [stripped][Self::strip_token].
+        ///
+        /// Accepted algorithms are up to decoder and failures still end up as [`Error`][enum@Error],
+        /// see [`Layer::secure`] for a preset covering those as well.
+        pub fn hardened(self) -> Self {
+            self.max_token_len(MAX_TOKEN_LEN)
+                .skip_preflight()
+                .strip_token()
+        }
+
+        /// Avoid heap allocations on requests presenting cached tokens, for latency-critical
+        /// proxies: turns off [timing][Self::timing], [token stripping][Self::strip_token] and
+        /// [fast path][Self::fast_path], which allocate per request.
+        ///
+        /// Takes a caching decoder of reference counted claims (e.g. [`Cached`] over [`Shared`]),
+        /// [`ClaimSlot`] preallocated on request extensions and token located upstream as
+        /// [`RawToken`], since parsing `Authorization` header allocates. [Gates][Gate] and
+        /// [`Baggage`] allocate as well.
+        pub fn no_alloc(mut self) -> Self {
+            self.options.timing = false;
+            self.options.strip = false;
+            self.options.fast_path = false;
+            self
+        }
+
+        /// Box response futures of [`Middleware`], see [`Boxed`]
+        pub fn boxed(self) -> Boxed<Self> {
+            Boxed::new(self)
+        }
+
+        /// Render rejections with `handler` rather than failing with [`Error`][enum@Error], see [`Rejecting`]
+        pub fn reject_with<H>(self, handler: H) -> Rejecting<Self, H> {
+            Rejecting::new(self, handler)
+        }
+
+        /// Respond with default rejections rather than failing with [`Error`][enum@Error], see [`Rejecting`]
+        pub fn respond(self) -> Rejecting<Self> {
+            Rejecting::new(self, DefaultRejection)
+        }
+    };
+}
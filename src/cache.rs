@@ -0,0 +1,496 @@
+use crate::{refresh::Limiter, BoxError, BoxFuture};
+use futures::{
+    future::{Shared, TryFutureExt},
+    FutureExt,
+};
+use jsonwebtoken::DecodingKey;
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// Most entries tracked at once, oldest ones are evicted to make room for new ones
+const CAPACITY: usize = 4096;
+
+/// Admissions into full cache between sweeps of expired entries, so each admission pays for
+/// a few entries' worth of sweeping rather than all of them
+const PRUNE_EVERY: usize = CAPACITY / 4;
+
+struct Entry<V> {
+    inserted: Instant,
+    /// `None` for entries living until removed
+    expires: Option<Instant>,
+    /// Position in [`Entries::order`], entries replaced since have stale ones there
+    seq: u64,
+    value: V,
+}
+
+impl<V> Entry<V> {
+    fn live(&self, now: Instant) -> bool {
+        self.expires.is_none_or(|expires| expires > now)
+    }
+}
+
+/// Entries along with keys in order they were (re)inserted in
+struct Entries<V> {
+    map: HashMap<String, Entry<V>>,
+    order: VecDeque<(u64, String)>,
+    seq: u64,
+    /// Admissions into full cache since last sweep
+    admitted: usize,
+}
+
+impl<V> Default for Entries<V> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::new(),
+            order: VecDeque::new(),
+            seq: 0,
+            admitted: 0,
+        }
+    }
+}
+
+impl<V> Entries<V> {
+    /// Sets entry `key`, evicting expired and then oldest entries to stay within [`CAPACITY`]
+    fn set(&mut self, key: &str, now: Instant, ttl: Duration, value: V) -> &mut Entry<V> {
+        if !self.map.contains_key(key) {
+            self.make_room(now);
+        }
+        let seq = self.seq;
+        self.seq += 1;
+        let entry = Entry {
+            inserted: now,
+            expires: now.checked_add(ttl),
+            seq,
+            value,
+        };
+        self.map.insert(key.to_owned(), entry);
+        self.order.push_back((seq, key.to_owned()));
+        if self.order.len() > CAPACITY * 2 {
+            let map = &self.map;
+            self.order
+                .retain(|(seq, key)| map.get(key).is_some_and(|entry| entry.seq == *seq));
+        }
+        self.map.get_mut(key).expect("Entry was just set")
+    }
+
+    fn make_room(&mut self, now: Instant) {
+        if self.map.len() < CAPACITY {
+            return;
+        }
+        self.admitted += 1;
+        if self.admitted >= PRUNE_EVERY {
+            self.admitted = 0;
+            self.map.retain(|_, entry| entry.live(now));
+        }
+        while self.map.len() >= CAPACITY {
+            let Some((seq, key)) = self.order.pop_front() else {
+                break;
+            };
+            if self.map.get(&key).is_some_and(|entry| entry.seq == seq) {
+                self.map.remove(&key);
+            }
+        }
+    }
+}
+
+/// Shared cache of lookups made by [gates][crate::Gate] and decoders, entries expire after
+/// `ttl` unless inserted with their own. Holds at most [`CAPACITY`] entries, once full, expired
+/// ones are swept every [`PRUNE_EVERY`] admissions and the oldest one is evicted otherwise.
+pub(crate) struct TtlCache<V> {
+    ttl: Duration,
+    entries: Arc<Mutex<Entries<V>>>,
+}
+
+impl<V> TtlCache<V> {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Default::default(),
+        }
+    }
+
+    pub(crate) fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries<V>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs `f` on entry `key`, replacing it with `default` expiring after `ttl` first unless
+    /// it's live
+    pub(crate) fn upsert<T>(
+        &self,
+        key: &str,
+        ttl: Duration,
+        default: impl FnOnce() -> V,
+        f: impl FnOnce(&mut V) -> T,
+    ) -> T {
+        let mut entries = self.lock();
+        let now = Instant::now();
+        let live = entries.map.get(key).is_some_and(|entry| entry.live(now));
+        let entry = match live {
+            true => entries.map.get_mut(key).expect("Live entry"),
+            false => entries.set(key, now, ttl, default()),
+        };
+        f(&mut entry.value)
+    }
+
+    /// Runs `f` on entry `key`, removing it when `f` returns `true`
+    pub(crate) fn remove_if(&self, key: &str, f: impl FnOnce(&mut V) -> bool) {
+        let mut entries = self.lock();
+        if entries
+            .map
+            .get_mut(key)
+            .is_some_and(|entry| f(&mut entry.value))
+        {
+            entries.map.remove(key);
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.lock().map.len()
+    }
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub(crate) fn get(&self, key: &str) -> Option<V> {
+        self.get_with_age(key).map(|(_, value)| value)
+    }
+
+    /// Unexpired entry along with its age
+    pub(crate) fn get_with_age(&self, key: &str) -> Option<(Duration, V)> {
+        let entries = self.lock();
+        let now = Instant::now();
+        entries
+            .map
+            .get(key)
+            .filter(|entry| entry.live(now))
+            .map(|entry| (now.duration_since(entry.inserted), entry.value.clone()))
+    }
+
+    /// No-op when `ttl` is zero
+    pub(crate) fn insert(&self, key: String, value: V) {
+        self.insert_for(key, self.ttl, value)
+    }
+
+    /// Same as [`insert`][Self::insert], with entry expiring after `ttl` instead
+    pub(crate) fn insert_for(&self, key: String, ttl: Duration, value: V) {
+        if ttl.is_zero() {
+            return;
+        }
+        self.lock().set(&key, Instant::now(), ttl, value);
+    }
+}
+
+impl<V> Clone for TtlCache<V> {
+    fn clone(&self) -> Self {
+        Self {
+            ttl: self.ttl,
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<V> Default for TtlCache<V> {
+    fn default() -> Self {
+        Self::new(Duration::ZERO)
+    }
+}
+
+impl<V> fmt::Debug for TtlCache<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TtlCache")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Tokens signed by keys missing from fetched set trigger another fetch at most that often
+const REFETCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Failed fetch is handed to lookups for that long before set is fetched again
+const FAILURE_BACKOFF: Duration = Duration::from_secs(5);
+
+type Keys = Arc<HashMap<String, DecodingKey>>;
+
+pub(crate) type SharedError = Arc<dyn Error + Send + Sync>;
+
+/// Fetch of key set shared by concurrent lookups, along with when it completed
+type Fetch = Shared<BoxFuture<(Instant, Keys), (Instant, SharedError)>>;
+
+/// Outcome of [`KeyCache::lookup`]
+pub(crate) enum Lookup {
+    Hit(DecodingKey),
+    /// Fetched set has no such key and it was fetched too recently to try again
+    Unknown,
+    /// Fetch of the set, possibly started by another lookup
+    Fetch(BoxFuture<Keys, SharedError>),
+    /// Last fetch failed too recently to try again
+    Failed(SharedError),
+}
+
+#[derive(Default)]
+struct State {
+    /// Last set fetched, along with when
+    keys: Option<(Instant, Keys)>,
+    /// Fetch in flight, or failed one until [`FAILURE_BACKOFF`] elapsed
+    fetch: Option<Fetch>,
+}
+
+impl State {
+    /// Replaces the set with completed fetch, forgets failed one once backoff elapsed
+    fn settle(&mut self) {
+        match self.fetch.as_ref().and_then(Shared::peek) {
+            Some(Ok(fetched)) => {
+                self.keys = Some(fetched.clone());
+                self.fetch = None;
+            }
+            Some(Err((failed, _))) if failed.elapsed() >= FAILURE_BACKOFF => self.fetch = None,
+            _ => {}
+        }
+    }
+
+    fn start<F>(&mut self, fetch: F) -> BoxFuture<Keys, SharedError>
+    where
+        F: FnOnce() -> BoxFuture<HashMap<String, DecodingKey>, BoxError>,
+    {
+        let fetch: BoxFuture<_, _> = fetch()
+            .map(|fetched| match fetched {
+                Ok(keys) => Ok((Instant::now(), Arc::new(keys))),
+                Err(err) => Err((Instant::now(), SharedError::from(err))),
+            })
+            .boxed();
+        let fetch = fetch.shared();
+        self.fetch = Some(fetch.clone());
+        shared(fetch)
+    }
+}
+
+/// Last set of keys fetched from remote source (JWKS endpoint, secret store, ..), by `kid`.
+///
+/// Set is fetched again once `ttl` elapsed, or sooner when token names a `kid` missing from it,
+/// though no more than once per [`REFETCH_INTERVAL`], so tokens with made up `kid`s can't turn
+/// into a fetch per request. Lookups made while the set is being fetched wait for that fetch
+/// rather than starting another, and a failed one is handed to lookups for
+/// [`FAILURE_BACKOFF`] before it's retried.
+#[derive(Clone)]
+pub(crate) struct KeyCache {
+    ttl: Duration,
+    state: Arc<Mutex<State>>,
+    refetch: Limiter,
+}
+
+impl KeyCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            state: Default::default(),
+            refetch: Limiter::new(REFETCH_INTERVAL),
+        }
+    }
+
+    pub(crate) fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// First of `kids` found in the set, or fetch of the set started with `fetch` unless
+    /// another one is in flight, failed recently, or the set was fetched too recently
+    pub(crate) fn lookup<F>(&self, kids: &[&str], fetch: F) -> Lookup
+    where
+        F: FnOnce() -> BoxFuture<HashMap<String, DecodingKey>, BoxError>,
+    {
+        let mut state = self.lock();
+        state.settle();
+
+        let fresh = state
+            .keys
+            .as_ref()
+            .filter(|(fetched, _)| fetched.elapsed() < self.ttl);
+        if let Some(key) = fresh.and_then(|(_, keys)| kids.iter().find_map(|kid| keys.get(*kid))) {
+            return Lookup::Hit(key.clone());
+        }
+        if let Some(fetch) = &state.fetch {
+            return match fetch.peek() {
+                Some(Err((_, err))) => Lookup::Failed(err.clone()),
+                _ => Lookup::Fetch(shared(fetch.clone())),
+            };
+        }
+        // counts as refetch when set is missing or stale as well, so that unknown `kid`s
+        // don't trigger another one right away
+        if !self.refetch.acquire() && fresh.is_some() {
+            return Lookup::Unknown;
+        }
+
+        Lookup::Fetch(state.start(fetch))
+    }
+
+    /// Fetches the set ahead of schedule, unless it has `kid` already or the last fetch failed
+    /// recently. Current set keeps being used until the new one arrives.
+    pub(crate) fn refresh<F>(
+        &self,
+        kid: Option<&str>,
+        fetch: F,
+    ) -> Option<BoxFuture<Keys, SharedError>>
+    where
+        F: FnOnce() -> BoxFuture<HashMap<String, DecodingKey>, BoxError>,
+    {
+        let mut state = self.lock();
+        state.settle();
+        let known = state
+            .keys
+            .as_ref()
+            .is_some_and(|(_, keys)| kid.is_some_and(|kid| keys.contains_key(kid)));
+        match &state.fetch {
+            _ if known => None,
+            Some(fetch) => fetch.peek().is_none().then(|| shared(fetch.clone())),
+            None => Some(state.start(fetch)),
+        }
+    }
+}
+
+fn shared(fetch: Fetch) -> BoxFuture<Keys, SharedError> {
+    fetch
+        .map_ok(|(_, keys)| keys)
+        .map_err(|(_, err)| err)
+        .boxed()
+}
+
+impl fmt::Debug for KeyCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyCache")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{KeyCache, Lookup, TtlCache, CAPACITY, PRUNE_EVERY};
+    use crate::{util, BoxError, BoxFuture};
+    use jsonwebtoken::DecodingKey;
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    #[test]
+    fn ttl_cache_capacity() {
+        let cache = TtlCache::new(Duration::from_secs(60));
+        for key in 0..CAPACITY {
+            cache.insert(key.to_string(), key);
+        }
+        assert_eq!(cache.len(), CAPACITY);
+
+        // Oldest entry makes room for the new one, replaced ones count as new
+        cache.insert(String::from("1"), 1);
+        cache.insert(String::from("late"), 0);
+        assert_eq!(cache.len(), CAPACITY);
+        assert_eq!(cache.get("0"), None);
+        assert_eq!(cache.get("1"), Some(1));
+        assert_eq!(cache.get("late"), Some(0));
+
+        // Counters are tracked the same way
+        let count = cache.upsert(
+            "counter",
+            Duration::from_secs(60),
+            || 0,
+            |count| {
+                *count += 1;
+                *count
+            },
+        );
+        assert_eq!(count, 1);
+        assert_eq!(cache.get("2"), None);
+        cache.remove_if("counter", |count| *count == 1);
+        assert_eq!(cache.get("counter"), None);
+
+        // Expired entries are replaced
+        cache.upsert("expired", Duration::ZERO, || 5, |_| ());
+        assert_eq!(
+            cache.upsert("expired", Duration::ZERO, || 0, |count| *count),
+            0
+        );
+
+        // and swept before live ones are evicted
+        let cache = TtlCache::new(Duration::from_secs(60));
+        for key in 0..CAPACITY {
+            cache.insert_for(format!("short-{}", key), Duration::from_nanos(1), key);
+        }
+        cache.insert(String::from("kept"), 0);
+        for key in 0..PRUNE_EVERY {
+            cache.insert(format!("new-{}", key), key);
+        }
+        assert_eq!(cache.get("kept"), Some(0));
+        assert!(cache.len() < CAPACITY);
+    }
+
+    #[tokio::test]
+    async fn key_cache_single_fetch() {
+        let fetches = AtomicUsize::new(0);
+        let fetch = |ok: bool| {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            let fetch: BoxFuture<_, BoxError> = Box::pin(async move {
+                match ok {
+                    true => {
+                        let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes())?;
+                        Ok(HashMap::from([(String::from("kid"), key)]))
+                    }
+                    false => Err(BoxError::from("Unavailable")),
+                }
+            });
+            fetch
+        };
+
+        // Concurrent lookups share a single fetch
+        let cache = KeyCache::new(Duration::from_secs(60));
+        let (Lookup::Fetch(first), Lookup::Fetch(second)) = (
+            cache.lookup(&["kid"], || fetch(true)),
+            cache.lookup(&["kid"], || fetch(true)),
+        ) else {
+            panic!("Keys must be fetched");
+        };
+        assert!(first.await.is_ok() && second.await.is_ok());
+        assert!(matches!(
+            cache.lookup(&["kid"], || fetch(true)),
+            Lookup::Hit(_)
+        ));
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        // Set having the key isn't refreshed, others keep being used until refreshed
+        assert!(cache.refresh(Some("kid"), || fetch(true)).is_none());
+        let refresh = cache
+            .refresh(Some("other"), || fetch(true))
+            .expect("Set has no such key");
+        assert!(matches!(
+            cache.lookup(&["kid"], || fetch(true)),
+            Lookup::Hit(_)
+        ));
+        assert!(refresh.await.is_ok());
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+
+        // Failures are handed out until backoff elapsed
+        let cache = KeyCache::new(Duration::from_secs(60));
+        let Lookup::Fetch(failing) = cache.lookup(&["kid"], || fetch(false)) else {
+            panic!("Keys must be fetched");
+        };
+        assert!(failing.await.is_err());
+        assert!(matches!(
+            cache.lookup(&["kid"], || fetch(true)),
+            Lookup::Failed(_)
+        ));
+        assert!(cache.refresh(None, || fetch(true)).is_none());
+        assert_eq!(fetches.load(Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{cache::TtlCache, CacheStatus, DecodeFailure, DecodeStats, Decoder, Payload};
+use core::future::Future;
+use core::task::{Context, Poll};
+use futures::{future::Either, ready};
+use pin_project::pin_project;
+use std::{
+    fmt,
+    future::{self, Ready},
+    pin::Pin,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Wraps any [`Decoder`] remembering claims of accepted tokens for `ttl`, but never past
+/// their `exp`, so tokens presented again skip verification altogether.
+///
+/// Cache hits are looked up by borrowed token and take no heap allocation as long as cloning
+/// the claim doesn't, so wrap the decoder in [`Shared`][crate::Shared] to have claims
+/// reference counted. See [`no_alloc`][crate::Layer::no_alloc] for the rest of the hot path.
+///
+/// Tokens revoked within `ttl` keep being accepted, keep it short where that matters.
+///
+/// ```rust
+/// # fn example(key: jsonwebtoken::DecodingKey, validation: jsonwebtoken::Validation) {
+/// use std::time::Duration;
+/// use tower_jwt::{Cached, InPlace, Shared};
+///
+/// let decoder = Cached::new(
+///     Shared::new(InPlace::<serde_json::Value>::new(key, validation)),
+///     Duration::from_secs(30),
+/// );
+/// # }
+/// ```
+pub struct Cached<D: Decoder> {
+    decoder: D,
+    cache: TtlCache<D::Claim>,
+}
+
+impl<D: Decoder> Cached<D> {
+    pub fn new(decoder: D, ttl: Duration) -> Self {
+        Self {
+            decoder,
+            cache: TtlCache::new(ttl),
+        }
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+impl<D> Clone for Cached<D>
+where
+    D: Decoder + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            decoder: self.decoder.clone(),
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<D> fmt::Debug for Cached<D>
+where
+    D: Decoder + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cached")
+            .field("decoder", &self.decoder)
+            .field("ttl", &self.cache.ttl())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D> Decoder for Cached<D>
+where
+    D: Decoder,
+    D::Claim: Clone,
+{
+    type Error = D::Error;
+    type Claim = D::Claim;
+    type Future = Either<Ready<Result<D::Claim, D::Error>>, CachedFuture<D>>;
+
+    fn decode(&self, token: &str) -> Self::Future {
+        let stats = DecodeStats::current();
+        if let Some(claim) = self.cache.get(token) {
+            if let Some(stats) = stats {
+                stats.cache(CacheStatus::Hit);
+            }
+            return Either::Left(future::ready(Ok(claim)));
+        }
+        if let Some(stats) = stats {
+            stats.cache(CacheStatus::Miss);
+        }
+
+        Either::Right(CachedFuture {
+            inner: self.decoder.decode(token),
+            token: token.to_owned(),
+            cache: self.cache.clone(),
+        })
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        D::failure(err)
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        D::error(err)
+    }
+}
+
+/// Future of [`Cached`] decoder on cache miss
+#[pin_project]
+pub struct CachedFuture<D: Decoder> {
+    #[pin]
+    inner: D::Future,
+    token: String,
+    cache: TtlCache<D::Claim>,
+}
+
+impl<D> Future for CachedFuture<D>
+where
+    D: Decoder,
+    D::Claim: Clone,
+{
+    type Output = Result<D::Claim, D::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let claim = ready!(this.inner.poll(cx))?;
+        let expiry = Payload::from_token(this.token)
+            .and_then(|payload| payload.i64("exp"))
+            .and_then(|exp| u64::try_from(exp).ok())
+            .map(|exp| UNIX_EPOCH + Duration::from_secs(exp));
+        let ttl = match expiry {
+            Some(expiry) => match expiry.duration_since(SystemTime::now()) {
+                Ok(left) => left.min(this.cache.ttl()),
+                Err(_) => Duration::ZERO,
+            },
+            None => this.cache.ttl(),
+        };
+        this.cache
+            .insert_for(std::mem::take(this.token), ttl, claim.clone());
+        Poll::Ready(Ok(claim))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Cached;
+    use crate::{util, Decoder};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn cached() {
+        let decoder = Cached::new(util::in_place_decoder(), Duration::from_secs(60));
+        let token = util::token(&util::claim(Some(100)));
+        let claim = decoder.decode(&token).await.expect("Valid token");
+        assert!(decoder.cache.get(&token).is_some());
+        assert_eq!(decoder.decode(&token).await.ok(), Some(claim));
+
+        // Expired tokens are never cached, nor accepted
+        let expired = util::token(&util::claim(None));
+        assert!(decoder.decode(&expired).await.is_err());
+        assert_eq!(decoder.cache.len(), 1);
+
+        let uncached = Cached::new(util::in_place_decoder(), Duration::ZERO);
+        assert!(uncached.decode(&token).await.is_ok());
+        assert_eq!(uncached.cache.len(), 0);
+    }
+}
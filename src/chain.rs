@@ -0,0 +1,209 @@
+use crate::{DecodeFailure, Decoder};
+use core::future::Future;
+use core::task::{Context, Poll};
+use pin_project::pin_project;
+use std::{error::Error as StdError, fmt, pin::Pin, sync::Arc};
+use thiserror::Error;
+
+/// Decoder of [`Chain`] that rejected the token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    pub decoder: String,
+    pub failure: DecodeFailure,
+}
+
+/// Every decoder of [`Chain`] rejected the token, listed in the order they were tried
+#[derive(Error, Debug, Clone)]
+#[error("No decoder accepted the token: {}", Attempts(.attempts))]
+pub struct ChainError {
+    attempts: Vec<Attempt>,
+}
+
+impl ChainError {
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+}
+
+struct Attempts<'a>(&'a [Attempt]);
+
+impl fmt::Display for Attempts<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, attempt) in self.0.iter().enumerate() {
+            if n > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} ({})", attempt.decoder, attempt.failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Tries named decoders in order until one accepts the token, e.g. to accept tokens of
+/// both issuers during migration.
+///
+/// Once all of them fail, [`ChainError`] lists each attempt with its [`DecodeFailure`], so
+/// operators can tell "rotated key missing" apart from "token from unknown issuer".
+///
+/// ```rust
+/// # fn example(okta: tower_jwt::InPlace<serde_json::Value>, legacy: tower_jwt::InPlace<serde_json::Value>) {
+/// use tower_jwt::{Chain, Layer};
+///
+/// let layer = Layer::new(Chain::new("okta", okta).or("legacy", legacy));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Chain<D> {
+    decoders: Arc<[(String, D)]>,
+}
+
+impl<D> Clone for Chain<D> {
+    fn clone(&self) -> Self {
+        Self {
+            decoders: self.decoders.clone(),
+        }
+    }
+}
+
+impl<D> Chain<D> {
+    pub fn new(name: impl Into<String>, decoder: D) -> Self {
+        Self {
+            decoders: Arc::new([(name.into(), decoder)]),
+        }
+    }
+
+    /// Tries `decoder` once every decoder chained so far rejected the token
+    pub fn or(self, name: impl Into<String>, decoder: D) -> Self
+    where
+        D: Clone,
+    {
+        let mut decoders = self.decoders.to_vec();
+        decoders.push((name.into(), decoder));
+        Self {
+            decoders: decoders.into(),
+        }
+    }
+}
+
+impl<D> Decoder for Chain<D>
+where
+    D: Decoder,
+    D::Error: StdError + 'static,
+{
+    type Error = ChainError;
+    type Claim = D::Claim;
+    type Future = ChainFuture<D>;
+
+    fn decode(&self, token: &str) -> Self::Future {
+        ChainFuture {
+            decoders: self.decoders.clone(),
+            token: token.to_owned(),
+            attempts: Vec::new(),
+            current: self.decoders[0].1.decode(token),
+        }
+    }
+
+    /// Failure shared by every attempt, [`DecodeFailure::Other`] if they differ
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        let mut failures = err.attempts().iter().map(|attempt| attempt.failure);
+        let first = failures.next()?;
+        Some(match failures.all(|failure| failure == first) {
+            true => first,
+            false => DecodeFailure::Other,
+        })
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(err)
+    }
+}
+
+/// Future of [`Chain`] decoder
+#[pin_project]
+pub struct ChainFuture<D: Decoder> {
+    decoders: Arc<[(String, D)]>,
+    token: String,
+    attempts: Vec<Attempt>,
+    #[pin]
+    current: D::Future,
+}
+
+impl<D> Future for ChainFuture<D>
+where
+    D: Decoder,
+    D::Error: StdError + 'static,
+{
+    type Output = Result<D::Claim, ChainError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            let err = match this.current.as_mut().poll(cx) {
+                Poll::Ready(Ok(claim)) => return Poll::Ready(Ok(claim)),
+                Poll::Ready(Err(err)) => err,
+                Poll::Pending => return Poll::Pending,
+            };
+            let name = &this.decoders[this.attempts.len()].0;
+            tracing::debug!("Chain::rejected {}", name);
+            this.attempts.push(Attempt {
+                decoder: name.clone(),
+                failure: DecodeFailure::of(&err),
+            });
+            match this.decoders.get(this.attempts.len()) {
+                Some((_, decoder)) => this.current.set(decoder.decode(this.token)),
+                None => {
+                    return Poll::Ready(Err(ChainError {
+                        attempts: std::mem::take(this.attempts),
+                    }))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Chain;
+    use crate::{util, DecodeFailure, Decoder, InPlace};
+    use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+    use serde_json::{json, Value};
+
+    fn decoder(issuer: &str) -> InPlace<Value> {
+        let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes()).expect("Valid key");
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.required_spec_claims.clear();
+        validation.validate_exp = false;
+        validation.set_issuer(&[issuer]);
+        InPlace::new(key, validation)
+    }
+
+    #[tokio::test]
+    async fn chain() {
+        let chain = Chain::new("okta", decoder("okta")).or("legacy", decoder("legacy"));
+
+        let token = util::token(&json!({ "iss": "legacy", "sub": "user" }));
+        let claim = chain.decode(&token).await.expect("Accepted by legacy");
+        assert_eq!(claim["iss"], "legacy");
+
+        let token = util::token(&json!({ "iss": "unknown", "sub": "user" }));
+        let err = chain.decode(&token).await.expect_err("Rejected by all");
+        let failures: Vec<_> = err
+            .attempts()
+            .iter()
+            .map(|attempt| (attempt.decoder.as_str(), attempt.failure))
+            .collect();
+        assert_eq!(
+            failures,
+            [
+                ("okta", DecodeFailure::Issuer),
+                ("legacy", DecodeFailure::Issuer)
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "No decoder accepted the token: okta (issuer), legacy (issuer)"
+        );
+        let err = chain.decode("not.a.token").await.expect_err("Malformed");
+        assert_eq!(err.attempts()[0].failure, DecodeFailure::Malformed);
+    }
+}
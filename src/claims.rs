@@ -0,0 +1,172 @@
+use crate::{ClaimSlot, Denied, Gate, GateContext};
+use http::{Extensions, Request};
+use serde::{Deserialize, Deserializer};
+use std::{ops::Deref, sync::Arc};
+
+/// Returns claim set on request extensions by [`Middleware`][crate::Middleware].
+///
+/// Looks up both bare `C` and [`Claims<C>`] (as set by [`Shared`][crate::Shared] decoders),
+/// either on extensions or in [`ClaimSlot`], so callers only need to name the claim type itself.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// #[derive(Deserialize)]
+/// struct Claim { jti: String }
+///
+/// fn handler(req: http::Request<()>) {
+///     if let Some(claim) = tower_jwt::claims::<Claim>(&req) {
+///         println!("{}", claim.jti);
+///     }
+/// }
+/// ```
+pub fn claims<C>(req: &Request<impl Sized>) -> Option<&C>
+where
+    C: Send + Sync + 'static,
+{
+    claims_from_extensions(req.extensions())
+}
+
+/// Same as [`claims`], but operates on bare [`Extensions`]
+pub fn claims_from_extensions<C>(extensions: &Extensions) -> Option<&C>
+where
+    C: Send + Sync + 'static,
+{
+    extensions
+        .get::<C>()
+        .or_else(|| extensions.get::<Claims<C>>().map(AsRef::as_ref))
+        .or_else(|| extensions.get::<ClaimSlot<C>>()?.get())
+        .or_else(|| {
+            extensions
+                .get::<ClaimSlot<Claims<C>>>()?
+                .get()
+                .map(AsRef::as_ref)
+        })
+}
+
+/// Reference counted claim, set on request extensions by [`Shared`][crate::Shared] decoders.
+///
+/// Cloning [`Claims`] only bumps the reference count, so neither handlers
+/// nor claim types need to pay for (or implement) `Clone` on the claim itself.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Claims<C>(Arc<C>);
+
+impl<C> Claims<C> {
+    pub fn new(claim: C) -> Self {
+        Self(Arc::new(claim))
+    }
+
+    /// Returns underlying [`Arc`]
+    pub fn into_arc(self) -> Arc<C> {
+        self.0
+    }
+}
+
+impl<C> Clone for Claims<C> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<C> Deref for Claims<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<C> AsRef<C> for Claims<C> {
+    fn as_ref(&self) -> &C {
+        &self.0
+    }
+}
+
+impl<C> From<C> for Claims<C> {
+    fn from(claim: C) -> Self {
+        Self::new(claim)
+    }
+}
+
+impl<C> From<Arc<C>> for Claims<C> {
+    fn from(claim: Arc<C>) -> Self {
+        Self(claim)
+    }
+}
+
+impl<'de, C> Deserialize<'de> for Claims<C>
+where
+    C: Deserialize<'de>,
+{
+    fn deserialize<De>(deserializer: De) -> Result<Self, De::Error>
+    where
+        De: Deserializer<'de>,
+    {
+        C::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// [`Gate`] inserting `project(&claim)` on request extensions, see
+/// [`Layer::project`][crate::Layer::project]
+pub(crate) fn projection<C, T, F>(project: F) -> impl Gate
+where
+    C: Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    F: Fn(&C) -> T + Send + Sync + 'static,
+{
+    move |cx: &GateContext<'_>| {
+        let mut extensions = Extensions::new();
+        if let Some(claim) = cx.claims::<C>() {
+            extensions.insert(project(claim));
+        }
+        Ok::<_, Denied>(extensions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{claims, Claims};
+    use crate::{util, Middleware};
+    use http::Request;
+    use tower::{service_fn, ServiceExt};
+
+    #[test]
+    fn claims_lookup() {
+        let claim = util::claim(Some(100));
+
+        let mut bare = Request::new(());
+        bare.extensions_mut().insert(claim.clone());
+        assert_eq!(claims::<util::Claim>(&bare), Some(&claim));
+
+        let mut shared = Request::new(());
+        shared.extensions_mut().insert(Claims::new(claim.clone()));
+        assert_eq!(claims::<util::Claim>(&shared), Some(&claim));
+
+        assert_eq!(claims::<util::Claim>(&Request::new(())), None);
+    }
+
+    #[tokio::test]
+    async fn projections() {
+        #[derive(Debug, PartialEq)]
+        struct Projected(util::Claim);
+        #[derive(Debug, PartialEq)]
+        struct Expiring(bool);
+
+        let svc = service_fn(|req: Request<()>| async move {
+            let projected = req.extensions().get::<Projected>().map(|p| p.0.clone());
+            let expiring = req.extensions().get::<Expiring>().map(|e| e.0);
+            Ok::<_, ()>((projected, expiring))
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc)
+            .project(|claim: &util::Claim| Projected(claim.clone()))
+            .project(|_: &util::Claim| Expiring(true));
+
+        let claim = util::claim(Some(100));
+        let req = Request::builder()
+            .header("Authorization", format!("Bearer {}", util::token(&claim)))
+            .body(())
+            .expect("Valid request");
+        let (projected, expiring) = middleware.oneshot(req).await.expect("Valid token");
+        assert_eq!(projected, Some(claim));
+        assert_eq!(expiring, Some(true));
+    }
+}
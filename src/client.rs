@@ -0,0 +1,724 @@
+use crate::{BoxError, BoxFuture};
+use core::future::Future;
+use futures::{
+    future::{Shared, TryFutureExt},
+    ready, FutureExt,
+};
+use http::{header::AUTHORIZATION, HeaderValue, Request, Response, StatusCode};
+use pin_project::pin_project;
+use std::{
+    collections::{BTreeSet, HashMap},
+    error::Error,
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+use tower::Service;
+
+/// Token to request from [`TokenProvider`].
+///
+/// [`Authorize`] uses the one set on request extensions, if any, so different downstream
+/// calls can ask for different audiences and scopes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TokenRequest {
+    audience: Option<String>,
+    scopes: BTreeSet<String>,
+}
+
+impl TokenRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    pub fn with_scopes<I, T>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.scopes.extend(scopes.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn audience(&self) -> Option<&str> {
+        self.audience.as_deref()
+    }
+
+    /// Requested scopes, in order
+    pub fn scopes(&self) -> impl Iterator<Item = &str> {
+        self.scopes.iter().map(String::as_str)
+    }
+}
+
+/// Token obtained by [`TokenProvider`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessToken {
+    token: String,
+    expires_at: Option<SystemTime>,
+}
+
+impl AccessToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+            expires_at: None,
+        }
+    }
+
+    pub fn with_expires_at(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Expires `expires_in` from now, as in token endpoint responses
+    pub fn with_expires_in(self, expires_in: Duration) -> Self {
+        self.with_expires_at(SystemTime::now() + expires_in)
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+
+    /// Whether token is still valid `margin` from now
+    fn fresh(&self, margin: Duration) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| SystemTime::now() + margin < expires_at)
+    }
+}
+
+/// Obtains tokens for outgoing requests, see [`Authorize`].
+///
+/// Any `Fn(&TokenRequest) -> impl Future<Output = Result<AccessToken, BoxError>>` is a provider.
+pub trait TokenProvider: Send + Sync + 'static {
+    fn token(&self, request: &TokenRequest) -> BoxFuture<AccessToken, BoxError>;
+
+    /// Called once `token` obtained for `request` was rejected downstream, so that providers
+    /// holding on to it fetch a fresh one next time. Does nothing by default.
+    fn invalidate(&self, _request: &TokenRequest, _token: &AccessToken) {}
+}
+
+impl<F, Fut> TokenProvider for F
+where
+    F: Fn(&TokenRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<AccessToken, BoxError>> + Send + 'static,
+{
+    fn token(&self, request: &TokenRequest) -> BoxFuture<AccessToken, BoxError> {
+        Box::pin(self(request))
+    }
+}
+
+type SharedError = Arc<dyn Error + Send + Sync>;
+type Fetch = Shared<BoxFuture<AccessToken, SharedError>>;
+
+/// [`TokenProvider`] caching tokens of another one per audience and scope set.
+///
+/// Tokens are reused until shortly (thirty seconds by default) before they expire. Concurrent
+/// requests for a token which isn't cached share a single call to the provider, rather than
+/// stampeding the token endpoint.
+///
+/// ```rust
+/// # fn example<P: tower_jwt::TokenProvider>(provider: P) {
+/// use std::time::Duration;
+/// use tower_jwt::TokenCache;
+///
+/// let provider = TokenCache::new(provider).margin(Duration::from_secs(60));
+/// # }
+/// ```
+pub struct TokenCache<P> {
+    provider: Arc<P>,
+    margin: Duration,
+    fetches: Arc<Mutex<HashMap<TokenRequest, Fetch>>>,
+}
+
+impl<P> Clone for TokenCache<P> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            margin: self.margin,
+            fetches: self.fetches.clone(),
+        }
+    }
+}
+
+impl<P> TokenCache<P> {
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            margin: Duration::from_secs(30),
+            fetches: Arc::default(),
+        }
+    }
+
+    /// How long before expiry tokens are refreshed
+    pub fn margin(mut self, margin: Duration) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<TokenRequest, Fetch>> {
+        self.fetches
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Drop cached token for `request`, so the next one is fetched anew
+    pub fn invalidate(&self, request: &TokenRequest) {
+        self.lock().remove(request);
+    }
+}
+
+impl<P> fmt::Debug for TokenCache<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenCache")
+            .field("margin", &self.margin)
+            .field("tokens", &self.lock().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<P: TokenProvider> TokenProvider for TokenCache<P> {
+    fn token(&self, request: &TokenRequest) -> BoxFuture<AccessToken, BoxError> {
+        let mut fetches = self.lock();
+        let reusable = fetches.get(request).filter(|fetch| match fetch.peek() {
+            Some(Ok(token)) => token.fresh(self.margin),
+            Some(Err(_)) => false,
+            // still in flight
+            None => true,
+        });
+        let fetch = match reusable {
+            Some(fetch) => fetch.clone(),
+            None => {
+                let fetch: BoxFuture<AccessToken, SharedError> = self
+                    .provider
+                    .token(request)
+                    .map_err(SharedError::from)
+                    .boxed();
+                let fetch = fetch.shared();
+                fetches.insert(request.clone(), fetch.clone());
+                fetch
+            }
+        };
+        fetch.map_err(BoxError::from).boxed()
+    }
+
+    /// Drops cached token only if it's still the rejected one, as concurrent requests may
+    /// have replaced it already
+    fn invalidate(&self, request: &TokenRequest, token: &AccessToken) {
+        let mut fetches = self.lock();
+        let rejected = fetches
+            .get(request)
+            .and_then(|fetch| fetch.peek())
+            .is_some_and(|cached| matches!(cached, Ok(cached) if cached.token == token.token));
+        if rejected {
+            fetches.remove(request);
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AuthorizeError<E> {
+    #[error("Failed to obtain token")]
+    Token(#[source] BoxError),
+
+    #[error(transparent)]
+    Inner(E),
+}
+
+/// Sets `Authorization: Bearer <token>` obtained from [`TokenProvider`] on outgoing requests.
+///
+/// Token is requested for [`TokenRequest`] found on request extensions, falling back to the
+/// one configured on the layer. Wrap providers in [`TokenCache`] to reuse tokens across requests.
+///
+/// ```rust
+/// # fn example<S, P: tower_jwt::TokenProvider>(service: S, provider: P) {
+/// use tower::Layer;
+/// use tower_jwt::{AuthorizeLayer, TokenCache, TokenRequest};
+///
+/// let layer = AuthorizeLayer::new(TokenCache::new(provider))
+///     .request(TokenRequest::new().with_audience("api://orders"));
+/// let service = layer.layer(service);
+/// # }
+/// ```
+pub struct AuthorizeLayer<P> {
+    provider: Arc<P>,
+    request: TokenRequest,
+}
+
+impl<P> Clone for AuthorizeLayer<P> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            request: self.request.clone(),
+        }
+    }
+}
+
+impl<P> AuthorizeLayer<P> {
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            request: TokenRequest::default(),
+        }
+    }
+
+    /// Token requested for requests without [`TokenRequest`] extension
+    pub fn request(mut self, request: TokenRequest) -> Self {
+        self.request = request;
+        self
+    }
+
+    /// Retry requests rejected downstream with `401 Unauthorized` once, with a fresh token,
+    /// see [`RetryAuthorize`]
+    pub fn retry_unauthorized(self) -> RetryAuthorizeLayer<P> {
+        RetryAuthorizeLayer { layer: self }
+    }
+
+    fn token_request<'a, B>(&'a self, req: &'a Request<B>) -> &'a TokenRequest {
+        req.extensions()
+            .get::<TokenRequest>()
+            .unwrap_or(&self.request)
+    }
+}
+
+impl<P> fmt::Debug for AuthorizeLayer<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthorizeLayer")
+            .field("request", &self.request)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, P> tower::Layer<S> for AuthorizeLayer<P> {
+    type Service = Authorize<S, P>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        Authorize {
+            service,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service produced by [`AuthorizeLayer`]
+pub struct Authorize<S, P> {
+    service: S,
+    layer: AuthorizeLayer<P>,
+}
+
+impl<S: Clone, P> Clone for Authorize<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug, P> fmt::Debug for Authorize<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authorize")
+            .field("service", &self.service)
+            .field("layer", &self.layer)
+            .finish()
+    }
+}
+
+impl<S, P, B> Service<Request<B>> for Authorize<S, P>
+where
+    S: Service<Request<B>> + Clone,
+    P: TokenProvider,
+{
+    type Response = S::Response;
+    type Error = AuthorizeError<S::Error>;
+    type Future = AuthorizeFuture<S, B>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(AuthorizeError::Inner)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let clone = self.service.clone();
+        let service = core::mem::replace(&mut self.service, clone);
+        let request = self.layer.token_request(&req);
+        AuthorizeFuture {
+            service,
+            state: AuthorizeState::Fetching {
+                fetching: self.layer.provider.token(request),
+                request: Some(Box::new(req)),
+            },
+        }
+    }
+}
+
+#[pin_project]
+pub struct AuthorizeFuture<S, B>
+where
+    S: Service<Request<B>>,
+{
+    service: S,
+    #[pin]
+    state: AuthorizeState<S::Future, B>,
+}
+
+#[pin_project(project = AuthorizeStateProj)]
+enum AuthorizeState<F, B> {
+    Fetching {
+        fetching: BoxFuture<AccessToken, BoxError>,
+        request: Option<Box<Request<B>>>,
+    },
+    Calling(#[pin] F),
+}
+
+impl<S, B> Future for AuthorizeFuture<S, B>
+where
+    S: Service<Request<B>>,
+{
+    type Output = Result<S::Response, AuthorizeError<S::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            let calling = match this.state.as_mut().project() {
+                AuthorizeStateProj::Fetching { fetching, request } => {
+                    let token =
+                        ready!(fetching.as_mut().poll(cx)).map_err(AuthorizeError::Token)?;
+                    let mut request = *request
+                        .take()
+                        .expect("AuthorizeFuture polled after completion");
+                    authorize(&mut request, &token).map_err(AuthorizeError::Token)?;
+                    this.service.call(request)
+                }
+                AuthorizeStateProj::Calling(future) => {
+                    return future.poll(cx).map_err(AuthorizeError::Inner)
+                }
+            };
+            this.state.set(AuthorizeState::Calling(calling));
+        }
+    }
+}
+
+fn authorize<B>(request: &mut Request<B>, token: &AccessToken) -> Result<(), BoxError> {
+    let value = HeaderValue::from_str(&format!("Bearer {}", token.token()))?;
+    request.headers_mut().insert(AUTHORIZATION, value);
+    Ok(())
+}
+
+/// [`tower::Layer`] producing [`RetryAuthorize`] services, see
+/// [`AuthorizeLayer::retry_unauthorized`]
+pub struct RetryAuthorizeLayer<P> {
+    layer: AuthorizeLayer<P>,
+}
+
+impl<P> Clone for RetryAuthorizeLayer<P> {
+    fn clone(&self) -> Self {
+        Self {
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<P> fmt::Debug for RetryAuthorizeLayer<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryAuthorizeLayer")
+            .field("layer", &self.layer)
+            .finish()
+    }
+}
+
+impl<S, P> tower::Layer<S> for RetryAuthorizeLayer<P> {
+    type Service = RetryAuthorize<S, P>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RetryAuthorize {
+            service,
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+/// [`Authorize`] retrying requests rejected downstream with `401 Unauthorized` once.
+///
+/// Token used for the rejected request is [invalidated][TokenProvider::invalidate] and a fresh
+/// one requested for the retry, so token expiry races don't surface as errors. Retried requests
+/// are copies of the original one, hence request bodies must be `Clone`.
+pub struct RetryAuthorize<S, P> {
+    service: S,
+    layer: AuthorizeLayer<P>,
+}
+
+impl<S: Clone, P> Clone for RetryAuthorize<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug, P> fmt::Debug for RetryAuthorize<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryAuthorize")
+            .field("service", &self.service)
+            .field("layer", &self.layer)
+            .finish()
+    }
+}
+
+/// Copies request head and body, extensions are only carried over for [`TokenRequest`]
+fn copy<B: Clone>(req: &Request<B>) -> Request<B> {
+    let mut copy = Request::new(req.body().clone());
+    *copy.method_mut() = req.method().clone();
+    *copy.uri_mut() = req.uri().clone();
+    *copy.version_mut() = req.version();
+    *copy.headers_mut() = req.headers().clone();
+    if let Some(request) = req.extensions().get::<TokenRequest>() {
+        copy.extensions_mut().insert(request.clone());
+    }
+    copy
+}
+
+impl<S, P, B, RB> Service<Request<B>> for RetryAuthorize<S, P>
+where
+    S: Service<Request<B>, Response = Response<RB>> + Clone,
+    P: TokenProvider,
+    B: Clone,
+{
+    type Response = S::Response;
+    type Error = AuthorizeError<S::Error>;
+    type Future = RetryAuthorizeFuture<S, P, B>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(AuthorizeError::Inner)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let clone = self.service.clone();
+        let service = core::mem::replace(&mut self.service, clone);
+        let request = self.layer.token_request(&req).clone();
+        RetryAuthorizeFuture {
+            service,
+            provider: self.layer.provider.clone(),
+            state: RetryState::Fetching {
+                fetching: self.layer.provider.token(&request),
+                retry: Some(Box::new(copy(&req))),
+                request: Some(Box::new(req)),
+            },
+            token_request: request,
+            token: None,
+        }
+    }
+}
+
+#[pin_project]
+pub struct RetryAuthorizeFuture<S, P, B>
+where
+    S: Service<Request<B>>,
+{
+    service: S,
+    provider: Arc<P>,
+    token_request: TokenRequest,
+    /// Token the request was last sent with
+    token: Option<AccessToken>,
+    #[pin]
+    state: RetryState<S::Future, B>,
+}
+
+#[pin_project(project = RetryStateProj)]
+enum RetryState<F, B> {
+    Fetching {
+        fetching: BoxFuture<AccessToken, BoxError>,
+        request: Option<Box<Request<B>>>,
+        retry: Option<Box<Request<B>>>,
+    },
+    Ready {
+        request: Option<Box<Request<B>>>,
+        retry: Option<Box<Request<B>>>,
+    },
+    Calling {
+        #[pin]
+        future: F,
+        retry: Option<Box<Request<B>>>,
+    },
+}
+
+impl<S, P, B, RB> Future for RetryAuthorizeFuture<S, P, B>
+where
+    S: Service<Request<B>, Response = Response<RB>>,
+    P: TokenProvider,
+{
+    type Output = Result<S::Response, AuthorizeError<S::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            let next = match this.state.as_mut().project() {
+                RetryStateProj::Fetching {
+                    fetching,
+                    request,
+                    retry,
+                } => {
+                    let token =
+                        ready!(fetching.as_mut().poll(cx)).map_err(AuthorizeError::Token)?;
+                    let mut request = request
+                        .take()
+                        .expect("RetryAuthorizeFuture polled after completion");
+                    authorize(&mut request, &token).map_err(AuthorizeError::Token)?;
+                    *this.token = Some(token);
+                    RetryState::Ready {
+                        request: Some(request),
+                        retry: retry.take(),
+                    }
+                }
+                RetryStateProj::Ready { request, retry } => {
+                    ready!(this.service.poll_ready(cx)).map_err(AuthorizeError::Inner)?;
+                    let request = request
+                        .take()
+                        .expect("RetryAuthorizeFuture polled after completion");
+                    RetryState::Calling {
+                        future: this.service.call(*request),
+                        retry: retry.take(),
+                    }
+                }
+                RetryStateProj::Calling { future, retry } => {
+                    let response = ready!(future.poll(cx)).map_err(AuthorizeError::Inner)?;
+                    match retry.take() {
+                        Some(retry) if response.status() == StatusCode::UNAUTHORIZED => {
+                            tracing::debug!("Token rejected downstream, retrying with fresh one");
+                            if let Some(token) = this.token.take() {
+                                this.provider.invalidate(this.token_request, &token);
+                            }
+                            RetryState::Fetching {
+                                fetching: this.provider.token(this.token_request),
+                                request: Some(retry),
+                                retry: None,
+                            }
+                        }
+                        _ => return Poll::Ready(Ok(response)),
+                    }
+                }
+            };
+            this.state.set(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AccessToken, AuthorizeLayer, TokenCache, TokenProvider, TokenRequest};
+    use crate::BoxError;
+    use http::{header::AUTHORIZATION, Request, Response, StatusCode};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use tower::{service_fn, Layer, ServiceExt};
+
+    #[tokio::test]
+    async fn token_cache() {
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let counter = fetched.clone();
+        let cache = TokenCache::new(move |request: &TokenRequest| {
+            let n = counter.fetch_add(1, Ordering::Relaxed);
+            let token = format!("{}-{}", request.audience().unwrap_or("default"), n);
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                Ok::<_, BoxError>(AccessToken::new(token).with_expires_in(Duration::from_secs(600)))
+            }
+        });
+        let svc = service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(req.headers().get(AUTHORIZATION).cloned())
+        });
+        let svc = AuthorizeLayer::new(cache.clone())
+            .request(TokenRequest::new().with_audience("orders"))
+            .layer(svc);
+
+        let (a, b) = tokio::join!(
+            svc.clone().oneshot(Request::new(())),
+            svc.clone().oneshot(Request::new(()))
+        );
+        assert_eq!(
+            a.expect("Authorized"),
+            Some("Bearer orders-0".parse().unwrap())
+        );
+        assert_eq!(
+            b.expect("Authorized"),
+            Some("Bearer orders-0".parse().unwrap())
+        );
+        assert_eq!(fetched.load(Ordering::Relaxed), 1);
+
+        let mut req = Request::new(());
+        req.extensions_mut().insert(
+            TokenRequest::new()
+                .with_audience("billing")
+                .with_scopes(["read"]),
+        );
+        let header = svc.clone().oneshot(req).await.expect("Authorized");
+        assert_eq!(header, Some("Bearer billing-1".parse().unwrap()));
+
+        cache.invalidate(&TokenRequest::new().with_audience("orders"));
+        let header = svc.oneshot(Request::new(())).await.expect("Authorized");
+        assert_eq!(header, Some("Bearer orders-2".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn retry_unauthorized() {
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let counter = fetched.clone();
+        let cache = TokenCache::new(move |_: &TokenRequest| {
+            let token = format!("token-{}", counter.fetch_add(1, Ordering::Relaxed));
+            async move { Ok::<_, BoxError>(AccessToken::new(token)) }
+        });
+        // first token got revoked downstream
+        let svc = service_fn(|req: Request<&'static str>| async move {
+            let mut response = Response::new(*req.body());
+            if req
+                .headers()
+                .get(AUTHORIZATION)
+                .is_some_and(|v| v == "Bearer token-0")
+            {
+                *response.status_mut() = StatusCode::UNAUTHORIZED;
+            }
+            Ok::<_, ()>(response)
+        });
+        let svc = AuthorizeLayer::new(cache.clone())
+            .retry_unauthorized()
+            .layer(svc);
+
+        let response = svc
+            .clone()
+            .oneshot(Request::new("body"))
+            .await
+            .expect("Retried");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(*response.body(), "body");
+        assert_eq!(fetched.load(Ordering::Relaxed), 2);
+
+        let response = svc
+            .clone()
+            .oneshot(Request::new("body"))
+            .await
+            .expect("Authorized");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(fetched.load(Ordering::Relaxed), 2);
+
+        // late rejection of replaced token keeps the fresh one
+        TokenProvider::invalidate(&cache, &TokenRequest::new(), &AccessToken::new("token-0"));
+        let response = svc.oneshot(Request::new("body")).await.expect("Authorized");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(fetched.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,252 @@
+//! Claim struct generation, meant to be called from `build.rs`.
+//!
+//! [`ClaimStruct`] turns a sample token payload, or `claims_supported` of issuer
+//! [OIDC discovery document](https://openid.net/specs/openid-connect-discovery-1_0.html),
+//! into Rust source of a claim type ready to be used with any [`Decoder`][crate::Decoder].
+//! Fetching the document is up to the caller, so no HTTP client is pulled in.
+//!
+//! ```rust,no_run
+//! // build.rs
+//! use tower_jwt::codegen::ClaimStruct;
+//!
+//! let sample = std::fs::read_to_string("claims/sample.json").unwrap();
+//! let sample: serde_json::Value = serde_json::from_str(&sample).unwrap();
+//! let source = ClaimStruct::new("Claim").from_sample(&sample);
+//!
+//! let out = std::env::var("OUT_DIR").unwrap();
+//! std::fs::write(format!("{}/claim.rs", out), source).unwrap();
+//! // and then in the crate: include!(concat!(env!("OUT_DIR"), "/claim.rs"));
+//! ```
+
+use serde_json::{Map, Value};
+use std::fmt::Write;
+
+/// Types of registered claims, used when claim type can't be inferred from sample
+const REGISTERED: [(&str, &str); 12] = [
+    ("iss", "String"),
+    ("sub", "String"),
+    ("aud", "serde_json::Value"),
+    ("exp", "i64"),
+    ("nbf", "i64"),
+    ("iat", "i64"),
+    ("auth_time", "i64"),
+    ("jti", "String"),
+    ("email", "String"),
+    ("email_verified", "bool"),
+    ("phone_number_verified", "bool"),
+    ("updated_at", "i64"),
+];
+
+const KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "yield",
+];
+
+/// Generator of claim struct source, see [module documentation][self]
+#[derive(Debug, Clone)]
+pub struct ClaimStruct {
+    name: String,
+    derives: Vec<String>,
+}
+
+impl ClaimStruct {
+    /// Struct named `name`, nested objects are named after it
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            derives: vec![
+                String::from("Debug"),
+                String::from("Clone"),
+                String::from("serde::Deserialize"),
+            ],
+        }
+    }
+
+    /// Additionally derive `derive` on generated structs, e.g. `serde::Serialize`
+    pub fn derive(mut self, derive: impl Into<String>) -> Self {
+        self.derives.push(derive.into());
+        self
+    }
+
+    /// Source of struct whose fields match claims of `sample` payload.
+    ///
+    /// Claims which are `null` in sample become `Option<serde_json::Value>`, nested objects
+    /// become nested structs.
+    pub fn from_sample(&self, sample: &Value) -> String {
+        let mut source = String::new();
+        match sample {
+            Value::Object(claims) => self.emit(&mut source, &self.name, claims),
+            _ => self.emit(&mut source, &self.name, &Map::new()),
+        }
+        source
+    }
+
+    /// Source of struct with optional field per `claims_supported` of OIDC discovery document.
+    ///
+    /// Registered claims get their specified types, others are kept as `serde_json::Value`.
+    pub fn from_metadata(&self, metadata: &Value) -> String {
+        let claims = metadata
+            .get("claims_supported")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str);
+        let fields: Vec<_> = claims
+            .map(|claim| {
+                let ty = registered(claim).unwrap_or("serde_json::Value");
+                (claim.to_owned(), format!("Option<{}>", ty))
+            })
+            .collect();
+        let mut source = String::new();
+        self.write_struct(&mut source, &self.name, &fields);
+        source
+    }
+
+    fn emit(&self, source: &mut String, name: &str, claims: &Map<String, Value>) {
+        let mut nested = String::new();
+        let fields: Vec<_> = claims
+            .iter()
+            .map(|(claim, value)| {
+                let ty = self.infer(&mut nested, name, claim, value);
+                (claim.clone(), ty)
+            })
+            .collect();
+        self.write_struct(source, name, &fields);
+        source.push_str(&nested);
+    }
+
+    fn infer(&self, nested: &mut String, parent: &str, claim: &str, value: &Value) -> String {
+        match value {
+            Value::Null => String::from("Option<serde_json::Value>"),
+            Value::Bool(_) => String::from("bool"),
+            Value::Number(number) if number.is_i64() => String::from("i64"),
+            Value::Number(number) if number.is_u64() => String::from("u64"),
+            Value::Number(_) => String::from("f64"),
+            Value::String(_) => String::from("String"),
+            Value::Array(items) => {
+                let mut types = items
+                    .iter()
+                    .map(|item| self.infer(nested, parent, claim, item));
+                match types.next() {
+                    Some(first) if types.all(|ty| ty == first) => format!("Vec<{}>", first),
+                    _ => String::from("Vec<serde_json::Value>"),
+                }
+            }
+            Value::Object(claims) => {
+                let name = format!("{}{}", parent, pascal_case(claim));
+                // arrays of objects infer the same struct per item, emit it once
+                if !nested.contains(&format!("pub struct {} {{", name)) {
+                    self.emit(nested, &name, claims);
+                }
+                name
+            }
+        }
+    }
+
+    fn write_struct(&self, source: &mut String, name: &str, fields: &[(String, String)]) {
+        let _ = writeln!(source, "#[derive({})]", self.derives.join(", "));
+        let _ = writeln!(source, "pub struct {} {{", name);
+        for (claim, ty) in fields {
+            let field = field_name(claim);
+            if field.trim_start_matches("r#") != claim {
+                let _ = writeln!(source, "    #[serde(rename = {:?})]", claim);
+            }
+            if ty.starts_with("Option<") {
+                let _ = writeln!(source, "    #[serde(default)]");
+            }
+            let _ = writeln!(source, "    pub {}: {},", field, ty);
+        }
+        source.push_str("}\n");
+    }
+}
+
+fn registered(claim: &str) -> Option<&'static str> {
+    REGISTERED
+        .iter()
+        .find(|(name, _)| *name == claim)
+        .map(|(_, ty)| *ty)
+}
+
+/// Snake case identifier for `claim`, e.g. `cognito_groups` for `cognito:groups`
+fn field_name(claim: &str) -> String {
+    let mut field = String::with_capacity(claim.len());
+    for (index, c) in claim.chars().enumerate() {
+        match c {
+            c if c.is_ascii_uppercase() => {
+                if index > 0 && !field.ends_with('_') {
+                    field.push('_');
+                }
+                field.push(c.to_ascii_lowercase());
+            }
+            c if c.is_ascii_alphanumeric() => field.push(c),
+            _ if !field.is_empty() && !field.ends_with('_') => field.push('_'),
+            _ => {}
+        }
+    }
+    let field = field.trim_end_matches('_');
+    match field.chars().next() {
+        None => String::from("claim"),
+        Some(first) if first.is_ascii_digit() => format!("claim_{}", field),
+        _ if KEYWORDS.contains(&field) => format!("r#{}", field),
+        _ => field.to_owned(),
+    }
+}
+
+fn pascal_case(claim: &str) -> String {
+    field_name(claim)
+        .trim_start_matches("r#")
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::ClaimStruct;
+    use serde_json::json;
+
+    #[test]
+    fn claim_struct_from_sample() {
+        let sample = json!({
+            "sub": "248289761001",
+            "exp": 1311281970,
+            "cognito:groups": ["admin"],
+            "type": "user",
+            "address": { "country": "NL" },
+            "nickname": null,
+        });
+        let source = ClaimStruct::new("Claim").from_sample(&sample);
+        assert_eq!(
+            source,
+            r#"#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Claim {
+    pub address: ClaimAddress,
+    #[serde(rename = "cognito:groups")]
+    pub cognito_groups: Vec<String>,
+    pub exp: i64,
+    #[serde(default)]
+    pub nickname: Option<serde_json::Value>,
+    pub sub: String,
+    pub r#type: String,
+}
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ClaimAddress {
+    pub country: String,
+}
+"#
+        );
+
+        let metadata = json!({ "claims_supported": ["sub", "email_verified", "locale"] });
+        let source = ClaimStruct::new("Claim").from_metadata(&metadata);
+        assert!(source.contains("pub email_verified: Option<bool>,"));
+        assert!(source.contains("pub locale: Option<serde_json::Value>,"));
+    }
+}
@@ -0,0 +1,430 @@
+//! Conformance checks for custom [`Decoder`] implementations, available with `test-util` feature.
+//!
+//! [`check_decoder`] runs decoder against a battery of well-formed and hostile tokens signed
+//! by [`Fixture`], so third-party decoders can prove they accept what they should
+//! and reject everything else.
+//!
+//! ```rust
+//! # async fn example<D: tower_jwt::Decoder>(decoder: D, key: jsonwebtoken::EncodingKey) {
+//! use jsonwebtoken::{Algorithm, Header};
+//! use tower_jwt::conformance::{check_decoder, Fixture};
+//!
+//! let fixture = Fixture::new(Header::new(Algorithm::EdDSA), key)
+//!     .issuer("https://issuer.example")
+//!     .audience("orders");
+//! if let Err(failed) = check_decoder(&decoder, &fixture).await {
+//!     panic!("Decoder failed conformance: {:?}", failed);
+//! }
+//! # }
+//! ```
+//!
+//! [`Adversarial`] generates endless stream of malformed tokens for fuzzing handlers
+//! and decoders beyond fixed cases.
+
+use crate::Decoder;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{EncodingKey, Header};
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Size of oversized garbage token, decoders are expected to reject it without choking
+const HUGE_TOKEN: usize = 1 << 20;
+
+/// Signs tokens decoder under test is expected to accept, as long as they're valid
+pub struct Fixture {
+    header: Header,
+    key: EncodingKey,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+impl Fixture {
+    /// `header` and `key` must produce signatures decoder under test trusts,
+    /// `kid` of the header is expected to be known to decoder if set.
+    pub fn new(header: Header, key: EncodingKey) -> Self {
+        Self {
+            header,
+            key,
+            issuer: None,
+            audience: None,
+        }
+    }
+
+    /// Issuer decoder under test expects, enables [`Case::WrongIssuer`]
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Audience decoder under test expects, enables [`Case::WrongAudience`]
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    fn claim(&self) -> Value {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut claim = json!({
+            "sub": "conformance",
+            "jti": "conformance",
+            "iat": now,
+            "nbf": now,
+            "exp": now + 600,
+        });
+        if let Some(issuer) = &self.issuer {
+            claim["iss"] = json!(issuer);
+        }
+        if let Some(audience) = &self.audience {
+            claim["aud"] = json!(audience);
+        }
+        claim
+    }
+
+    fn sign(&self, header: &Header, claim: &Value) -> String {
+        jsonwebtoken::encode(header, claim, &self.key).expect("Fixture failed to sign token")
+    }
+
+    /// Token exercising `case`
+    pub fn token(&self, case: Case) -> String {
+        let mut claim = self.claim();
+        let mut header = self.header.clone();
+        match case {
+            Case::Valid => {}
+            Case::Expired => {
+                let exp = claim["iat"].as_u64().unwrap_or_default() - 3600;
+                claim["iat"] = json!(exp - 600);
+                claim["nbf"] = json!(exp - 600);
+                claim["exp"] = json!(exp);
+            }
+            Case::NotYetValid => {
+                let nbf = claim["iat"].as_u64().unwrap_or_default() + 3600;
+                claim["nbf"] = json!(nbf);
+                claim["exp"] = json!(nbf + 600);
+            }
+            Case::WrongIssuer => claim["iss"] = json!("https://attacker.example"),
+            Case::WrongAudience => claim["aud"] = json!("attacker"),
+            Case::UnknownKid => header.kid = Some(String::from("conformance-unknown-kid")),
+            Case::TamperedSignature => {
+                let token = self.sign(&header, &claim);
+                let (message, signature) = token.rsplit_once('.').unwrap_or_default();
+                let mut signature = URL_SAFE_NO_PAD.decode(signature).unwrap_or_default();
+                if let Some(byte) = signature.first_mut() {
+                    *byte ^= 0x01;
+                }
+                return format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature));
+            }
+            Case::TamperedPayload => {
+                let token = self.sign(&header, &claim);
+                claim["sub"] = json!("admin");
+                let payload = URL_SAFE_NO_PAD.encode(claim.to_string());
+                let mut segments = token.split('.');
+                let (header, signature) = (segments.next(), segments.nth(1));
+                return format!(
+                    "{}.{}.{}",
+                    header.unwrap_or_default(),
+                    payload,
+                    signature.unwrap_or_default()
+                );
+            }
+            Case::Unsigned => {
+                let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none","typ":"JWT"}"#);
+                let payload = URL_SAFE_NO_PAD.encode(claim.to_string());
+                return format!("{}.{}.", header, payload);
+            }
+            Case::Malformed => return String::from("not.a-token"),
+            Case::Huge => {
+                let segment = "A".repeat(HUGE_TOKEN / 3);
+                return format!("{}.{}.{}", segment, segment, segment);
+            }
+        }
+        self.sign(&header, &claim)
+    }
+}
+
+impl fmt::Debug for Fixture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fixture")
+            .field("header", &self.header)
+            .field("issuer", &self.issuer)
+            .field("audience", &self.audience)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Individual conformance check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Case {
+    /// Well-formed token signed by [`Fixture`] must be accepted
+    Valid,
+    /// Token with `exp` an hour in the past must be rejected
+    Expired,
+    /// Token with `nbf` an hour in the future must be rejected
+    NotYetValid,
+    /// Token issued by someone else must be rejected
+    WrongIssuer,
+    /// Token issued for someone else must be rejected
+    WrongAudience,
+    /// Token referring to unknown key must be rejected, only checked when [`Fixture`] sets `kid`
+    UnknownKid,
+    /// Token with altered signature must be rejected
+    TamperedSignature,
+    /// Token with payload altered after signing must be rejected
+    TamperedPayload,
+    /// Token with `alg: none` must be rejected
+    Unsigned,
+    /// Garbage must be rejected
+    Malformed,
+    /// Megabyte of garbage shaped like a token must be rejected
+    Huge,
+}
+
+impl Case {
+    fn expects_acceptance(&self) -> bool {
+        matches!(self, Self::Valid)
+    }
+
+    fn applies(&self, fixture: &Fixture) -> bool {
+        match self {
+            Self::WrongIssuer => fixture.issuer.is_some(),
+            Self::WrongAudience => fixture.audience.is_some(),
+            Self::UnknownKid => fixture.header.kid.is_some(),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expectation = match self.expects_acceptance() {
+            true => "accepted",
+            false => "rejected",
+        };
+        write!(f, "{:?} token must be {}", self, expectation)
+    }
+}
+
+const CASES: [Case; 11] = [
+    Case::Valid,
+    Case::Expired,
+    Case::NotYetValid,
+    Case::WrongIssuer,
+    Case::WrongAudience,
+    Case::UnknownKid,
+    Case::TamperedSignature,
+    Case::TamperedPayload,
+    Case::Unsigned,
+    Case::Malformed,
+    Case::Huge,
+];
+
+/// Runs every applicable [`Case`] against `decoder`, returns cases it failed
+pub async fn check_decoder<D: Decoder>(decoder: &D, fixture: &Fixture) -> Result<(), Vec<Case>> {
+    let mut failed = Vec::new();
+    for case in CASES.into_iter().filter(|case| case.applies(fixture)) {
+        let accepted = decoder.decode(&fixture.token(case)).await.is_ok();
+        if accepted != case.expects_acceptance() {
+            failed.push(case);
+        }
+    }
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(failed),
+    }
+}
+
+/// Header algorithms [`Adversarial`] swaps in, including lookalikes of legitimate ones
+const ALGORITHMS: [&str; 7] = ["none", "None", "HS256", "RS256", "\u{395}dDSA", "eddsa", ""];
+
+/// Characters [`Adversarial`] splices into tokens: zero-width space, right-to-left override,
+/// fullwidth full stop, byte order mark and a few which aren't valid base64url
+const SPLICES: [&str; 9] = [
+    "\u{200b}", "\u{202e}", "\u{ff0e}", "\u{feff}", "!", "+", "/", " ", "\n",
+];
+
+/// Deterministic generator of malformed and adversarial tokens derived from a valid one.
+///
+/// Every produced token is a mutation of the original which must be rejected: truncated or
+/// missing segments, invalid base64, swapped `alg`, flipped signature bits, unicode tricks.
+/// Same `seed` always yields the same sequence, so failures are reproducible.
+///
+/// ```rust
+/// # async fn example<D: tower_jwt::Decoder>(decoder: D, fixture: tower_jwt::conformance::Fixture) {
+/// use tower_jwt::conformance::{Adversarial, Case};
+///
+/// for token in Adversarial::new(fixture.token(Case::Valid), 42).take(1000) {
+///     assert!(decoder.decode(&token).await.is_err(), "Accepted {:?}", token);
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Adversarial {
+    token: String,
+    rng: XorShift,
+}
+
+impl Adversarial {
+    pub fn new(token: impl Into<String>, seed: u64) -> Self {
+        Self {
+            token: token.into(),
+            // xorshift gets stuck on zero
+            rng: XorShift(seed | 1),
+        }
+    }
+
+    fn truncate(&mut self) -> String {
+        let len = self.token.len();
+        let mut keep = len.saturating_sub(1 + self.rng.below(len / 2));
+        while !self.token.is_char_boundary(keep) {
+            keep -= 1;
+        }
+        self.token[..keep].to_owned()
+    }
+
+    fn drop_segment(&mut self) -> String {
+        let mut segments: Vec<_> = self.token.split('.').collect();
+        segments.remove(self.rng.below(segments.len()));
+        segments.join(".")
+    }
+
+    fn extra_segment(&mut self) -> String {
+        let segments: Vec<_> = self.token.split('.').collect();
+        let index = self.rng.below(segments.len());
+        format!("{}.{}", self.token, segments[index])
+    }
+
+    fn splice(&mut self) -> String {
+        let splice = SPLICES[self.rng.below(SPLICES.len())];
+        let mut token = self.token.clone();
+        let mut at = self.rng.below(token.len() + 1);
+        while !token.is_char_boundary(at) {
+            at -= 1;
+        }
+        token.insert_str(at, splice);
+        token
+    }
+
+    fn replace_dot(&mut self) -> String {
+        let replacement = ["\u{ff0e}", "\u{2024}", ",", ""][self.rng.below(4)];
+        let mut segments: Vec<_> = self.token.split('.').collect();
+        let at = (self.rng.below(2) + 1).min(segments.len());
+        let tail = segments.split_off(at).join(".");
+        format!("{}{}{}", segments.join("."), replacement, tail)
+    }
+
+    fn swap_alg(&mut self) -> String {
+        let alg = ALGORITHMS[self.rng.below(ALGORITHMS.len())];
+        let header = URL_SAFE_NO_PAD.encode(json!({ "alg": alg, "typ": "JWT" }).to_string());
+        let (_, rest) = self.token.split_once('.').unwrap_or_default();
+        format!("{}.{}", header, rest)
+    }
+
+    fn flip_signature(&mut self) -> String {
+        let (message, signature) = self.token.rsplit_once('.').unwrap_or_default();
+        let mut signature = URL_SAFE_NO_PAD.decode(signature).unwrap_or_default();
+        let index = self.rng.below(signature.len());
+        if let Some(byte) = signature.get_mut(index) {
+            *byte ^= 1 << self.rng.below(8);
+        }
+        format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound.max(1) as u64) as usize
+    }
+}
+
+impl Iterator for Adversarial {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = match self.rng.below(7) {
+            0 => self.truncate(),
+            1 => self.drop_segment(),
+            2 => self.extra_segment(),
+            3 => self.splice(),
+            4 => self.replace_dot(),
+            5 => self.swap_alg(),
+            _ => self.flip_signature(),
+        };
+        Some(token)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_decoder, Adversarial, Case, Fixture};
+    use crate::{util, Decoder, InPlace};
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+    use serde_json::{Map, Value};
+
+    fn fixture() -> Fixture {
+        let key = EncodingKey::from_ed_pem(util::PRIVATE_KEY.as_bytes()).expect("Valid key");
+        Fixture::new(Header::new(Algorithm::EdDSA), key)
+            .issuer("issuer")
+            .audience("orders")
+    }
+
+    fn decoder(validation: Validation) -> InPlace<Map<String, Value>> {
+        let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes()).expect("Valid key");
+        InPlace::new(key, validation)
+    }
+
+    #[tokio::test]
+    async fn conformance() {
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.leeway = 0;
+        validation.validate_nbf = true;
+        validation.set_issuer(&["issuer"]);
+        validation.set_audience(&["orders"]);
+        let outcome = check_decoder(&decoder(validation.clone()), &fixture()).await;
+        assert_eq!(outcome, Ok(()));
+
+        validation.insecure_disable_signature_validation();
+        let failed = check_decoder(&decoder(validation), &fixture())
+            .await
+            .expect_err("Decoder skipping signature validation must fail conformance");
+        assert!(failed.contains(&Case::TamperedSignature));
+        assert!(failed.contains(&Case::TamperedPayload));
+    }
+
+    #[tokio::test]
+    async fn adversarial() {
+        let decoder = decoder(Validation::new(Algorithm::EdDSA));
+        let token = fixture().token(Case::Valid);
+        assert!(decoder.decode(&token).await.is_ok());
+
+        let generated = Adversarial::new(token.as_str(), 7).take(1000);
+        for adversarial in generated {
+            assert_ne!(adversarial, token);
+            let outcome = decoder.decode(&adversarial).await;
+            assert!(outcome.is_err(), "Accepted {:?}", adversarial);
+        }
+
+        let first: Vec<_> = Adversarial::new(token.as_str(), 7).take(10).collect();
+        let second: Vec<_> = Adversarial::new(token.as_str(), 7).take(10).collect();
+        assert_eq!(first, second);
+
+        for token in ["", "a", "\u{e9}.\u{e9}"] {
+            assert_eq!(Adversarial::new(token, 7).take(100).count(), 100);
+        }
+    }
+}
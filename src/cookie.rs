@@ -0,0 +1,59 @@
+use http::{header::COOKIE, HeaderMap, HeaderValue};
+
+/// Returns value of the first cookie named `name` across all `Cookie` headers
+pub(crate) fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.trim_matches('"'))
+}
+
+/// Removes every cookie named `name` from `Cookie` headers, dropping headers left empty
+pub(crate) fn remove_cookie(headers: &mut HeaderMap, name: &str) {
+    let kept: Vec<_> = headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .map(|header| {
+            header
+                .split(';')
+                .map(str::trim)
+                .filter(|pair| pair.split_once('=').is_none_or(|(key, _)| key != name))
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+        .filter(|header| !header.is_empty())
+        .filter_map(|header| HeaderValue::from_str(&header).ok())
+        .collect();
+    headers.remove(COOKIE);
+    for header in kept {
+        headers.append(COOKIE, header);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cookie, remove_cookie};
+    use http::{header::COOKIE, HeaderMap, HeaderValue};
+
+    #[test]
+    fn cookie_lookup() {
+        let mut headers = HeaderMap::new();
+        headers.append(COOKIE, HeaderValue::from_static("theme=dark; session=abc"));
+        headers.append(COOKIE, HeaderValue::from_static("__Secure-Fgp=\"f00\""));
+
+        assert_eq!(cookie(&headers, "session"), Some("abc"));
+        assert_eq!(cookie(&headers, "__Secure-Fgp"), Some("f00"));
+        assert_eq!(cookie(&headers, "missing"), None);
+
+        remove_cookie(&mut headers, "session");
+        assert_eq!(cookie(&headers, "session"), None);
+        assert_eq!(cookie(&headers, "theme"), Some("dark"));
+        remove_cookie(&mut headers, "__Secure-Fgp");
+        assert_eq!(headers.get_all(COOKIE).iter().count(), 1);
+    }
+}
@@ -1,10 +1,13 @@
-use jsonwebtoken::{DecodingKey, Validation};
+use crate::{Claims, DecodeFailure, DecodeStats, KeySource, ValidationProfile};
+use futures::future::{MapOk, TryFutureExt};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
 use serde::de::DeserializeOwned;
 use std::{
     future::{self, Future, Ready},
     marker::PhantomData,
     sync::Arc,
 };
+use thiserror::Error;
 
 /// Implementors are capable of decoding jwt tokens returning associated claim or error.
 pub trait Decoder {
@@ -13,6 +16,22 @@ pub trait Decoder {
     type Future: Future<Output = Result<Self::Claim, Self::Error>>;
 
     fn decode(&self, token: &str) -> Self::Future;
+
+    /// Why token was rejected with `err`, reported on [`AuthFailure`][crate::AuthFailure].
+    ///
+    /// `None` unless overridden, as decoder errors aren't required to be inspectable.
+    /// Decoders wrapping others delegate to them.
+    fn failure(_err: &Self::Error) -> Option<DecodeFailure> {
+        None
+    }
+
+    /// `err` as [`std::error::Error`], so its [source][std::error::Error::source] chain
+    /// ends up on [`RejectionEvent`][crate::RejectionEvent].
+    ///
+    /// `None` unless overridden, decoders wrapping others delegate to them.
+    fn error(_err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
 }
 
 impl<C> Decoder for InPlace<C>
@@ -26,11 +45,25 @@ where
     #[tracing::instrument(skip_all)]
     fn decode(&self, token: &str) -> Self::Future {
         tracing::trace!("InPlace::entered");
+        if let Some(stats) = DecodeStats::current() {
+            stats.key_source(KeySource::Static);
+        }
         let decoded = jsonwebtoken::decode::<Self::Claim>(token, &self.key, &self.validation)
-            .map(|token_data| token_data.claims);
+            .and_then(|token_data| match &self.profile {
+                Some(profile) => profile.check(token).map(|_| token_data.claims),
+                None => Ok(token_data.claims),
+            });
         tracing::trace!("InPlace::decoded");
         future::ready(decoded)
     }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        Some(DecodeFailure::from(err.kind()))
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(err)
+    }
 }
 
 /// Simplest implementer of [`Decoder`] trait which
@@ -38,6 +71,7 @@ where
 pub struct InPlace<C> {
     validation: Validation,
     key: Arc<DecodingKey>,
+    profile: Option<ValidationProfile>,
     _claim: PhantomData<fn() -> C>,
 }
 
@@ -46,6 +80,7 @@ impl<C> Clone for InPlace<C> {
         Self {
             validation: self.validation.clone(),
             key: self.key.clone(),
+            profile: self.profile,
             _claim: PhantomData,
         }
     }
@@ -56,6 +91,7 @@ impl<C> InPlace<C> {
         Self {
             key: Arc::new(key),
             validation,
+            profile: None,
             _claim: PhantomData,
         }
     }
@@ -63,6 +99,78 @@ impl<C> InPlace<C> {
     pub fn builder() -> InPlaceBuilder<Empty, Empty> {
         Default::default()
     }
+
+    /// Apply [`ValidationProfile`] to decoder's validation
+    pub fn with_profile(mut self, profile: ValidationProfile) -> Self {
+        profile.apply(&mut self.validation);
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Development profile accepting tokens of any issuer and for any audience, for local
+    /// tunnels (ngrok, Heroku review apps, ..) whose changing hostnames break audience checks.
+    ///
+    /// **Never use in production**: tokens issued to any other application trusting the same
+    /// keys become valid here. Signature, algorithm and time-based checks stay enforced.
+    pub fn danger_relax_issuer_audience(mut self) -> Self {
+        tracing::warn!(
+            "InPlace decoder accepts tokens of ANY issuer for ANY audience, \
+            this must never be enabled in production"
+        );
+        self.validation.iss = None;
+        self.validation.aud = None;
+        self
+    }
+
+    /// Shared-secret decoder accepting only `HS256` tokens.
+    ///
+    /// `validation` algorithms are overridden, so tokens signed with any other algorithm
+    /// are rejected, and secrets shorter than hash output (32 bytes) are refused as
+    /// recommended by [RFC 7518](https://www.rfc-editor.org/rfc/rfc7518#section-3.2).
+    pub fn hs256(secret: &[u8], validation: Validation) -> Result<Self, WeakSecret> {
+        Self::hmac(Algorithm::HS256, secret, validation)
+    }
+
+    /// Same as [`InPlace::hs256`], but for `HS384` with minimum secret length of 48 bytes
+    pub fn hs384(secret: &[u8], validation: Validation) -> Result<Self, WeakSecret> {
+        Self::hmac(Algorithm::HS384, secret, validation)
+    }
+
+    /// Same as [`InPlace::hs256`], but for `HS512` with minimum secret length of 64 bytes
+    pub fn hs512(secret: &[u8], validation: Validation) -> Result<Self, WeakSecret> {
+        Self::hmac(Algorithm::HS512, secret, validation)
+    }
+
+    fn hmac(
+        algorithm: Algorithm,
+        secret: &[u8],
+        mut validation: Validation,
+    ) -> Result<Self, WeakSecret> {
+        let required = match algorithm {
+            Algorithm::HS256 => 32,
+            Algorithm::HS384 => 48,
+            _ => 64,
+        };
+        if secret.len() < required {
+            return Err(WeakSecret {
+                algorithm,
+                required,
+                actual: secret.len(),
+            });
+        }
+
+        validation.algorithms = vec![algorithm];
+        Ok(Self::new(DecodingKey::from_secret(secret), validation))
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{algorithm:?} requires secret of at least {required} bytes, got {actual}")]
+/// Returned when shared secret is too short for requested HMAC algorithm
+pub struct WeakSecret {
+    pub algorithm: Algorithm,
+    pub required: usize,
+    pub actual: usize,
 }
 
 #[derive(Debug, Default)]
@@ -72,6 +180,7 @@ pub struct Empty;
 pub struct InPlaceBuilder<K, V> {
     key: K,
     validation: V,
+    profile: Option<ValidationProfile>,
 }
 
 impl Default for InPlaceBuilder<Empty, Empty> {
@@ -79,16 +188,31 @@ impl Default for InPlaceBuilder<Empty, Empty> {
         Self {
             key: Default::default(),
             validation: Default::default(),
+            profile: None,
         }
     }
 }
 
+impl<K> InPlaceBuilder<K, Validation> {
+    /// Apply [`ValidationProfile`] to validation set on the builder
+    pub fn set_profile(mut self, profile: ValidationProfile) -> Self {
+        profile.apply(&mut self.validation);
+        self.profile = Some(profile);
+        self
+    }
+}
+
 impl InPlaceBuilder<DecodingKey, Validation> {
     pub fn build<C>(self) -> InPlace<C> {
-        let Self { key, validation } = self;
+        let Self {
+            key,
+            validation,
+            profile,
+        } = self;
         InPlace {
             validation,
             key: Arc::new(key),
+            profile,
             _claim: PhantomData,
         }
     }
@@ -96,23 +220,93 @@ impl InPlaceBuilder<DecodingKey, Validation> {
 
 impl<K, V> InPlaceBuilder<K, V> {
     pub fn new(key: K, validation: V) -> Self {
-        Self { key, validation }
+        Self {
+            key,
+            validation,
+            profile: None,
+        }
     }
 
     pub fn set_key(self, key: DecodingKey) -> InPlaceBuilder<DecodingKey, V> {
-        let Self { validation, .. } = self;
-        InPlaceBuilder { validation, key }
+        let Self {
+            validation,
+            profile,
+            ..
+        } = self;
+        InPlaceBuilder {
+            validation,
+            key,
+            profile,
+        }
     }
 
+    /// Replaces validation, along with any [`ValidationProfile`] applied to previous one
     pub fn set_validation(self, validation: Validation) -> InPlaceBuilder<K, Validation> {
         let Self { key, .. } = self;
-        InPlaceBuilder { key, validation }
+        InPlaceBuilder {
+            key,
+            validation,
+            profile: None,
+        }
+    }
+}
+
+/// Wraps any [`Decoder`] so that decoded claim is set on request extensions
+/// as reference counted [`Claims`] rather than bare claim.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # fn example(key: jsonwebtoken::DecodingKey, validation: jsonwebtoken::Validation) {
+/// use tower_jwt::{Claims, InPlace, Shared};
+///
+/// // Note: no `Clone` on the claim
+/// #[derive(Deserialize)]
+/// struct Claim { jti: String }
+///
+/// let decoder = Shared::new(InPlace::<Claim>::new(key, validation));
+/// // inner services get `Claims<Claim>` on req.extensions()
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Shared<D> {
+    decoder: D,
+}
+
+impl<D> Shared<D> {
+    pub fn new(decoder: D) -> Self {
+        Self { decoder }
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+impl<D> Decoder for Shared<D>
+where
+    D: Decoder,
+{
+    type Error = D::Error;
+    type Claim = Claims<D::Claim>;
+    type Future = MapOk<D::Future, fn(D::Claim) -> Claims<D::Claim>>;
+
+    fn decode(&self, token: &str) -> Self::Future {
+        self.decoder.decode(token).map_ok(Claims::new)
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        D::failure(err)
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        D::error(err)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{util, Decoder};
+    use super::WeakSecret;
+    use crate::{util, Decoder, InPlace, Shared};
 
     #[tokio::test]
     async fn in_place_not_expired() {
@@ -145,4 +339,86 @@ mod test {
             _ => unreachable!("Decoded expired claim"),
         }
     }
+
+    #[tokio::test]
+    async fn shared_not_expired() {
+        let decoder = Shared::new(util::in_place_decoder());
+        let valid = util::claim(Some(100));
+        let decoded = decoder
+            .decode(&util::token(&valid))
+            .await
+            .expect("Failed to decode token with shared decoder");
+
+        assert_eq!(*decoded, valid);
+        assert_eq!(decoded.clone().into_arc().as_ref(), &valid);
+    }
+
+    #[tokio::test]
+    async fn in_place_hmac() {
+        use jsonwebtoken::{errors::ErrorKind, Algorithm, EncodingKey, Header, Validation};
+
+        let secret = [7u8; 32];
+        let weak = InPlace::<util::Claim>::hs256(&secret[..31], Validation::default());
+        assert!(matches!(
+            weak,
+            Err(WeakSecret {
+                required: 32,
+                actual: 31,
+                ..
+            })
+        ));
+        let weak = InPlace::<util::Claim>::hs512(&secret, Validation::default());
+        assert!(matches!(
+            weak,
+            Err(WeakSecret {
+                required: 64,
+                actual: 32,
+                ..
+            })
+        ));
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.algorithms.push(Algorithm::EdDSA);
+        let decoder = InPlace::<util::Claim>::hs256(&secret, validation).expect("Strong secret");
+
+        let valid = util::claim(Some(100));
+        let token = jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &valid,
+            &EncodingKey::from_secret(&secret),
+        )
+        .expect("Failed to encode valid claim");
+        let decoded = decoder
+            .decode(&token)
+            .await
+            .expect("Failed to decode HS256 token");
+        assert_eq!(decoded, valid);
+
+        let asymmetric = decoder.decode(&util::token(&valid)).await;
+        match asymmetric {
+            Err(err) => assert_eq!(err.kind(), &ErrorKind::InvalidAlgorithm),
+            _ => unreachable!("Accepted EdDSA token with shared-secret decoder"),
+        }
+    }
+
+    #[tokio::test]
+    async fn in_place_relaxed() {
+        use jsonwebtoken::{errors::ErrorKind, DecodingKey, Validation};
+
+        let mut validation = Validation::new(jsonwebtoken::Algorithm::EdDSA);
+        validation.set_issuer(&["https://abc123.ngrok.io"]);
+        validation.set_audience(&["https://abc123.ngrok.io/api"]);
+        let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes()).expect("Valid key");
+        let decoder = InPlace::<util::Claim>::new(key, validation);
+        let token = util::token(&util::claim(Some(100)));
+        match decoder.decode(&token).await {
+            Err(err) => assert_eq!(err.kind(), &ErrorKind::InvalidIssuer),
+            _ => unreachable!("Accepted token of another issuer"),
+        }
+
+        let relaxed = decoder.danger_relax_issuer_audience();
+        assert!(relaxed.decode(&token).await.is_ok());
+        let expired = util::token(&util::claim(None));
+        assert!(relaxed.decode(&expired).await.is_err());
+    }
 }
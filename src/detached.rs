@@ -0,0 +1,279 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures::future::{self, Either, MapErr, Ready, TryFutureExt};
+use http::{header::HeaderName, Request};
+use jsonwebtoken::{Algorithm, DecodingKey};
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tower::Service;
+
+/// Protected header of detached JWS verified by [`DetachedJws`], set on request extensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct JwsHeader(Map<String, Value>);
+
+impl JwsHeader {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+
+    pub fn as_map(&self) -> &Map<String, Value> {
+        &self.0
+    }
+}
+
+/// [`Layer`][tower::Layer] verifying [RFC 7515 Appendix F](https://www.rfc-editor.org/rfc/rfc7515#appendix-F)
+/// detached JWS over the request body, as required by several payment-industry APIs.
+///
+/// Signature is read from configured header (`x-jws-signature` by default) as
+/// `<header>..<signature>`, with request body as payload. Unencoded payloads
+/// ([RFC 7797](https://www.rfc-editor.org/rfc/rfc7797), `"b64": false` listed in `crit`)
+/// are signed as is, others as base64url. Header algorithm must be one of allowed ones, and
+/// `crit` may list nothing but `b64`
+/// ([RFC 7515 §4.1.11](https://www.rfc-editor.org/rfc/rfc7515#section-4.1.11)).
+/// Protected header is set on request extensions as [`JwsHeader`].
+///
+/// Same as [`Webhook`][crate::Webhook], body must already be buffered.
+///
+/// ```rust
+/// # fn example(key: jsonwebtoken::DecodingKey) {
+/// use jsonwebtoken::Algorithm;
+/// use tower_jwt::DetachedJws;
+///
+/// let layer = DetachedJws::new(key, [Algorithm::PS256]);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct DetachedJws {
+    key: Arc<DecodingKey>,
+    algorithms: Vec<Algorithm>,
+    header: HeaderName,
+}
+
+impl DetachedJws {
+    pub fn new(key: DecodingKey, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        Self {
+            key: Arc::new(key),
+            algorithms: algorithms.into_iter().collect(),
+            header: HeaderName::from_static("x-jws-signature"),
+        }
+    }
+
+    /// Header carrying detached JWS
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    fn verify<E>(&self, jws: &str, body: &[u8]) -> Result<JwsHeader, DetachedJwsError<E>> {
+        let (encoded, signature) = match jws.trim().split_once("..") {
+            Some((header, signature)) if !signature.contains('.') => (header, signature),
+            _ => return Err(DetachedJwsError::Malformed),
+        };
+        let header: Map<String, Value> = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .ok()
+            .and_then(|header| serde_json::from_slice(&header).ok())
+            .ok_or(DetachedJwsError::Malformed)?;
+
+        let algorithm = header
+            .get("alg")
+            .and_then(Value::as_str)
+            .and_then(|alg| Algorithm::from_str(alg).ok())
+            .filter(|alg| self.algorithms.contains(alg))
+            .ok_or(DetachedJwsError::Algorithm)?;
+
+        // b64 is the only extension understood, anything else listed as critical is rejected
+        let critical = match header.get("crit") {
+            None => false,
+            Some(Value::Array(crit)) if !crit.is_empty() => {
+                if crit.iter().any(|name| name != "b64") {
+                    return Err(DetachedJwsError::Malformed);
+                }
+                true
+            }
+            Some(_) => return Err(DetachedJwsError::Malformed),
+        };
+        let encode = match header.get("b64") {
+            None if critical => return Err(DetachedJwsError::Malformed),
+            None | Some(Value::Bool(true)) => true,
+            Some(Value::Bool(false)) if critical => false,
+            Some(_) => return Err(DetachedJwsError::Malformed),
+        };
+
+        let mut message = Vec::with_capacity(encoded.len() + 1 + body.len() * 4 / 3 + 4);
+        message.extend_from_slice(encoded.as_bytes());
+        message.push(b'.');
+        match encode {
+            true => message.extend_from_slice(URL_SAFE_NO_PAD.encode(body).as_bytes()),
+            false => message.extend_from_slice(body),
+        }
+
+        match jsonwebtoken::crypto::verify(signature, &message, &self.key, algorithm) {
+            Ok(true) => Ok(JwsHeader(header)),
+            _ => Err(DetachedJwsError::Signature),
+        }
+    }
+}
+
+impl fmt::Debug for DetachedJws {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DetachedJws")
+            .field("algorithms", &self.algorithms)
+            .field("header", &self.header)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> tower::Layer<S> for DetachedJws {
+    type Service = DetachedJwsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DetachedJwsService {
+            jws: self.clone(),
+            service: inner,
+        }
+    }
+}
+
+/// [`Service`] produced by [`DetachedJws`]
+#[derive(Debug, Clone)]
+pub struct DetachedJwsService<S> {
+    jws: DetachedJws,
+    service: S,
+}
+
+#[derive(Error, Debug)]
+pub enum DetachedJwsError<E> {
+    #[error("Detached JWS header must be set")]
+    MissingSignature,
+
+    #[error("Detached JWS is malformed")]
+    Malformed,
+
+    #[error("Detached JWS algorithm is not allowed")]
+    Algorithm,
+
+    #[error("Detached JWS signature doesn't match body")]
+    Signature,
+
+    #[error(transparent)]
+    Inner(E),
+}
+
+type Inner<F, E> = MapErr<F, fn(E) -> DetachedJwsError<E>>;
+
+impl<S, B> Service<Request<B>> for DetachedJwsService<S>
+where
+    S: Service<Request<B>>,
+    B: AsRef<[u8]>,
+{
+    type Response = S::Response;
+    type Error = DetachedJwsError<S::Error>;
+    type Future = Either<Inner<S::Future, S::Error>, Ready<Result<S::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(DetachedJwsError::Inner)
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        tracing::trace!("DetachedJwsService::entered");
+        let verified = match req.headers().get(&self.jws.header).map(|v| v.to_str()) {
+            Some(Ok(jws)) => self.jws.verify(jws, req.body().as_ref()),
+            Some(Err(_)) => Err(DetachedJwsError::Malformed),
+            None => Err(DetachedJwsError::MissingSignature),
+        };
+        match verified {
+            Ok(header) => {
+                req.extensions_mut().insert(header);
+                let inner: fn(S::Error) -> DetachedJwsError<S::Error> = DetachedJwsError::Inner;
+                Either::Left(self.service.call(req).map_err(inner))
+            }
+            Err(err) => Either::Right(future::ready(Err(err))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DetachedJws, DetachedJwsError, JwsHeader};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use http::Request;
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+    use tower::{service_fn, Layer, ServiceExt};
+
+    const SECRET: &[u8] = b"detached-jws-secret";
+
+    fn detached(header: serde_json::Value, body: &[u8], unencoded: bool) -> String {
+        let header = URL_SAFE_NO_PAD.encode(header.to_string());
+        let mut message = format!("{}.", header).into_bytes();
+        match unencoded {
+            true => message.extend_from_slice(body),
+            false => message.extend_from_slice(URL_SAFE_NO_PAD.encode(body).as_bytes()),
+        }
+        let key = EncodingKey::from_secret(SECRET);
+        let signature =
+            jsonwebtoken::crypto::sign(&message, &key, Algorithm::HS256).expect("Valid key");
+        format!("{}..{}", header, signature)
+    }
+
+    #[tokio::test]
+    async fn detached_jws() {
+        let svc = service_fn(|req: Request<&'static [u8]>| async move {
+            Ok::<_, ()>(req.extensions().get::<JwsHeader>().cloned())
+        });
+        let layer = DetachedJws::new(DecodingKey::from_secret(SECRET), [Algorithm::HS256]);
+        let svc = layer.layer(svc);
+        let body: &[u8] = br#"{"amount":"10.00"}"#;
+
+        for (header, unencoded) in [
+            (serde_json::json!({ "alg": "HS256" }), false),
+            (
+                serde_json::json!({ "alg": "HS256", "b64": false, "crit": ["b64"] }),
+                true,
+            ),
+        ] {
+            let req = Request::builder()
+                .header("x-jws-signature", detached(header, body, unencoded))
+                .body(body)
+                .expect("Valid request");
+            let header = svc.clone().oneshot(req).await.expect("Valid signature");
+            assert!(header.is_some());
+        }
+
+        let jws = detached(serde_json::json!({ "alg": "HS256" }), body, false);
+        let req = Request::builder()
+            .header("x-jws-signature", jws)
+            .body(&br#"{"amount":"99.00"}"#[..])
+            .expect("Valid request");
+        let outcome = svc.clone().oneshot(req).await;
+        assert!(matches!(outcome, Err(DetachedJwsError::Signature)));
+
+        for header in [
+            serde_json::json!({ "alg": "HS256", "b64": false, "crit": ["b64", "exp"] }),
+            serde_json::json!({ "alg": "HS256", "exp": 0, "crit": ["exp"] }),
+            serde_json::json!({ "alg": "HS256", "crit": ["b64"] }),
+            serde_json::json!({ "alg": "HS256", "crit": [] }),
+        ] {
+            let req = Request::builder()
+                .header("x-jws-signature", detached(header, body, false))
+                .body(body)
+                .expect("Valid request");
+            let outcome = svc.clone().oneshot(req).await;
+            assert!(matches!(outcome, Err(DetachedJwsError::Malformed)));
+        }
+
+        let jws = detached(serde_json::json!({ "alg": "HS384" }), body, false);
+        let req = Request::builder()
+            .header("x-jws-signature", jws)
+            .body(body)
+            .expect("Valid request");
+        let outcome = svc.oneshot(req).await;
+        assert!(matches!(outcome, Err(DetachedJwsError::Algorithm)));
+    }
+}
@@ -0,0 +1,366 @@
+use crate::{BoxError, BoxFuture};
+use core::future::Future;
+use futures::future;
+use jsonwebtoken::{
+    jwk::{AlgorithmParameters, Jwk},
+    DecodingKey,
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Resolves verification keys of issuers identified by [DIDs](https://www.w3.org/TR/did-core/).
+pub trait DidResolver: Send + Sync + 'static {
+    /// Returns key of `did`, `kid` is token header `kid`, typically DID URL of verification method
+    fn resolve(&self, did: &str, kid: Option<&str>) -> BoxFuture<DecodingKey, BoxError>;
+}
+
+impl<F, Fut> DidResolver for F
+where
+    F: Fn(&str, Option<&str>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<DecodingKey, BoxError>> + Send + 'static,
+{
+    fn resolve(&self, did: &str, kid: Option<&str>) -> BoxFuture<DecodingKey, BoxError> {
+        Box::pin(self(did, kid))
+    }
+}
+
+/// Multicodec prefix of Ed25519 public keys, varint encoded
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+
+/// DID documents are cached for that long by default
+const DOCUMENT_TTL: Duration = Duration::from_secs(300);
+
+type Fetch = dyn Fn(String) -> BoxFuture<Vec<u8>, BoxError> + Send + Sync;
+type Documents = Arc<Mutex<HashMap<String, (Instant, Arc<Value>)>>>;
+
+#[derive(Error, Debug)]
+pub enum DidError {
+    #[error("DID method is not supported: {0}")]
+    UnsupportedMethod(String),
+
+    #[error("Malformed DID: {0}")]
+    Malformed(String),
+
+    #[error("Key type is not supported")]
+    UnsupportedKey,
+
+    #[error("DID document has no matching verification method")]
+    NoVerificationMethod,
+}
+
+/// [`DidResolver`] for `did:key` and (once fetcher is configured) `did:web` issuers.
+///
+/// `did:key` is resolved offline, `did:web` documents are fetched from
+/// `https://<domain>/.well-known/did.json` (or `https://<domain>/<path>/did.json`)
+/// through pluggable fetcher, so any HTTP client can be used, and cached for five minutes.
+/// Ed25519 keys are supported in multibase form, any key supported by `jsonwebtoken`
+/// in JWK form, except for symmetric ones.
+///
+/// Resolving a DID only yields its key, `did:key` in particular is self-certifying, so which
+/// issuers are trusted is decided by the decoder, e.g. [`JwtVc`][crate::JwtVc].
+///
+/// ```rust
+/// # async fn get(url: &str) -> Result<Vec<u8>, tower_jwt::BoxError> { todo!() }
+/// # fn example() {
+/// use tower_jwt::DidMethods;
+///
+/// let resolver = DidMethods::new().web(|url: String| async move { get(&url).await });
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct DidMethods {
+    fetch: Option<Arc<Fetch>>,
+    ttl: Option<Duration>,
+    documents: Documents,
+}
+
+impl DidMethods {
+    /// Resolves `did:key` only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve `did:web` by fetching DID documents with `fetch`
+    pub fn web<F, Fut>(mut self, fetch: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<u8>, BoxError>> + Send + 'static,
+    {
+        self.fetch = Some(Arc::new(move |url| Box::pin(fetch(url))));
+        self
+    }
+
+    /// How long fetched DID documents are cached for, zero disables caching
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    fn resolve_web(&self, did: &str, kid: Option<&str>) -> BoxFuture<DecodingKey, BoxError> {
+        let (fetch, url) = match (self.fetch.clone(), web_url(did)) {
+            (Some(fetch), Ok(url)) => (fetch, url),
+            (None, _) => {
+                let err = DidError::UnsupportedMethod(String::from("web"));
+                return Box::pin(future::ready(Err(err.into())));
+            }
+            (_, Err(err)) => return Box::pin(future::ready(Err(err.into()))),
+        };
+        let (did, kid) = (did.to_owned(), kid.map(String::from));
+        let ttl = self.ttl.unwrap_or(DOCUMENT_TTL);
+        let documents = self.documents.clone();
+        let cached = {
+            let documents = documents.lock().unwrap_or_else(|e| e.into_inner());
+            documents
+                .get(&did)
+                .filter(|(fetched, _)| fetched.elapsed() < ttl)
+                .map(|(_, document)| document.clone())
+        };
+
+        Box::pin(async move {
+            let document = match cached {
+                Some(document) => document,
+                None => {
+                    let body = fetch(url).await?;
+                    let document = Arc::new(serde_json::from_slice::<Value>(&body)?);
+                    if !ttl.is_zero() {
+                        let mut documents = documents.lock().unwrap_or_else(|e| e.into_inner());
+                        documents.retain(|_, (fetched, _)| fetched.elapsed() < ttl);
+                        documents.insert(did.clone(), (Instant::now(), document.clone()));
+                    }
+                    document
+                }
+            };
+            document_key(&did, &document, kid.as_deref())
+        })
+    }
+}
+
+impl DidResolver for DidMethods {
+    fn resolve(&self, did: &str, kid: Option<&str>) -> BoxFuture<DecodingKey, BoxError> {
+        match did.split(':').nth(1) {
+            Some("key") => Box::pin(future::ready(did_key(did).map_err(BoxError::from))),
+            Some("web") => self.resolve_web(did, kid),
+            Some(method) => {
+                let err = DidError::UnsupportedMethod(method.to_owned());
+                Box::pin(future::ready(Err(err.into())))
+            }
+            None => Box::pin(future::ready(Err(
+                DidError::Malformed(did.to_owned()).into()
+            ))),
+        }
+    }
+}
+
+impl fmt::Debug for DidMethods {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DidMethods")
+            .field("web", &self.fetch.is_some())
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Key encoded in `did:key:z<base58btc multicodec key>`
+fn did_key(did: &str) -> Result<DecodingKey, DidError> {
+    let encoded = did
+        .strip_prefix("did:key:")
+        .map(|key| key.split('#').next().unwrap_or(key))
+        .ok_or_else(|| DidError::Malformed(did.to_owned()))?;
+    multibase_key(encoded).ok_or_else(|| DidError::Malformed(did.to_owned()))?
+}
+
+fn multibase_key(encoded: &str) -> Option<Result<DecodingKey, DidError>> {
+    let bytes = base58_decode(encoded.strip_prefix('z')?)?;
+    Some(match bytes.strip_prefix(&ED25519_PUB[..]) {
+        Some(key) if key.len() == 32 => Ok(DecodingKey::from_ed_der(key)),
+        _ => Err(DidError::UnsupportedKey),
+    })
+}
+
+/// `did:web:example.com:users:alice` is hosted at `https://example.com/users/alice/did.json`
+fn web_url(did: &str) -> Result<String, DidError> {
+    let mut segments = did
+        .strip_prefix("did:web:")
+        .map(|rest| rest.split('#').next().unwrap_or(rest))
+        .filter(|rest| !rest.is_empty())
+        .ok_or_else(|| DidError::Malformed(did.to_owned()))?
+        .split(':');
+    let domain = segments.next().unwrap_or_default().replace("%3A", ":");
+    if domain.is_empty() || domain.contains(['/', '\\', '?', '@']) {
+        return Err(DidError::Malformed(did.to_owned()));
+    }
+    let path: Vec<_> = segments.collect();
+    if !path.iter().all(|segment| path_segment(segment)) {
+        return Err(DidError::Malformed(did.to_owned()));
+    }
+    Ok(match path.is_empty() {
+        true => format!("https://{}/.well-known/did.json", domain),
+        false => format!("https://{}/{}/did.json", domain, path.join("/")),
+    })
+}
+
+/// Whether `segment` stays within its own path segment once it's part of the URL
+fn path_segment(segment: &str) -> bool {
+    let lowercase = segment.to_ascii_lowercase();
+    let decoded = lowercase.replace("%2e", ".");
+    !matches!(decoded.as_str(), "" | "." | "..")
+        && !lowercase.contains("%2f")
+        && !lowercase.contains("%5c")
+        && !segment.contains(['/', '\\', '?', '#', '@'])
+}
+
+/// Picks verification method matching `kid`, or the first one if token doesn't name it
+fn document_key(did: &str, document: &Value, kid: Option<&str>) -> Result<DecodingKey, BoxError> {
+    let fragment = kid.and_then(|kid| kid.rsplit_once('#').map(|(_, fragment)| fragment));
+    let method = document
+        .get("verificationMethod")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .find(|method| {
+            let id = method.get("id").and_then(Value::as_str).unwrap_or_default();
+            match (kid, fragment) {
+                (None, _) => true,
+                (Some(kid), Some(fragment)) => {
+                    id == kid
+                        || id == format!("#{}", fragment)
+                        || id == format!("{}#{}", did, fragment)
+                }
+                (Some(kid), None) => id == kid,
+            }
+        })
+        .ok_or(DidError::NoVerificationMethod)?;
+
+    if let Some(jwk) = method.get("publicKeyJwk") {
+        let jwk: Jwk = serde_json::from_value(jwk.clone())?;
+        if matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_)) {
+            return Err(DidError::UnsupportedKey.into());
+        }
+        return Ok(DecodingKey::from_jwk(&jwk)?);
+    }
+    match method.get("publicKeyMultibase").and_then(Value::as_str) {
+        Some(encoded) => Ok(multibase_key(encoded).ok_or(DidError::UnsupportedKey)??),
+        None => Err(DidError::UnsupportedKey.into()),
+    }
+}
+
+fn base58_decode(encoded: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let mut bytes: Vec<u8> = Vec::with_capacity(encoded.len());
+    for c in encoded.bytes() {
+        let mut carry = ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = encoded.bytes().take_while(|&c| c == b'1').count();
+    bytes.extend(std::iter::repeat_n(0, zeros));
+    bytes.reverse();
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{web_url, DidMethods, DidResolver};
+    use crate::BoxError;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, Validation};
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use serde_json::{json, Value};
+
+    // RFC 8032 test vector 1
+    const SEED: [u8; 32] = [
+        0x9d, 0x61, 0xb1, 0x9d, 0xef, 0xfd, 0x5a, 0x60, 0xba, 0x84, 0x4a, 0xf4, 0x92, 0xec, 0x2c,
+        0xc4, 0x44, 0x49, 0xc5, 0x69, 0x7b, 0x32, 0x69, 0x19, 0x70, 0x3b, 0xac, 0x03, 0x1c, 0xae,
+        0x7f, 0x60,
+    ];
+    const DID_KEY: &str = "did:key:z6MktwupdmLXVVqTzCw4i46r4uGyosGXRnR3XjN4Zq7oMMsw";
+
+    fn token() -> String {
+        // PKCS#8 v1 wrapping of the seed
+        let mut der = vec![
+            0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22,
+            0x04, 0x20,
+        ];
+        der.extend_from_slice(&SEED);
+        let claim = json!({ "sub": "holder", "exp": chrono::Utc::now().timestamp() + 100 });
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::EdDSA),
+            &claim,
+            &EncodingKey::from_ed_der(&der),
+        )
+        .expect("Failed to encode valid claim")
+    }
+
+    fn verify(key: &jsonwebtoken::DecodingKey) -> bool {
+        jsonwebtoken::decode::<Value>(&token(), key, &Validation::new(Algorithm::EdDSA)).is_ok()
+    }
+
+    #[tokio::test]
+    async fn did_key() {
+        let key = DidMethods::new()
+            .resolve(DID_KEY, None)
+            .await
+            .expect("Failed to resolve did:key");
+        assert!(verify(&key));
+
+        let outcome = DidMethods::new().resolve("did:web:example.com", None).await;
+        assert!(outcome.is_err(), "did:web must not resolve without fetcher");
+    }
+
+    #[tokio::test]
+    async fn did_web() {
+        let pair = Ed25519KeyPair::from_seed_unchecked(&SEED).expect("Valid seed");
+        let x = URL_SAFE_NO_PAD.encode(pair.public_key().as_ref());
+        let document = json!({
+            "id": "did:web:issuer.example:tenants:acme",
+            "verificationMethod": [
+                { "id": "#other", "publicKeyMultibase": "zQ3s" },
+                { "id": "did:web:issuer.example:tenants:acme#signing", "publicKeyJwk": { "kty": "OKP", "crv": "Ed25519", "x": x } },
+            ],
+        });
+        let resolver = DidMethods::new().web(move |url: String| {
+            let document = document.clone();
+            async move {
+                assert_eq!(url, "https://issuer.example/tenants/acme/did.json");
+                Ok::<_, BoxError>(serde_json::to_vec(&document)?)
+            }
+        });
+
+        let key = resolver
+            .resolve(
+                "did:web:issuer.example:tenants:acme",
+                Some("did:web:issuer.example:tenants:acme#signing"),
+            )
+            .await
+            .expect("Failed to resolve did:web");
+        assert!(verify(&key));
+
+        assert_eq!(
+            web_url("did:web:localhost%3A8443").expect("Valid DID"),
+            "https://localhost:8443/.well-known/did.json"
+        );
+        assert!(web_url("did:web:evil.example/path").is_err());
+        for did in [
+            "did:web:issuer.example:..:admin",
+            "did:web:issuer.example:%2E%2e:admin",
+            "did:web:issuer.example::users",
+            "did:web:issuer.example:users%2Falice",
+            "did:web::users",
+        ] {
+            assert!(web_url(did).is_err(), "{} must be rejected", did);
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use crate::{DecodeFailure, Decoder, Payload};
+use core::future::Future;
+use futures::ready;
+use pin_project::pin_project;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    task::{Context, Poll},
+};
+
+/// JSON type of a claim, see [`Drift`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimKind {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl ClaimKind {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(_) => Self::Bool,
+            Value::Number(_) => Self::Number,
+            Value::String(_) => Self::String,
+            Value::Array(_) => Self::Array,
+            Value::Object(_) => Self::Object,
+        }
+    }
+}
+
+/// Change in claims issued by an issuer, reported by [`ClaimDrift`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    /// Claim never seen before from the issuer
+    Added {
+        issuer: String,
+        claim: String,
+        kind: ClaimKind,
+    },
+    /// Claim changed its type
+    Changed {
+        issuer: String,
+        claim: String,
+        from: ClaimKind,
+        to: ClaimKind,
+    },
+}
+
+impl Drift {
+    pub fn issuer(&self) -> &str {
+        match self {
+            Self::Added { issuer, .. } | Self::Changed { issuer, .. } => issuer,
+        }
+    }
+
+    pub fn claim(&self) -> &str {
+        match self {
+            Self::Added { claim, .. } | Self::Changed { claim, .. } => claim,
+        }
+    }
+}
+
+type Callback = dyn Fn(&Drift) + Send + Sync;
+
+#[derive(Default)]
+struct Schemas {
+    issuers: Mutex<HashMap<String, HashMap<String, ClaimKind>>>,
+    seen: AtomicU64,
+}
+
+impl Schemas {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, HashMap<String, ClaimKind>>> {
+        self.issuers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Updates baseline of payload issuer, returns detected changes
+    fn record(&self, payload: &Payload) -> Vec<Drift> {
+        let issuer = payload.str("iss").unwrap_or_default();
+        let mut issuers = self.lock();
+        let first = !issuers.contains_key(issuer);
+        let schema = issuers.entry(issuer.to_owned()).or_default();
+        payload
+            .as_map()
+            .iter()
+            .filter_map(|(claim, value)| {
+                let kind = ClaimKind::of(value);
+                match schema.insert(claim.clone(), kind) {
+                    None if !first => Some(Drift::Added {
+                        issuer: issuer.to_owned(),
+                        claim: claim.clone(),
+                        kind,
+                    }),
+                    Some(from) if from != kind => Some(Drift::Changed {
+                        issuer: issuer.to_owned(),
+                        claim: claim.clone(),
+                        from,
+                        to: kind,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Wraps any [`Decoder`] watching claims of accepted tokens for schema drift, so IdP-side
+/// changes are noticed before they break deserialization.
+///
+/// Claims of the first sampled token of each issuer (`iss`) form its baseline, later tokens
+/// introducing new claims or changing JSON type of known ones are logged and reported to
+/// optional callback, and the baseline is updated. Claims missing from a token are not
+/// considered drift, as many claims are optional.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder>(decoder: D) {
+/// use tower_jwt::ClaimDrift;
+///
+/// let decoder = ClaimDrift::new(decoder).sample(100).on_drift(|drift| {
+///     eprintln!("{} changed {}", drift.issuer(), drift.claim());
+/// });
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ClaimDrift<D> {
+    decoder: D,
+    every: u64,
+    schemas: Arc<Schemas>,
+    on_drift: Option<Arc<Callback>>,
+}
+
+impl<D> ClaimDrift<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            every: 1,
+            schemas: Arc::default(),
+            on_drift: None,
+        }
+    }
+
+    /// Only inspect every `every`-th decoded token, 1 inspects all of them
+    pub fn sample(mut self, every: u64) -> Self {
+        self.every = every.max(1);
+        self
+    }
+
+    /// Call `callback` for every detected change
+    pub fn on_drift<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Drift) + Send + Sync + 'static,
+    {
+        self.on_drift = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+impl<D: fmt::Debug> fmt::Debug for ClaimDrift<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClaimDrift")
+            .field("decoder", &self.decoder)
+            .field("every", &self.every)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: Decoder> Decoder for ClaimDrift<D> {
+    type Error = D::Error;
+    type Claim = D::Claim;
+    type Future = ClaimDriftFuture<D::Future>;
+
+    fn decode(&self, token: &str) -> Self::Future {
+        let seen = self.schemas.seen.fetch_add(1, Ordering::Relaxed);
+        let sampled = seen.is_multiple_of(self.every);
+        let payload = sampled.then(|| Payload::from_token(token)).flatten();
+        ClaimDriftFuture {
+            inner: self.decoder.decode(token),
+            sampled: payload.map(|payload| Sampled {
+                payload,
+                schemas: self.schemas.clone(),
+                on_drift: self.on_drift.clone(),
+            }),
+        }
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        D::failure(err)
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        D::error(err)
+    }
+}
+
+struct Sampled {
+    payload: Payload,
+    schemas: Arc<Schemas>,
+    on_drift: Option<Arc<Callback>>,
+}
+
+#[pin_project]
+pub struct ClaimDriftFuture<F> {
+    #[pin]
+    inner: F,
+    sampled: Option<Sampled>,
+}
+
+impl<F, T, E> Future for ClaimDriftFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let outcome = ready!(this.inner.poll(cx));
+        if let (Ok(_), Some(sampled)) = (&outcome, this.sampled.take()) {
+            for drift in sampled.schemas.record(&sampled.payload) {
+                tracing::warn!(?drift, "Issuer claims drifted");
+                if let Some(on_drift) = &sampled.on_drift {
+                    on_drift(&drift);
+                }
+            }
+        }
+        Poll::Ready(outcome)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClaimDrift, ClaimKind, Drift};
+    use crate::{util, Decoder};
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn claim_drift() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let decoder = ClaimDrift::new(util::in_place_decoder())
+            .on_drift(move |drift| sink.lock().unwrap().push(drift.clone()));
+
+        let key = EncodingKey::from_ed_pem(util::PRIVATE_KEY.as_bytes()).expect("Valid key");
+        let claim = serde_json::to_value(util::claim(Some(100))).expect("Valid claim");
+        let token = |extra: serde_json::Value| {
+            let mut claims = claim.clone();
+            claims
+                .as_object_mut()
+                .expect("Claim is an object")
+                .extend(extra.as_object().cloned().unwrap_or_default());
+            encode(&Header::new(Algorithm::EdDSA), &claims, &key).expect("Valid token")
+        };
+
+        decoder
+            .decode(&token(json!({ "roles": ["admin"] })))
+            .await
+            .expect("Valid token");
+        decoder
+            .decode(&token(json!({})))
+            .await
+            .expect("Valid token");
+        assert!(reported.lock().unwrap().is_empty());
+
+        decoder
+            .decode(&token(json!({ "roles": "admin", "tid": "t1" })))
+            .await
+            .expect("Valid token");
+        let mut drifts = reported.lock().unwrap().clone();
+        drifts.sort_by(|a, b| a.claim().cmp(b.claim()));
+        assert_eq!(
+            drifts,
+            vec![
+                Drift::Changed {
+                    issuer: String::from("issuer"),
+                    claim: String::from("roles"),
+                    from: ClaimKind::Array,
+                    to: ClaimKind::String,
+                },
+                Drift::Added {
+                    issuer: String::from("issuer"),
+                    claim: String::from("tid"),
+                    kind: ClaimKind::String,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{cache::TtlCache, BoxError, BoxFuture, Denied, Gate, GateContext, Payload};
+use core::future::Future;
+use futures::{future, FutureExt};
+use http::Extensions;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Concrete entitlements coarse claims expand into, set on request extensions
+/// by [`ExpandEntitlements`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Entitlements(Arc<BTreeSet<String>>);
+
+impl Entitlements {
+    pub fn contains(&self, entitlement: &str) -> bool {
+        self.0.contains(entitlement)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for Entitlements {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        Self(Arc::new(iter.into_iter().map(Into::into).collect()))
+    }
+}
+
+/// Service expanding coarse claims into [`Entitlements`], see [`ExpandEntitlements`].
+pub trait EntitlementSource: Send + Sync + 'static {
+    fn expand(&self, payload: &Payload) -> BoxFuture<Entitlements, BoxError>;
+}
+
+impl<F, Fut> EntitlementSource for F
+where
+    F: Fn(&Payload) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Entitlements, BoxError>> + Send + 'static,
+{
+    fn expand(&self, payload: &Payload) -> BoxFuture<Entitlements, BoxError> {
+        Box::pin(self(payload))
+    }
+}
+
+/// Background revalidation handed over to executor, see
+/// [`stale_while_revalidate`][ExpandEntitlements::stale_while_revalidate]
+pub type Revalidation = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// [`Gate`] resolving coarse claims (plan, organization, ..) into concrete [`Entitlements`]
+/// through [`EntitlementSource`], for downstream authorization layers to consult.
+///
+/// Expansions are cached per values of `claims` (a minute by default), so source must
+/// only depend on those. Failing expansions deny the request.
+///
+/// ```rust
+/// # use tower_jwt::{BoxError, Entitlements, Payload};
+/// # async fn lookup(plan: &str) -> Result<Entitlements, BoxError> { todo!() }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use std::time::Duration;
+/// use tower_jwt::{ExpandEntitlements, Layer};
+///
+/// let expand = ExpandEntitlements::new(["plan", "org"], |payload: &Payload| {
+///     let plan = payload.str("plan").unwrap_or("free").to_owned();
+///     async move { lookup(&plan).await }
+/// })
+/// .stale_while_revalidate(Duration::from_secs(300), |revalidation| {
+///     tokio::spawn(revalidation);
+/// });
+/// let layer = Layer::new(decoder).gate(expand);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ExpandEntitlements {
+    claims: Arc<[String]>,
+    source: Arc<dyn EntitlementSource>,
+    ttl: Duration,
+    revalidate: Option<Revalidate>,
+    cache: TtlCache<Entitlements>,
+}
+
+#[derive(Clone)]
+struct Revalidate {
+    spawn: Arc<dyn Fn(Revalidation) + Send + Sync>,
+    /// Keys being revalidated, so that only one revalidation per key is in flight
+    pending: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ExpandEntitlements {
+    pub fn new<I, C, S>(claims: I, source: S) -> Self
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<String>,
+        S: EntitlementSource,
+    {
+        let ttl = Duration::from_secs(60);
+        Self {
+            claims: claims.into_iter().map(Into::into).collect(),
+            source: Arc::new(source),
+            ttl,
+            revalidate: None,
+            cache: TtlCache::new(ttl),
+        }
+    }
+
+    /// How long expansions are fresh for, zero disables caching
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        let stale = self.stale();
+        self.ttl = ttl;
+        self.cache = TtlCache::new(ttl + stale);
+        self
+    }
+
+    /// Keep serving expansions for `stale` past their [ttl][Self::ttl], while refreshing them
+    /// in the background with `spawn` (e.g. `|revalidation| { tokio::spawn(revalidation); }`).
+    ///
+    /// Failed revalidations keep stale expansion until it expires.
+    pub fn stale_while_revalidate<F>(mut self, stale: Duration, spawn: F) -> Self
+    where
+        F: Fn(Revalidation) + Send + Sync + 'static,
+    {
+        self.revalidate = Some(Revalidate {
+            spawn: Arc::new(spawn),
+            pending: Default::default(),
+        });
+        self.cache = TtlCache::new(self.ttl + stale);
+        self
+    }
+
+    fn stale(&self) -> Duration {
+        self.cache.ttl().saturating_sub(self.ttl)
+    }
+
+    fn key(&self, payload: &Payload) -> String {
+        let values: Vec<_> = self
+            .claims
+            .iter()
+            .map(|claim| {
+                payload
+                    .get(claim)
+                    .map(ToString::to_string)
+                    .unwrap_or_default()
+            })
+            .collect();
+        values.join("\u{1f}")
+    }
+
+    /// Refreshes stale expansion of `key` in the background, unless it's underway already
+    fn revalidate(&self, revalidate: &Revalidate, key: String, payload: &Payload) {
+        let mut pending = revalidate
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !pending.insert(key.clone()) {
+            return;
+        }
+        drop(pending);
+
+        let cache = self.cache.clone();
+        let pending = revalidate.pending.clone();
+        let expansion = self.source.expand(payload);
+        (revalidate.spawn)(Box::pin(async move {
+            match expansion.await {
+                Ok(entitlements) => cache.insert(key.clone(), entitlements),
+                Err(err) => tracing::warn!("ExpandEntitlements::revalidate {}", err),
+            }
+            pending
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .remove(&key);
+        }));
+    }
+}
+
+fn extensions(entitlements: Entitlements) -> Extensions {
+    let mut extensions = Extensions::new();
+    extensions.insert(entitlements);
+    extensions
+}
+
+impl Gate for ExpandEntitlements {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let payload = match cx.payload() {
+            Some(payload) => payload,
+            None => return Box::pin(future::ready(Err(Denied::Claim(self.claims.join(" "))))),
+        };
+        let key = self.key(payload);
+        match self.cache.get_with_age(&key) {
+            Some((age, entitlements)) if age < self.ttl => {
+                return Box::pin(future::ready(Ok(extensions(entitlements))));
+            }
+            Some((_, entitlements)) => {
+                if let Some(revalidate) = &self.revalidate {
+                    tracing::trace!("ExpandEntitlements::stale");
+                    self.revalidate(revalidate, key, payload);
+                    return Box::pin(future::ready(Ok(extensions(entitlements))));
+                }
+            }
+            None => {}
+        }
+
+        let cache = self.cache.clone();
+        self.source
+            .expand(payload)
+            .map(move |entitlements| {
+                let entitlements = entitlements.map_err(Denied::Other)?;
+                cache.insert(key, entitlements.clone());
+                Ok(extensions(entitlements))
+            })
+            .boxed()
+    }
+}
+
+impl fmt::Debug for ExpandEntitlements {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExpandEntitlements")
+            .field("claims", &self.claims)
+            .field("ttl", &self.ttl)
+            .field("stale", &self.stale())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Entitlements, ExpandEntitlements};
+    use crate::{util, BoxError, Gate, GateContext, Payload};
+    use http::Request;
+    use serde_json::json;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn expand_entitlements() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let gate = ExpandEntitlements::new(["plan"], move |payload: &Payload| {
+            let n = counter.fetch_add(1, Ordering::Relaxed);
+            let plan = payload.str("plan").unwrap_or_default().to_owned();
+            async move { Ok::<_, BoxError>(Entitlements::from_iter([format!("{}:{}", plan, n)])) }
+        })
+        .ttl(Duration::ZERO)
+        .stale_while_revalidate(Duration::from_secs(60), |revalidation| {
+            tokio::spawn(revalidation);
+        });
+        let (parts, _) = Request::new(()).into_parts();
+        let token = util::token(&json!({ "sub": "alice", "plan": "pro" }));
+        let expand = || async {
+            let extensions = gate.check(&GateContext::new(&parts, Some(&token))).await;
+            let entitlements = extensions.expect("Expanded").remove::<Entitlements>();
+            entitlements.expect("Entitlements set")
+        };
+
+        assert!(expand().await.contains("pro:0"));
+        // Stale right away, served while revalidation runs
+        assert!(expand().await.contains("pro:0"));
+        tokio::task::yield_now().await;
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert!(expand().await.contains("pro:1"));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::path::{canonical, glob};
+use http::{Method, Request};
+use std::sync::Arc;
+
+/// Requests let through without authentication, see [`Layer::skip_path`][crate::Layer::skip_path]
+/// and [`Layer::skip_method`][crate::Layer::skip_method]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Exemptions {
+    paths: Arc<[String]>,
+    methods: Arc<[Method]>,
+}
+
+impl Exemptions {
+    pub(crate) fn path(&mut self, pattern: String) {
+        let mut paths = self.paths.to_vec();
+        paths.push(pattern);
+        self.paths = paths.into();
+    }
+
+    pub(crate) fn method(&mut self, method: Method) {
+        let mut methods = self.methods.to_vec();
+        methods.push(method);
+        self.methods = methods.into();
+    }
+
+    pub(crate) fn matches<B>(&self, req: &Request<B>) -> bool {
+        let path = req.uri().path();
+        self.methods.contains(req.method())
+            || canonical(path)
+                && self
+                    .paths
+                    .iter()
+                    .any(|pattern| glob(pattern.as_bytes(), path.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Exemptions;
+    use http::{Method, Request};
+
+    #[test]
+    fn exemptions() {
+        let mut exemptions = Exemptions::default();
+        exemptions.path(String::from("/healthz"));
+        exemptions.path(String::from("/public/*.css"));
+        exemptions.path(String::from("/docs/**"));
+        exemptions.method(Method::OPTIONS);
+
+        let matches = |method: Method, path: &str| {
+            let req = Request::builder().method(method).uri(path).body(());
+            exemptions.matches(&req.expect("Valid request"))
+        };
+        assert!(matches(Method::GET, "/healthz"));
+        assert!(!matches(Method::GET, "/healthz/deep"));
+        assert!(matches(Method::GET, "/public/site.css"));
+        assert!(!matches(Method::GET, "/public/nested/site.css"));
+        assert!(matches(Method::GET, "/docs/api/v1/index.html"));
+        assert!(!matches(Method::GET, "/orders"));
+        assert!(matches(Method::OPTIONS, "/orders"));
+        assert!(!matches(Method::GET, "/docs/../admin"));
+        assert!(!matches(Method::GET, "/docs/%2e%2e/admin"));
+        assert!(!matches(Method::GET, "//docs/x"));
+    }
+}
@@ -0,0 +1,505 @@
+use crate::{
+    cookie::{cookie, remove_cookie},
+    query::{form_param, query_param, remove_query_param},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{
+    header::{HeaderName, AUTHORIZATION, CONTENT_TYPE},
+    HeaderMap, Method, Request, Uri,
+};
+use std::{borrow::Cow, sync::Arc};
+use typed_headers::Credentials;
+
+/// Locates the token on incoming requests for [`Middleware`][crate::Middleware].
+///
+/// [`DefaultExtractor`] reads `Bearer` credentials off `Authorization` header. Any
+/// `Fn(&Request<B>) -> Option<String>` is an extractor, returning `None` rejects the request
+/// as if no token was presented.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use http::Request;
+/// use tower_jwt::Layer;
+///
+/// let layer = Layer::new(decoder).extractor(|req: &Request<()>| {
+///     let value = req.headers().get("x-amzn-oidc-data")?;
+///     value.to_str().ok().map(String::from)
+/// });
+/// # }
+/// ```
+pub trait TokenExtractor<B> {
+    fn extract(&self, req: &Request<B>) -> Option<String>;
+
+    /// Same as [`extract`][Self::extract], but borrows the token off the request where
+    /// possible, sparing an allocation per request. Delegates to `extract` by default.
+    fn extract_borrowed<'r>(&self, req: &'r Request<B>) -> Option<Cow<'r, str>> {
+        self.extract(req).map(Cow::Owned)
+    }
+
+    /// Removes credentials from request headers before request reaches inner service, when
+    /// [`strip_token`][crate::Layer::strip_token] is set. Removes `Authorization` by default.
+    fn strip(&self, headers: &mut HeaderMap) {
+        headers.remove(AUTHORIZATION);
+    }
+
+    /// Same as [`strip`][Self::strip] for request URI, e.g. removing token query parameter.
+    /// Leaves URI as is by default.
+    fn strip_uri(&self, _uri: &mut Uri) {}
+}
+
+impl<F, B> TokenExtractor<B> for F
+where
+    F: Fn(&Request<B>) -> Option<String>,
+{
+    fn extract(&self, req: &Request<B>) -> Option<String> {
+        self(req)
+    }
+}
+
+/// Expected format of header value
+#[derive(Debug, Clone, Default)]
+pub(crate) enum Scheme {
+    /// `Bearer` credentials, bare token is also accepted from custom headers
+    #[default]
+    Bearer,
+    /// Credentials of given scheme
+    Named(Arc<str>),
+    /// Bare token
+    Bare,
+    /// Password of `Basic` credentials
+    BasicPassword,
+}
+
+impl Scheme {
+    fn credentials(&self, value: &str, custom_header: bool) -> Option<String> {
+        let token = match self {
+            Scheme::Bearer if custom_header => match strip_scheme(value, "Bearer") {
+                Some(token) => token,
+                None => value,
+            },
+            Scheme::Bearer => strip_scheme(value, "Bearer")?,
+            Scheme::Named(scheme) => strip_scheme(value, scheme)?,
+            Scheme::Bare => value,
+            Scheme::BasicPassword => {
+                let encoded = strip_scheme(value, "Basic")?.trim();
+                let decoded = STANDARD.decode(encoded).ok()?;
+                let decoded = String::from_utf8(decoded).ok()?;
+                let (_, password) = decoded.split_once(':')?;
+                return Some(password.trim().to_owned()).filter(|token| !token.is_empty());
+            }
+        };
+        Some(token.trim().to_owned()).filter(|token| !token.is_empty())
+    }
+
+    /// Credentials from header `name`, `Authorization` when not set.
+    ///
+    /// Every value of the header is considered, e.g. `Basic` credentials meant for a proxy
+    /// next to `Bearer` ones meant for the service.
+    pub(crate) fn header<B>(&self, req: &Request<B>, name: Option<&HeaderName>) -> Option<String> {
+        req.headers()
+            .get_all(name.unwrap_or(&AUTHORIZATION))
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|value| self.credentials(value, name.is_some()))
+    }
+}
+
+/// Strips case-insensitive `scheme` followed by whitespace off `value`
+fn strip_scheme<'a>(value: &'a str, scheme: &str) -> Option<&'a str> {
+    let (prefix, credentials) = value
+        .trim_start()
+        .split_once(|c: char| c.is_ascii_whitespace())?;
+    prefix.eq_ignore_ascii_case(scheme).then_some(credentials)
+}
+
+/// Token already located by an upstream layer, e.g. custom protocol adapter.
+///
+/// [`DefaultExtractor`] takes token set on request extensions as is, without looking at
+/// headers at all.
+///
+/// ```rust
+/// use http::Request;
+/// use tower_jwt::RawToken;
+///
+/// let mut req = Request::new(());
+/// req.extensions_mut().insert(RawToken(String::from("a.b.c")));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawToken(pub String);
+
+/// [`TokenExtractor`] reading `Bearer` credentials off `Authorization` header, unless
+/// configured otherwise through [`Layer`][crate::Layer] or [`Middleware`][crate::Middleware].
+///
+/// [`RawToken`] set on request extensions takes precedence.
+#[derive(Debug, Clone, Default)]
+pub struct DefaultExtractor {
+    header: Option<HeaderName>,
+    scheme: Scheme,
+    query: Option<Arc<str>>,
+    lenient: bool,
+}
+
+impl DefaultExtractor {
+    pub(crate) fn header(&mut self, name: HeaderName) {
+        self.header = Some(name);
+    }
+
+    pub(crate) fn scheme(&mut self, scheme: String) {
+        self.scheme = Scheme::Named(Arc::from(scheme));
+    }
+
+    pub(crate) fn bare_token(&mut self) {
+        self.scheme = Scheme::Bare;
+    }
+
+    pub(crate) fn basic_password(&mut self) {
+        self.scheme = Scheme::BasicPassword;
+    }
+
+    pub(crate) fn lenient_scheme(&mut self) {
+        self.lenient = true;
+    }
+
+    pub(crate) fn query_param(&mut self, name: String) {
+        self.query = Some(Arc::from(name));
+    }
+}
+
+impl<B> TokenExtractor<B> for DefaultExtractor {
+    fn extract(&self, req: &Request<B>) -> Option<String> {
+        self.extract_borrowed(req).map(Cow::into_owned)
+    }
+
+    fn extract_borrowed<'r>(&self, req: &'r Request<B>) -> Option<Cow<'r, str>> {
+        if let Some(RawToken(token)) = req.extensions().get() {
+            return Some(Cow::Borrowed(token));
+        }
+        let header = match (&self.header, &self.scheme) {
+            (None, Scheme::Bearer) if !self.lenient => req
+                .headers()
+                .get_all(AUTHORIZATION)
+                .iter()
+                .filter_map(|value| value.to_str().ok()?.parse::<Credentials>().ok())
+                .find_map(|credentials| credentials.as_bearer().map(|t| t.as_str().to_owned()))
+                .map(Cow::Owned),
+            (name, scheme) => scheme.header(req, name.as_ref()).map(Cow::Owned),
+        };
+        header.or_else(|| {
+            let name = self.query.as_deref()?;
+            query_param(req.uri(), name).map(Cow::Owned)
+        })
+    }
+
+    fn strip(&self, headers: &mut HeaderMap) {
+        headers.remove(self.header.as_ref().unwrap_or(&AUTHORIZATION));
+    }
+
+    fn strip_uri(&self, uri: &mut Uri) {
+        if let Some(name) = &self.query {
+            remove_query_param(uri, name);
+        }
+    }
+}
+
+/// Where [`Sources`] look for the token
+#[derive(Debug, Clone)]
+enum Source {
+    Header(Option<HeaderName>, Scheme),
+    Cookie(String),
+    Query(String),
+    Path(PathToken),
+}
+
+/// [`TokenExtractor`] trying several sources in order, stopping at the first hit.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, Sources};
+///
+/// let layer = Layer::new(decoder).extractor(
+///     Sources::new()
+///         .bearer()
+///         .cookie("access_token")
+///         .query("access_token"),
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    sources: Vec<Source>,
+}
+
+impl Sources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `Bearer` credentials of `Authorization` header
+    pub fn bearer(mut self) -> Self {
+        self.sources.push(Source::Header(None, Scheme::Bearer));
+        self
+    }
+
+    /// Header `name`, holding either bare token or `Bearer` credentials
+    pub fn header(mut self, name: HeaderName) -> Self {
+        self.sources
+            .push(Source::Header(Some(name), Scheme::Bearer));
+        self
+    }
+
+    /// Cookie `name`
+    pub fn cookie(mut self, name: impl Into<String>) -> Self {
+        self.sources.push(Source::Cookie(name.into()));
+        self
+    }
+
+    /// Query parameter `name`
+    pub fn query(mut self, name: impl Into<String>) -> Self {
+        self.sources.push(Source::Query(name.into()));
+        self
+    }
+
+    /// Path segment, see [`PathToken`]
+    pub fn path(mut self, pattern: &str) -> Self {
+        self.sources.push(Source::Path(PathToken::new(pattern)));
+        self
+    }
+}
+
+impl<B> TokenExtractor<B> for Sources {
+    fn extract(&self, req: &Request<B>) -> Option<String> {
+        self.sources.iter().find_map(|source| match source {
+            Source::Header(name, scheme) => scheme.header(req, name.as_ref()),
+            Source::Cookie(name) => cookie(req.headers(), name)
+                .filter(|token| !token.is_empty())
+                .map(String::from),
+            Source::Query(name) => query_param(req.uri(), name),
+            Source::Path(path) => path.extract(req),
+        })
+    }
+
+    fn strip(&self, headers: &mut HeaderMap) {
+        for source in &self.sources {
+            match source {
+                Source::Header(name, _) => {
+                    headers.remove(name.as_ref().unwrap_or(&AUTHORIZATION));
+                }
+                Source::Cookie(name) => remove_cookie(headers, name),
+                Source::Query(_) | Source::Path(_) => {}
+            }
+        }
+    }
+
+    fn strip_uri(&self, uri: &mut Uri) {
+        for source in &self.sources {
+            if let Source::Query(name) = source {
+                remove_query_param(uri, name);
+            }
+        }
+    }
+}
+
+/// [`TokenExtractor`] reading token from path segment, for signed-URL style endpoints
+/// where headers can't be set.
+///
+/// Pattern segments are either literal, `*` matching any segment, or `{token}` marking
+/// the token. Request path must have exactly as many segments as the pattern.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, PathToken};
+///
+/// let layer = Layer::new(decoder).extractor(PathToken::new("/files/*/{token}/download"));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PathToken {
+    segments: Arc<[PathSegment]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathSegment {
+    Literal(String),
+    Any,
+    Token,
+}
+
+impl PathToken {
+    pub fn new(pattern: &str) -> Self {
+        let segments = pattern
+            .trim_matches('/')
+            .split('/')
+            .map(|segment| match segment {
+                "{token}" => PathSegment::Token,
+                "*" => PathSegment::Any,
+                literal => PathSegment::Literal(literal.to_owned()),
+            })
+            .collect();
+        Self { segments }
+    }
+}
+
+impl<B> TokenExtractor<B> for PathToken {
+    fn extract(&self, req: &Request<B>) -> Option<String> {
+        let path: Vec<_> = req.uri().path().trim_matches('/').split('/').collect();
+        if path.len() != self.segments.len() {
+            return None;
+        }
+        let mut token = None;
+        for (segment, pattern) in path.into_iter().zip(self.segments.iter()) {
+            match pattern {
+                PathSegment::Literal(literal) if literal != segment => return None,
+                PathSegment::Token => token = Some(segment),
+                _ => {}
+            }
+        }
+        token.filter(|token| !token.is_empty()).map(String::from)
+    }
+
+    fn strip(&self, _: &mut HeaderMap) {}
+}
+
+/// [`TokenExtractor`] reading `Bearer` credentials off `Authorization` header, falling back to
+/// `access_token` parameter of form-encoded body as described in
+/// [RFC 6750](https://www.rfc-editor.org/rfc/rfc6750#section-2.2).
+///
+/// Only non-`GET` requests with `application/x-www-form-urlencoded` content type are
+/// considered. Body must already be buffered, i.e. implement `AsRef<[u8]>` (`Bytes`,
+/// `Vec<u8>`, `String`, ..).
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{FormBody, Layer};
+///
+/// let layer = Layer::new(decoder).extractor(FormBody);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FormBody;
+
+impl<B: AsRef<[u8]>> TokenExtractor<B> for FormBody {
+    fn extract(&self, req: &Request<B>) -> Option<String> {
+        Scheme::Bearer.header(req, None).or_else(|| {
+            let form = req.method() != Method::GET
+                && req
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.split(';').next())
+                    .is_some_and(|mime| {
+                        mime.trim()
+                            .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+                    });
+            let body = std::str::from_utf8(req.body().as_ref()).ok();
+            form_param(body.filter(|_| form)?, "access_token")
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DefaultExtractor, FormBody, RawToken, Sources, TokenExtractor};
+    use http::{Method, Request};
+
+    #[test]
+    fn default_extractor() {
+        let req = Request::builder()
+            .header("Authorization", "Bearer a.b.c")
+            .body(())
+            .expect("Valid request");
+        assert_eq!(
+            DefaultExtractor::default().extract(&req).as_deref(),
+            Some("a.b.c")
+        );
+
+        let mut extractor = DefaultExtractor::default();
+        extractor.scheme(String::from("Token"));
+        assert_eq!(extractor.extract(&req), None);
+
+        let mut extractor = DefaultExtractor::default();
+        extractor.basic_password();
+        // ci:a.b.c
+        let basic = Request::builder()
+            .header("Authorization", "Basic Y2k6YS5iLmM=")
+            .body(())
+            .expect("Valid request");
+        assert_eq!(extractor.extract(&basic).as_deref(), Some("a.b.c"));
+
+        let padded = Request::builder()
+            .header("Authorization", " bearer\ta.b.c  ")
+            .body(())
+            .expect("Valid request");
+        assert_eq!(DefaultExtractor::default().extract(&padded), None);
+        let mut extractor = DefaultExtractor::default();
+        extractor.lenient_scheme();
+        assert_eq!(extractor.extract(&padded).as_deref(), Some("a.b.c"));
+
+        let req = Request::builder()
+            .header("Authorization", "Basic cHJveHk6c2VjcmV0")
+            .header("Authorization", "Bearer a.b.c")
+            .body(())
+            .expect("Valid request");
+        assert_eq!(
+            DefaultExtractor::default().extract(&req).as_deref(),
+            Some("a.b.c")
+        );
+
+        let mut req = Request::new(());
+        req.extensions_mut().insert(RawToken(String::from("d.e.f")));
+        assert_eq!(
+            DefaultExtractor::default().extract(&req).as_deref(),
+            Some("d.e.f")
+        );
+    }
+
+    #[test]
+    fn sources_in_order() {
+        let sources = Sources::new()
+            .bearer()
+            .cookie("access_token")
+            .query("access_token");
+        let req = Request::builder()
+            .uri("/?access_token=from-query")
+            .header("Cookie", "theme=dark; access_token=from-cookie")
+            .body(())
+            .expect("Valid request");
+        assert_eq!(sources.extract(&req).as_deref(), Some("from-cookie"));
+
+        let req = Request::builder()
+            .uri("/?access_token=from-query")
+            .body(())
+            .expect("Valid request");
+        assert_eq!(sources.extract(&req).as_deref(), Some("from-query"));
+
+        let sources = Sources::new().path("/files/*/{token}/download");
+        let req = Request::builder()
+            .uri("/files/report.pdf/a.b.c/download")
+            .body(())
+            .expect("Valid request");
+        assert_eq!(sources.extract(&req).as_deref(), Some("a.b.c"));
+        let req = Request::builder()
+            .uri("/files/a.b.c/download")
+            .body(())
+            .expect("Valid request");
+        assert_eq!(sources.extract(&req), None);
+    }
+
+    #[test]
+    fn form_body() {
+        let req = |method| {
+            Request::builder()
+                .method(method)
+                .header(
+                    "Content-Type",
+                    "application/x-www-form-urlencoded; charset=utf-8",
+                )
+                .body("grant=1&access_token=a.b%2Ec")
+                .expect("Valid request")
+        };
+        assert_eq!(
+            FormBody.extract(&req(Method::POST)).as_deref(),
+            Some("a.b.c")
+        );
+        assert_eq!(FormBody.extract(&req(Method::GET)), None);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::Payload;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{errors::Result, Header};
+use serde_json::{Map, Value};
+use std::{cell::RefCell, sync::Arc};
+
+thread_local! {
+    /// Token decoder stack is being built for, see [`scope`]
+    static CURRENT: RefCell<Option<Arc<Parsed>>> = const { RefCell::new(None) };
+    /// Scratch space for base64 decoding, reused across tokens
+    static BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Token split into segments and decoded once for peeks ahead of verification, by
+/// [fast path][crate::Layer::fast_path]
+#[derive(Debug)]
+pub(crate) struct Parsed {
+    token: Box<str>,
+    header: Option<Header>,
+    payload: Option<Payload>,
+}
+
+impl Parsed {
+    pub(crate) fn new(token: &str) -> Self {
+        let mut segments = token.splitn(3, '.');
+        let (header, payload) = match (segments.next(), segments.next(), segments.next()) {
+            (Some(header), Some(payload), Some(_)) => (
+                segment::<Header>(header),
+                segment::<Map<String, Value>>(payload).map(Payload::from_map),
+            ),
+            _ => (None, None),
+        };
+        Self {
+            token: token.into(),
+            header,
+            payload,
+        }
+    }
+
+    pub(crate) fn payload(&self) -> Option<&Payload> {
+        self.payload.as_ref()
+    }
+}
+
+/// Decodes base64url JSON `segment` through pooled buffer
+fn segment<T: serde::de::DeserializeOwned>(segment: &str) -> Option<T> {
+    BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        buffer.clear();
+        URL_SAFE_NO_PAD.decode_vec(segment, &mut buffer).ok()?;
+        serde_json::from_slice(&buffer).ok()
+    })
+}
+
+/// Runs `f` with `parsed` token available to [`decode_header`] and [`payload`],
+/// so decoders peeking at token while building their futures don't decode it again
+pub(crate) fn scope<T>(parsed: &Arc<Parsed>, f: impl FnOnce() -> T) -> T {
+    let previous = CURRENT.with(|current| current.replace(Some(parsed.clone())));
+    let outcome = f();
+    CURRENT.with(|current| *current.borrow_mut() = previous);
+    outcome
+}
+
+fn current<T>(token: &str, f: impl FnOnce(&Parsed) -> Option<T>) -> Option<T> {
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        current
+            .as_deref()
+            .filter(|parsed| &*parsed.token == token)
+            .and_then(f)
+    })
+}
+
+/// Same as [`jsonwebtoken::decode_header`], but reuses header parsed by [`scope`]
+pub(crate) fn decode_header(token: &str) -> Result<Header> {
+    match current(token, |parsed| parsed.header.clone()) {
+        Some(header) => Ok(header),
+        // Either no fast path or malformed header, let jsonwebtoken explain what's wrong
+        None => jsonwebtoken::decode_header(token),
+    }
+}
+
+/// Same as [`Payload::from_token`], but reuses payload parsed by [`scope`]
+pub(crate) fn payload(token: &str) -> Option<Payload> {
+    current(token, |parsed| parsed.payload.clone()).or_else(|| Payload::from_token(token))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_header, payload, scope, Parsed};
+    use crate::{util, AlgorithmGuard, KeyFamily, Middleware};
+    use http::Request;
+    use jsonwebtoken::Algorithm;
+    use std::sync::Arc;
+    use tower::{service_fn, ServiceExt};
+
+    #[tokio::test]
+    async fn fast_path() {
+        let token = util::token(&util::claim(Some(100)));
+        let parsed = Arc::new(Parsed::new(&token));
+        // Forged header is only ever seen within scope, proving it isn't decoded again
+        let forged = Arc::new(Parsed {
+            token: token.as_str().into(),
+            header: Some(jsonwebtoken::Header::new(Algorithm::HS256)),
+            payload: parsed.payload.clone(),
+        });
+        let alg = scope(&forged, || decode_header(&token).map(|header| header.alg));
+        assert_eq!(alg.ok(), Some(Algorithm::HS256));
+        assert_eq!(
+            decode_header(&token).ok().map(|header| header.alg),
+            Some(Algorithm::EdDSA)
+        );
+        let sub = scope(&parsed, || payload(&token));
+        assert_eq!(
+            sub.as_ref().and_then(|payload| payload.str("sub")),
+            Some("sub")
+        );
+
+        let svc = service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(crate::claims::<util::Claim>(&req).is_some())
+        });
+        let decoder = AlgorithmGuard::new(util::in_place_decoder(), [KeyFamily::Ed]);
+        let middleware = Middleware::new(decoder, svc).fast_path().baggage(["sub"]);
+        let req = Request::builder()
+            .header("authorization", format!("Bearer {}", token))
+            .body(())
+            .expect("Valid request");
+        assert_eq!(middleware.oneshot(req).await.ok(), Some(true));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{cookie::cookie, BoxFuture, Denied, Gate, GateContext};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures::future;
+use http::{header::HeaderName, Extensions};
+use ring::{constant_time, digest};
+use std::fmt::Write;
+
+/// Hex-encoded SHA-256 of `bytes`
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, bytes).as_ref())
+}
+
+/// Lowercase hex encoding of `bytes`
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+/// Where fingerprint is read from
+#[derive(Debug, Clone)]
+enum Source {
+    Cookie(String),
+    Header(HeaderName),
+}
+
+/// [`Gate`] binding tokens to client context, following OWASP "token sidejacking" guidance.
+///
+/// Token must carry hex-encoded SHA-256 of a fingerprint, which client presents alongside
+/// the token (typically in a hardened cookie), or its base64url-encoded [thumbprint][Self::cnf]
+/// under `cnf` claim. Requests where fingerprint is missing or doesn't hash to the claimed
+/// value are denied, so stolen tokens can't be replayed from a different client context.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Fingerprint, Layer};
+///
+/// let layer = Layer::new(decoder).gate(Fingerprint::cookie("__Secure-Fgp", "fgp"));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    source: Source,
+    claim: String,
+    cnf: bool,
+}
+
+impl Fingerprint {
+    /// Fingerprint is read from cookie named `cookie`, its hash from `claim`
+    pub fn cookie(cookie: impl Into<String>, claim: impl Into<String>) -> Self {
+        Self {
+            source: Source::Cookie(cookie.into()),
+            claim: claim.into(),
+            cnf: false,
+        }
+    }
+
+    /// Fingerprint is read from header named `header`, its hash from `claim`
+    pub fn header(header: HeaderName, claim: impl Into<String>) -> Self {
+        Self {
+            source: Source::Header(header),
+            claim: claim.into(),
+            cnf: false,
+        }
+    }
+
+    /// Read [thumbprint][Self::thumbprint] from `claim` member of `cnf` claim
+    /// ([RFC 7800](https://www.rfc-editor.org/rfc/rfc7800)) rather than hash from top-level
+    /// `claim`, as with `x5t#S256` or `jkt` confirmation methods
+    pub fn cnf(mut self) -> Self {
+        self.cnf = true;
+        self
+    }
+
+    /// Hex-encoded SHA-256 of the fingerprint, as expected in the token
+    pub fn hash(fingerprint: &str) -> String {
+        sha256_hex(fingerprint.as_bytes())
+    }
+
+    /// Base64url-encoded SHA-256 of the fingerprint, as expected under `cnf` claim
+    pub fn thumbprint(fingerprint: &str) -> String {
+        URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, fingerprint.as_bytes()))
+    }
+
+    fn matches(&self, cx: &GateContext<'_>) -> bool {
+        let fingerprint = match &self.source {
+            Source::Cookie(name) => cookie(cx.headers(), name),
+            Source::Header(name) => cx.headers().get(name).and_then(|v| v.to_str().ok()),
+        };
+        let payload = cx.payload();
+        let expected = match self.cnf {
+            true => payload
+                .and_then(|payload| payload.get("cnf"))
+                .and_then(|cnf| cnf.get(&self.claim))
+                .and_then(|thumbprint| thumbprint.as_str()),
+            false => payload.and_then(|payload| payload.str(&self.claim)),
+        };
+        let Some((fingerprint, expected)) = fingerprint.zip(expected) else {
+            return false;
+        };
+        let (fingerprint, expected) = match self.cnf {
+            true => (Self::thumbprint(fingerprint), expected.to_owned()),
+            false => (Self::hash(fingerprint), expected.to_ascii_lowercase()),
+        };
+        constant_time::verify_slices_are_equal(fingerprint.as_bytes(), expected.as_bytes()).is_ok()
+    }
+}
+
+impl Gate for Fingerprint {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let outcome = match self.matches(cx) {
+            true => Ok(Extensions::new()),
+            false => Err(Denied::Fingerprint),
+        };
+        Box::pin(future::ready(outcome))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Fingerprint;
+    use crate::{util, Denied, Gate, GateContext};
+    use http::{
+        header::{HeaderName, COOKIE},
+        Request,
+    };
+
+    #[tokio::test]
+    async fn fingerprint_cookie() {
+        let gate = Fingerprint::cookie("__Secure-Fgp", "fgp");
+        let token = util::token(&serde_json::json!({ "fgp": Fingerprint::hash("random") }));
+
+        let (parts, _) = Request::builder()
+            .header(COOKIE, "__Secure-Fgp=random")
+            .body(())
+            .expect("Valid request")
+            .into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+
+        let (parts, _) = Request::builder()
+            .header(COOKIE, "__Secure-Fgp=stolen")
+            .body(())
+            .expect("Valid request")
+            .into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Fingerprint)));
+
+        let (parts, _) = Request::new(()).into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Fingerprint)));
+
+        let gate = Fingerprint::header(HeaderName::from_static("x-client-cert"), "x5t#S256").cnf();
+        let token = util::token(&serde_json::json!({
+            "cnf": { "x5t#S256": Fingerprint::thumbprint("cert") }
+        }));
+        let (parts, _) = Request::builder()
+            .header("x-client-cert", "cert")
+            .body(())
+            .expect("Valid request")
+            .into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+
+        let token = util::token(&serde_json::json!({ "x5t#S256": Fingerprint::hash("cert") }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Fingerprint)));
+    }
+}
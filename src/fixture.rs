@@ -0,0 +1,332 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, EncodingKey, Header};
+use ring::{
+    digest,
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// Path JWKS is served at
+const JWKS_PATH: &str = "/.well-known/jwks.json";
+/// Path discovery document is served at
+const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+/// PKCS#8 v1 prefix of Ed25519 private key, followed by 32 bytes seed
+const PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// Tiny in-process issuer for integration tests, available with `test-util` feature.
+///
+/// Serves its JWKS at [`jwks_url`][FixtureIdp::jwks_url] and discovery document
+/// from a local listener, mints `EdDSA` tokens with arbitrary claims and rotates keys
+/// on command, so JWKS decoders, rotation and caching can be exercised end-to-end.
+/// Keys are derived from their `kid`, so the same claims minted under the same `kid`
+/// yield the same token across runs.
+/// Listener is shut down once fixture is dropped.
+///
+/// ```rust
+/// use serde_json::json;
+/// use tower_jwt::FixtureIdp;
+///
+/// let idp = FixtureIdp::start().expect("Listener bound");
+/// let token = idp.mint(&json!({"sub": "alice", "iss": idp.issuer()}));
+/// let previous = idp.kid();
+/// idp.rotate();
+/// // Both keys are published until the previous one is retired
+/// assert_eq!(idp.jwks().keys.len(), 2);
+/// idp.retire(&previous);
+/// # let _ = token;
+/// ```
+pub struct FixtureIdp {
+    addr: SocketAddr,
+    state: Arc<State>,
+    worker: Option<JoinHandle<()>>,
+}
+
+struct State {
+    keys: Mutex<Keys>,
+    fetches: AtomicUsize,
+    shutdown: AtomicBool,
+}
+
+struct Keys {
+    published: Vec<String>,
+    current: String,
+    generation: usize,
+}
+
+impl FixtureIdp {
+    /// Binds listener to a random local port and publishes the first key
+    pub fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let addr = listener.local_addr()?;
+        let kid = kid(1);
+        let state = Arc::new(State {
+            keys: Mutex::new(Keys {
+                published: vec![kid.clone()],
+                current: kid,
+                generation: 1,
+            }),
+            fetches: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let worker = {
+            let state = state.clone();
+            thread::Builder::new()
+                .name(String::from("fixture-idp"))
+                .spawn(move || serve(listener, addr, &state))?
+        };
+        Ok(Self {
+            addr,
+            state,
+            worker: Some(worker),
+        })
+    }
+
+    /// Issuer identifier, origin of the listener
+    pub fn issuer(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn jwks_url(&self) -> String {
+        format!("{}{}", self.issuer(), JWKS_PATH)
+    }
+
+    /// `kid` of the key tokens are currently signed with
+    pub fn kid(&self) -> String {
+        self.state.lock().current.clone()
+    }
+
+    /// Signs `claims` with current key
+    pub fn mint<T: Serialize>(&self, claims: &T) -> String {
+        self.mint_with_kid(&self.kid(), claims)
+    }
+
+    /// Signs `claims` with the key of `kid`, which needn't be published,
+    /// e.g. to check how decoders treat unknown keys
+    pub fn mint_with_kid<T: Serialize>(&self, kid: &str, claims: &T) -> String {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(kid.to_owned());
+        let key = EncodingKey::from_ed_der(&pkcs8(kid));
+        jsonwebtoken::encode(&header, claims, &key).expect("Ed25519 signing never fails")
+    }
+
+    /// Publishes a new key and signs subsequent tokens with it, previous keys stay published
+    /// until [retired][FixtureIdp::retire]. Returns `kid` of the new key.
+    pub fn rotate(&self) -> String {
+        let mut keys = self.state.lock();
+        keys.generation += 1;
+        let kid = kid(keys.generation);
+        keys.published.push(kid.clone());
+        keys.current = kid.clone();
+        kid
+    }
+
+    /// Removes key of `kid` from JWKS, tokens can still be minted with it
+    pub fn retire(&self, kid: &str) {
+        self.state
+            .lock()
+            .published
+            .retain(|published| published != kid);
+    }
+
+    /// JWKS as served
+    pub fn jwks(&self) -> JwkSet {
+        self.state.jwks()
+    }
+
+    /// Number of times JWKS was fetched over the listener
+    pub fn fetches(&self) -> usize {
+        self.state.fetches.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for FixtureIdp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixtureIdp")
+            .field("addr", &self.addr)
+            .field("kid", &self.kid())
+            .field("fetches", &self.fetches())
+            .finish()
+    }
+}
+
+impl Drop for FixtureIdp {
+    fn drop(&mut self) {
+        self.state.shutdown.store(true, Ordering::Relaxed);
+        // Wake the listener up, so it notices
+        let _ = TcpStream::connect(self.addr);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl State {
+    fn lock(&self) -> MutexGuard<'_, Keys> {
+        self.keys
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn jwks(&self) -> JwkSet {
+        let keys: Vec<_> = self.lock().published.iter().map(|kid| jwk(kid)).collect();
+        serde_json::from_value(json!({ "keys": keys })).expect("Valid JWKS")
+    }
+}
+
+fn kid(generation: usize) -> String {
+    format!("fixture-{}", generation)
+}
+
+fn seed(kid: &str) -> digest::Digest {
+    digest::digest(
+        &digest::SHA256,
+        format!("tower-jwt fixture {}", kid).as_bytes(),
+    )
+}
+
+fn pkcs8(kid: &str) -> Vec<u8> {
+    let mut der = PKCS8_PREFIX.to_vec();
+    der.extend_from_slice(seed(kid).as_ref());
+    der
+}
+
+fn jwk(kid: &str) -> serde_json::Value {
+    let pair = Ed25519KeyPair::from_seed_unchecked(seed(kid).as_ref()).expect("32 bytes seed");
+    json!({
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "x": URL_SAFE_NO_PAD.encode(pair.public_key()),
+        "kid": kid,
+        "alg": "EdDSA",
+        "use": "sig",
+    })
+}
+
+fn serve(listener: TcpListener, addr: SocketAddr, state: &State) {
+    for stream in listener.incoming() {
+        if state.shutdown.load(Ordering::Relaxed) {
+            break;
+        }
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = respond(stream, addr, state) {
+                    tracing::debug!("FixtureIdp::respond {}", err);
+                }
+            }
+            Err(err) => tracing::debug!("FixtureIdp::accept {}", err),
+        }
+    }
+}
+
+/// Answers a single HTTP/1.1 request and closes the connection
+fn respond(mut stream: TcpStream, addr: SocketAddr, state: &State) -> io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut line = head.lines().next().unwrap_or_default().split(' ');
+    let (status, body) = match (line.next(), line.next()) {
+        (Some("GET"), Some(JWKS_PATH)) => {
+            state.fetches.fetch_add(1, Ordering::Relaxed);
+            let jwks = serde_json::to_string(&state.jwks()).expect("Serializable JWKS");
+            ("200 OK", jwks)
+        }
+        (Some("GET"), Some(DISCOVERY_PATH)) => {
+            let issuer = format!("http://{}", addr);
+            let discovery = json!({
+                "jwks_uri": format!("{}{}", issuer, JWKS_PATH),
+                "issuer": issuer,
+                "id_token_signing_alg_values_supported": ["EdDSA"],
+            });
+            ("200 OK", discovery.to_string())
+        }
+        _ => ("404 Not Found", String::from("{}")),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::FixtureIdp;
+    use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+    use serde_json::{json, Value};
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    fn fetch(idp: &FixtureIdp) -> JwkSet {
+        let url = idp.jwks_url();
+        let (origin, path) = url["http://".len()..].split_once('/').expect("Path");
+        let mut stream = TcpStream::connect(origin).expect("Connected");
+        write!(stream, "GET /{} HTTP/1.1\r\nhost: {}\r\n\r\n", path, origin).expect("Sent");
+        let mut res = String::new();
+        stream.read_to_string(&mut res).expect("Received");
+        let (_, body) = res.split_once("\r\n\r\n").expect("Body");
+        serde_json::from_str(body).expect("Valid JWKS")
+    }
+
+    fn decode(jwks: &JwkSet, token: &str) -> Option<Value> {
+        let kid = jsonwebtoken::decode_header(token).ok()?.kid?;
+        let key = DecodingKey::from_jwk(jwks.find(&kid)?).ok()?;
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.required_spec_claims.clear();
+        validation.validate_exp = false;
+        jsonwebtoken::decode(token, &key, &validation)
+            .ok()
+            .map(|data| data.claims)
+    }
+
+    #[test]
+    fn fixture_idp() {
+        let idp = FixtureIdp::start().expect("Listener bound");
+        let claims = json!({"sub": "alice"});
+        let first = idp.mint(&claims);
+        assert_eq!(first, idp.mint(&claims), "Replayable");
+        assert_eq!(decode(&fetch(&idp), &first), Some(claims.clone()));
+
+        let previous = idp.kid();
+        idp.rotate();
+        let second = idp.mint(&claims);
+        let jwks = fetch(&idp);
+        assert_eq!(decode(&jwks, &first), Some(claims.clone()));
+        assert_eq!(decode(&jwks, &second), Some(claims.clone()));
+
+        idp.retire(&previous);
+        assert_eq!(decode(&fetch(&idp), &first), None);
+        assert_eq!(
+            decode(&fetch(&idp), &idp.mint_with_kid("unknown", &claims)),
+            None
+        );
+        assert_eq!(idp.fetches(), 4);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{BoxError, BoxFuture, Denied, Gate, GateContext, Payload};
+use core::future::Future;
+use futures::{future, FutureExt};
+use http::Extensions;
+use ring::digest;
+use std::{collections::BTreeSet, fmt, sync::Arc};
+
+/// Feature flags enabled for the authenticated identity, set on request extensions
+/// by [`FeatureFlags`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Flags(BTreeSet<String>);
+
+impl Flags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enable(mut self, flag: impl Into<String>) -> Self {
+        self.0.insert(flag.into());
+        self
+    }
+
+    pub fn is_enabled(&self, flag: &str) -> bool {
+        self.0.contains(flag)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+
+    /// Whether `key` (e.g. token `sub`) falls within first `percent` of rollout of `flag`.
+    ///
+    /// Buckets are derived from hash of both, so the same identity stays in or out as the
+    /// percentage grows, while different flags roll out to different identities.
+    pub fn rollout(flag: &str, key: &str, percent: u8) -> bool {
+        let hash = digest::digest(&digest::SHA256, format!("{}:{}", flag, key).as_bytes());
+        let bucket = u16::from_be_bytes([hash.as_ref()[0], hash.as_ref()[1]]) % 100;
+        bucket < u16::from(percent)
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for Flags {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        Self(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Evaluates feature flags for accepted token, see [`FeatureFlags`].
+pub trait FeatureFlagResolver: Send + Sync + 'static {
+    fn resolve(&self, payload: &Payload) -> BoxFuture<Flags, BoxError>;
+}
+
+impl<F, Fut> FeatureFlagResolver for F
+where
+    F: Fn(&Payload) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Flags, BoxError>> + Send + 'static,
+{
+    fn resolve(&self, payload: &Payload) -> BoxFuture<Flags, BoxError> {
+        Box::pin(self(payload))
+    }
+}
+
+/// [`Gate`] setting [`Flags`] resolved from token claims on request extensions, so rollouts
+/// and entitlement checks keyed on identity are evaluated once per request.
+///
+/// Resolver failures leave every flag off rather than deny the request,
+/// unless [`strict`][FeatureFlags::strict].
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{BoxError, FeatureFlags, Flags, Layer, Payload};
+///
+/// let layer = Layer::new(decoder).gate(FeatureFlags::new(|payload: &Payload| {
+///     let sub = payload.str("sub").unwrap_or_default();
+///     let mut flags = Flags::new();
+///     if Flags::rollout("new-checkout", sub, 10) {
+///         flags = flags.enable("new-checkout");
+///     }
+///     async move { Ok::<_, BoxError>(flags) }
+/// }));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct FeatureFlags {
+    resolver: Arc<dyn FeatureFlagResolver>,
+    strict: bool,
+}
+
+impl FeatureFlags {
+    pub fn new<R: FeatureFlagResolver>(resolver: R) -> Self {
+        Self {
+            resolver: Arc::new(resolver),
+            strict: false,
+        }
+    }
+
+    /// Deny requests when resolver fails
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+}
+
+impl Gate for FeatureFlags {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let payload = match cx.payload() {
+            Some(payload) => payload,
+            None => return Box::pin(future::ready(Ok(Extensions::new()))),
+        };
+        let strict = self.strict;
+        self.resolver
+            .resolve(payload)
+            .map(move |flags| {
+                let flags = match flags {
+                    Ok(flags) => flags,
+                    Err(err) if strict => return Err(Denied::Other(err)),
+                    Err(err) => {
+                        tracing::warn!("FeatureFlags::resolve {}", err);
+                        Flags::default()
+                    }
+                };
+                let mut extensions = Extensions::new();
+                extensions.insert(flags);
+                Ok(extensions)
+            })
+            .boxed()
+    }
+}
+
+impl fmt::Debug for FeatureFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FeatureFlags")
+            .field("strict", &self.strict)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FeatureFlags, Flags};
+    use crate::{util, BoxError, Gate, GateContext, Payload};
+    use http::Request;
+
+    #[tokio::test]
+    async fn feature_flags() {
+        let gate = FeatureFlags::new(|payload: &Payload| {
+            let flags = match payload.str("sub") {
+                Some("beta") => Ok(Flags::new().enable("preview")),
+                Some(_) => Ok(Flags::new()),
+                None => Err(BoxError::from("No subject")),
+            };
+            async move { flags }
+        });
+        let (parts, _) = Request::new(()).into_parts();
+
+        let token = util::token(&serde_json::json!({ "sub": "beta" }));
+        let extensions = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        let flags = extensions.expect("Resolved").remove::<Flags>();
+        assert!(flags.is_some_and(|flags| flags.is_enabled("preview")));
+
+        let token = util::token(&serde_json::json!({ "iss": "issuer" }));
+        let extensions = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        let flags = extensions.expect("Fails open").remove::<Flags>();
+        assert_eq!(flags, Some(Flags::new()));
+        let strict = gate.strict();
+        let outcome = strict.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_err());
+
+        let enabled = (0..1000)
+            .filter(|n| Flags::rollout("preview", &n.to_string(), 25))
+            .count();
+        assert!((200..300).contains(&enabled));
+        assert!(Flags::rollout("preview", "sub", 100));
+        assert!(!Flags::rollout("preview", "sub", 0));
+    }
+}
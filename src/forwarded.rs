@@ -0,0 +1,176 @@
+use crate::{extract::Scheme, TokenExtractor};
+use http::{header::HeaderName, Extensions, HeaderMap, Request};
+use std::net::{IpAddr, SocketAddr};
+
+/// Addresses of proxies allowed to inject credentials, see [`ForwardedToken`].
+///
+/// Remote address is read from [`SocketAddr`] request extension, expected to be set by
+/// whatever accepts connections. Requests without it are never trusted.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust proxy at `addr`
+    pub fn addr(self, addr: IpAddr) -> Self {
+        let prefix = if addr.to_canonical().is_ipv4() {
+            32
+        } else {
+            128
+        };
+        self.network(addr, prefix)
+    }
+
+    /// Trust proxies within `addr/prefix` network, e.g. load balancer subnet
+    pub fn network(mut self, addr: IpAddr, prefix: u8) -> Self {
+        self.networks.push((addr.to_canonical(), prefix));
+        self
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        self.networks
+            .iter()
+            .any(|(network, prefix)| match (network, addr) {
+                (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                    same_prefix(&network.octets(), &addr.octets(), *prefix)
+                }
+                (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                    same_prefix(&network.octets(), &addr.octets(), *prefix)
+                }
+                _ => false,
+            })
+    }
+
+    /// Whether request with `extensions` arrived from trusted proxy
+    pub fn trusts(&self, extensions: &Extensions) -> bool {
+        extensions
+            .get::<SocketAddr>()
+            .is_some_and(|remote| self.contains(remote.ip()))
+    }
+}
+
+/// Whether the first `prefix` bits of `a` and `b` match
+fn same_prefix(a: &[u8], b: &[u8], prefix: u8) -> bool {
+    let prefix = usize::from(prefix).min(a.len() * 8);
+    let (bytes, bits) = (prefix / 8, prefix % 8);
+    if a[..bytes] != b[..bytes] {
+        return false;
+    }
+    let mask = (0xff_u8).checked_shl(8 - bits as u32).unwrap_or(0);
+    bits == 0 || (a[bytes] & mask) == (b[bytes] & mask)
+}
+
+/// [`TokenExtractor`] reading token forwarded by ingress which terminated authentication,
+/// from `X-Forwarded-Access-Token` header unless configured otherwise.
+///
+/// Header value is either bare token or `Bearer` credentials. When service is also reachable
+/// directly, anybody could set the header, so forwarded token should only be honored for
+/// requests from [trusted proxies][TrustedProxies].
+///
+/// Presets cover assertions injected by managed edges, such as
+/// [AWS ALB](ForwardedToken::aws_alb) or [Google IAP](ForwardedToken::google_iap).
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use std::net::{IpAddr, Ipv4Addr};
+/// use tower_jwt::{ForwardedToken, Layer, TrustedProxies};
+///
+/// let ingress = TrustedProxies::new().addr(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+/// let layer = Layer::new(decoder).extractor(ForwardedToken::new().require_proxy(ingress));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ForwardedToken {
+    header: HeaderName,
+    proxies: Option<TrustedProxies>,
+}
+
+impl Default for ForwardedToken {
+    fn default() -> Self {
+        Self {
+            header: HeaderName::from_static("x-forwarded-access-token"),
+            proxies: None,
+        }
+    }
+}
+
+impl ForwardedToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `X-Amzn-Oidc-Data` assertion of AWS Application Load Balancer
+    pub fn aws_alb() -> Self {
+        Self::new().header(HeaderName::from_static("x-amzn-oidc-data"))
+    }
+
+    /// `X-Goog-Iap-Jwt-Assertion` of Google Identity-Aware Proxy
+    pub fn google_iap() -> Self {
+        Self::new().header(HeaderName::from_static("x-goog-iap-jwt-assertion"))
+    }
+
+    /// Header carrying the token
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Ignore the header unless request arrived from one of `proxies`
+    pub fn require_proxy(mut self, proxies: TrustedProxies) -> Self {
+        self.proxies = Some(proxies);
+        self
+    }
+}
+
+impl<B> TokenExtractor<B> for ForwardedToken {
+    fn extract(&self, req: &Request<B>) -> Option<String> {
+        if let Some(proxies) = &self.proxies {
+            if !proxies.trusts(req.extensions()) {
+                tracing::debug!("Forwarded token from untrusted peer ignored");
+                return None;
+            }
+        }
+        Scheme::Bearer.header(req, Some(&self.header))
+    }
+
+    fn strip(&self, headers: &mut HeaderMap) {
+        headers.remove(&self.header);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ForwardedToken, TrustedProxies};
+    use crate::TokenExtractor;
+    use http::Request;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn forwarded_from_trusted_proxy() {
+        let proxy: SocketAddr = "10.0.0.2:41000".parse().expect("Valid address");
+        let extractor = ForwardedToken::new().require_proxy(TrustedProxies::new().addr(proxy.ip()));
+        let req = |remote: SocketAddr| {
+            let mut req = Request::builder()
+                .header("X-Forwarded-Access-Token", "a.b.c")
+                .body(())
+                .expect("Valid request");
+            req.extensions_mut().insert(remote);
+            req
+        };
+
+        assert_eq!(extractor.extract(&req(proxy)).as_deref(), Some("a.b.c"));
+        let direct = "203.0.113.7:52000".parse().expect("Valid address");
+        assert_eq!(extractor.extract(&req(direct)), None);
+
+        let subnet = TrustedProxies::new().network("10.0.0.0".parse().expect("Valid address"), 20);
+        assert!(subnet.contains("10.0.15.254".parse().expect("Valid address")));
+        assert!(subnet.contains("::ffff:10.0.1.1".parse().expect("Valid address")));
+        assert!(!subnet.contains("10.0.16.1".parse().expect("Valid address")));
+    }
+}
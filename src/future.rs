@@ -1,12 +1,18 @@
-use crate::{Decoder, Error};
+use crate::{
+    fast::Parsed, observe::Observation, AuthFailure, AuthTiming, Baggage, BoxFuture, ClaimSlot,
+    DecodeStats, Decoder, Denied, Error, GateContext, Options, Payload, TimingSlot,
+};
 use core::future::Future;
 use core::task::{Context, Poll};
 use futures::ready;
-use http::Request;
+use http::{Extensions, HeaderMap, Request, Uri};
 use pin_project::pin_project;
 use std::marker::PhantomData;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
 use tower::Service;
+use tracing::Span;
 
 #[pin_project]
 pub struct MiddlewareFuture<B, S, D>
@@ -16,6 +22,15 @@ where
 {
     service: S,
     request: Option<Request<B>>,
+    token: Option<String>,
+    parsed: Option<Arc<Parsed>>,
+    stripped: Option<(HeaderMap, Uri)>,
+    options: Options,
+    started: Option<Instant>,
+    timing: AuthTiming,
+    stats: Option<DecodeStats>,
+    span: Span,
+    observation: Option<Observation>,
     #[pin]
     state: State<D::Future, S::Future>,
     _decoder: PhantomData<fn() -> D>,
@@ -44,15 +59,78 @@ where
         MiddlewareFuture {
             service,
             request: Some(request),
+            token: None,
+            parsed: None,
+            stripped: None,
+            options: Options::default(),
+            started: None,
+            timing: AuthTiming::default(),
+            stats: None,
+            span: Span::none(),
+            observation: None,
             state: State::Decoding(decoder_future),
             _decoder: PhantomData,
         }
     }
+
+    /// Future for requests let through without authentication
+    pub(crate) fn bypass(mut service: S, request: Request<B>) -> Self {
+        let responding = service.call(request);
+        MiddlewareFuture {
+            service,
+            request: None,
+            token: None,
+            parsed: None,
+            stripped: None,
+            options: Options::default(),
+            started: None,
+            timing: AuthTiming::default(),
+            stats: None,
+            span: Span::none(),
+            observation: None,
+            state: State::Responding(responding),
+            _decoder: PhantomData,
+        }
+    }
+
+    /// Headers and URI to replace request ones with right before calling inner service
+    pub(crate) fn with_stripped(mut self, stripped: Option<(HeaderMap, Uri)>) -> Self {
+        self.stripped = stripped;
+        self
+    }
+
+    /// Token already parsed by fast path, reused by gates and baggage
+    pub(crate) fn with_parsed(mut self, parsed: Option<Arc<Parsed>>) -> Self {
+        self.parsed = parsed;
+        self
+    }
+
+    /// Statistics decoder reported while building its future
+    pub(crate) fn with_stats(mut self, stats: Option<DecodeStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub(crate) fn with_options(mut self, token: Option<String>, options: Options) -> Self {
+        self.token = token;
+        self.started = options.timing.then(Instant::now);
+        self.observation = self
+            .request
+            .as_ref()
+            .and_then(|request| Observation::new(&options, request));
+        self.options = options;
+        self
+    }
 }
 
 #[pin_project(project = StateProject)]
 enum State<D, S> {
     Decoding(#[pin] D),
+    /// Gate `index` is run next, once `check` of the previous one passed
+    Gating {
+        check: Option<BoxFuture<Extensions, Denied>>,
+        index: usize,
+    },
     Responding(#[pin] S),
 }
 
@@ -74,6 +152,12 @@ where
                 StateProject::Decoding(mut decoding) => {
                     let outcome = ready!(decoding.as_mut().poll(cx));
                     tracing::trace!("MiddlewareFuture::decoded");
+                    if let Some(started) = this.started.replace(Instant::now()) {
+                        this.timing.decode = started.elapsed();
+                    }
+                    if let Some(stats) = this.stats.take() {
+                        stats.report(this.timing);
+                    }
                     match outcome {
                         Ok(claim) => {
                             let mut request = this
@@ -82,20 +166,162 @@ where
                                 // only way to construct future is via MiddlewareFuture::new(),
                                 // which takes ownership of actual request struct
                                 .expect("Request was missing on the future");
-                            request.extensions_mut().insert::<D::Claim>(claim);
+                            set_claim(&mut request, claim);
                             tracing::trace!("MiddlewareFuture::modified_request");
-                            let fut = this.service.call(request);
-                            this.state.set(State::Responding(fut));
+                            *this.request = Some(request);
+                            this.state.set(State::Gating {
+                                check: None,
+                                index: 0,
+                            });
                             tracing::trace!("MiddlewareFuture::state_switched");
                         }
-                        Err(err) => return Poll::Ready(Err(Error::Decoder(err))),
+                        Err(err) if this.options.soft_fail => {
+                            tracing::debug!("MiddlewareFuture::soft_failed");
+                            let mut request = this
+                                .request
+                                .take()
+                                .expect("Request was missing on the future");
+                            let decode_failure = D::failure(&err);
+                            if let Some(mut failure) =
+                                AuthFailure::new(&Error::<S::Error, _>::Decoder(err))
+                            {
+                                failure.failure = decode_failure;
+                                request.extensions_mut().insert(failure);
+                            }
+                            strip(&mut request, this.stripped);
+                            let fut = this.span.in_scope(|| this.service.call(request));
+                            this.state.set(State::Responding(fut));
+                        }
+                        Err(err) => {
+                            if let Some(observation) = this.observation {
+                                observation.decoder_error(D::error(&err));
+                            }
+                            return Poll::Ready(Err(reject(this.observation, Error::Decoder(err))));
+                        }
+                    }
+                }
+                StateProject::Gating { check, index } => {
+                    let outcome = match check {
+                        Some(check) => ready!(check.as_mut().poll(cx)),
+                        None => Ok(Extensions::new()),
+                    };
+                    let index = *index;
+                    match outcome {
+                        Ok(extensions) => {
+                            let mut request = this
+                                .request
+                                .take()
+                                .expect("Request was missing on the future");
+                            request.extensions_mut().extend(extensions);
+                            if index < this.options.gates.len() {
+                                let (parts, body) = request.into_parts();
+                                let check = this.options.gates.check(
+                                    index,
+                                    &GateContext::new(&parts, this.token.as_deref()).with_payload(
+                                        this.parsed
+                                            .as_ref()
+                                            .and_then(|parsed| parsed.payload().cloned()),
+                                    ),
+                                );
+                                *this.request = Some(Request::from_parts(parts, body));
+                                this.state.set(State::Gating {
+                                    check: Some(check),
+                                    index: index + 1,
+                                });
+                                continue;
+                            }
+                            tracing::trace!("MiddlewareFuture::gated");
+                            if let Some(started) = this.started.take() {
+                                if index > 0 {
+                                    this.timing.gates = started.elapsed();
+                                }
+                            }
+                            if this.options.timing {
+                                record_timing(&mut request, this.timing);
+                            }
+                            if let Some(claims) = &this.options.baggage {
+                                *this.span = record_baggage(
+                                    &mut request,
+                                    claims,
+                                    payload(this.parsed, this.token.as_deref()),
+                                );
+                            }
+                            strip(&mut request, this.stripped);
+                            let fut = this.span.in_scope(|| this.service.call(request));
+                            this.state.set(State::Responding(fut));
+                        }
+                        Err(denied) => {
+                            this.started.take();
+                            return Poll::Ready(Err(reject(
+                                this.observation,
+                                Error::Denied(denied),
+                            )));
+                        }
                     }
                 }
                 StateProject::Responding(responding) => {
                     tracing::trace!("MiddlewareFuture::polling_inner");
-                    return responding.poll(cx).map_err(Error::Inner)
+                    let _entered = this.span.enter();
+                    return responding.poll(cx).map_err(Error::Inner);
                 }
             }
         }
     }
 }
+
+/// Fills [`ClaimSlot`] preallocated on request extensions, if any, inserting `claim` otherwise
+fn set_claim<B, C: Send + Sync + 'static>(request: &mut Request<B>, claim: C) {
+    let claim = match request.extensions().get::<ClaimSlot<C>>() {
+        Some(slot) => match slot.set(claim) {
+            Ok(()) => return,
+            Err(claim) => claim,
+        },
+        None => claim,
+    };
+    request.extensions_mut().insert(claim);
+}
+
+/// Reports `err` to observer, if any
+fn reject<E, D>(observation: &mut Option<Observation>, err: Error<E, D>) -> Error<E, D> {
+    if let Some(observation) = observation.take() {
+        observation.report(&err);
+    }
+    err
+}
+
+/// Replaces request headers and URI with `stripped` ones, if any
+fn strip<B>(request: &mut Request<B>, stripped: &mut Option<(HeaderMap, Uri)>) {
+    if let Some((headers, uri)) = stripped.take() {
+        *request.headers_mut() = headers;
+        *request.uri_mut() = uri;
+    }
+}
+
+fn record_timing<B>(request: &mut Request<B>, timing: &AuthTiming) {
+    if let Some(slot) = request.extensions().get::<TimingSlot>() {
+        slot.set(timing.total());
+    }
+    request.extensions_mut().insert(timing.clone());
+}
+
+/// Payload of `token`, unless parsed already by fast path
+fn payload(parsed: &Option<Arc<Parsed>>, token: Option<&str>) -> Option<Payload> {
+    match parsed {
+        Some(parsed) => parsed.payload().cloned(),
+        None => token.and_then(Payload::from_token),
+    }
+}
+
+/// Sets [`Baggage`] on request extensions, returns span inner service runs in
+fn record_baggage<B>(
+    request: &mut Request<B>,
+    claims: &[String],
+    payload: Option<Payload>,
+) -> Span {
+    let baggage = payload
+        .map(|payload| Baggage::from_payload(&payload, claims))
+        .unwrap_or_default();
+    let span = baggage.span();
+    request.extensions_mut().insert(baggage);
+    span
+}
@@ -0,0 +1,257 @@
+use crate::{
+    claims_from_extensions, AccountStatus, BoxFuture, Payload, QuotaUsage, StepUpChallenge,
+};
+use futures::future;
+use http::{request::Parts, Extensions, HeaderMap, Method, Uri};
+use std::{cell::OnceCell, fmt, sync::Arc, time::Duration};
+use thiserror::Error;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Post-decode step run by [`Middleware`][crate::Middleware] once token was accepted by [`Decoder`][crate::Decoder]
+/// and claim was set on request extensions, but before request reaches inner service.
+///
+/// Gates can either deny the request or contribute extensions. They run one after another in
+/// the order they were added, each seeing extensions set by the ones before it, and the first
+/// denial stops the request before later gates run.
+/// Any `Fn(&GateContext) -> Result<Extensions, Denied>` is a gate.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)] struct Claim { sub: String }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use http::Extensions;
+/// use tower_jwt::{Denied, GateContext, Layer};
+///
+/// struct UserId(String);
+///
+/// let layer = Layer::new(decoder).gate(|cx: &GateContext<'_>| {
+///     let mut extensions = Extensions::new();
+///     if let Some(claim) = cx.claims::<Claim>() {
+///         extensions.insert(UserId(claim.sub.clone()));
+///     }
+///     Ok::<_, Denied>(extensions)
+/// });
+/// # }
+/// ```
+pub trait Gate: Send + Sync + 'static {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied>;
+}
+
+impl<F> Gate for F
+where
+    F: Fn(&GateContext<'_>) -> Result<Extensions, Denied> + Send + Sync + 'static,
+{
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        Box::pin(future::ready(self(cx)))
+    }
+}
+
+/// Request as seen by [gates][Gate]
+pub struct GateContext<'a> {
+    parts: &'a Parts,
+    token: Option<&'a str>,
+    payload: OnceCell<Option<Payload>>,
+}
+
+impl<'a> GateContext<'a> {
+    pub fn new(parts: &'a Parts, token: Option<&'a str>) -> Self {
+        Self {
+            parts,
+            token,
+            payload: OnceCell::new(),
+        }
+    }
+
+    /// Payload already parsed by [fast path][crate::Layer::fast_path]
+    pub(crate) fn with_payload(self, payload: Option<Payload>) -> Self {
+        if let Some(payload) = payload {
+            let _ = self.payload.set(Some(payload));
+        }
+        self
+    }
+
+    pub fn parts(&self) -> &Parts {
+        self.parts
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.parts.method
+    }
+
+    pub fn uri(&self) -> &Uri {
+        &self.parts.uri
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.parts.headers
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.parts.extensions
+    }
+
+    /// Raw token accepted by decoder
+    pub fn token(&self) -> Option<&str> {
+        self.token
+    }
+
+    /// Untyped view of accepted token payload, parsed on first access
+    pub fn payload(&self) -> Option<&Payload> {
+        self.payload
+            .get_or_init(|| self.token.and_then(Payload::from_token))
+            .as_ref()
+    }
+
+    /// Decoded claim, see [`claims`][crate::claims]
+    pub fn claims<C>(&self) -> Option<&C>
+    where
+        C: Send + Sync + 'static,
+    {
+        claims_from_extensions(&self.parts.extensions)
+    }
+}
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+/// Reasons for [gates][Gate] to deny the request
+pub enum Denied {
+    #[error("Token audience is not accepted")]
+    Audience,
+
+    #[error("Token is not bound to client fingerprint")]
+    Fingerprint,
+
+    /// Account is not active, or token doesn't identify one
+    #[error("Account is not active")]
+    Account(Option<AccountStatus>),
+
+    #[error("Message signature is missing or invalid")]
+    Signature,
+
+    #[error("Token was issued for another tenant")]
+    Tenant,
+
+    #[error("Token was invalidated")]
+    Invalidated,
+
+    #[error("Token subject is not known")]
+    UnknownSubject,
+
+    #[error("Stronger authentication required")]
+    StepUp(StepUpChallenge),
+
+    /// ID token is missing or was rejected by its decoder, see [`IdToken`][crate::IdToken]
+    #[error("ID token is missing or invalid")]
+    IdToken(Option<BoxError>),
+
+    /// Token is longer than [allowed][crate::Layer::max_token_len]
+    #[error("Token exceeds size limit")]
+    TokenTooLarge,
+
+    /// Token lacks scopes required for the request
+    #[error("Token lacks required scope")]
+    InsufficientScope(Vec<String>),
+
+    /// Value of the claim doesn't permit the request
+    #[error("Token claim {0} is not accepted")]
+    Claim(String),
+
+    /// Client used up its [quota][crate::ClientQuota]
+    #[error("Client quota exceeded")]
+    QuotaExceeded(QuotaUsage),
+
+    #[error("Quota exceeded")]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error(transparent)]
+    Other(BoxError),
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Gates(Arc<[Arc<dyn Gate>]>);
+
+impl Gates {
+    pub(crate) fn push<G: Gate>(&mut self, gate: G) {
+        let mut gates = self.0.to_vec();
+        gates.push(Arc::new(gate));
+        self.0 = gates.into();
+    }
+
+    pub(crate) fn extend(&mut self, other: &Gates) {
+        let mut gates = self.0.to_vec();
+        gates.extend(other.0.iter().cloned());
+        self.0 = gates.into();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check of gate at `index`, gates are run one at a time
+    pub(crate) fn check(
+        &self,
+        index: usize,
+        cx: &GateContext<'_>,
+    ) -> BoxFuture<Extensions, Denied> {
+        self.0[index].check(cx)
+    }
+}
+
+impl fmt::Debug for Gates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Gates").field("len", &self.0.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{util, Denied, Error, GateContext, Middleware};
+    use http::{Extensions, Request};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tower::{service_fn, ServiceExt};
+
+    #[derive(Clone)]
+    struct Resolved;
+
+    #[tokio::test]
+    async fn gates_in_order() {
+        let svc = service_fn(|_: Request<()>| async { Ok::<_, ()>(()) });
+        let checked = Arc::new(AtomicUsize::new(0));
+        let counter = checked.clone();
+        let middleware = Middleware::new(util::in_place_decoder(), svc)
+            .gate(|_: &GateContext<'_>| {
+                let mut extensions = Extensions::new();
+                extensions.insert(Resolved);
+                Ok::<_, Denied>(extensions)
+            })
+            .gate(
+                |cx: &GateContext<'_>| match cx.extensions().get::<Resolved>() {
+                    Some(_) => Err(Denied::Tenant),
+                    None => Ok(Extensions::new()),
+                },
+            )
+            .gate(move |_: &GateContext<'_>| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, Denied>(Extensions::new())
+            });
+
+        let req = Request::builder()
+            .header(
+                "Authorization",
+                format!("Bearer {}", util::token(&util::claim(Some(100)))),
+            )
+            .body(())
+            .expect("Valid request");
+        let outcome = middleware.oneshot(req).await;
+        assert!(matches!(outcome, Err(Error::Denied(Denied::Tenant))));
+        assert_eq!(checked.load(Ordering::SeqCst), 0);
+    }
+}
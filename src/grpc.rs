@@ -0,0 +1,104 @@
+use crate::{AuthFailure, Error, FailureKind, Rejection, RejectionHandler, RenderFuture};
+use futures::future;
+use http::{header::CONTENT_TYPE, request::Parts, HeaderValue, Response};
+
+/// `UNAUTHENTICATED` status code
+const UNAUTHENTICATED: &str = "16";
+/// `PERMISSION_DENIED` status code
+const PERMISSION_DENIED: &str = "7";
+/// `RESOURCE_EXHAUSTED` status code
+const RESOURCE_EXHAUSTED: &str = "8";
+
+/// [`RejectionHandler`] answering failures the way gRPC clients expect them: trailers-only
+/// response with `grpc-status` and `grpc-message`, rather than HTTP error status.
+///
+/// gRPC `authorization` metadata travels as HTTP/2 header, so [`DefaultExtractor`][crate::DefaultExtractor]
+/// reads `Bearer` token off it as is and `tonic` handlers find the claim on
+/// `Request::extensions`. Responding with [`GrpcResponder`] is all it takes to put the same
+/// [`Decoder`][crate::Decoder] in front of gRPC services.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{GrpcResponder, Layer};
+///
+/// // e.g. `tonic::transport::Server::builder().layer(layer)`
+/// let layer = Layer::new(decoder).reject_with(GrpcResponder);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GrpcResponder;
+
+impl<E, D, B> RejectionHandler<E, D, B> for GrpcResponder
+where
+    B: Default + Send + 'static,
+{
+    fn render(&self, _: &Parts, err: &Error<E, D>, _: Rejection) -> RenderFuture<B> {
+        let status = match err.kind() {
+            Some(FailureKind::Authorization) => PERMISSION_DENIED,
+            Some(FailureKind::RateLimited) => RESOURCE_EXHAUSTED,
+            _ => UNAUTHENTICATED,
+        };
+        let mut res = Response::new(B::default());
+        let headers = res.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+        headers.insert("grpc-status", HeaderValue::from_static(status));
+        if let Some(failure) = AuthFailure::new(err) {
+            if let Ok(message) = HeaderValue::from_str(&percent_encode(&failure.reason)) {
+                headers.insert("grpc-message", message);
+            }
+        }
+        Box::pin(future::ready(res))
+    }
+}
+
+/// Percent-encodes `message` as required of `grpc-message`
+fn percent_encode(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+    for byte in message.bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'%' => encoded.push(char::from(byte)),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::GrpcResponder;
+    use crate::{util, Middleware};
+    use http::{Request, Response, StatusCode};
+    use std::convert::Infallible;
+    use tower::{service_fn, ServiceExt};
+
+    #[tokio::test]
+    async fn grpc_responder() {
+        let svc = service_fn(|req: Request<()>| async move {
+            let claimed = crate::claims::<util::Claim>(&req).is_some();
+            Ok::<_, Infallible>(Response::new(claimed.to_string()))
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).reject_with(GrpcResponder);
+
+        let req = Request::builder()
+            .uri("/orders.Orders/List")
+            .body(())
+            .expect("Valid request");
+        let res = middleware.clone().oneshot(req).await.expect("Rejection");
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "application/grpc");
+        assert_eq!(res.headers()["grpc-status"], "16");
+        assert_eq!(
+            res.headers()["grpc-message"],
+            "Authorization header must be set"
+        );
+
+        let token = util::token(&util::claim(Some(100)));
+        let req = Request::builder()
+            .uri("/orders.Orders/List")
+            .header("authorization", format!("Bearer {}", token))
+            .body(())
+            .expect("Valid request");
+        let res = middleware.oneshot(req).await.expect("Response");
+        assert_eq!(res.into_body(), "true");
+    }
+}
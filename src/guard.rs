@@ -0,0 +1,236 @@
+use crate::{fast, DecodeFailure, Decoder};
+use futures::future::{self, Either, MapErr, Ready, TryFutureExt};
+use jsonwebtoken::{
+    jwk::{AlgorithmParameters, Jwk, JwkSet},
+    Algorithm,
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Type of key algorithms operate on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyFamily {
+    /// Shared secret, `HS*`
+    Hmac,
+    /// RSA public key, `RS*` and `PS*`
+    Rsa,
+    /// Elliptic curve public key, `ES*`
+    Ec,
+    /// Edwards curve public key, `EdDSA`
+    Ed,
+}
+
+impl KeyFamily {
+    pub fn of(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => Self::Hmac,
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => Self::Rsa,
+            Algorithm::ES256 | Algorithm::ES384 => Self::Ec,
+            Algorithm::EdDSA => Self::Ed,
+        }
+    }
+
+    /// Family of key described by `jwk`
+    pub fn of_jwk(jwk: &Jwk) -> Self {
+        match jwk.algorithm {
+            AlgorithmParameters::OctetKey(_) => Self::Hmac,
+            AlgorithmParameters::RSA(_) => Self::Rsa,
+            AlgorithmParameters::EllipticCurve(_) => Self::Ec,
+            AlgorithmParameters::OctetKeyPair(_) => Self::Ed,
+        }
+    }
+}
+
+/// Wraps any [`Decoder`] refusing tokens whose header-supplied `alg` is not compatible
+/// with configured key families, before the token reaches wrapped decoder.
+///
+/// [`InPlace`][crate::InPlace] decoders get this from `jsonwebtoken`, but custom decoders picking
+/// keys from their own stores are prone to key-confusion attacks, e.g. verifying `HS256` token
+/// using RSA public key as shared secret. Guard makes such tokens structurally impossible
+/// regardless of allowlist configured further down, specific algorithms can be denied on top.
+///
+/// Built [from key set][AlgorithmGuard::from_jwks] decoder verifies tokens against, families
+/// are derived from the keys themselves, and tokens naming a key by `kid` must use algorithm
+/// of that key's family.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder>(decoder: D) {
+/// use jsonwebtoken::Algorithm;
+/// use tower_jwt::{AlgorithmGuard, KeyFamily};
+///
+/// let decoder = AlgorithmGuard::new(decoder, [KeyFamily::Rsa]).deny(Algorithm::RS256);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AlgorithmGuard<D> {
+    decoder: D,
+    families: Vec<KeyFamily>,
+    keys: HashMap<String, KeyFamily>,
+    denied: Vec<Algorithm>,
+}
+
+impl<D> AlgorithmGuard<D> {
+    /// Only accept algorithms operating on `families` of keys
+    pub fn new<I>(decoder: D, families: I) -> Self
+    where
+        I: IntoIterator<Item = KeyFamily>,
+    {
+        Self {
+            decoder,
+            families: families.into_iter().collect(),
+            keys: HashMap::new(),
+            denied: Vec::new(),
+        }
+    }
+
+    /// Only accept algorithms operating on keys of `jwks`
+    pub fn from_jwks(decoder: D, jwks: &JwkSet) -> Self {
+        let keys: HashMap<_, _> = jwks
+            .keys
+            .iter()
+            .filter_map(|jwk| Some((jwk.common.key_id.clone()?, KeyFamily::of_jwk(jwk))))
+            .collect();
+        let mut guard = Self::new(decoder, jwks.keys.iter().map(KeyFamily::of_jwk));
+        guard.keys = keys;
+        guard
+    }
+
+    /// Refuse `algorithm` even if it's compatible with configured key families
+    pub fn deny(mut self, algorithm: Algorithm) -> Self {
+        self.denied.push(algorithm);
+        self
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+
+    fn verify(&self, token: &str) -> Result<(), jsonwebtoken::errors::Error> {
+        let header = fast::decode_header(token)?;
+        let family = KeyFamily::of(header.alg);
+        let key = header.kid.as_ref().and_then(|kid| self.keys.get(kid));
+        if self.denied.contains(&header.alg)
+            || !self.families.contains(&family)
+            || key.is_some_and(|key| *key != family)
+        {
+            return Err(jsonwebtoken::errors::ErrorKind::InvalidAlgorithm.into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AlgorithmGuardError<E> {
+    #[error("Token algorithm is not accepted")]
+    Algorithm(#[source] jsonwebtoken::errors::Error),
+
+    #[error(transparent)]
+    Decoder(E),
+}
+
+impl<D> Decoder for AlgorithmGuard<D>
+where
+    D: Decoder,
+{
+    type Error = AlgorithmGuardError<D::Error>;
+    type Claim = D::Claim;
+    type Future = Either<
+        MapErr<D::Future, fn(D::Error) -> AlgorithmGuardError<D::Error>>,
+        Ready<Result<D::Claim, AlgorithmGuardError<D::Error>>>,
+    >;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        match self.verify(token) {
+            Ok(()) => Either::Left(
+                self.decoder
+                    .decode(token)
+                    .map_err(AlgorithmGuardError::Decoder as fn(_) -> _),
+            ),
+            Err(err) => {
+                tracing::trace!("AlgorithmGuard::rejected");
+                Either::Right(future::ready(Err(AlgorithmGuardError::Algorithm(err))))
+            }
+        }
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        match err {
+            AlgorithmGuardError::Algorithm(_) => Some(DecodeFailure::Algorithm),
+            AlgorithmGuardError::Decoder(err) => D::failure(err),
+        }
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        match err {
+            AlgorithmGuardError::Decoder(err) => D::error(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AlgorithmGuard, AlgorithmGuardError, KeyFamily};
+    use crate::{util, Decoder};
+    use jsonwebtoken::{jwk::JwkSet, Algorithm, EncodingKey, Header};
+
+    #[tokio::test]
+    async fn algorithm_guard() {
+        let valid = util::claim(Some(100));
+        let decoder = AlgorithmGuard::new(util::in_place_decoder(), [KeyFamily::Ed]);
+        let decoded = decoder
+            .decode(&util::token(&valid))
+            .await
+            .expect("EdDSA token is accepted");
+        assert_eq!(decoded, valid);
+
+        let confused = jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &valid,
+            &EncodingKey::from_secret(util::PUBLIC_KEY.as_bytes()),
+        )
+        .expect("Failed to encode valid claim");
+        let outcome = decoder.decode(&confused).await;
+        assert!(matches!(outcome, Err(AlgorithmGuardError::Algorithm(_))));
+
+        let decoder = decoder.deny(Algorithm::EdDSA);
+        let outcome = decoder.decode(&util::token(&valid)).await;
+        assert!(matches!(outcome, Err(AlgorithmGuardError::Algorithm(_))));
+    }
+
+    #[tokio::test]
+    async fn algorithm_guard_jwks() {
+        let jwks: JwkSet = serde_json::from_value(serde_json::json!({ "keys": [
+            { "kty": "OKP", "crv": "Ed25519", "x": "AAAA", "kid": "ed" },
+            { "kty": "RSA", "n": "AQAB", "e": "AQAB", "kid": "rsa" },
+        ]}))
+        .expect("Valid key set");
+        let decoder = AlgorithmGuard::from_jwks(util::in_place_decoder(), &jwks);
+        let valid = util::claim(Some(100));
+        let key = EncodingKey::from_ed_pem(util::PRIVATE_KEY.as_bytes()).expect("Valid key");
+        let token = |kid: &str| {
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some(kid.into());
+            jsonwebtoken::encode(&header, &valid, &key).expect("Failed to encode valid claim")
+        };
+
+        assert!(decoder.decode(&token("ed")).await.is_ok());
+        let outcome = decoder.decode(&token("rsa")).await;
+        assert!(matches!(outcome, Err(AlgorithmGuardError::Algorithm(_))));
+
+        let confused = jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &valid,
+            &EncodingKey::from_secret(util::PUBLIC_KEY.as_bytes()),
+        )
+        .expect("Failed to encode valid claim");
+        let outcome = decoder.decode(&confused).await;
+        assert!(matches!(outcome, Err(AlgorithmGuardError::Algorithm(_))));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::{
+    cache::{KeyCache, Lookup},
+    BoxError, BoxFuture, DecodeFailure, DecodeStats, Decoder, ForwardedToken, KeySource, Layer,
+    RefreshKeys, SyncBoxFuture, TrustedProxies, ValidationProfile,
+};
+use core::future::Future;
+use futures::TryFutureExt;
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+use thiserror::Error;
+
+/// Issuer of IAP assertions
+pub const IAP_ISSUER: &str = "https://cloud.google.com/iap";
+
+/// JWK set of keys signing IAP assertions
+pub const IAP_KEYS_URL: &str = "https://www.gstatic.com/iap/verify/public_key-jwk";
+
+/// Fetches JWK set published at [`IAP_KEYS_URL`], see [`GoogleIap`].
+///
+/// Any `Fn() -> impl Future<Output = Result<JwkSet, BoxError>>` fetches keys.
+pub trait IapKeys: Send + Sync + 'static {
+    fn keys(&self) -> BoxFuture<JwkSet, BoxError>;
+}
+
+impl<F, Fut> IapKeys for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<JwkSet, BoxError>> + Send + 'static,
+{
+    fn keys(&self) -> BoxFuture<JwkSet, BoxError> {
+        Box::pin(self())
+    }
+}
+
+/// Identity asserted by Google Identity-Aware Proxy, produced by [`GoogleIap`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IapClaim {
+    sub: String,
+    email: String,
+    hd: Option<String>,
+}
+
+impl IapClaim {
+    /// Stable user identifier, `accounts.google.com:<id>`
+    pub fn subject(&self) -> &str {
+        &self.sub
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    /// Hosted domain of Google Workspace accounts
+    pub fn hosted_domain(&self) -> Option<&str> {
+        self.hd.as_deref()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum IapError {
+    #[error("Failed to decode assertion")]
+    Decode(#[source] jsonwebtoken::errors::Error),
+
+    #[error("Assertion is signed by unknown key")]
+    UnknownKey,
+
+    #[error("Failed to fetch IAP keys")]
+    Keys(#[source] BoxError),
+}
+
+/// [`Decoder`] of `X-Goog-Iap-Jwt-Assertion` set by
+/// [Google Identity-Aware Proxy](https://cloud.google.com/iap/docs/signed-headers-howto).
+///
+/// Assertions must be ES256 signed by one of IAP keys, issued by [`IAP_ISSUER`] for expected
+/// audience: [`compute_engine`][GoogleIap::compute_engine] backend service or
+/// [`app_engine`][GoogleIap::app_engine] application. Keys are fetched through [`IapKeys`],
+/// typically from [`IAP_KEYS_URL`], and cached for an hour. Assertions signed by keys missing
+/// from the cached set have keys fetched again, at most once per 30 seconds.
+///
+/// Assertion only proves the request passed through IAP if nothing else can reach the service,
+/// [`layer`][GoogleIap::layer] thus only accepts it from [trusted proxies][TrustedProxies].
+///
+/// ```rust
+/// # async fn fetch(url: &str) -> Result<jsonwebtoken::jwk::JwkSet, tower_jwt::BoxError> { todo!() }
+/// # fn example(lb: std::net::IpAddr) {
+/// use tower_jwt::{GoogleIap, TrustedProxies, IAP_KEYS_URL};
+///
+/// let iap = GoogleIap::compute_engine("1234567890", "987654321", || fetch(IAP_KEYS_URL));
+/// let layer = iap.layer(TrustedProxies::new().network(lb, 22));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct GoogleIap {
+    validation: Validation,
+    profile: Option<ValidationProfile>,
+    keys: Arc<dyn IapKeys>,
+    cache: KeyCache,
+}
+
+impl GoogleIap {
+    /// Accept assertions issued for `audience`
+    pub fn new<K: IapKeys>(audience: impl Into<String>, keys: K) -> Self {
+        let mut validation = Validation::new(Algorithm::ES256);
+        validation.set_issuer(&[IAP_ISSUER]);
+        validation.set_audience(&[audience.into()]);
+        Self {
+            validation,
+            profile: None,
+            keys: Arc::new(keys),
+            cache: KeyCache::new(Duration::from_secs(3600)),
+        }
+    }
+
+    /// Accept assertions for backend service behind HTTPS load balancer
+    pub fn compute_engine<K: IapKeys>(
+        project_number: &str,
+        backend_service_id: &str,
+        keys: K,
+    ) -> Self {
+        let audience = format!(
+            "/projects/{}/global/backendServices/{}",
+            project_number, backend_service_id
+        );
+        Self::new(audience, keys)
+    }
+
+    /// Accept assertions for App Engine application
+    pub fn app_engine<K: IapKeys>(project_number: &str, project_id: &str, keys: K) -> Self {
+        Self::new(
+            format!("/projects/{}/apps/{}", project_number, project_id),
+            keys,
+        )
+    }
+
+    /// How long fetched keys are used for
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.cache = KeyCache::new(ttl);
+        self
+    }
+
+    /// Apply [`ValidationProfile`] to assertion validation
+    pub fn with_profile(mut self, profile: ValidationProfile) -> Self {
+        profile.apply(&mut self.validation);
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Fetch of keys shared through [`KeyCache`]
+    fn fetch_keys(&self) -> BoxFuture<HashMap<String, DecodingKey>, BoxError> {
+        let keys = self.keys.clone();
+        Box::pin(async move {
+            let jwks = keys.keys().await?;
+            Ok(jwks
+                .keys
+                .iter()
+                .filter_map(|jwk| {
+                    let kid = jwk.common.key_id.clone()?;
+                    Some((kid, DecodingKey::from_jwk(jwk).ok()?))
+                })
+                .collect())
+        })
+    }
+
+    /// [`Layer`] reading assertion off `X-Goog-Iap-Jwt-Assertion` of requests from `proxies`
+    pub fn layer(self, proxies: TrustedProxies) -> Layer<Self, ForwardedToken> {
+        Layer::new(self).extractor(ForwardedToken::google_iap().require_proxy(proxies))
+    }
+}
+
+impl fmt::Debug for GoogleIap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GoogleIap")
+            .field("audience", &self.validation.aud)
+            .field("ttl", &self.cache.ttl())
+            .finish_non_exhaustive()
+    }
+}
+
+impl RefreshKeys for GoogleIap {
+    fn refresh_keys(&self, token: &str) -> Option<BoxFuture<(), BoxError>> {
+        let kid = jsonwebtoken::decode_header(token).ok()?.kid;
+        let refresh = self.cache.refresh(kid.as_deref(), || self.fetch_keys())?;
+        Some(Box::pin(refresh.map_ok(|_| ()).map_err(BoxError::from)))
+    }
+}
+
+impl Decoder for GoogleIap {
+    type Error = IapError;
+    type Claim = IapClaim;
+    type Future = SyncBoxFuture<Self::Claim, Self::Error>;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        tracing::trace!("GoogleIap::entered");
+        let this = self.clone();
+        let token = token.to_owned();
+        let stats = DecodeStats::current();
+        SyncBoxFuture::new(Box::pin(async move {
+            let header = jsonwebtoken::decode_header(&token).map_err(IapError::Decode)?;
+            let kid = header.kid.ok_or(IapError::UnknownKey)?;
+            let key = match this.cache.lookup(&[&kid], || this.fetch_keys()) {
+                Lookup::Hit(key) => {
+                    tracing::Span::current().record("cache", "hit");
+                    if let Some(stats) = &stats {
+                        stats.key_source(KeySource::Cached);
+                    }
+                    key
+                }
+                Lookup::Unknown => return Err(IapError::UnknownKey),
+                Lookup::Failed(err) => return Err(IapError::Keys(err.into())),
+                Lookup::Fetch(fetch) => {
+                    tracing::Span::current().record("cache", "miss");
+                    if let Some(stats) = &stats {
+                        stats.key_source(KeySource::Fetched);
+                    }
+                    let keys = fetch.await.map_err(|err| IapError::Keys(err.into()))?;
+                    keys.get(&kid).cloned().ok_or(IapError::UnknownKey)?
+                }
+            };
+            let claims = jsonwebtoken::decode(&token, &key, &this.validation)
+                .map_err(IapError::Decode)?
+                .claims;
+            if let Some(profile) = this.profile {
+                profile.check(&token).map_err(IapError::Decode)?;
+            }
+            Ok(claims)
+        }))
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        match err {
+            IapError::UnknownKey => Some(DecodeFailure::Key),
+            err => Some(DecodeFailure::of(err)),
+        }
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GoogleIap, IapError, IAP_ISSUER};
+    use crate::{BoxError, Decoder};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{encode, jwk::JwkSet, Algorithm, EncodingKey, Header};
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+    };
+    use serde_json::json;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    async fn google_iap() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .expect("Generated key");
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref())
+            .expect("Valid key");
+        // uncompressed point, 0x04 || x || y
+        let point = pair.public_key().as_ref();
+        let jwks: JwkSet = serde_json::from_value(json!({ "keys": [{
+            "kty": "EC",
+            "crv": "P-256",
+            "alg": "ES256",
+            "kid": "iap-1",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+        }]}))
+        .expect("Valid JWK set");
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = fetches.clone();
+        let decoder = GoogleIap::compute_engine("42", "7", move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            let jwks = jwks.clone();
+            async move { Ok::<_, BoxError>(jwks) }
+        });
+        let key = EncodingKey::from_ec_der(pkcs8.as_ref());
+        let assertion = |kid: &str, aud: &str| {
+            let mut header = Header::new(Algorithm::ES256);
+            header.kid = Some(String::from(kid));
+            let claims = json!({
+                "iss": IAP_ISSUER,
+                "aud": aud,
+                "sub": "accounts.google.com:1234",
+                "email": "ada@example.com",
+                "exp": chrono::Utc::now().timestamp() + 600,
+            });
+            encode(&header, &claims, &key).expect("Valid token")
+        };
+
+        let claim = decoder
+            .decode(&assertion("iap-1", "/projects/42/global/backendServices/7"))
+            .await
+            .expect("Valid assertion");
+        assert_eq!(claim.email(), "ada@example.com");
+
+        let other = decoder
+            .decode(&assertion("iap-1", "/projects/42/apps/other"))
+            .await;
+        assert!(matches!(other, Err(IapError::Decode(_))));
+
+        // made up `kid`s don't trigger a fetch each
+        for kid in ["forged-1", "forged-2"] {
+            let forged = decoder
+                .decode(&assertion(kid, "/projects/42/global/backendServices/7"))
+                .await;
+            assert!(matches!(forged, Err(IapError::UnknownKey)));
+        }
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{BoxError, BoxFuture, Decoder, Denied, Gate, GateContext, Payload};
+use futures::{future, FutureExt};
+use http::{header::HeaderName, Extensions};
+use std::{fmt, ops::Deref};
+
+/// Claim of ID token verified by [`IdToken`], set on request extensions apart from
+/// access token claim, even when both are of the same type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdClaims<C>(C);
+
+impl<C> IdClaims<C> {
+    pub fn into_inner(self) -> C {
+        self.0
+    }
+}
+
+impl<C> Deref for IdClaims<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<C> AsRef<C> for IdClaims<C> {
+    fn as_ref(&self) -> &C {
+        &self.0
+    }
+}
+
+/// [`Gate`] decoding ID token forwarded next to the access token, e.g. by BFF gateways,
+/// and setting its claim on request extensions as [`IdClaims`].
+///
+/// ID token is read off `X-Id-Token` by default and verified by its own [`Decoder`], its `sub`
+/// and `iss` must match those of access token, so callers can't pair their access token
+/// with someone else's ID token. Requests without valid ID token are denied with
+/// [`Denied::IdToken`], unless it was made [optional][IdToken::optional], in which case invalid
+/// tokens are still denied.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)] struct IdClaim { email: String }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D, id: tower_jwt::InPlace<IdClaim>) {
+/// use tower_jwt::{IdToken, Layer};
+///
+/// let layer = Layer::new(decoder).gate(IdToken::new(id));
+/// // handlers read `IdClaims<IdClaim>` off request extensions next to access token claim
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct IdToken<D> {
+    decoder: D,
+    header: HeaderName,
+    required: bool,
+}
+
+impl<D> IdToken<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            header: HeaderName::from_static("x-id-token"),
+            required: true,
+        }
+    }
+
+    /// Read ID token off `header` instead of `X-Id-Token`
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Let requests without ID token through
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+}
+
+impl<D> fmt::Debug for IdToken<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdToken")
+            .field("header", &self.header)
+            .field("required", &self.required)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D> Gate for IdToken<D>
+where
+    D: Decoder + Send + Sync + 'static,
+    D::Claim: Send + Sync,
+    D::Error: std::error::Error + Send + Sync + 'static,
+    D::Future: Send + 'static,
+{
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let token = cx
+            .headers()
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|token| !token.is_empty());
+        match token {
+            Some(token) => {
+                // ID token is only checked against access token once its decoder accepted it
+                let bound = bound_to(Payload::from_token(token), cx.payload());
+                self.decoder
+                    .decode(token)
+                    .map(move |outcome| {
+                        let claim = outcome.map_err(|err| Denied::IdToken(Some(err.into())))?;
+                        if !bound {
+                            let reason = "ID token doesn't belong to access token subject";
+                            return Err(Denied::IdToken(Some(BoxError::from(reason))));
+                        }
+                        let mut extensions = Extensions::new();
+                        extensions.insert(IdClaims(claim));
+                        Ok(extensions)
+                    })
+                    .boxed()
+            }
+            None if self.required => Box::pin(future::ready(Err(Denied::IdToken(None)))),
+            None => Box::pin(future::ready(Ok(Extensions::new()))),
+        }
+    }
+}
+
+/// Whether ID token is issued for the same `sub` by the same `iss` as access token
+fn bound_to(id: Option<Payload>, access: Option<&Payload>) -> bool {
+    match (id, access) {
+        (Some(id), Some(access)) => ["sub", "iss"]
+            .iter()
+            .all(|claim| id.str(claim).is_some() && id.str(claim) == access.str(claim)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IdClaims, IdToken};
+    use crate::{util, Denied, Error, InPlace, InPlaceBuilder, Middleware};
+    use http::Request;
+    use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+    use serde::Deserialize;
+    use serde_json::json;
+    use tower::{service_fn, ServiceExt};
+
+    #[derive(Deserialize)]
+    struct IdClaim {
+        sub: String,
+    }
+
+    #[tokio::test]
+    async fn id_token() {
+        let svc = service_fn(|req: Request<()>| async move {
+            let access = crate::claims::<util::Claim>(&req).is_some();
+            let id = req
+                .extensions()
+                .get::<IdClaims<IdClaim>>()
+                .map(|claim| claim.sub.clone());
+            Ok::<_, ()>((access, id))
+        });
+        let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes()).expect("Valid key");
+        let id_decoder: InPlace<IdClaim> =
+            InPlaceBuilder::new(key, Validation::new(Algorithm::EdDSA)).build();
+        let middleware =
+            Middleware::new(util::in_place_decoder(), svc).gate(IdToken::new(id_decoder));
+        let token = util::token(&util::claim(Some(100)));
+        let req = |id: Option<&str>| {
+            let mut req = Request::builder().header("authorization", format!("Bearer {}", token));
+            if let Some(id) = id {
+                req = req.header("x-id-token", id);
+            }
+            req.body(()).expect("Valid request")
+        };
+
+        let outcome = middleware.clone().oneshot(req(Some(&token))).await;
+        assert_eq!(
+            outcome.expect("Valid ID token"),
+            (true, Some(String::from("sub")))
+        );
+
+        let outcome = middleware.clone().oneshot(req(None)).await;
+        assert!(matches!(outcome, Err(Error::Denied(Denied::IdToken(None)))));
+
+        let outcome = middleware.clone().oneshot(req(Some("not-a-token"))).await;
+        assert!(matches!(
+            outcome,
+            Err(Error::Denied(Denied::IdToken(Some(_))))
+        ));
+
+        // valid ID token of someone else
+        let exp = chrono::Utc::now().timestamp() + 100;
+        let other = util::token(&json!({ "sub": "other", "iss": "issuer", "exp": exp }));
+        match middleware.oneshot(req(Some(&other))).await {
+            Err(Error::Denied(Denied::IdToken(Some(reason)))) => assert_eq!(
+                reason.to_string(),
+                "ID token doesn't belong to access token subject"
+            ),
+            _ => unreachable!("Accepted ID token of other subject"),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{AsyncTokenExtractor, ExtractFuture, RawToken, TokenExtractor};
+use futures::{
+    channel::oneshot,
+    future::{ready, Shared},
+    FutureExt,
+};
+use http::{request::Parts, Request};
+use std::fmt;
+
+/// Token supplied after the request entered the stack, e.g. by upstream filter injecting
+/// headers once `100 Continue` was sent, see [`Late`].
+///
+/// Insert it into request extensions and hand [`LateTokenSender`] over to whatever
+/// produces the token.
+///
+/// ```rust
+/// use http::Request;
+/// use tower_jwt::LateToken;
+///
+/// let (sender, late) = LateToken::channel();
+/// let mut req = Request::new(());
+/// req.extensions_mut().insert(late);
+/// // Later on
+/// sender.send("a.b.c");
+/// ```
+#[derive(Clone)]
+pub struct LateToken(Shared<oneshot::Receiver<String>>);
+
+impl LateToken {
+    pub fn channel() -> (LateTokenSender, Self) {
+        let (sender, receiver) = oneshot::channel();
+        (LateTokenSender(sender), Self(receiver.shared()))
+    }
+}
+
+impl fmt::Debug for LateToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LateToken")
+            .field(&self.0.peek().map(|token| token.is_ok()))
+            .finish()
+    }
+}
+
+/// Supplies [`LateToken`], dropping it without sending rejects the request as if no token
+/// was presented
+#[derive(Debug)]
+pub struct LateTokenSender(oneshot::Sender<String>);
+
+impl LateTokenSender {
+    pub fn send(self, token: impl Into<String>) {
+        // Request may be gone already
+        let _ = self.0.send(token.into());
+    }
+}
+
+/// [`AsyncTokenExtractor`] giving the token a second chance: whenever wrapped extractor
+/// finds none, waits for [`LateToken`] in request extensions, if any.
+///
+/// Wrapped extractor sees request head without extensions, save for [`RawToken`].
+/// Waiting is unbounded, so the stack is expected to time requests out.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::Layer;
+///
+/// let layer = Layer::new(decoder).late_token();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Late<X> {
+    extractor: X,
+}
+
+impl<X> Late<X> {
+    pub fn new(extractor: X) -> Self {
+        Self { extractor }
+    }
+
+    pub fn into_inner(self) -> X {
+        self.extractor
+    }
+}
+
+impl<X> AsyncTokenExtractor for Late<X>
+where
+    X: TokenExtractor<()> + Send + Sync + 'static,
+{
+    fn extract(&self, parts: &Parts) -> ExtractFuture {
+        let mut req = Request::new(());
+        *req.method_mut() = parts.method.clone();
+        *req.uri_mut() = parts.uri.clone();
+        *req.version_mut() = parts.version;
+        *req.headers_mut() = parts.headers.clone();
+        if let Some(raw) = parts.extensions.get::<RawToken>() {
+            req.extensions_mut().insert(raw.clone());
+        }
+        if let Some(token) = self.extractor.extract(&req) {
+            return Box::pin(ready(Some(token)));
+        }
+
+        match parts.extensions.get::<LateToken>() {
+            Some(LateToken(late)) => {
+                tracing::trace!("Late::waiting");
+                Box::pin(late.clone().map(Result::ok))
+            }
+            None => Box::pin(ready(None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LateToken;
+    use crate::{util, Error, Middleware};
+    use http::Request;
+    use tower::{service_fn, ServiceExt};
+
+    #[tokio::test]
+    async fn late_token() {
+        let svc = service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(crate::claims::<util::Claim>(&req).cloned())
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).late_token();
+        let claim = util::claim(Some(100));
+
+        let (sender, late) = LateToken::channel();
+        let mut req = Request::new(());
+        req.extensions_mut().insert(late);
+        let token = util::token(&claim);
+        tokio::spawn(async move { sender.send(token) });
+        let res = middleware.clone().oneshot(req).await.expect("Late token");
+        assert_eq!(res, Some(claim));
+
+        let (sender, late) = LateToken::channel();
+        let mut req = Request::new(());
+        req.extensions_mut().insert(late);
+        drop(sender);
+        let err = middleware.oneshot(req).await.expect_err("No token");
+        assert!(matches!(err, Error::MissingAuthorizationHeader));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{Claims, Decoder, DefaultExtractor, Error, TokenExtractor};
+use core::task::{Context, Poll};
+use futures::{
+    future::{self, Either, MapErr, Ready, TryFutureExt},
+    lock::Mutex,
+};
+use http::Request;
+use std::{fmt, sync::Arc};
+use tower::Service;
+
+/// Layer deferring verification until claim is requested by the handler.
+///
+/// Instead of decoded claim, [`LazyToken`] is set on request extensions, so routes which only
+/// occasionally need identity don't pay verification cost on every request. Requests without
+/// token are still rejected.
+///
+/// Unlike [`Middleware`][crate::Middleware], nothing runs on the token before handler verifies
+/// it: there are no [gates][crate::Gate], [baggage][crate::Baggage] or other options looking
+/// at claims, as those would be acting on unverified ones.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)] struct Claim { jti: String }
+/// # async fn example(req: http::Request<()>) {
+/// use tower_jwt::{InPlace, LazyToken};
+///
+/// // given `Lazy::new(InPlace::<Claim>::new(..))` layer
+/// if let Some(lazy) = req.extensions().get::<LazyToken<InPlace<Claim>>>() {
+///     match lazy.claims().await {
+///         Ok(claim) => println!("{}", claim.jti),
+///         Err(err) => eprintln!("Rejected token: {}", err),
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Lazy<D, X = DefaultExtractor> {
+    decoder: D,
+    extractor: X,
+}
+
+impl<D> Lazy<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            extractor: DefaultExtractor::default(),
+        }
+    }
+}
+
+impl<D, X> Lazy<D, X> {
+    /// Locate token with `extractor` instead of [`DefaultExtractor`]
+    pub fn extractor<Y>(self, extractor: Y) -> Lazy<D, Y> {
+        Lazy {
+            decoder: self.decoder,
+            extractor,
+        }
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+impl<D, X, S> tower::Layer<S> for Lazy<D, X>
+where
+    D: Clone,
+    X: Clone,
+{
+    type Service = LazyService<D, S, X>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        LazyService {
+            lazy: self.clone(),
+            service: inner,
+        }
+    }
+}
+
+/// [`Service`] produced by [`Lazy`]
+#[derive(Debug, Clone)]
+pub struct LazyService<D, S, X = DefaultExtractor> {
+    lazy: Lazy<D, X>,
+    service: S,
+}
+
+type Rejected<S, D, B> = Ready<
+    Result<<S as Service<Request<B>>>::Response, Error<<S as Service<Request<B>>>::Error, D>>,
+>;
+type Forward<S, D, B> = MapErr<
+    <S as Service<Request<B>>>::Future,
+    fn(<S as Service<Request<B>>>::Error) -> Error<<S as Service<Request<B>>>::Error, D>,
+>;
+
+impl<D, S, X, B> Service<Request<B>> for LazyService<D, S, X>
+where
+    S: Service<Request<B>>,
+    D: Decoder + Clone + Send + Sync + 'static,
+    D::Claim: Send + Sync,
+    D::Error: Send + Sync,
+    X: TokenExtractor<B>,
+{
+    type Response = S::Response;
+    type Error = Error<S::Error, D::Error>;
+    type Future = Either<Rejected<S, D::Error, B>, Forward<S, D::Error, B>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(Error::Inner)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let Some(token) = self.lazy.extractor.extract(&req) else {
+            return Either::Left(future::ready(Err(Error::MissingAuthorizationHeader)));
+        };
+        let lazy = LazyToken::new(token, self.lazy.decoder.clone());
+        req.extensions_mut().insert(lazy);
+        Either::Right(self.service.call(req).map_err(Error::Inner as fn(_) -> _))
+    }
+}
+
+/// Token verified on first access, see [`Lazy`]
+pub struct LazyToken<D: Decoder> {
+    inner: Arc<Inner<D>>,
+}
+
+type Outcome<D> = Result<Claims<<D as Decoder>::Claim>, Arc<<D as Decoder>::Error>>;
+
+struct Inner<D: Decoder> {
+    token: String,
+    decoder: D,
+    outcome: Mutex<Option<Outcome<D>>>,
+}
+
+impl<D: Decoder> LazyToken<D> {
+    pub fn new(token: impl Into<String>, decoder: D) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                token: token.into(),
+                decoder,
+                outcome: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Raw, not yet verified token
+    pub fn token(&self) -> &str {
+        &self.inner.token
+    }
+
+    /// Decodes the token on first call, subsequent calls return memoized outcome
+    pub async fn claims(&self) -> Outcome<D> {
+        let mut outcome = self.inner.outcome.lock().await;
+        if let Some(outcome) = outcome.as_ref() {
+            return outcome.clone();
+        }
+
+        let decoded = self
+            .inner
+            .decoder
+            .decode(&self.inner.token)
+            .await
+            .map(Claims::new)
+            .map_err(Arc::new);
+        *outcome = Some(decoded.clone());
+        decoded
+    }
+}
+
+impl<D: Decoder> Clone for LazyToken<D> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<D: Decoder> fmt::Debug for LazyToken<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyToken").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Lazy, LazyToken};
+    use crate::{util, Error, InPlace};
+    use http::Request;
+    use tower::{service_fn, Layer, ServiceExt};
+
+    #[tokio::test]
+    async fn lazy_memoized() {
+        let svc = service_fn(|req: Request<()>| async move {
+            let lazy = req
+                .extensions()
+                .get::<LazyToken<InPlace<util::Claim>>>()
+                .cloned()
+                .expect("LazyToken must be set");
+            Ok::<_, ()>(lazy)
+        });
+        let svc = Lazy::new(util::in_place_decoder()).layer(svc);
+        let request = |claim| {
+            Request::builder()
+                .header("Authorization", format!("Bearer {}", util::token(&claim)))
+                .body(())
+                .expect("Valid request")
+        };
+
+        let valid = util::claim(Some(100));
+        let lazy = svc
+            .clone()
+            .oneshot(request(valid.clone()))
+            .await
+            .expect("Token is not verified upfront");
+        let first = lazy.claims().await.expect("Failed to decode valid token");
+        let second = lazy.clone().claims().await.expect("Outcome is memoized");
+        assert_eq!(*first, valid);
+        assert!(std::sync::Arc::ptr_eq(
+            &first.into_arc(),
+            &second.into_arc()
+        ));
+
+        let expired = svc
+            .clone()
+            .oneshot(request(util::claim(None)))
+            .await
+            .expect("Token is not verified upfront");
+        assert!(expired.claims().await.is_err());
+
+        let missing = svc.oneshot(Request::new(())).await;
+        assert!(matches!(missing, Err(Error::MissingAuthorizationHeader)));
+    }
+}
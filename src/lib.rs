@@ -25,6 +25,11 @@
 //!middleware.call(req).await;
 //!// inner services have `Claim` set on req.extensions()!
 //!# }
+//!# fn handler(req: http::Request<()>) {
+//!# #[derive(serde::Deserialize)] struct Claim { jti: String }
+//!// which is easiest to access via `tower_jwt::claims`
+//!let claim: Option<&Claim> = tower_jwt::claims::<Claim>(&req);
+//!# }
 //!```
 //!
 //!Slightly more involved with custom decoder:
@@ -92,64 +97,444 @@
 //!```
 
 use futures::future::Either;
-use http::Request;
+use http::{
+    header::{HeaderName, ACCESS_CONTROL_REQUEST_METHOD},
+    Method, Request,
+};
 use serde::de::DeserializeOwned;
 use std::future::Ready;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use thiserror::Error;
 use tower::Service;
-use typed_headers::{Authorization, HeaderMapExt};
+
+mod account;
+pub use account::{AccountStatus, CheckAccount, UserStatusStore};
+
+mod audience;
+pub use audience::{
+    AudienceHandle, AudienceSunset, MatchAudience, MatchedAudience, VersionedAudience,
+};
+
+mod auth_age;
+pub use auth_age::AuthAge;
+
+#[cfg(feature = "aws")]
+mod aws;
+#[cfg(feature = "aws")]
+pub use aws::{AwsCredentials, AwsError, AwsKeys, AwsSecret, ProvideCredentials};
+
+mod baggage;
+pub use baggage::Baggage;
+
+mod batch;
+pub use batch::{BatchError, BatchFuture, BatchVerifier, Batched};
+
+mod boxed;
+pub use boxed::{BoxFuture, Boxed, SyncBoxFuture};
+
+#[macro_use]
+mod builder;
+
+mod cache;
+
+mod cached;
+pub use cached::{Cached, CachedFuture};
+
+mod chain;
+pub use chain::{Attempt, Chain, ChainError, ChainFuture};
+
+mod claims;
+pub use claims::{claims, claims_from_extensions, Claims};
+
+mod client;
+pub use client::{
+    AccessToken, Authorize, AuthorizeError, AuthorizeFuture, AuthorizeLayer, RetryAuthorize,
+    RetryAuthorizeFuture, RetryAuthorizeLayer, TokenCache, TokenProvider, TokenRequest,
+};
+
+pub mod codegen;
+
+#[cfg(feature = "test-util")]
+pub mod conformance;
+
+mod cookie;
 
 mod decoder;
-pub use decoder::{Decoder, InPlace, InPlaceBuilder};
+pub use decoder::{Decoder, InPlace, InPlaceBuilder, Shared, WeakSecret};
+
+mod detached;
+pub use detached::{DetachedJws, DetachedJwsError, DetachedJwsService, JwsHeader};
+
+mod drift;
+pub use drift::{ClaimDrift, ClaimDriftFuture, ClaimKind, Drift};
+
+#[cfg(feature = "did")]
+mod did;
+#[cfg(feature = "did")]
+pub use did::{DidError, DidMethods, DidResolver};
+
+mod entitlements;
+pub use entitlements::{EntitlementSource, Entitlements, ExpandEntitlements, Revalidation};
+
+mod exempt;
+use exempt::Exemptions;
+
+mod extract;
+pub use extract::{DefaultExtractor, FormBody, PathToken, RawToken, Sources, TokenExtractor};
+
+mod fast;
+
+mod fingerprint;
+pub use fingerprint::Fingerprint;
+
+#[cfg(feature = "test-util")]
+mod fixture;
+#[cfg(feature = "test-util")]
+pub use fixture::FixtureIdp;
+
+mod flags;
+pub use flags::{FeatureFlagResolver, FeatureFlags, Flags};
+
+mod forwarded;
+pub use forwarded::{ForwardedToken, TrustedProxies};
 
 mod future;
 pub use future::MiddlewareFuture;
 
+mod gate;
+use gate::Gates;
+pub use gate::{BoxError, Denied, Gate, GateContext};
+
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "grpc")]
+pub use grpc::GrpcResponder;
+
+mod guard;
+pub use guard::{AlgorithmGuard, AlgorithmGuardError, KeyFamily};
+
+mod iap;
+pub use iap::{GoogleIap, IapClaim, IapError, IapKeys, IAP_ISSUER, IAP_KEYS_URL};
+
+mod id_token;
+pub use id_token::{IdClaims, IdToken};
+
+mod late;
+pub use late::{Late, LateToken, LateTokenSender};
+
+mod lazy;
+pub use lazy::{Lazy, LazyService, LazyToken};
+
+mod login;
+pub use login::LoginRedirect;
+
+mod metadata;
+pub use metadata::{ResourceMetadata, WELL_KNOWN_PATH};
+
+mod mint;
+pub use mint::{Mint, MintError, MintLayer, Minter};
+
+mod nbf;
+pub use nbf::{NbfRetry, NbfStats, Sleep, SleepFuture};
+
+mod oauth;
+pub use oauth::{ClientAuth, ClientCredentials, JwtBearer, OAuthError, TokenEndpoint};
+
+mod observe;
+use observe::{Observation, Observer};
+pub use observe::{RejectionEvent, RejectionObserver};
+
+mod offload;
+pub use offload::{Job, Offload, OffloadError, OffloadFuture, Spawner};
+
+mod path;
+
+mod payload;
+pub use payload::Payload;
+
+mod peer;
+pub use peer::{Peer, PeerError, PeerIdentity, PeerToken};
+
+mod priority;
+pub use priority::{Prioritize, Priority};
+
+mod problem;
+pub use problem::ProblemJson;
+
+mod profile;
+pub use profile::ValidationProfile;
+
+mod query;
+
+mod quota;
+pub use quota::{ClientQuota, MemoryQuotaStore, Period, QuotaStore, QuotaUsage};
+
+mod rate_limit;
+pub use rate_limit::RateLimit;
+
+mod redact;
+pub use redact::{Redact, RedactFuture, Redaction, RedactionKey};
+
+mod refresh;
+pub use refresh::{RefreshKeys, RefreshOnMismatch, RefreshOnMismatchFuture};
+
+mod rejection;
+pub use rejection::{
+    DecodeFailure, DefaultRejection, FailureKind, Rejecting, RejectingFuture, Rejection,
+    RejectionHandler, RenderFuture, StatusMap,
+};
+
+#[cfg(feature = "test-util")]
+mod replay;
+#[cfg(feature = "test-util")]
+pub use replay::{Replay, ReplayError, ReplayFuture, RECORD_ENV, REPLAY_ENV};
+
+mod resolve;
+pub use resolve::{
+    AsyncExtract, AsyncExtractFuture, AsyncTokenExtractor, ExtractFuture, ResolvedToken,
+};
+
+mod route;
+pub use route::Route;
+use route::Routes;
+
+#[cfg(feature = "saml")]
+mod saml;
+#[cfg(feature = "saml")]
+pub use saml::{SamlBridge, SamlBridgeError};
+
+mod scope;
+pub use scope::RequireScope;
+
+mod service;
+pub use service::{DecoderService, VerifyRequest};
+
+mod signature;
+pub use signature::{MessageSignature, SignatureKey, SignatureKeys, SignedMessage};
+
+mod step_up;
+pub use step_up::{StepUp, StepUpChallenge};
+
+mod slot;
+pub use slot::ClaimSlot;
+
+mod soft_fail;
+pub use soft_fail::AuthFailure;
+
+mod subject;
+pub use subject::{ResolveSubject, ResolvedSubject, SubjectResolver};
+
+mod tenant;
+pub use tenant::{MatchTenant, Tenant};
+
+mod timing;
+use timing::TimingSlot;
+pub use timing::{
+    AuthTiming, CacheStatus, DecodeStats, KeySource, ServerTiming, ServerTimingFuture,
+};
+
+mod traced;
+pub use traced::{Traced, TracedFuture};
+
+mod usage;
+pub use usage::{KeyStats, KeyUsage, TrackKeys, TrackKeysFuture};
+
+#[cfg(feature = "vc")]
+mod vc;
+#[cfg(feature = "vc")]
+pub use vc::{Credential, JwtVc, VcError};
+
 #[cfg(test)]
 mod util;
 
+mod watermark;
+pub use watermark::{Watermark, WatermarkStore};
+
+mod webhook;
+pub use webhook::{Webhook, WebhookError, WebhookFuture, WebhookSender, WebhookService};
+
+mod websocket;
+pub use websocket::{
+    EchoSubprotocol, EchoSubprotocolFuture, EchoSubprotocolService, WebSocketProtocol,
+};
+
 #[derive(Debug, Clone)]
-/// - Parses `Authorization` header off the incoming request
+/// - Extracts token off the incoming request, see [`TokenExtractor`]
 /// - Decodes the token or rejects the request
 /// - Sets decoded claim in request extensions
-pub struct Middleware<D, S> {
+pub struct Middleware<D, S, X = DefaultExtractor> {
     service: S,
     decoder: D,
+    extractor: X,
+    options: Options,
 }
 
 #[derive(Debug, Clone)]
-pub struct Layer<D> {
+pub struct Layer<D, X = DefaultExtractor> {
     decoder: D,
+    extractor: X,
+    options: Options,
+}
+
+/// Longest token accepted by [hardened][Layer::hardened] middleware, in bytes
+pub const MAX_TOKEN_LEN: usize = 8 * 1024;
+
+/// Settings shared by [`Layer`], [`Middleware`] and [`MiddlewareFuture`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Options {
+    gates: Gates,
+    timing: bool,
+    baggage: Option<Arc<[String]>>,
+    strip: bool,
+    max_token_len: Option<usize>,
+    preflight: bool,
+    optional: bool,
+    exempt: Exemptions,
+    routes: Routes,
+    fast_path: bool,
+    observer: Option<Observer>,
+    soft_fail: bool,
 }
 
 impl<D> Layer<D> {
     pub fn new(decoder: D) -> Self {
-        Self { decoder }
+        Self {
+            decoder,
+            extractor: DefaultExtractor::default(),
+            options: Options::default(),
+        }
+    }
+
+    /// Secure API defaults in one call: only tokens signed with algorithms of key `families`
+    /// are passed on to `decoder` (see [`AlgorithmGuard`]), settings are
+    /// [hardened][Self::hardened] and failures answered with `401`/`403` challenges
+    /// (see [`Rejecting`]).
+    ///
+    /// Same as `Layer::new(AlgorithmGuard::new(decoder, families)).hardened().respond()`, spell
+    /// it out to configure more.
+    ///
+    /// ```rust
+    /// # fn example(decoder: tower_jwt::InPlace<serde_json::Value>) {
+    /// use tower_jwt::{KeyFamily, Layer};
+    ///
+    /// let layer = Layer::secure(decoder, [KeyFamily::Rsa, KeyFamily::Ec]);
+    /// # }
+    /// ```
+    pub fn secure<I>(decoder: D, families: I) -> Rejecting<Layer<AlgorithmGuard<D>>>
+    where
+        I: IntoIterator<Item = KeyFamily>,
+    {
+        Layer::new(AlgorithmGuard::new(decoder, families))
+            .hardened()
+            .respond()
+    }
+
+    extractor_builders!();
+}
+
+impl<D, X> Layer<D, X> {
+    options_builders!();
+
+    /// Locate token with `extractor` instead of [`DefaultExtractor`]
+    pub fn extractor<Y>(self, extractor: Y) -> Layer<D, Y> {
+        Layer {
+            decoder: self.decoder,
+            extractor,
+            options: self.options,
+        }
+    }
+
+    /// Locate token with asynchronous `extractor`, see [`AsyncExtract`]
+    pub fn async_extractor<Y>(self, extractor: Y) -> AsyncExtract<Layer<D, ResolvedToken>, Y> {
+        AsyncExtract::new(self.extractor(ResolvedToken), extractor)
+    }
+
+    /// Wait for [`LateToken`] when extractor finds no token, see [`Late`]
+    pub fn late_token(self) -> AsyncExtract<Layer<D, ResolvedToken>, Late<X>> {
+        let late = Late::new(self.extractor);
+        AsyncExtract::new(
+            Layer {
+                decoder: self.decoder,
+                extractor: ResolvedToken,
+                options: self.options,
+            },
+            late,
+        )
     }
 }
 
-impl<S, D> tower::Layer<S> for Layer<D>
+impl<S, D, X> tower::Layer<S> for Layer<D, X>
 where
     D: Decoder + Clone,
+    X: Clone,
 {
-    type Service = Middleware<D, S>;
+    type Service = Middleware<D, S, X>;
 
     fn layer(&self, inner: S) -> Self::Service {
         let decoder = self.decoder.clone();
-        Middleware::new(decoder, inner)
+        Middleware {
+            service: inner,
+            decoder,
+            extractor: self.extractor.clone(),
+            options: self.options.clone(),
+        }
     }
 }
 
 impl<D, S> Middleware<D, S> {
     pub fn new(decoder: D, service: S) -> Self {
-        Middleware { service, decoder }
+        Middleware {
+            service,
+            decoder,
+            extractor: DefaultExtractor::default(),
+            options: Options::default(),
+        }
     }
+
+    extractor_builders!();
 }
 
-impl<D, S, B> Service<Request<B>> for Middleware<D, S>
+impl<D, S, X> Middleware<D, S, X> {
+    options_builders!();
+
+    /// Locate token with `extractor` instead of [`DefaultExtractor`]
+    pub fn extractor<Y>(self, extractor: Y) -> Middleware<D, S, Y> {
+        Middleware {
+            service: self.service,
+            decoder: self.decoder,
+            extractor,
+            options: self.options,
+        }
+    }
+
+    /// Locate token with asynchronous `extractor`, see [`AsyncExtract`]
+    pub fn async_extractor<Y>(
+        self,
+        extractor: Y,
+    ) -> AsyncExtract<Middleware<D, S, ResolvedToken>, Y> {
+        AsyncExtract::new(self.extractor(ResolvedToken), extractor)
+    }
+
+    /// Wait for [`LateToken`] when extractor finds no token, see [`Late`]
+    pub fn late_token(self) -> AsyncExtract<Middleware<D, S, ResolvedToken>, Late<X>> {
+        let late = Late::new(self.extractor);
+        AsyncExtract::new(
+            Middleware {
+                decoder: self.decoder,
+                service: self.service,
+                extractor: ResolvedToken,
+                options: self.options,
+            },
+            late,
+        )
+    }
+}
+
+impl<D, S, X, B> Service<Request<B>> for Middleware<D, S, X>
 where
     S: Service<Request<B>> + Clone + 'static,
+    X: TokenExtractor<B>,
     D: Decoder,
     D::Claim: DeserializeOwned + Send + Sync + 'static,
     D::Future: Send + Sync + 'static,
@@ -165,35 +550,94 @@ where
     #[tracing::instrument(skip_all)]
     fn call(&mut self, req: Request<B>) -> Self::Future {
         tracing::trace!("Middleware::entered");
-        let token = match req
-            .headers()
-            .typed_get::<Authorization>()
-            .ok()
-            .flatten()
-            .and_then(|header| header.as_bearer().map(|h| h.as_str().to_owned()))
-        {
+        let routed = self.options.routes.apply(&self.options, req.uri().path());
+        let options = routed.as_ref().unwrap_or(&self.options);
+        let preflight = options.preflight
+            && req.method() == Method::OPTIONS
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+        if preflight || options.exempt.matches(&req) {
+            tracing::trace!("Middleware::exempt");
+            let clone = self.service.clone();
+            let service = core::mem::replace(&mut self.service, clone);
+            return Either::Left(MiddlewareFuture::bypass(service, req));
+        }
+        let token = match self.extractor.extract_borrowed(&req) {
             Some(authorization_header) => authorization_header,
-            _ => return Either::Right(std::future::ready(Err(Error::MissingAuthorizationHeader))),
+            None if options.optional => {
+                tracing::trace!("Middleware::anonymous");
+                let clone = self.service.clone();
+                let service = core::mem::replace(&mut self.service, clone);
+                return Either::Left(MiddlewareFuture::bypass(service, req));
+            }
+            None => {
+                let err = Error::MissingAuthorizationHeader;
+                if let Some(observation) = Observation::new(options, &req) {
+                    observation.report(&err);
+                }
+                return Either::Right(std::future::ready(Err(err)));
+            }
         };
 
         tracing::trace!("Middleware::header_extracted");
+        if options.max_token_len.is_some_and(|max| token.len() > max) {
+            let err = Error::Denied(Denied::TokenTooLarge);
+            if let Some(observation) = Observation::new(options, &req) {
+                observation.report(&err);
+            }
+            return Either::Right(std::future::ready(Err(err)));
+        }
         let clone = self.service.clone();
         let service = core::mem::replace(&mut self.service, clone);
-        let decoder_future = self.decoder.decode(&token);
+        let parsed = options
+            .fast_path
+            .then(|| Arc::new(fast::Parsed::new(&token)));
+        let stats = options.timing.then(DecodeStats::default);
+        let decode = || match &parsed {
+            Some(parsed) => fast::scope(parsed, || self.decoder.decode(&token)),
+            None => self.decoder.decode(&token),
+        };
+        let peer = req.extensions().get::<PeerIdentity>();
+        let decoder_future = match &stats {
+            Some(stats) => stats.scope(|| peer::scope(peer, decode)),
+            None => peer::scope(peer, decode),
+        };
         tracing::trace!("Middleware::decoder_future_created");
-        Either::Left(MiddlewareFuture::new(service, req, decoder_future))
+        let stripped = options.strip.then(|| {
+            let mut headers = req.headers().clone();
+            let mut uri = req.uri().clone();
+            self.extractor.strip(&mut headers);
+            self.extractor.strip_uri(&mut uri);
+            (headers, uri)
+        });
+        // only gates and baggage look at the token once it's decoded
+        let token =
+            (!options.gates.is_empty() || options.baggage.is_some()).then(|| token.into_owned());
+        Either::Left(
+            MiddlewareFuture::new(service, req, decoder_future)
+                .with_stripped(stripped)
+                .with_parsed(parsed)
+                .with_stats(stats)
+                .with_options(token, options.clone()),
+        )
     }
 }
 
 #[derive(Error, Debug)]
 /// Combines underlying [service][tower::Service] errors
 /// with [`Decoder`] errors
+///
+/// [`Decoder`] error is exposed via [`source`][std::error::Error::source] rather than
+/// being formatted into the message, so error reporters (`anyhow`, `eyre`, ..) can walk
+/// the whole chain down to the root cause.
 pub enum Error<E, D> {
     #[error("Authorization header must be set")]
     MissingAuthorizationHeader,
 
-    #[error("Failed to decode token: {0}")]
-    Decoder(D),
+    #[error("Failed to decode token")]
+    Decoder(#[source] D),
+
+    #[error(transparent)]
+    Denied(Denied),
 
     #[error(transparent)]
     Inner(#[from] E),
@@ -201,7 +645,7 @@ pub enum Error<E, D> {
 
 #[cfg(test)]
 mod tests {
-    use super::Middleware;
+    use super::{Error, Middleware};
     use crate::util;
     use core::future::Ready;
     use http::{HeaderValue, Request, Response, StatusCode};
@@ -258,4 +702,287 @@ mod tests {
         let response = outcome.unwrap().into_body();
         assert_eq!(response, claim);
     }
+
+    #[tokio::test]
+    async fn decoder_error_is_source() {
+        let svc = S::<()>(PhantomData);
+        let decoder = util::in_place_decoder();
+        let mut middleware = Middleware::new(decoder, svc);
+
+        let mut req = Request::new(());
+        let token = util::token(&util::claim(None));
+        req.headers_mut().insert(
+            "Authorization",
+            format!("Bearer {}", token)
+                .parse::<HeaderValue>()
+                .expect("Failed to parse valid header"),
+        );
+
+        let err = match middleware.call(req).await {
+            Err(Error::Decoder(err)) => Error::<std::convert::Infallible, _>::Decoder(err),
+            _ => unreachable!("Accepted expired token"),
+        };
+        let source = std::error::Error::source(&err)
+            .and_then(|source| source.downcast_ref::<jsonwebtoken::errors::Error>())
+            .expect("Decoder error must be exposed as source");
+        assert_eq!(
+            source.kind(),
+            &jsonwebtoken::errors::ErrorKind::ExpiredSignature
+        );
+    }
+
+    #[tokio::test]
+    async fn boxed() {
+        let svc = S::<()>(PhantomData);
+        let decoder = util::in_place_decoder();
+        let mut middleware = Middleware::new(decoder, svc).boxed();
+
+        let mut req = Request::new(());
+        let claim = util::claim(Some(100));
+        let token = util::token(&claim);
+
+        req.headers_mut().insert(
+            "Authorization",
+            format!("Bearer {}", token)
+                .parse::<HeaderValue>()
+                .expect("Failed to parse valid header"),
+        );
+
+        let future = middleware.call(req);
+        assert_eq!(
+            std::mem::size_of_val(&future),
+            std::mem::size_of::<crate::BoxFuture<(), ()>>()
+        );
+        let response = future.await.expect("Failed to call boxed middleware");
+        assert_eq!(response.into_body(), claim);
+    }
+
+    #[tokio::test]
+    async fn gate_denies() {
+        let svc = S::<()>(PhantomData);
+        let decoder = util::in_place_decoder();
+        let mut middleware = Middleware::new(decoder, svc).gate(crate::MatchAudience::new(["aud"]));
+
+        let mut req = Request::new(());
+        let token = util::token(&util::claim(Some(100)));
+        req.headers_mut().insert(
+            "Authorization",
+            format!("Bearer {}", token)
+                .parse::<HeaderValue>()
+                .expect("Failed to parse valid header"),
+        );
+
+        let outcome = middleware.call(req).await;
+        assert!(matches!(
+            outcome,
+            Err(Error::Denied(crate::Denied::Audience))
+        ));
+    }
+
+    #[tokio::test]
+    async fn query_param() {
+        let decoder = util::in_place_decoder();
+        let claim = util::claim(Some(100));
+        let uri = format!("/events?access_token={}", util::token(&claim));
+
+        let mut middleware = Middleware::new(decoder.clone(), S::<()>(PhantomData));
+        let outcome = middleware
+            .call(Request::get(&uri).body(()).expect("Valid request"))
+            .await;
+        assert!(matches!(outcome, Err(Error::MissingAuthorizationHeader)));
+
+        let mut middleware =
+            Middleware::new(decoder, S::<()>(PhantomData)).query_param("access_token");
+        let outcome = middleware
+            .call(Request::get(&uri).body(()).expect("Valid request"))
+            .await;
+        assert_eq!(outcome.expect("Token in query").into_body(), claim);
+    }
+
+    #[tokio::test]
+    async fn custom_header() {
+        let decoder = util::in_place_decoder();
+        let claim = util::claim(Some(100));
+        let token = util::token(&claim);
+        let mut middleware = Middleware::new(decoder, S::<()>(PhantomData))
+            .header(http::header::HeaderName::from_static("x-api-token"));
+
+        let req = Request::builder()
+            .header("Authorization", format!("Bearer {}", token))
+            .body(())
+            .expect("Valid request");
+        let outcome = middleware.call(req).await;
+        assert!(matches!(outcome, Err(Error::MissingAuthorizationHeader)));
+
+        let req = Request::builder()
+            .header("X-Api-Token", &token)
+            .body(())
+            .expect("Valid request");
+        let outcome = middleware.call(req).await;
+        assert_eq!(outcome.expect("Token in header").into_body(), claim);
+    }
+
+    #[tokio::test]
+    async fn custom_scheme() {
+        let decoder = util::in_place_decoder();
+        let claim = util::claim(Some(100));
+        let token = util::token(&claim);
+
+        let mut middleware = Middleware::new(decoder.clone(), S::<()>(PhantomData)).scheme("Token");
+        let req = Request::builder()
+            .header("Authorization", format!("Bearer {}", token))
+            .body(())
+            .expect("Valid request");
+        let outcome = middleware.call(req).await;
+        assert!(matches!(outcome, Err(Error::MissingAuthorizationHeader)));
+
+        let req = Request::builder()
+            .header("Authorization", format!("token {}", token))
+            .body(())
+            .expect("Valid request");
+        let outcome = middleware.call(req).await;
+        assert_eq!(outcome.expect("Token scheme").into_body(), claim);
+
+        let mut middleware = Middleware::new(decoder, S::<()>(PhantomData)).bare_token();
+        let req = Request::builder()
+            .header("Authorization", &token)
+            .body(())
+            .expect("Valid request");
+        let outcome = middleware.call(req).await;
+        assert_eq!(outcome.expect("Bare token").into_body(), claim);
+    }
+
+    #[tokio::test]
+    async fn strip_token() {
+        let svc = tower::service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(req.headers().contains_key("authorization"))
+        });
+        let gate = |cx: &crate::GateContext<'_>| match cx.headers().contains_key("authorization") {
+            true => Ok(http::Extensions::new()),
+            false => Err(crate::Denied::Signature),
+        };
+        let mut middleware = Middleware::new(util::in_place_decoder(), svc)
+            .gate(gate)
+            .strip_token();
+
+        let req = Request::builder()
+            .header(
+                "Authorization",
+                format!("Bearer {}", util::token(&util::claim(Some(100)))),
+            )
+            .body(())
+            .expect("Valid request");
+        let outcome = middleware.call(req).await;
+        assert!(!outcome.expect("Authorized request"));
+
+        let svc =
+            tower::service_fn(|req: Request<()>| async move { Ok::<_, ()>(req.uri().clone()) });
+        let mut middleware = Middleware::new(util::in_place_decoder(), svc)
+            .query_param("access_token")
+            .strip_token();
+        let req = Request::builder()
+            .uri(format!(
+                "/events?stream=1&access_token={}",
+                util::token(&util::claim(Some(100)))
+            ))
+            .body(())
+            .expect("Valid request");
+        let uri = middleware.call(req).await.expect("Authorized request");
+        assert_eq!(uri, "/events?stream=1");
+    }
+
+    #[tokio::test]
+    async fn optional() {
+        let svc = tower::service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(crate::claims::<util::Claim>(&req).cloned())
+        });
+        let mut middleware = Middleware::new(util::in_place_decoder(), svc).optional();
+
+        let req = Request::builder().body(()).expect("Valid request");
+        let claim = middleware.call(req).await.expect("Anonymous request");
+        assert_eq!(claim, None);
+
+        let claim = util::claim(Some(100));
+        let req = Request::builder()
+            .header("Authorization", format!("Bearer {}", util::token(&claim)))
+            .body(())
+            .expect("Valid request");
+        let decoded = middleware.call(req).await.expect("Authenticated request");
+        assert_eq!(decoded, Some(claim));
+
+        let req = Request::builder()
+            .header("Authorization", "Bearer not-a-token")
+            .body(())
+            .expect("Valid request");
+        let err = middleware.call(req).await.expect_err("Invalid token");
+        assert!(matches!(err, Error::Decoder(_)));
+    }
+
+    #[tokio::test]
+    async fn skip_path() {
+        let svc = tower::service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(crate::claims::<util::Claim>(&req).is_some())
+        });
+        let mut middleware = Middleware::new(util::in_place_decoder(), svc)
+            .skip_path("/healthz")
+            .skip_path("/assets/**");
+
+        for path in ["/healthz", "/assets/css/site.css"] {
+            let req = Request::builder()
+                .uri(path)
+                .body(())
+                .expect("Valid request");
+            let authenticated = middleware.call(req).await.expect("Exempt path");
+            assert!(!authenticated);
+        }
+
+        let req = Request::builder()
+            .uri("/orders")
+            .body(())
+            .expect("Valid request");
+        let err = middleware.call(req).await.expect_err("Protected path");
+        assert!(matches!(err, Error::MissingAuthorizationHeader));
+    }
+
+    #[tokio::test]
+    async fn secure_preset() {
+        use tower::{Layer as _, ServiceExt};
+
+        let svc = tower::service_fn(|req: Request<()>| async move {
+            Ok::<_, std::convert::Infallible>(Response::new(format!(
+                "{} {}",
+                req.extensions().get::<util::Claim>().is_some(),
+                req.headers().contains_key("authorization")
+            )))
+        });
+        let layer = crate::Layer::secure(util::in_place_decoder(), [crate::KeyFamily::Ed]);
+        let middleware = layer.layer(svc);
+
+        let token = util::token(&util::claim(Some(100)));
+        let req = Request::builder()
+            .header("Authorization", format!("Bearer {}", token))
+            .body(())
+            .expect("Valid request");
+        let res = middleware.clone().oneshot(req).await.expect("Response");
+        assert_eq!(res.body(), "true false");
+
+        let req = Request::builder()
+            .method("OPTIONS")
+            .header("Access-Control-Request-Method", "POST")
+            .body(())
+            .expect("Valid request");
+        let res = middleware.clone().oneshot(req).await.expect("Response");
+        assert_eq!(res.body(), "false false");
+
+        let req = Request::builder()
+            .header("Authorization", format!("Bearer {}", "a".repeat(10_000)))
+            .body(())
+            .expect("Valid request");
+        let res = middleware.clone().oneshot(req).await.expect("Response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = Request::builder().body(()).expect("Valid request");
+        let res = middleware.oneshot(req).await.expect("Response");
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
 }
@@ -0,0 +1,188 @@
+use crate::{query::percent_encode, Error, Rejection, RejectionHandler, RenderFuture};
+use futures::future;
+use http::{
+    header::{ACCEPT, LOCATION},
+    request::Parts,
+    HeaderValue, Response, StatusCode,
+};
+
+/// [`RejectionHandler`] sending browsers to login page, so server-rendered apps can use
+/// [`Middleware`][crate::Middleware] directly.
+///
+/// Unauthenticated requests accepting `text/html` are answered with `302 Found` to login URL,
+/// with path and query of the original request passed in `return_to` parameter, prefixed with
+/// [configured origin][Self::origin] if any. Other rejections, e.g. `403` which re-authenticating wouldn't fix, render as usual.
+/// Usable with [`reject_with`][crate::Layer::reject_with].
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, LoginRedirect};
+///
+/// let layer = Layer::new(decoder).reject_with(LoginRedirect::new("/login").return_to("next"));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LoginRedirect {
+    login: String,
+    return_to: String,
+    origin: Option<String>,
+}
+
+impl LoginRedirect {
+    pub fn new(login: impl Into<String>) -> Self {
+        Self {
+            login: login.into(),
+            return_to: String::from("return_to"),
+            origin: None,
+        }
+    }
+
+    /// Query parameter carrying original location
+    pub fn return_to(mut self, param: impl Into<String>) -> Self {
+        self.return_to = param.into();
+        self
+    }
+
+    /// Origin of the app, e.g. `https://app.example.com`, making original location absolute
+    /// for login pages served elsewhere. Configured rather than taken from `Host` header,
+    /// which clients control.
+    pub fn origin(mut self, origin: impl Into<String>) -> Self {
+        let mut origin = origin.into();
+        origin.truncate(origin.trim_end_matches('/').len());
+        self.origin = Some(origin);
+        self
+    }
+
+    fn location(&self, parts: &Parts) -> Option<HeaderValue> {
+        let path = parts.uri.path_and_query().map_or("/", |path| path.as_str());
+        let original = match &self.origin {
+            Some(origin) => format!("{}{}", origin, path),
+            None => path.to_owned(),
+        };
+        let separator = if self.login.contains('?') { '&' } else { '?' };
+        let location = format!(
+            "{}{}{}={}",
+            self.login,
+            separator,
+            self.return_to,
+            percent_encode(&original)
+        );
+        HeaderValue::from_str(&location).ok()
+    }
+
+    /// Redirect to login page if `rejection` calls for it, `rejection` itself otherwise
+    fn response<B: From<String>>(&self, parts: &Parts, rejection: Rejection) -> Response<B> {
+        let location = (rejection.status() == StatusCode::UNAUTHORIZED && accepts_html(parts))
+            .then(|| self.location(parts))
+            .flatten();
+        match location {
+            Some(location) => {
+                let mut res = Response::new(B::from(String::new()));
+                *res.status_mut() = StatusCode::FOUND;
+                res.headers_mut().insert(LOCATION, location);
+                res
+            }
+            None => rejection.into_response(),
+        }
+    }
+}
+
+fn accepts_html(parts: &Parts) -> bool {
+    parts
+        .headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .flat_map(|accept| accept.split(','))
+        .filter_map(|media| media.split(';').next())
+        .any(|media| media.trim().eq_ignore_ascii_case("text/html"))
+}
+
+impl<E, D, B> RejectionHandler<E, D, B> for LoginRedirect
+where
+    B: From<String> + Send + 'static,
+{
+    fn render(&self, parts: &Parts, _: &Error<E, D>, rejection: Rejection) -> RenderFuture<B> {
+        Box::pin(future::ready(self.response(parts, rejection)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LoginRedirect;
+    use crate::{util, Error, Middleware, Rejection, RejectionHandler};
+    use http::{header::LOCATION, Request, Response, StatusCode};
+    use std::convert::Infallible;
+    use tower::{service_fn, ServiceExt};
+
+    type Failure = Error<Infallible, jsonwebtoken::errors::Error>;
+
+    #[tokio::test]
+    async fn login_redirect() {
+        let handler = LoginRedirect::new("https://id.example.com/login?client=app");
+        let (parts, ()) = Request::builder()
+            .uri("/orders?page=2")
+            .header("Accept", "text/html,application/xhtml+xml;q=0.9")
+            .body(())
+            .expect("Valid request")
+            .into_parts();
+
+        let err = Failure::MissingAuthorizationHeader;
+        let res = RejectionHandler::<_, _, String>::render(
+            &handler,
+            &parts,
+            &err,
+            Rejection::unauthorized(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(
+            res.headers()[LOCATION],
+            "https://id.example.com/login?client=app&return_to=%2Forders%3Fpage%3D2"
+        );
+
+        let res = RejectionHandler::<_, _, String>::render(
+            &handler,
+            &parts,
+            &err,
+            Rejection::forbidden(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let handler = handler.origin("https://app.example.com/");
+        let res = RejectionHandler::<_, _, String>::render(
+            &handler,
+            &parts,
+            &err,
+            Rejection::unauthorized(),
+        )
+        .await;
+        assert_eq!(
+            res.headers()[LOCATION],
+            "https://id.example.com/login?client=app&return_to=\
+            https%3A%2F%2Fapp.example.com%2Forders%3Fpage%3D2"
+        );
+
+        let svc = service_fn(|_: Request<()>| async move {
+            Ok::<_, Infallible>(Response::new(String::new()))
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc)
+            .reject_with(LoginRedirect::new("/login"));
+        let req = Request::builder()
+            .uri("/orders")
+            .header("Accept", "text/html")
+            .body(())
+            .expect("Valid request");
+        let res = middleware.clone().oneshot(req).await.expect("Redirect");
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(res.headers()[LOCATION], "/login?return_to=%2Forders");
+        let req = Request::builder()
+            .uri("/orders")
+            .header("Accept", "application/json")
+            .body(())
+            .expect("Valid request");
+        let res = middleware.oneshot(req).await.expect("Challenge");
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,121 @@
+use http::{header::CONTENT_TYPE, HeaderValue, Response};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Conventional path metadata document is served at
+pub const WELL_KNOWN_PATH: &str = "/.well-known/oauth-protected-resource";
+
+/// OAuth protected resource metadata document
+/// ([RFC 9728](https://www.rfc-editor.org/rfc/rfc9728), the resource server counterpart
+/// of RFC 8414), letting clients discover which issuers and scopes the service accepts.
+///
+/// Besides standard members, scopes required per route are published as `route_scopes`,
+/// mapping path prefix to scopes.
+///
+/// ```rust
+/// use tower_jwt::{ResourceMetadata, WELL_KNOWN_PATH};
+///
+/// let metadata = ResourceMetadata::new("https://api.example.com")
+///     .issuer("https://login.example.com")
+///     .route("/orders", ["orders:read"])
+///     .route("/admin", ["admin"]);
+///
+/// // serve at WELL_KNOWN_PATH
+/// let res: http::Response<String> = metadata.response();
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceMetadata {
+    resource: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authorization_servers: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    scopes_supported: Vec<String>,
+    bearer_methods_supported: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    route_scopes: BTreeMap<String, Vec<String>>,
+}
+
+impl ResourceMetadata {
+    /// Metadata of resource identified by `resource` URL
+    pub fn new(resource: impl Into<String>) -> Self {
+        Self {
+            resource: resource.into(),
+            authorization_servers: Vec::new(),
+            scopes_supported: Vec::new(),
+            bearer_methods_supported: vec![String::from("header")],
+            route_scopes: BTreeMap::new(),
+        }
+    }
+
+    /// Accepted token issuer
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.authorization_servers.push(issuer.into());
+        self
+    }
+
+    /// Scopes required on routes under `prefix`, also listed as supported
+    pub fn route<I, S>(mut self, prefix: impl Into<String>, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let scopes: Vec<String> = scopes.into_iter().map(Into::into).collect();
+        for scope in &scopes {
+            if !self.scopes_supported.contains(scope) {
+                self.scopes_supported.push(scope.clone());
+            }
+        }
+        self.route_scopes.insert(prefix.into(), scopes);
+        self
+    }
+
+    /// Methods tokens are accepted through, `header` by default
+    pub fn bearer_methods<I, M>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = M>,
+        M: Into<String>,
+    {
+        self.bearer_methods_supported = methods.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("Metadata is serializable")
+    }
+
+    /// `200 OK` response with metadata document
+    pub fn response<B: From<String>>(&self) -> Response<B> {
+        let mut res = Response::new(B::from(self.to_json().to_string()));
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ResourceMetadata;
+    use serde_json::json;
+
+    #[test]
+    fn resource_metadata() {
+        let metadata = ResourceMetadata::new("https://api.example.com")
+            .issuer("https://login.example.com")
+            .route("/orders", ["orders:read", "orders:write"])
+            .route("/admin", ["admin", "orders:write"]);
+
+        assert_eq!(
+            metadata.to_json(),
+            json!({
+                "resource": "https://api.example.com",
+                "authorization_servers": ["https://login.example.com"],
+                "scopes_supported": ["orders:read", "orders:write", "admin"],
+                "bearer_methods_supported": ["header"],
+                "route_scopes": {
+                    "/admin": ["admin", "orders:write"],
+                    "/orders": ["orders:read", "orders:write"],
+                },
+            })
+        );
+    }
+}
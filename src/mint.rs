@@ -0,0 +1,212 @@
+use crate::PeerIdentity;
+use core::task::{Context, Poll};
+use futures::future::{self, Either, MapErr, Ready, TryFutureExt};
+use http::{header::AUTHORIZATION, HeaderValue, Request};
+use jsonwebtoken::{EncodingKey, Header};
+use serde::Serialize;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+use tower::Service;
+
+/// Signs short-lived tokens for [`PeerIdentity`], see [`Mint`]
+#[derive(Clone)]
+pub struct Minter {
+    header: Header,
+    key: Arc<EncodingKey>,
+    issuer: String,
+    audience: Option<String>,
+    ttl: Duration,
+}
+
+#[derive(Serialize)]
+struct MintedClaim<'a> {
+    iss: &'a str,
+    sub: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aud: Option<&'a str>,
+    iat: u64,
+    exp: u64,
+}
+
+impl Minter {
+    /// Tokens are valid for one minute by default
+    pub fn new(header: Header, key: EncodingKey, issuer: impl Into<String>) -> Self {
+        Self {
+            header,
+            key: Arc::new(key),
+            issuer: issuer.into(),
+            audience: None,
+            ttl: Duration::from_secs(60),
+        }
+    }
+
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Signs token with `sub` set to peer identity
+    pub fn mint(&self, peer: &PeerIdentity) -> Result<String, jsonwebtoken::errors::Error> {
+        let iat = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let claim = MintedClaim {
+            iss: &self.issuer,
+            sub: peer.as_str(),
+            aud: self.audience.as_deref(),
+            iat,
+            exp: iat + self.ttl.as_secs(),
+        };
+        jsonwebtoken::encode(&self.header, &claim, &self.key)
+    }
+}
+
+/// Bridges mTLS-authenticated edges to hops which only understand JWTs:
+/// sets `Authorization: Bearer <token>` minted for [`PeerIdentity`] found on request extensions.
+///
+/// Requests without [`PeerIdentity`] are passed through untouched,
+/// any pre-existing `Authorization` header is replaced otherwise.
+///
+/// ```rust
+/// # fn example<S>(service: S, key: jsonwebtoken::EncodingKey) {
+/// use jsonwebtoken::{Algorithm, Header};
+/// use tower_jwt::{Mint, Minter};
+///
+/// let minter = Minter::new(Header::new(Algorithm::EdDSA), key, "https://mesh.internal")
+///     .audience("orders");
+/// let service = Mint::new(minter, service);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Mint<S> {
+    minter: Minter,
+    service: S,
+}
+
+impl<S> Mint<S> {
+    pub fn new(minter: Minter, service: S) -> Self {
+        Self { minter, service }
+    }
+}
+
+/// [`tower::Layer`] producing [`Mint`] services
+#[derive(Clone)]
+pub struct MintLayer {
+    minter: Minter,
+}
+
+impl MintLayer {
+    pub fn new(minter: Minter) -> Self {
+        Self { minter }
+    }
+}
+
+impl<S> tower::Layer<S> for MintLayer {
+    type Service = Mint<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Mint::new(self.minter.clone(), inner)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MintError<E> {
+    #[error("Failed to mint token")]
+    Mint(#[source] jsonwebtoken::errors::Error),
+
+    #[error(transparent)]
+    Inner(E),
+}
+
+impl<S, B> Service<Request<B>> for Mint<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = MintError<S::Error>;
+    type Future = Either<
+        MapErr<S::Future, fn(S::Error) -> MintError<S::Error>>,
+        Ready<Result<S::Response, MintError<S::Error>>>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(MintError::Inner)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        if let Some(peer) = req.extensions().get::<PeerIdentity>() {
+            let token = match self.minter.mint(peer) {
+                Ok(token) => token,
+                Err(err) => return Either::Right(future::ready(Err(MintError::Mint(err)))),
+            };
+            let value = HeaderValue::from_str(&format!("Bearer {}", token))
+                // compact serialization only consists of base64url segments and dots
+                .expect("Minted token is not a valid header value");
+            req.headers_mut().insert(AUTHORIZATION, value);
+        }
+        Either::Left(self.service.call(req).map_err(MintError::Inner))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mint, Minter};
+    use crate::{Decoder, InPlace, PeerIdentity};
+    use http::{header::AUTHORIZATION, Request};
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, Validation};
+    use serde::Deserialize;
+    use tower::{service_fn, ServiceExt};
+
+    #[derive(Deserialize)]
+    struct Minted {
+        sub: String,
+        aud: String,
+    }
+
+    #[tokio::test]
+    async fn mint_for_peer() {
+        let secret = [3u8; 32];
+        let minter = Minter::new(
+            Header::new(Algorithm::HS256),
+            EncodingKey::from_secret(&secret),
+            "mesh",
+        )
+        .audience("orders");
+        let svc = service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(req.headers().get(AUTHORIZATION).cloned())
+        });
+
+        let mut req = Request::new(());
+        req.extensions_mut()
+            .insert(PeerIdentity::new("spiffe://cluster.local/sa/billing"));
+        let header = Mint::new(minter, svc)
+            .oneshot(req)
+            .await
+            .expect("Failed to mint token")
+            .expect("Authorization header must be set");
+        let token = header
+            .to_str()
+            .expect("Valid header")
+            .strip_prefix("Bearer ")
+            .expect("Bearer token");
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_audience(&["orders"]);
+        let minted = InPlace::<Minted>::hs256(&secret, validation)
+            .expect("Strong secret")
+            .decode(token)
+            .await
+            .expect("Minted token must be valid");
+        assert_eq!(minted.sub, "spiffe://cluster.local/sa/billing");
+        assert_eq!(minted.aud, "orders");
+    }
+}
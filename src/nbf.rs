@@ -0,0 +1,213 @@
+use crate::{fast, DecodeFailure, Decoder, SyncBoxFuture, ValidationProfile};
+use core::future::Future;
+use jsonwebtoken::errors::{Error, ErrorKind};
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Future of [`Sleep`]
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Timer used by [`NbfRetry`] to wait for tokens to become valid.
+///
+/// Any `Fn(Duration) -> impl Future<Output = ()>` is a timer, e.g. `tokio::time::sleep`.
+pub trait Sleep: Send + Sync + 'static {
+    fn sleep(&self, duration: Duration) -> SleepFuture;
+}
+
+impl<F, Fut> Sleep for F
+where
+    F: Fn(Duration) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        Box::pin(self(duration))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    immature: AtomicU64,
+    recovered: AtomicU64,
+}
+
+/// Handle to counters collected by [`NbfRetry`]
+#[derive(Debug, Clone, Default)]
+pub struct NbfStats(Arc<Counters>);
+
+impl NbfStats {
+    /// Number of tokens decoder rejected as not yet valid
+    pub fn immature(&self) -> u64 {
+        self.0.immature.load(Ordering::Relaxed)
+    }
+
+    /// Number of those accepted after waiting
+    pub fn recovered(&self) -> u64 {
+        self.0.recovered.load(Ordering::Relaxed)
+    }
+}
+
+/// Wraps [`Decoder`] counting tokens rejected for `nbf` in the future, optionally waiting
+/// for them to become valid.
+///
+/// Clock skew between IdP (or edge) and the service causes bursts of such rejections right
+/// after tokens are issued. With [`wait`][NbfRetry::wait] configured, tokens becoming valid
+/// within the bound are decoded again once their `nbf` passed, rather than rejected.
+/// Note that decoder must [validate `nbf`][jsonwebtoken::Validation::validate_nbf] for any of
+/// that to happen.
+///
+/// ```rust
+/// # fn example(decoder: tower_jwt::InPlace<serde_json::Value>) {
+/// use std::time::Duration;
+/// use tower_jwt::NbfRetry;
+///
+/// let decoder = NbfRetry::new(decoder).wait(Duration::from_secs(2), tokio::time::sleep);
+/// let stats = decoder.stats();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct NbfRetry<D> {
+    decoder: D,
+    stats: NbfStats,
+    wait: Option<(Duration, Arc<dyn Sleep>)>,
+}
+
+impl<D> NbfRetry<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            stats: NbfStats::default(),
+            wait: None,
+        }
+    }
+
+    /// Wait with `sleep` for tokens becoming valid within `max`
+    pub fn wait<S: Sleep>(mut self, max: Duration, sleep: S) -> Self {
+        self.wait = Some((max, Arc::new(sleep)));
+        self
+    }
+
+    /// Wait with `sleep` for as long as [grace window][ValidationProfile::grace] of `profile`,
+    /// if it has one
+    pub fn with_profile<S: Sleep>(mut self, profile: ValidationProfile, sleep: S) -> Self {
+        self.wait = profile
+            .grace()
+            .map(|max| (max, Arc::new(sleep) as Arc<dyn Sleep>));
+        self
+    }
+
+    /// Handle to collected counters
+    pub fn stats(&self) -> NbfStats {
+        self.stats.clone()
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+impl<D: fmt::Debug> fmt::Debug for NbfRetry<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NbfRetry")
+            .field("decoder", &self.decoder)
+            .field("stats", &self.stats)
+            .field("wait", &self.wait.as_ref().map(|(max, _)| max))
+            .finish()
+    }
+}
+
+/// How long until token `nbf`, as long as it's within `max`
+fn delay(token: &str, max: Duration) -> Option<Duration> {
+    let nbf = fast::payload(token)?.i64("nbf")?;
+    let nbf = UNIX_EPOCH + Duration::from_secs(u64::try_from(nbf).ok()?);
+    let delay = nbf.duration_since(SystemTime::now()).unwrap_or_default();
+    (delay <= max).then_some(delay)
+}
+
+impl<D> Decoder for NbfRetry<D>
+where
+    D: Decoder<Error = Error> + Clone + Send + Sync + 'static,
+    D::Claim: Send,
+    D::Future: Send,
+{
+    type Error = Error;
+    type Claim = D::Claim;
+    type Future = SyncBoxFuture<Self::Claim, Self::Error>;
+
+    fn decode(&self, token: &str) -> Self::Future {
+        let this = self.clone();
+        let token = token.to_owned();
+        SyncBoxFuture::new(Box::pin(async move {
+            let err = match this.decoder.decode(&token).await {
+                Err(err) if *err.kind() == ErrorKind::ImmatureSignature => err,
+                outcome => return outcome,
+            };
+            this.stats.0.immature.fetch_add(1, Ordering::Relaxed);
+            let (delay, sleep) = match &this.wait {
+                Some((max, sleep)) => match delay(&token, *max) {
+                    Some(delay) => (delay, sleep),
+                    None => return Err(err),
+                },
+                None => return Err(err),
+            };
+
+            tracing::debug!(?delay, "Waiting for token to become valid");
+            sleep.sleep(delay).await;
+            let outcome = this.decoder.decode(&token).await;
+            if outcome.is_ok() {
+                this.stats.0.recovered.fetch_add(1, Ordering::Relaxed);
+            }
+            outcome
+        }))
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        Some(DecodeFailure::from(err.kind()))
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NbfRetry;
+    use crate::{util, Decoder, InPlace, InPlaceBuilder};
+    use jsonwebtoken::{encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn nbf_retry() {
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.validate_nbf = true;
+        validation.leeway = 0;
+        let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes()).expect("Valid key");
+        let decoder: InPlace<serde_json::Value> = InPlaceBuilder::new(key, validation).build();
+        let decoder = NbfRetry::new(decoder).wait(Duration::from_secs(2), tokio::time::sleep);
+        let stats = decoder.stats();
+
+        let key = EncodingKey::from_ed_pem(util::PRIVATE_KEY.as_bytes()).expect("Valid key");
+        let token = |nbf: i64| {
+            let now = chrono::Utc::now().timestamp();
+            let claims = json!({ "exp": now + 600, "nbf": now + nbf });
+            encode(&Header::new(Algorithm::EdDSA), &claims, &key).expect("Valid token")
+        };
+
+        decoder
+            .decode(&token(1))
+            .await
+            .expect("Valid once nbf passed");
+        assert_eq!((stats.immature(), stats.recovered()), (1, 1));
+
+        assert!(decoder.decode(&token(60)).await.is_err());
+        assert_eq!((stats.immature(), stats.recovered()), (2, 1));
+    }
+}
@@ -0,0 +1,572 @@
+use crate::{query::percent_encode, AccessToken, BoxError, BoxFuture, TokenProvider, TokenRequest};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use core::future::Future;
+use futures::TryFutureExt;
+use http::{
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+    Method, Request, Response,
+};
+use jsonwebtoken::{EncodingKey, Header};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+/// Sends requests to token endpoint, see [`ClientCredentials`], or to other HTTP APIs keys
+/// are fetched from (e.g. `AwsKeys` with `aws` feature).
+///
+/// The crate doesn't come with HTTP client, any
+/// `Fn(Request<String>) -> impl Future<Output = Result<Response<Vec<u8>>, BoxError>>`
+/// (e.g. wrapping `hyper` or `reqwest`) is an endpoint. Error responses must be returned as
+/// responses rather than errors, so OAuth errors they carry can be reported.
+pub trait TokenEndpoint: Send + Sync + 'static {
+    fn send(&self, request: Request<String>) -> BoxFuture<Response<Vec<u8>>, BoxError>;
+}
+
+impl<F, Fut> TokenEndpoint for F
+where
+    F: Fn(Request<String>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response<Vec<u8>>, BoxError>> + Send + 'static,
+{
+    fn send(&self, request: Request<String>) -> BoxFuture<Response<Vec<u8>>, BoxError> {
+        Box::pin(self(request))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum OAuthError {
+    #[error("Token endpoint responded with {error}")]
+    Endpoint {
+        error: String,
+        description: Option<String>,
+    },
+
+    #[error("Failed to parse token endpoint response")]
+    Response(#[source] serde_json::Error),
+
+    #[error("Failed to sign assertion")]
+    Assertion(#[source] jsonwebtoken::errors::Error),
+
+    #[error("Failed to reach token endpoint")]
+    Transport(#[source] BoxError),
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+impl TokenResponse {
+    fn parse(body: &[u8]) -> Result<AccessToken, OAuthError> {
+        let response: Self = serde_json::from_slice(body).map_err(OAuthError::Response)?;
+        match (response.access_token, response.error) {
+            (Some(token), None) => {
+                let token = AccessToken::new(token);
+                Ok(match response.expires_in {
+                    Some(expires_in) => token.with_expires_in(Duration::from_secs(expires_in)),
+                    None => token,
+                })
+            }
+            (_, error) => Err(OAuthError::Endpoint {
+                error: error.unwrap_or_else(|| String::from("invalid_response")),
+                description: response.error_description,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AssertionClaims<'a> {
+    iss: &'a str,
+    sub: &'a str,
+    aud: &'a str,
+    jti: String,
+    iat: u64,
+    exp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+}
+
+/// Signs short-lived assertions presented to token endpoint
+#[derive(Clone)]
+pub(crate) struct Signer {
+    header: Arc<Header>,
+    key: Arc<EncodingKey>,
+}
+
+impl Signer {
+    pub(crate) fn new(header: Header, key: EncodingKey) -> Self {
+        Self {
+            header: Arc::new(header),
+            key: Arc::new(key),
+        }
+    }
+
+    pub(crate) fn sign(
+        &self,
+        iss: &str,
+        sub: &str,
+        aud: &str,
+        scope: Option<String>,
+    ) -> Result<String, OAuthError> {
+        let mut jti = [0u8; 16];
+        SystemRandom::new()
+            .fill(&mut jti)
+            .map_err(|err| OAuthError::Assertion(err.into()))?;
+        let iat = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let claims = AssertionClaims {
+            iss,
+            sub,
+            aud,
+            jti: jti.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            iat,
+            exp: iat + 60,
+            scope,
+        };
+        jsonwebtoken::encode(&self.header, &claims, &self.key).map_err(OAuthError::Assertion)
+    }
+}
+
+#[derive(Clone)]
+enum AuthMethod {
+    SecretBasic(String),
+    SecretPost(String),
+    PrivateKeyJwt(Signer),
+}
+
+/// How OAuth client authenticates to token endpoint
+#[derive(Clone)]
+pub struct ClientAuth {
+    client_id: String,
+    method: AuthMethod,
+}
+
+impl ClientAuth {
+    /// `client_secret_basic`, credentials in `Authorization` header
+    pub fn secret_basic(client_id: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            method: AuthMethod::SecretBasic(secret.into()),
+        }
+    }
+
+    /// `client_secret_post`, credentials in request body
+    pub fn secret_post(client_id: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            method: AuthMethod::SecretPost(secret.into()),
+        }
+    }
+
+    /// `private_key_jwt`, client assertion signed with `key`, as described in
+    /// [RFC 7523](https://www.rfc-editor.org/rfc/rfc7523#section-2.2)
+    pub fn private_key_jwt(client_id: impl Into<String>, header: Header, key: EncodingKey) -> Self {
+        Self {
+            client_id: client_id.into(),
+            method: AuthMethod::PrivateKeyJwt(Signer::new(header, key)),
+        }
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Authenticates `form` sent to token endpoint at `url`
+    pub(crate) fn apply(
+        &self,
+        url: &str,
+        request: &mut http::request::Builder,
+        form: &mut Vec<(&'static str, String)>,
+    ) -> Result<(), OAuthError> {
+        match &self.method {
+            AuthMethod::SecretBasic(secret) => {
+                let credentials = format!(
+                    "{}:{}",
+                    percent_encode(&self.client_id),
+                    percent_encode(secret)
+                );
+                let value = format!("Basic {}", STANDARD.encode(credentials));
+                *request = std::mem::take(request).header(AUTHORIZATION, value);
+            }
+            AuthMethod::SecretPost(secret) => {
+                form.push(("client_id", self.client_id.clone()));
+                form.push(("client_secret", secret.clone()));
+            }
+            AuthMethod::PrivateKeyJwt(signer) => {
+                let assertion = signer.sign(&self.client_id, &self.client_id, url, None)?;
+                form.push(("client_id", self.client_id.clone()));
+                form.push((
+                    "client_assertion_type",
+                    String::from("urn:ietf:params:oauth:client-assertion-type:jwt-bearer"),
+                ));
+                form.push(("client_assertion", assertion));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ClientAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match self.method {
+            AuthMethod::SecretBasic(_) => "client_secret_basic",
+            AuthMethod::SecretPost(_) => "client_secret_post",
+            AuthMethod::PrivateKeyJwt(_) => "private_key_jwt",
+        };
+        f.debug_struct("ClientAuth")
+            .field("client_id", &self.client_id)
+            .field("method", &method)
+            .finish()
+    }
+}
+
+/// Sends form to token endpoint at `url`, parsing access token off the response
+pub(crate) fn exchange(
+    endpoint: &Arc<dyn TokenEndpoint>,
+    url: &str,
+    auth: Option<&ClientAuth>,
+    mut form: Vec<(&'static str, String)>,
+) -> BoxFuture<AccessToken, BoxError> {
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(ACCEPT, "application/json");
+    if let Some(auth) = auth {
+        if let Err(err) = auth.apply(url, &mut request, &mut form) {
+            return Box::pin(futures::future::ready(Err(err.into())));
+        }
+    }
+    let body = form
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, percent_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let request = match request.body(body) {
+        Ok(request) => request,
+        Err(err) => return Box::pin(futures::future::ready(Err(err.into()))),
+    };
+    Box::pin(
+        endpoint
+            .send(request)
+            .map_err(OAuthError::Transport)
+            .and_then(|response| async move { TokenResponse::parse(response.body()) })
+            .map_err(BoxError::from),
+    )
+}
+
+/// Space separated scopes of `request`, if any
+pub(crate) fn scope(request: &TokenRequest) -> Option<String> {
+    let scopes: Vec<_> = request.scopes().collect();
+    (!scopes.is_empty()).then(|| scopes.join(" "))
+}
+
+/// [`TokenProvider`] obtaining tokens through OAuth client credentials grant.
+///
+/// Requested scopes are sent as `scope`, requested audience as `resource` parameter
+/// ([RFC 8707](https://www.rfc-editor.org/rfc/rfc8707)) unless configured otherwise.
+/// Wrap it in [`TokenCache`][crate::TokenCache] to reuse tokens.
+///
+/// ```rust
+/// # fn example<E: tower_jwt::TokenEndpoint>(endpoint: E, key: jsonwebtoken::EncodingKey) {
+/// use jsonwebtoken::{Algorithm, Header};
+/// use tower_jwt::{ClientAuth, ClientCredentials, TokenCache};
+///
+/// let auth = ClientAuth::private_key_jwt("orders", Header::new(Algorithm::RS256), key);
+/// let provider = ClientCredentials::new("https://idp.example.com/oauth/token", auth, endpoint)
+///     .audience_param("audience");
+/// let provider = TokenCache::new(provider);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ClientCredentials {
+    url: String,
+    auth: ClientAuth,
+    audience_param: &'static str,
+    endpoint: Arc<dyn TokenEndpoint>,
+}
+
+impl ClientCredentials {
+    pub fn new<E: TokenEndpoint>(url: impl Into<String>, auth: ClientAuth, endpoint: E) -> Self {
+        Self {
+            url: url.into(),
+            auth,
+            audience_param: "resource",
+            endpoint: Arc::new(endpoint),
+        }
+    }
+
+    /// Send requested audience as `name` parameter (e.g. `audience`) instead of `resource`
+    pub fn audience_param(mut self, name: &'static str) -> Self {
+        self.audience_param = name;
+        self
+    }
+}
+
+impl fmt::Debug for ClientCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientCredentials")
+            .field("url", &self.url)
+            .field("auth", &self.auth)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenProvider for ClientCredentials {
+    fn token(&self, request: &TokenRequest) -> BoxFuture<AccessToken, BoxError> {
+        let mut form = vec![("grant_type", String::from("client_credentials"))];
+        if let Some(scope) = scope(request) {
+            form.push(("scope", scope));
+        }
+        if let Some(audience) = request.audience() {
+            form.push((self.audience_param, audience.to_owned()));
+        }
+        exchange(&self.endpoint, &self.url, Some(&self.auth), form)
+    }
+}
+
+/// [`TokenProvider`] exchanging locally signed assertions for access tokens through JWT bearer
+/// grant, as described in [RFC 7523](https://www.rfc-editor.org/rfc/rfc7523#section-2.1).
+///
+/// Every assertion is issued by `issuer` on behalf of `subject` (the issuer itself, unless
+/// configured otherwise) to token endpoint, or to [other audience][JwtBearer::assertion_audience]
+/// if IdP expects one. Requested scopes are sent as `scope` parameter, or as `scope` claim
+/// of the assertion with [`scope_claim`][JwtBearer::scope_claim], requested audience as
+/// `resource` parameter unless configured otherwise.
+///
+/// ```rust
+/// # fn example<E: tower_jwt::TokenEndpoint>(endpoint: E, key: jsonwebtoken::EncodingKey) {
+/// use jsonwebtoken::{Algorithm, Header};
+/// use tower_jwt::{JwtBearer, TokenCache};
+///
+/// let mut header = Header::new(Algorithm::RS256);
+/// header.kid = Some(String::from("service-account-key-id"));
+/// let provider = JwtBearer::new(
+///     "https://oauth2.googleapis.com/token",
+///     "orders@project.iam.gserviceaccount.com",
+///     header,
+///     key,
+///     endpoint,
+/// )
+/// .scope_claim();
+/// let provider = TokenCache::new(provider);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct JwtBearer {
+    url: String,
+    issuer: String,
+    subject: Option<String>,
+    audience: Option<String>,
+    signer: Signer,
+    scope_claim: bool,
+    audience_param: &'static str,
+    auth: Option<ClientAuth>,
+    endpoint: Arc<dyn TokenEndpoint>,
+}
+
+impl JwtBearer {
+    pub fn new<E: TokenEndpoint>(
+        url: impl Into<String>,
+        issuer: impl Into<String>,
+        header: Header,
+        key: EncodingKey,
+        endpoint: E,
+    ) -> Self {
+        Self {
+            url: url.into(),
+            issuer: issuer.into(),
+            subject: None,
+            audience: None,
+            signer: Signer::new(header, key),
+            scope_claim: false,
+            audience_param: "resource",
+            auth: None,
+            endpoint: Arc::new(endpoint),
+        }
+    }
+
+    /// Principal assertions are issued for, e.g. user to impersonate
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    /// `aud` of assertions, when IdP expects something other than token endpoint URL
+    pub fn assertion_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Send requested scopes as `scope` claim of the assertion rather than parameter
+    pub fn scope_claim(mut self) -> Self {
+        self.scope_claim = true;
+        self
+    }
+
+    /// Send requested audience as `name` parameter (e.g. `audience`) instead of `resource`
+    pub fn audience_param(mut self, name: &'static str) -> Self {
+        self.audience_param = name;
+        self
+    }
+
+    /// Authenticate client alongside the assertion
+    pub fn client_auth(mut self, auth: ClientAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+}
+
+impl fmt::Debug for JwtBearer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtBearer")
+            .field("url", &self.url)
+            .field("issuer", &self.issuer)
+            .field("subject", &self.subject)
+            .field("audience", &self.audience)
+            .field("auth", &self.auth)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenProvider for JwtBearer {
+    fn token(&self, request: &TokenRequest) -> BoxFuture<AccessToken, BoxError> {
+        let scope = scope(request);
+        let (scope_claim, scope_param) = match self.scope_claim {
+            true => (scope, None),
+            false => (None, scope),
+        };
+        let assertion = match self.signer.sign(
+            &self.issuer,
+            self.subject.as_deref().unwrap_or(&self.issuer),
+            self.audience.as_deref().unwrap_or(&self.url),
+            scope_claim,
+        ) {
+            Ok(assertion) => assertion,
+            Err(err) => return Box::pin(futures::future::ready(Err(err.into()))),
+        };
+
+        let mut form = vec![
+            (
+                "grant_type",
+                String::from("urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ),
+            ("assertion", assertion),
+        ];
+        if let Some(scope) = scope_param {
+            form.push(("scope", scope));
+        }
+        if let Some(audience) = request.audience() {
+            form.push((self.audience_param, audience.to_owned()));
+        }
+        exchange(&self.endpoint, &self.url, self.auth.as_ref(), form)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClientAuth, ClientCredentials, JwtBearer, OAuthError};
+    use crate::{query::form_param, util, BoxError, TokenProvider, TokenRequest};
+    use http::{header::AUTHORIZATION, Request, Response};
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+    use serde_json::json;
+
+    async fn endpoint(req: Request<String>) -> Result<Response<Vec<u8>>, BoxError> {
+        let form = req.body();
+        let body = match form_param(form, "client_assertion") {
+            Some(assertion) => {
+                let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes())?;
+                let mut validation = Validation::new(Algorithm::EdDSA);
+                validation.set_audience(&["https://idp/token"]);
+                validation.set_issuer(&["orders"]);
+                jsonwebtoken::decode::<serde_json::Value>(&assertion, &key, &validation)?;
+                json!({ "access_token": "signed", "expires_in": 300 })
+            }
+            None if req.headers().contains_key(AUTHORIZATION) => {
+                json!({ "access_token": form_param(form, "scope"), "expires_in": 300 })
+            }
+            None => json!({ "error": "invalid_client" }),
+        };
+        Ok(Response::new(serde_json::to_vec(&body)?))
+    }
+
+    #[tokio::test]
+    async fn client_credentials() {
+        let request = TokenRequest::new().with_scopes(["read", "write"]);
+        let basic = ClientCredentials::new(
+            "https://idp/token",
+            ClientAuth::secret_basic("orders", "secret"),
+            endpoint,
+        );
+        let token = basic.token(&request).await.expect("Issued token");
+        assert_eq!(token.token(), "read write");
+        assert!(token.expires_at().is_some());
+
+        let key = EncodingKey::from_ed_pem(util::PRIVATE_KEY.as_bytes()).expect("Valid key");
+        let jwt = ClientCredentials::new(
+            "https://idp/token",
+            ClientAuth::private_key_jwt("orders", Header::new(Algorithm::EdDSA), key),
+            endpoint,
+        );
+        let token = jwt.token(&request).await.expect("Issued token");
+        assert_eq!(token.token(), "signed");
+
+        let post = ClientCredentials::new(
+            "https://idp/token",
+            ClientAuth::secret_post("orders", "secret"),
+            endpoint,
+        );
+        let err = post.token(&request).await.expect_err("Rejected client");
+        assert!(matches!(
+            err.downcast_ref::<OAuthError>(),
+            Some(OAuthError::Endpoint { error, .. }) if error == "invalid_client"
+        ));
+    }
+
+    #[tokio::test]
+    async fn jwt_bearer() {
+        let endpoint = |req: Request<String>| async move {
+            let form = req.body();
+            let grant = form_param(form, "grant_type").ok_or("Missing grant type")?;
+            assert_eq!(grant, "urn:ietf:params:oauth:grant-type:jwt-bearer");
+            assert_eq!(form_param(form, "scope"), None);
+            let assertion = form_param(form, "assertion").ok_or("Missing assertion")?;
+            let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes())?;
+            let mut validation = Validation::new(Algorithm::EdDSA);
+            validation.set_audience(&["https://login"]);
+            let claims = jsonwebtoken::decode::<serde_json::Value>(&assertion, &key, &validation)?;
+            let body = json!({
+                "access_token": format!("{} {}", claims.claims["sub"], claims.claims["scope"]),
+            });
+            Ok::<_, BoxError>(Response::new(serde_json::to_vec(&body)?))
+        };
+
+        let key = EncodingKey::from_ed_pem(util::PRIVATE_KEY.as_bytes()).expect("Valid key");
+        let provider = JwtBearer::new(
+            "https://idp/token",
+            "orders",
+            Header::new(Algorithm::EdDSA),
+            key,
+            endpoint,
+        )
+        .subject("alice")
+        .assertion_audience("https://login")
+        .scope_claim();
+        let token = provider
+            .token(&TokenRequest::new().with_scopes(["read"]))
+            .await
+            .expect("Issued token");
+        assert_eq!(token.token(), r#""alice" "read""#);
+        assert!(token.expires_at().is_none());
+    }
+}
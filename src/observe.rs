@@ -0,0 +1,158 @@
+use crate::{AuthFailure, Error, FailureKind, Options};
+use http::{Method, Request};
+use std::{error::Error as StdError, fmt, net::SocketAddr, sync::Arc};
+
+/// Authentication or authorization failure reported to [`RejectionObserver`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RejectionEvent {
+    pub kind: FailureKind,
+    /// Why the request was rejected, errors of decoders are not included as they
+    /// aren't required to be printable
+    pub reason: String,
+    /// Decoder error followed by its [sources][std::error::Error::source] down to root cause,
+    /// empty unless decoder exposes it through [`Decoder::error`][crate::Decoder::error]
+    pub sources: Vec<String>,
+    pub method: Method,
+    /// Request path, without query as it may carry credentials
+    pub path: String,
+    /// Read from [`SocketAddr`] request extension, when set by the server
+    pub remote: Option<SocketAddr>,
+}
+
+/// Hook invoked on every rejected request, e.g. to ship authentication failures to SIEM,
+/// see [`observe_rejections`][crate::Layer::observe_rejections].
+///
+/// Any `Fn(&RejectionEvent)` is an observer. It's called on the request path, so anything
+/// slower than a channel send or log write should be handed over to a background task.
+pub trait RejectionObserver: Send + Sync + 'static {
+    fn observe(&self, event: &RejectionEvent);
+}
+
+impl<F> RejectionObserver for F
+where
+    F: Fn(&RejectionEvent) + Send + Sync + 'static,
+{
+    fn observe(&self, event: &RejectionEvent) {
+        self(event)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Observer(Arc<dyn RejectionObserver>);
+
+impl Observer {
+    pub(crate) fn new<O: RejectionObserver>(observer: O) -> Self {
+        Self(Arc::new(observer))
+    }
+}
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observer").finish_non_exhaustive()
+    }
+}
+
+/// Request metadata captured up front, as the request may be handed over to inner service
+/// before the outcome is known
+#[derive(Debug)]
+pub(crate) struct Observation {
+    observer: Observer,
+    method: Method,
+    path: String,
+    remote: Option<SocketAddr>,
+    sources: Vec<String>,
+}
+
+impl Observation {
+    /// `None` unless observer is configured
+    pub(crate) fn new<B>(options: &Options, req: &Request<B>) -> Option<Self> {
+        let observer = options.observer.clone()?;
+        Some(Self {
+            observer,
+            method: req.method().clone(),
+            path: req.uri().path().to_owned(),
+            remote: req.extensions().get::<SocketAddr>().copied(),
+            sources: Vec::new(),
+        })
+    }
+
+    /// Captures source chain of decoder error `err` to be reported
+    pub(crate) fn decoder_error(&mut self, err: Option<&(dyn StdError + 'static)>) {
+        let mut source = err;
+        while let Some(err) = source {
+            self.sources.push(err.to_string());
+            source = err.source();
+        }
+    }
+
+    /// Reports `err` to observer, unless it's an error of inner service
+    pub(crate) fn report<E, D>(self, err: &Error<E, D>) {
+        let Some(failure) = AuthFailure::new(err) else {
+            return;
+        };
+        self.observer.0.observe(&RejectionEvent {
+            kind: failure.kind,
+            reason: failure.reason,
+            method: self.method,
+            path: self.path,
+            remote: self.remote,
+            sources: self.sources,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RejectionEvent;
+    use crate::{util, FailureKind, Middleware};
+    use http::{Method, Request};
+    use std::{
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    };
+    use tower::{service_fn, ServiceExt};
+
+    #[tokio::test]
+    async fn observe_rejections() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let observed = events.clone();
+        let svc = service_fn(|_: Request<()>| async { Ok::<_, ()>(()) });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).observe_rejections(
+            move |event: &RejectionEvent| {
+                observed.lock().unwrap().push(event.clone());
+            },
+        );
+        let remote: SocketAddr = "192.0.2.7:52000".parse().expect("Valid address");
+
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri("/orders?access_token=secret")
+            .body(())
+            .expect("Valid request");
+        req.extensions_mut().insert(remote);
+        assert!(middleware.clone().oneshot(req).await.is_err());
+        let req = Request::builder()
+            .header("authorization", "Bearer not.a.token")
+            .body(())
+            .expect("Valid request");
+        assert!(middleware.clone().oneshot(req).await.is_err());
+        let token = util::token(&util::claim(Some(100)));
+        let req = Request::builder()
+            .header("authorization", format!("Bearer {}", token))
+            .body(())
+            .expect("Valid request");
+        assert!(middleware.oneshot(req).await.is_ok());
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, FailureKind::Authentication);
+        assert_eq!(events[0].method, Method::POST);
+        assert_eq!(events[0].path, "/orders");
+        assert_eq!(events[0].remote, Some(remote));
+        assert_eq!(events[1].reason, "Failed to decode token");
+        assert_eq!(events[1].sources.len(), 1);
+        assert!(events[0].sources.is_empty());
+        assert_eq!(events[1].remote, None);
+    }
+}
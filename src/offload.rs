@@ -0,0 +1,366 @@
+use crate::{fast, DecodeFailure, Decoder, KeyFamily};
+use core::future::Future;
+use futures::{channel::oneshot, executor, ready};
+use pin_project::pin_project;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Job running verification off the async executor
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Runs [jobs][Job] on a pool dedicated to blocking work, see [`Offload`].
+///
+/// Any `Fn(Job)` is a spawner, e.g. `|job| { tokio::task::spawn_blocking(job); }`.
+pub trait Spawner: Send + Sync + 'static {
+    fn spawn(&self, job: Job);
+}
+
+impl<F> Spawner for F
+where
+    F: Fn(Job) + Send + Sync + 'static,
+{
+    fn spawn(&self, job: Job) {
+        self(job)
+    }
+}
+
+/// Wraps any [`Decoder`] routing verification of expensive algorithms to a blocking pool.
+///
+/// Algorithm is read from token header: EdDSA and ECDSA verification is cheap enough to stay
+/// inline, while RSA verification (by default) is handed over to [`Spawner`] so it can't stall
+/// the executor. Routing is per [key family][KeyFamily], so deployments accepting several
+/// algorithms don't have to pick a single global strategy. Tokens with malformed headers are
+/// decoded inline, as wrapped decoder rejects them right away.
+///
+/// Alternatively routing can be [adaptive][Offload::adaptive], based on measured
+/// verification cost and load.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone + Send + Sync + 'static>(decoder: D)
+/// # where D::Claim: Send, D::Error: Send {
+/// use tower_jwt::{KeyFamily, Offload};
+///
+/// let decoder = Offload::new(decoder, |job| {
+///     tokio::task::spawn_blocking(job);
+/// })
+/// .offload(KeyFamily::Hmac);
+/// # }
+/// ```
+pub struct Offload<D> {
+    decoder: D,
+    spawner: Arc<dyn Spawner>,
+    offloaded: Vec<KeyFamily>,
+    adaptive: Option<Arc<Adaptive>>,
+}
+
+impl<D: Clone> Clone for Offload<D> {
+    fn clone(&self) -> Self {
+        Self {
+            decoder: self.decoder.clone(),
+            spawner: self.spawner.clone(),
+            offloaded: self.offloaded.clone(),
+            adaptive: self.adaptive.clone(),
+        }
+    }
+}
+
+impl<D> Offload<D> {
+    /// Offloads RSA verification to `spawner`
+    pub fn new<S: Spawner>(decoder: D, spawner: S) -> Self {
+        Self {
+            decoder,
+            spawner: Arc::new(spawner),
+            offloaded: vec![KeyFamily::Rsa],
+            adaptive: None,
+        }
+    }
+
+    /// Route dynamically instead of by configured families.
+    ///
+    /// Verification cost is tracked per key family as moving average. While fewer decodes
+    /// than available CPUs are in flight everything is verified inline, keeping latency minimal
+    /// for small deployments. Under higher load, families costing more than `threshold` are
+    /// offloaded, so busy deployments keep the executor responsive.
+    pub fn adaptive(mut self, threshold: Duration) -> Self {
+        let concurrency = thread::available_parallelism().map_or(1, usize::from);
+        self.adaptive = Some(Arc::new(Adaptive::new(threshold, concurrency)));
+        self
+    }
+
+    /// Offload verification of algorithms of `family`
+    pub fn offload(mut self, family: KeyFamily) -> Self {
+        if !self.offloaded.contains(&family) {
+            self.offloaded.push(family);
+        }
+        self
+    }
+
+    /// Verify algorithms of `family` inline
+    pub fn inline(mut self, family: KeyFamily) -> Self {
+        self.offloaded.retain(|offloaded| *offloaded != family);
+        self
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+
+    fn family(token: &str) -> Option<KeyFamily> {
+        fast::decode_header(token)
+            .ok()
+            .map(|header| KeyFamily::of(header.alg))
+    }
+
+    fn offloaded(&self, family: KeyFamily) -> bool {
+        match &self.adaptive {
+            Some(adaptive) => adaptive.offloaded(family),
+            None => self.offloaded.contains(&family),
+        }
+    }
+}
+
+impl<D: fmt::Debug> fmt::Debug for Offload<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Offload")
+            .field("decoder", &self.decoder)
+            .field("offloaded", &self.offloaded)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum OffloadError<E> {
+    #[error(transparent)]
+    Decoder(E),
+
+    #[error("Offloaded verification was dropped by spawner")]
+    Canceled,
+}
+
+impl<D> Decoder for Offload<D>
+where
+    D: Decoder + Clone + Send + Sync + 'static,
+    D::Claim: Send,
+    D::Error: Send,
+{
+    type Error = OffloadError<D::Error>;
+    type Claim = D::Claim;
+    type Future = OffloadFuture<D>;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        tracing::trace!("Offload::entered");
+        let family = Self::family(token);
+        let in_flight = self.adaptive.clone().map(InFlight::new);
+        let adaptive = self.adaptive.clone().zip(family);
+
+        if !family.is_some_and(|family| self.offloaded(family)) {
+            let started = Instant::now();
+            return OffloadFuture {
+                state: State::Inline(self.decoder.decode(token)),
+                measured: adaptive.map(|(adaptive, family)| (adaptive, family, started)),
+                _in_flight: in_flight,
+            };
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let decoder = self.decoder.clone();
+        let token = token.to_owned();
+        self.spawner.spawn(Box::new(move || {
+            let started = Instant::now();
+            let outcome = executor::block_on(decoder.decode(&token));
+            if let Some((adaptive, family)) = adaptive {
+                adaptive.record(family, started.elapsed());
+            }
+            let _ = tx.send(outcome);
+        }));
+        OffloadFuture {
+            state: State::Offloaded(rx),
+            measured: None,
+            _in_flight: in_flight,
+        }
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        match err {
+            OffloadError::Decoder(err) => D::failure(err),
+            OffloadError::Canceled => None,
+        }
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        match err {
+            OffloadError::Decoder(err) => D::error(err),
+            _ => None,
+        }
+    }
+}
+
+/// Load and cost measurements of [adaptive][Offload::adaptive] routing
+struct Adaptive {
+    threshold: Duration,
+    concurrency: usize,
+    in_flight: AtomicUsize,
+    /// Moving average of verification cost in nanoseconds, per key family
+    costs: [AtomicU64; 4],
+}
+
+impl Adaptive {
+    fn new(threshold: Duration, concurrency: usize) -> Self {
+        Self {
+            threshold,
+            concurrency,
+            in_flight: AtomicUsize::new(0),
+            costs: Default::default(),
+        }
+    }
+
+    fn cost(&self, family: KeyFamily) -> &AtomicU64 {
+        let index = match family {
+            KeyFamily::Hmac => 0,
+            KeyFamily::Rsa => 1,
+            KeyFamily::Ec => 2,
+            KeyFamily::Ed => 3,
+        };
+        &self.costs[index]
+    }
+
+    fn record(&self, family: KeyFamily, elapsed: Duration) {
+        let sample = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let cost = self.cost(family);
+        let _ = cost.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+            Some(match average {
+                0 => sample,
+                average => average - average / 8 + sample / 8,
+            })
+        });
+    }
+
+    fn offloaded(&self, family: KeyFamily) -> bool {
+        // in-flight count includes decode being routed
+        self.in_flight.load(Ordering::Relaxed) > self.concurrency
+            && Duration::from_nanos(self.cost(family).load(Ordering::Relaxed)) > self.threshold
+    }
+}
+
+/// Counts decode as in flight until dropped
+struct InFlight(Arc<Adaptive>);
+
+impl InFlight {
+    fn new(adaptive: Arc<Adaptive>) -> Self {
+        adaptive.in_flight.fetch_add(1, Ordering::Relaxed);
+        Self(adaptive)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[pin_project]
+pub struct OffloadFuture<D: Decoder> {
+    #[pin]
+    state: State<D>,
+    /// Cost of inline decodes is recorded once they complete, offloaded jobs record their own
+    measured: Option<(Arc<Adaptive>, KeyFamily, Instant)>,
+    _in_flight: Option<InFlight>,
+}
+
+#[pin_project(project = StateProj)]
+enum State<D: Decoder> {
+    Inline(#[pin] D::Future),
+    Offloaded(oneshot::Receiver<Result<D::Claim, D::Error>>),
+}
+
+impl<D: Decoder> Future for OffloadFuture<D> {
+    type Output = Result<D::Claim, OffloadError<D::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let outcome = match this.state.project() {
+            StateProj::Inline(future) => {
+                let outcome = ready!(future.poll(cx));
+                if let Some((adaptive, family, started)) = this.measured.take() {
+                    adaptive.record(family, started.elapsed());
+                }
+                outcome
+            }
+            StateProj::Offloaded(rx) => match ready!(Pin::new(rx).poll(cx)) {
+                Ok(outcome) => outcome,
+                Err(oneshot::Canceled) => return Poll::Ready(Err(OffloadError::Canceled)),
+            },
+        };
+        Poll::Ready(outcome.map_err(OffloadError::Decoder))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Adaptive, InFlight, Job, Offload};
+    use crate::{util, Decoder, KeyFamily};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn offload_by_family() {
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let counter = spawned.clone();
+        let spawner = move |job: Job| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            tokio::task::spawn_blocking(job);
+        };
+        let claim = util::claim(Some(100));
+        let token = util::token(&claim);
+
+        // util tokens are EdDSA, which stays inline by default
+        let decoder = Offload::new(util::in_place_decoder(), spawner);
+        let decoded = decoder.decode(&token).await.expect("Valid token");
+        assert_eq!(decoded, claim);
+        assert_eq!(spawned.load(Ordering::Relaxed), 0);
+
+        let decoder = decoder.offload(KeyFamily::Ed);
+        let decoded = decoder.decode(&token).await.expect("Valid token");
+        assert_eq!(decoded, claim);
+        assert_eq!(spawned.load(Ordering::Relaxed), 1);
+
+        // inline decodes are measured once complete
+        let decoder =
+            Offload::new(util::in_place_decoder(), |_: Job| {}).adaptive(Duration::from_millis(1));
+        let adaptive = decoder.adaptive.clone().expect("Adaptive routing");
+        let future = decoder.decode(&token);
+        assert_eq!(adaptive.cost(KeyFamily::Ed).load(Ordering::Relaxed), 0);
+        future.await.expect("Valid token");
+        assert!(adaptive.cost(KeyFamily::Ed).load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn adaptive_routing() {
+        let adaptive = Arc::new(Adaptive::new(Duration::from_micros(100), 1));
+        adaptive.record(KeyFamily::Rsa, Duration::from_millis(1));
+        adaptive.record(KeyFamily::Ed, Duration::from_micros(10));
+
+        let _first = InFlight::new(adaptive.clone());
+        assert!(!adaptive.offloaded(KeyFamily::Rsa), "Idle, stays inline");
+
+        let _second = InFlight::new(adaptive.clone());
+        assert!(adaptive.offloaded(KeyFamily::Rsa));
+        assert!(!adaptive.offloaded(KeyFamily::Ed), "Cheap, stays inline");
+    }
+}
@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+
+/// Matches `path` against `pattern`, where `*` stands for anything within one segment and
+/// `**` for anything at all
+pub(crate) fn glob(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|skip| glob(rest, &path[skip..])),
+        [b'*', rest @ ..] => {
+            let segment = path.iter().position(|&c| c == b'/').unwrap_or(path.len());
+            (0..=segment).any(|skip| glob(rest, &path[skip..]))
+        }
+        [c, rest @ ..] => path.first() == Some(c) && glob(rest, &path[1..]),
+    }
+}
+
+/// Whether `path` is free of dot segments, repeated slashes, backslashes and percent-encoded
+/// dots or slashes, so whatever router sits behind the middleware can't resolve it to another path
+pub(crate) fn canonical(path: &str) -> bool {
+    let encoded = |window: &[u8]| {
+        [b"%2e", b"%2f", b"%5c"]
+            .iter()
+            .any(|encoded| window.eq_ignore_ascii_case(*encoded))
+    };
+    !path.contains("//")
+        && !path.contains('\\')
+        && !path.as_bytes().windows(3).any(encoded)
+        && path
+            .split('/')
+            .all(|segment| segment != "." && segment != "..")
+}
+
+/// `path` with percent-encoded dots and slashes decoded, backslashes turned into slashes,
+/// repeated slashes collapsed, dot segments resolved and ASCII letters lowercased
+pub(crate) fn normalize(path: &str) -> Cow<'_, str> {
+    if canonical(path) && !path.bytes().any(|c| c.is_ascii_uppercase()) {
+        return Cow::Borrowed(path);
+    }
+    let decoded = path
+        .to_ascii_lowercase()
+        .replace("%2e", ".")
+        .replace("%2f", "/")
+        .replace("%5c", "/")
+        .replace('\\', "/");
+    let mut segments = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let mut normalized = format!("/{}", segments.join("/"));
+    let trailing = decoded.ends_with('/') || decoded.ends_with("/.") || decoded.ends_with("/..");
+    if trailing && !segments.is_empty() {
+        normalized.push('/');
+    }
+    Cow::Owned(normalized)
+}
+
+/// Whether [normalized][normalize] `path` is `prefix` or lies beneath it, segment-wise
+pub(crate) fn under(prefix: &str, path: &str) -> bool {
+    let prefix = normalize(prefix);
+    let prefix = prefix.trim_end_matches('/');
+    let path = normalize(path);
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{canonical, normalize, under};
+
+    #[test]
+    fn normalize_paths() {
+        assert!(canonical("/public/site.css"));
+        for path in [
+            "/public/../admin",
+            "/public/%2e%2E/admin",
+            "//admin",
+            "/a\\b",
+            "/a%2Fb",
+        ] {
+            assert!(!canonical(path), "{}", path);
+        }
+
+        assert_eq!(normalize("/public/site.css"), "/public/site.css");
+        assert_eq!(normalize("/public/../admin"), "/admin");
+        assert_eq!(normalize("/public/%2e%2e/admin/"), "/admin/");
+        assert_eq!(normalize("//Admin/./x"), "/admin/x");
+        assert_eq!(normalize("/../.."), "/");
+        assert_eq!(normalize("/public/..%2fadmin"), "/admin");
+
+        assert!(under("/admin", "/admin"));
+        assert!(under("/admin", "//Admin/x"));
+        assert!(under("/admin/", "/admin"));
+        assert!(!under("/admin", "/administrator"));
+        assert!(!under("/admin", "/public"));
+    }
+}
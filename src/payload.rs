@@ -0,0 +1,82 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{Map, Value};
+
+/// Untyped view of token payload.
+///
+/// Produced by parsing payload segment of the token *without* verifying it,
+/// so it must only be consulted once the token was accepted by [`Decoder`][crate::Decoder],
+/// as is the case for [gates][crate::Gate].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payload(Map<String, Value>);
+
+impl Payload {
+    /// Parses payload segment of compact JWS, returns `None` when token is malformed
+    pub fn from_token(token: &str) -> Option<Self> {
+        let mut segments = token.split('.');
+        let payload = match (segments.next(), segments.next(), segments.next()) {
+            (Some(_), Some(payload), Some(_)) => payload,
+            _ => return None,
+        };
+        let bytes = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        serde_json::from_slice(&bytes).ok().map(Self)
+    }
+
+    pub(crate) fn from_map(map: Map<String, Value>) -> Self {
+        Self(map)
+    }
+
+    pub fn get(&self, claim: &str) -> Option<&Value> {
+        self.0.get(claim)
+    }
+
+    /// Returns claim if it is a string
+    pub fn str(&self, claim: &str) -> Option<&str> {
+        self.get(claim).and_then(Value::as_str)
+    }
+
+    /// Returns claim if it is an integer, e.g. `iat`, `exp`, `auth_time`
+    pub fn i64(&self, claim: &str) -> Option<i64> {
+        self.get(claim).and_then(Value::as_i64)
+    }
+
+    /// Returns claim which may be either single string or an array of strings, e.g. `aud`
+    pub fn strings(&self, claim: &str) -> Vec<&str> {
+        match self.get(claim) {
+            Some(Value::String(value)) => vec![value.as_str()],
+            Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Audiences token was issued for
+    pub fn audiences(&self) -> Vec<&str> {
+        self.strings("aud")
+    }
+
+    pub fn as_map(&self) -> &Map<String, Value> {
+        &self.0
+    }
+
+    pub fn into_map(self) -> Map<String, Value> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Payload;
+    use crate::util;
+
+    #[test]
+    fn payload_from_token() {
+        let token = util::token(&util::claim(Some(100)));
+        let payload = Payload::from_token(&token).expect("Failed to parse valid token");
+
+        assert_eq!(payload.str("iss"), Some("issuer"));
+        assert!(payload.i64("exp").is_some());
+        assert!(payload.audiences().is_empty());
+
+        assert_eq!(Payload::from_token("not.a-token"), None);
+        assert_eq!(Payload::from_token("a.!!!.c"), None);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{DecodeFailure, Decoder, DefaultExtractor, TokenExtractor};
+use futures::future::{self, Either, MapErr, Ready, TryFutureExt};
+use http::{HeaderMap, Request, Uri};
+use std::{borrow::Cow, cell::RefCell, fmt, sync::Arc};
+use thiserror::Error;
+
+thread_local! {
+    /// Peer identity of request whose token is being decoded, see [`scope`]
+    static CURRENT: RefCell<Option<PeerIdentity>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with `peer` available to [`Peer`] decoders building their futures
+pub(crate) fn scope<T>(peer: Option<&PeerIdentity>, f: impl FnOnce() -> T) -> T {
+    let Some(peer) = peer else {
+        return f();
+    };
+    let previous = CURRENT.with(|current| current.replace(Some(peer.clone())));
+    let outcome = f();
+    CURRENT.with(|current| *current.borrow_mut() = previous);
+    outcome
+}
+
+/// Verified identity of the TLS client, e.g. URI SAN or SPIFFE ID of its certificate.
+///
+/// Expected to be set on request extensions by whatever terminates TLS
+/// once the client certificate chain was verified.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PeerIdentity(String);
+
+impl PeerIdentity {
+    pub fn new(identity: impl Into<String>) -> Self {
+        Self(identity.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Trust domain of `spiffe://<trust-domain>/<path>` identities
+    pub fn spiffe_trust_domain(&self) -> Option<&str> {
+        let rest = self.0.strip_prefix("spiffe://")?;
+        rest.split('/').next().filter(|domain| !domain.is_empty())
+    }
+}
+
+/// [`TokenExtractor`] falling back to [`PeerIdentity`] of requests wrapped extractor found
+/// no token on, for [`Peer`] decoder to authenticate.
+#[derive(Debug, Clone, Default)]
+pub struct PeerToken<E = DefaultExtractor> {
+    inner: E,
+}
+
+impl<E> PeerToken<E> {
+    pub fn new(inner: E) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E, B> TokenExtractor<B> for PeerToken<E>
+where
+    E: TokenExtractor<B>,
+{
+    fn extract(&self, req: &Request<B>) -> Option<String> {
+        self.extract_borrowed(req).map(Cow::into_owned)
+    }
+
+    fn extract_borrowed<'r>(&self, req: &'r Request<B>) -> Option<Cow<'r, str>> {
+        self.inner.extract_borrowed(req).or_else(|| {
+            let peer = req.extensions().get::<PeerIdentity>()?;
+            Some(Cow::Borrowed(peer.as_str()))
+        })
+    }
+
+    fn strip(&self, headers: &mut HeaderMap) {
+        self.inner.strip(headers)
+    }
+
+    fn strip_uri(&self, uri: &mut Uri) {
+        self.inner.strip_uri(uri)
+    }
+}
+
+/// Wraps [`Decoder`] (or [`Chain`][crate::Chain] of them) accepting verified [`PeerIdentity`]
+/// as an alternative credential, e.g. for internal traffic over mutual TLS.
+///
+/// Identity reaches the decoder as token through [`PeerToken`] extractor, and is only
+/// accepted when it is the one set on request extensions, so tokens merely looking like
+/// identities are passed on to wrapped decoder. Claim is produced by `authenticate`,
+/// returning `None` rejects the peer.
+///
+/// ```rust
+/// # fn example(decoder: tower_jwt::InPlace<String>) {
+/// use tower_jwt::{DefaultExtractor, Layer, Peer, PeerIdentity, PeerToken};
+///
+/// let decoder = Peer::new(decoder, |peer: &PeerIdentity| {
+///     let domain = peer.spiffe_trust_domain()?;
+///     (domain == "cluster.local").then(|| peer.as_str().to_owned())
+/// });
+/// let layer = Layer::new(decoder).extractor(PeerToken::<DefaultExtractor>::default());
+/// # }
+/// ```
+pub struct Peer<D: Decoder> {
+    decoder: D,
+    authenticate: Arc<Authenticate<D::Claim>>,
+}
+
+type Authenticate<C> = dyn Fn(&PeerIdentity) -> Option<C> + Send + Sync;
+
+impl<D: Decoder> Peer<D> {
+    pub fn new<F>(decoder: D, authenticate: F) -> Self
+    where
+        F: Fn(&PeerIdentity) -> Option<D::Claim> + Send + Sync + 'static,
+    {
+        Self {
+            decoder,
+            authenticate: Arc::new(authenticate),
+        }
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+impl<D: Decoder + Clone> Clone for Peer<D> {
+    fn clone(&self) -> Self {
+        Self {
+            decoder: self.decoder.clone(),
+            authenticate: self.authenticate.clone(),
+        }
+    }
+}
+
+impl<D: Decoder + fmt::Debug> fmt::Debug for Peer<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Peer")
+            .field("decoder", &self.decoder)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PeerError<E> {
+    #[error("Peer identity is not accepted")]
+    Rejected,
+
+    #[error(transparent)]
+    Decoder(E),
+}
+
+impl<D> Decoder for Peer<D>
+where
+    D: Decoder,
+{
+    type Error = PeerError<D::Error>;
+    type Claim = D::Claim;
+    type Future = Either<
+        MapErr<D::Future, fn(D::Error) -> PeerError<D::Error>>,
+        Ready<Result<D::Claim, PeerError<D::Error>>>,
+    >;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        let peer = CURRENT.with(|current| {
+            let current = current.borrow();
+            current
+                .as_ref()
+                .filter(|peer| peer.as_str() == token)
+                .map(|peer| (self.authenticate)(peer))
+        });
+        match peer {
+            Some(claim) => {
+                tracing::trace!("Peer::authenticated");
+                Either::Right(future::ready(claim.ok_or(PeerError::Rejected)))
+            }
+            None => Either::Left(
+                self.decoder
+                    .decode(token)
+                    .map_err(PeerError::Decoder as fn(_) -> _),
+            ),
+        }
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        match err {
+            PeerError::Rejected => Some(DecodeFailure::Claims),
+            PeerError::Decoder(err) => D::failure(err),
+        }
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        match err {
+            PeerError::Decoder(err) => D::error(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Peer, PeerIdentity, PeerToken};
+    use crate::{util, DefaultExtractor, Error, Middleware, RawToken};
+    use http::Request;
+    use tower::{service_fn, ServiceExt};
+
+    #[test]
+    fn spiffe_trust_domain() {
+        let spiffe = PeerIdentity::new("spiffe://cluster.local/ns/default/sa/orders");
+        assert_eq!(spiffe.spiffe_trust_domain(), Some("cluster.local"));
+        assert_eq!(
+            PeerIdentity::new("orders.internal").spiffe_trust_domain(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn peer_identity_claim() {
+        let svc = service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(crate::claims::<util::Claim>(&req).cloned())
+        });
+        let claim = util::claim(Some(100));
+        let accepted = claim.clone();
+        let decoder = Peer::new(util::in_place_decoder(), move |peer: &PeerIdentity| {
+            peer.spiffe_trust_domain()
+                .filter(|domain| *domain == "cluster.local")
+                .map(|_| accepted.clone())
+        });
+        let middleware =
+            Middleware::new(decoder, svc).extractor(PeerToken::<DefaultExtractor>::default());
+
+        let mut req = Request::new(());
+        req.extensions_mut()
+            .insert(PeerIdentity::new("spiffe://cluster.local/sa/billing"));
+        let decoded = middleware
+            .clone()
+            .oneshot(req)
+            .await
+            .expect("Peer identity must be accepted");
+        assert_eq!(decoded, Some(claim.clone()));
+
+        let token = util::token(&claim);
+        let req = Request::builder()
+            .header("authorization", format!("Bearer {}", token))
+            .body(())
+            .expect("Valid request");
+        let decoded = middleware
+            .clone()
+            .oneshot(req)
+            .await
+            .expect("Token must be accepted");
+        assert_eq!(decoded, Some(claim));
+
+        let mut req = Request::new(());
+        req.extensions_mut()
+            .insert(PeerIdentity::new("spiffe://elsewhere/sa/billing"));
+        let outcome = middleware.clone().oneshot(req).await;
+        assert!(matches!(outcome, Err(Error::Decoder(_))));
+
+        // identity must come from the connection, not from the token
+        let mut req = Request::new(());
+        req.extensions_mut()
+            .insert(RawToken(String::from("spiffe://cluster.local/sa/billing")));
+        let outcome = middleware.oneshot(req).await;
+        assert!(matches!(outcome, Err(Error::Decoder(_))));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{BoxFuture, Denied, Gate, GateContext};
+use futures::future;
+use http::Extensions;
+use std::{fmt, sync::Arc};
+
+/// Request class derived from authenticated identity, set on request extensions by [`Prioritize`].
+///
+/// Higher values are more important, downstream load-shedding or queueing layers
+/// can use it to implement tiered QoS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Priority(pub u8);
+
+impl Priority {
+    pub const LOW: Self = Self(64);
+    pub const NORMAL: Self = Self(128);
+    pub const HIGH: Self = Self(192);
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::NORMAL
+    }
+}
+
+type Classify = dyn Fn(&GateContext<'_>) -> Priority + Send + Sync;
+
+/// [`Gate`] mapping decoded claims to [`Priority`], never denies requests.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)] struct Claim { tier: String }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, Prioritize, Priority};
+///
+/// let layer = Layer::new(decoder).gate(Prioritize::claims(|claim: &Claim| {
+///     match claim.tier.as_str() {
+///         "premium" => Priority::HIGH,
+///         _ => Priority::NORMAL,
+///     }
+/// }));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Prioritize {
+    classify: Arc<Classify>,
+}
+
+impl Prioritize {
+    pub fn new<F>(classify: F) -> Self
+    where
+        F: Fn(&GateContext<'_>) -> Priority + Send + Sync + 'static,
+    {
+        Self {
+            classify: Arc::new(classify),
+        }
+    }
+
+    /// Classify by decoded claim, requests without claim of type `C` get default [`Priority`]
+    pub fn claims<C, F>(classify: F) -> Self
+    where
+        C: Send + Sync + 'static,
+        F: Fn(&C) -> Priority + Send + Sync + 'static,
+    {
+        Self::new(move |cx| cx.claims::<C>().map(&classify).unwrap_or_default())
+    }
+}
+
+impl Gate for Prioritize {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let mut extensions = Extensions::new();
+        extensions.insert((self.classify)(cx));
+        Box::pin(future::ready(Ok(extensions)))
+    }
+}
+
+impl fmt::Debug for Prioritize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prioritize").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Prioritize, Priority};
+    use crate::{Gate, GateContext};
+    use http::Request;
+
+    #[tokio::test]
+    async fn prioritize_by_claims() {
+        let gate = Prioritize::claims(|tier: &String| match tier.as_str() {
+            "premium" => Priority::HIGH,
+            _ => Priority::LOW,
+        });
+
+        let (mut parts, _) = Request::new(()).into_parts();
+        let extensions = gate
+            .check(&GateContext::new(&parts, None))
+            .await
+            .expect("Prioritize never denies");
+        assert_eq!(extensions.get::<Priority>(), Some(&Priority::NORMAL));
+
+        parts.extensions.insert(String::from("premium"));
+        let extensions = gate
+            .check(&GateContext::new(&parts, None))
+            .await
+            .expect("Prioritize never denies");
+        assert_eq!(extensions.get::<Priority>(), Some(&Priority::HIGH));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{Error, Rejection, RejectionHandler, RenderFuture};
+use futures::future;
+use http::{header::CONTENT_TYPE, request::Parts, HeaderValue};
+use serde_json::{json, Map, Value};
+
+/// Renders rejections as [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) problem details,
+/// `application/problem+json` documents with `type`, `title`, `status`, `detail` and `instance`
+/// members.
+///
+/// `detail` is taken from `error_description` challenge parameter and `error` code, if any,
+/// is kept as extension member. Problem `type` is `about:blank`, unless
+/// [`type_base`][ProblemJson::type_base] is set, in which case error code is appended to it.
+/// Usable with [`reject_with`][crate::Layer::reject_with].
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, ProblemJson};
+///
+/// let layer = Layer::new(decoder).reject_with(ProblemJson::new().type_base("https://errors.example.com/"));
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProblemJson {
+    type_base: Option<String>,
+}
+
+impl ProblemJson {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prefix of problem types, followed by error code, e.g. `https://errors.example.com/`
+    pub fn type_base(mut self, base: impl Into<String>) -> Self {
+        self.type_base = Some(base.into());
+        self
+    }
+
+    /// Problem document of `rejection` of request to `instance`
+    pub fn document(&self, rejection: &Rejection, instance: Option<&str>) -> Value {
+        let status = rejection.status();
+        let error = rejection.param("error");
+        let kind = match (&self.type_base, error) {
+            (Some(base), Some(error)) => format!("{}{}", base, error),
+            _ => String::from("about:blank"),
+        };
+
+        let mut problem = Map::new();
+        problem.insert(String::from("type"), json!(kind));
+        problem.insert(
+            String::from("title"),
+            json!(status.canonical_reason().unwrap_or("Unknown")),
+        );
+        problem.insert(String::from("status"), json!(status.as_u16()));
+        let optional = [
+            ("detail", rejection.param("error_description")),
+            ("instance", instance),
+            ("error", error),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                problem.insert(String::from(name), json!(value));
+            }
+        }
+        Value::Object(problem)
+    }
+
+    /// `rejection` with problem document of request to `instance` as body
+    pub fn render(&self, rejection: Rejection, instance: Option<&str>) -> Rejection {
+        let body = self.document(&rejection, instance).to_string();
+        rejection.with_body(body).with_header(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        )
+    }
+}
+
+impl<E, D, B> RejectionHandler<E, D, B> for ProblemJson
+where
+    B: From<String> + Send + 'static,
+{
+    fn render(&self, parts: &Parts, _: &Error<E, D>, rejection: Rejection) -> RenderFuture<B> {
+        let res = ProblemJson::render(self, rejection, Some(parts.uri.path())).into_response();
+        Box::pin(future::ready(res))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProblemJson;
+    use crate::{util, Middleware};
+    use http::{header::CONTENT_TYPE, Request, Response, StatusCode};
+    use serde_json::json;
+    use std::convert::Infallible;
+    use tower::{service_fn, ServiceExt};
+
+    #[tokio::test]
+    async fn problem_json() {
+        let svc = service_fn(|_: Request<()>| async move {
+            Ok::<_, Infallible>(Response::new(String::new()))
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc)
+            .reject_with(ProblemJson::new().type_base("https://errors.example.com/"));
+
+        let req = Request::builder()
+            .uri("/orders")
+            .header("authorization", "Bearer not-a-token")
+            .body(())
+            .expect("Valid request");
+        let res = middleware.oneshot(req).await.expect("Problem");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.headers()[CONTENT_TYPE], "application/problem+json");
+        let problem: serde_json::Value = serde_json::from_str(res.body()).expect("Valid JSON");
+        assert_eq!(
+            problem,
+            json!({
+                "type": "https://errors.example.com/invalid_request",
+                "title": "Bad Request",
+                "status": 400,
+                "detail": "The access token is malformed",
+                "instance": "/orders",
+                "error": "invalid_request",
+            })
+        );
+    }
+}
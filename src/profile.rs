@@ -0,0 +1,156 @@
+use crate::fast;
+use jsonwebtoken::{
+    errors::{Error, ErrorKind},
+    Validation,
+};
+use std::time::Duration;
+
+/// Claims [`ValidationProfile::Rfc9068`] requires
+const RFC9068_CLAIMS: [&str; 7] = ["iss", "exp", "aud", "sub", "client_id", "iat", "jti"];
+
+/// Named presets for time-based claim validation.
+///
+/// Profiles only touch `exp`/`nbf` handling and leeway, leaving algorithms, issuers
+/// and audiences of the [`Validation`] intact, so the same profile can be applied
+/// consistently to every built-in decoder in a deployment, along with the
+/// [grace window][Self::grace] of [`NbfRetry`][crate::NbfRetry].
+///
+/// [`Rfc9068`][ValidationProfile::Rfc9068] additionally enforces structure of the token,
+/// which [`Validation`] can't express, decoders applying the profile [`check`][Self::check]
+/// tokens after verifying them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum ValidationProfile {
+    /// No leeway, both `exp` and `nbf` enforced, with two seconds of [grace][Self::grace]
+    Strict,
+    /// One minute of leeway, both `exp` and `nbf` enforced
+    #[default]
+    Default,
+    /// Five minutes of leeway with `nbf` ignored, for edges with poorly synchronized clocks
+    Lenient,
+    /// [RFC 9068](https://www.rfc-editor.org/rfc/rfc9068) JWT access tokens: `typ` of `at+jwt`,
+    /// `iss`, `exp`, `aud`, `sub`, `client_id`, `iat` and `jti` claims required, with time-based
+    /// checks of [`Default`][ValidationProfile::Default] profile
+    Rfc9068,
+}
+
+impl ValidationProfile {
+    /// Leeway (in seconds) applied to `exp` and `nbf` checks
+    pub fn leeway(&self) -> u64 {
+        match self {
+            Self::Strict => 0,
+            Self::Default | Self::Rfc9068 => 60,
+            Self::Lenient => 300,
+        }
+    }
+
+    /// How long tokens issued ahead of local clock are waited for to become valid, rather than
+    /// rejected. Only [`Strict`][ValidationProfile::Strict] profile has one, as others either
+    /// have leeway covering that or don't validate `nbf`
+    pub fn grace(&self) -> Option<Duration> {
+        match self {
+            Self::Strict => Some(Duration::from_secs(2)),
+            _ => None,
+        }
+    }
+
+    /// Whether `nbf` is validated
+    pub fn validate_nbf(&self) -> bool {
+        !matches!(self, Self::Lenient)
+    }
+
+    /// Claims tokens must carry
+    pub fn required_claims(&self) -> &'static [&'static str] {
+        match self {
+            Self::Rfc9068 => &RFC9068_CLAIMS,
+            _ => &[],
+        }
+    }
+
+    /// Configures time-based checks of `validation` according to profile
+    pub fn apply(&self, validation: &mut Validation) {
+        validation.leeway = self.leeway();
+        validation.validate_exp = true;
+        validation.validate_nbf = self.validate_nbf();
+        if let Self::Rfc9068 = self {
+            validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        }
+    }
+
+    /// Checks verified `token` against what [`Validation`] doesn't cover, `typ` header and
+    /// non-registered required claims
+    pub fn check(&self, token: &str) -> Result<(), Error> {
+        if !matches!(self, Self::Rfc9068) {
+            return Ok(());
+        }
+        let header = fast::decode_header(token)?;
+        let typ = header.typ.as_deref().unwrap_or_default();
+        if !typ.eq_ignore_ascii_case("at+jwt") && !typ.eq_ignore_ascii_case("application/at+jwt") {
+            return Err(ErrorKind::MissingRequiredClaim(String::from("typ")).into());
+        }
+        let payload = fast::payload(token).ok_or(ErrorKind::InvalidToken)?;
+        match self
+            .required_claims()
+            .iter()
+            .find(|claim| payload.get(claim).is_none())
+        {
+            Some(claim) => Err(ErrorKind::MissingRequiredClaim(claim.to_string()).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ValidationProfile;
+    use crate::{util, Decoder, InPlace};
+    use jsonwebtoken::{encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn profiles() {
+        // expired 90 seconds ago
+        let expired = util::token(&util::claim(None));
+
+        let strict = util::in_place_decoder().with_profile(ValidationProfile::Strict);
+        assert!(strict.decode(&expired).await.is_err());
+
+        let lenient = util::in_place_decoder().with_profile(ValidationProfile::Lenient);
+        assert!(lenient.decode(&expired).await.is_ok());
+
+        assert_eq!(
+            ValidationProfile::Strict.grace(),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(ValidationProfile::Lenient.grace(), None);
+    }
+
+    #[tokio::test]
+    async fn rfc9068() {
+        let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes()).expect("Valid key");
+        let decoder = InPlace::<serde_json::Value>::new(key, Validation::new(Algorithm::EdDSA))
+            .with_profile(ValidationProfile::Rfc9068);
+        let key = EncodingKey::from_ed_pem(util::PRIVATE_KEY.as_bytes()).expect("Valid key");
+        let now = chrono::Utc::now().timestamp();
+        let mut claims = json!({
+            "iss": "https://issuer.example", "exp": now + 60, "aud": "orders", "sub": "alice",
+            "client_id": "web", "iat": now, "jti": "1", "nbf": now - 10,
+        });
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.typ = Some(String::from("at+jwt"));
+
+        let token = encode(&header, &claims, &key).expect("Valid token");
+        assert!(decoder.decode(&token).await.is_ok());
+
+        claims
+            .as_object_mut()
+            .map(|claims| claims.remove("client_id"));
+        let token = encode(&header, &claims, &key).expect("Valid token");
+        assert!(decoder.decode(&token).await.is_err());
+
+        header.typ = Some(String::from("JWT"));
+        let token = encode(&header, &json!({}), &key).expect("Valid token");
+        assert!(decoder.decode(&token).await.is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use http::Uri;
+
+/// Returns percent-decoded value of the first query parameter named `name`
+pub(crate) fn query_param(uri: &Uri, name: &str) -> Option<String> {
+    form_param(uri.query()?, name)
+}
+
+/// Returns percent-decoded value of the first `application/x-www-form-urlencoded`
+/// parameter named `name`
+pub(crate) fn form_param(form: &str, name: &str) -> Option<String> {
+    form.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| percent_decode(value))
+        .filter(|value| !value.is_empty())
+}
+
+/// Removes every query parameter named `name` from `uri`, keeping the rest in order
+pub(crate) fn remove_query_param(uri: &mut Uri, name: &str) {
+    let Some(query) = uri.query() else {
+        return;
+    };
+    let kept: Vec<_> = query
+        .split('&')
+        .filter(|pair| pair.split('=').next() != Some(name))
+        .collect();
+    let path_and_query = match kept.is_empty() {
+        true => uri.path().to_owned(),
+        false => format!("{}?{}", uri.path(), kept.join("&")),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = path_and_query.parse().ok();
+    if let Ok(stripped) = Uri::from_parts(parts) {
+        *uri = stripped;
+    }
+}
+
+/// Percent-encodes `value` for use as query parameter value
+pub(crate) fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = value.bytes();
+    let mut decoded = Vec::with_capacity(value.len());
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{query_param, remove_query_param};
+    use http::Uri;
+
+    #[test]
+    fn query_lookup() {
+        let uri: Uri = "/events?stream=1&access_token=a.b%2Ec&empty="
+            .parse()
+            .expect("Valid uri");
+
+        assert_eq!(query_param(&uri, "access_token").as_deref(), Some("a.b.c"));
+        assert_eq!(query_param(&uri, "stream").as_deref(), Some("1"));
+        assert_eq!(query_param(&uri, "empty"), None);
+        assert_eq!(query_param(&uri, "missing"), None);
+
+        let mut uri = uri;
+        remove_query_param(&mut uri, "access_token");
+        assert_eq!(uri, "/events?stream=1&empty=");
+        let mut uri: Uri = "https://example.com/ws?access_token=a.b.c"
+            .parse()
+            .expect("Valid uri");
+        remove_query_param(&mut uri, "access_token");
+        assert_eq!(uri, "https://example.com/ws");
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{cache::TtlCache, BoxError, BoxFuture, Denied, Gate, GateContext};
+use core::future::Future;
+use futures::{future, FutureExt};
+use http::{header::HeaderName, Extensions, HeaderMap, HeaderValue};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Calendar period quota is counted over, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Period {
+    Day,
+    Month,
+}
+
+impl Period {
+    /// Index of the period `time` falls into, along with start of the next one
+    fn window(self, time: SystemTime) -> (u64, SystemTime) {
+        let days = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            / 86400;
+        let (index, next) = match self {
+            Period::Day => (days, days + 1),
+            Period::Month => {
+                let (year, month, _) = civil_from_days(days);
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                (year * 12 + month - 1, days_from_civil(year, month, 1))
+            }
+        };
+        (index, UNIX_EPOCH + Duration::from_secs(next * 86400))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Month => "month",
+        }
+    }
+}
+
+/// Civil date of `days` since epoch, see <http://howardhinnant.github.io/date_algorithms.html>
+pub(crate) fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + u64::from(month <= 2), month, day)
+}
+
+/// Days since epoch of civil date, inverse of [`civil_from_days`]
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Usage counters shared by every instance enforcing [`ClientQuota`], e.g. Redis `INCR`
+/// followed by `EXPIREAT`.
+///
+/// Any `Fn(&str, SystemTime) -> impl Future<Output = Result<u64, BoxError>>` is a store.
+pub trait QuotaStore: Send + Sync + 'static {
+    /// Increments counter `key`, returning its new value. Counter is no longer needed
+    /// after `expires`.
+    fn increment(&self, key: &str, expires: SystemTime) -> BoxFuture<u64, BoxError>;
+}
+
+impl<F, Fut> QuotaStore for F
+where
+    F: Fn(&str, SystemTime) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<u64, BoxError>> + Send + 'static,
+{
+    fn increment(&self, key: &str, expires: SystemTime) -> BoxFuture<u64, BoxError> {
+        Box::pin(self(key, expires))
+    }
+}
+
+/// [`QuotaStore`] keeping counters in memory of a single instance
+#[derive(Debug, Clone, Default)]
+pub struct MemoryQuotaStore(TtlCache<u64>);
+
+impl MemoryQuotaStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl QuotaStore for MemoryQuotaStore {
+    fn increment(&self, key: &str, expires: SystemTime) -> BoxFuture<u64, BoxError> {
+        let ttl = expires
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        let count = self.0.upsert(key, ttl, u64::default, |count| {
+            *count += 1;
+            *count
+        });
+        Box::pin(future::ready(Ok(count)))
+    }
+}
+
+/// Standing of client against its tightest quota, set on request extensions by [`ClientQuota`]
+/// and carried by [`Denied::QuotaExceeded`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub limit: u64,
+    pub remaining: u64,
+    /// When the period ends and usage is reset
+    pub reset: SystemTime,
+}
+
+impl QuotaUsage {
+    /// Time left until [`reset`][Self::reset], rounded up to whole seconds
+    pub fn reset_after(&self) -> Duration {
+        let left = self
+            .reset
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        Duration::from_secs(left.as_secs() + u64::from(left.subsec_nanos() > 0))
+    }
+
+    /// `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers, e.g. for
+    /// handlers to advertise usage on successful responses as well
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let values = [
+            ("ratelimit-limit", self.limit),
+            ("ratelimit-remaining", self.remaining),
+            ("ratelimit-reset", self.reset_after().as_secs()),
+        ];
+        for (name, value) in values {
+            headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+        }
+        headers
+    }
+}
+
+/// [`Gate`] enforcing per-client request quotas (per day, per month) tracked in [`QuotaStore`],
+/// so licensed usage is enforced by the layer that knows client identity.
+///
+/// Client is identified by `client_id` claim, unless [configured][ClientQuota::claim]
+/// otherwise, tokens without it are denied. Requests over quota are denied with
+/// [`Denied::QuotaExceeded`], rendered as `429` with `Retry-After` and `RateLimit-*` headers,
+/// accepted ones carry [`QuotaUsage`] on extensions. Store failures let requests through,
+/// unless [`strict`][ClientQuota::strict].
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{ClientQuota, Layer, MemoryQuotaStore};
+///
+/// let quota = ClientQuota::new(MemoryQuotaStore::new())
+///     .per_day(10_000)
+///     .per_month(200_000);
+/// let layer = Layer::new(decoder).gate(quota);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ClientQuota {
+    claim: String,
+    limits: Vec<(Period, u64)>,
+    store: Arc<dyn QuotaStore>,
+    strict: bool,
+}
+
+impl ClientQuota {
+    pub fn new<S: QuotaStore>(store: S) -> Self {
+        Self {
+            claim: String::from("client_id"),
+            limits: Vec::new(),
+            store: Arc::new(store),
+            strict: false,
+        }
+    }
+
+    /// Claim identifying the client, e.g. `azp`
+    pub fn claim(mut self, claim: impl Into<String>) -> Self {
+        self.claim = claim.into();
+        self
+    }
+
+    pub fn per_day(self, requests: u64) -> Self {
+        self.limit(Period::Day, requests)
+    }
+
+    pub fn per_month(self, requests: u64) -> Self {
+        self.limit(Period::Month, requests)
+    }
+
+    /// At most `requests` per `period`, any number of limits can be set
+    pub fn limit(mut self, period: Period, requests: u64) -> Self {
+        self.limits.push((period, requests));
+        self
+    }
+
+    /// Deny requests when store fails
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+}
+
+impl Gate for ClientQuota {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let client = match cx.payload().and_then(|payload| payload.str(&self.claim)) {
+            Some(client) => client,
+            None => return Box::pin(future::ready(Err(Denied::Claim(self.claim.clone())))),
+        };
+        let now = SystemTime::now();
+        let counted = self.limits.iter().map(|&(period, limit)| {
+            let (index, reset) = period.window(now);
+            let key = format!("{}\u{1f}{}\u{1f}{}", client, period.name(), index);
+            self.store.increment(&key, reset).map(move |count| {
+                count.map(|count| {
+                    let usage = QuotaUsage {
+                        limit,
+                        remaining: limit.saturating_sub(count),
+                        reset,
+                    };
+                    (usage, count > limit)
+                })
+            })
+        });
+
+        let strict = self.strict;
+        future::join_all(counted)
+            .map(move |usages| {
+                let mut tightest: Option<QuotaUsage> = None;
+                for usage in usages {
+                    let (usage, exceeded) = match usage {
+                        Ok(usage) => usage,
+                        Err(err) if strict => return Err(Denied::Other(err)),
+                        Err(err) => {
+                            tracing::warn!("ClientQuota::increment {}", err);
+                            continue;
+                        }
+                    };
+                    if exceeded {
+                        return Err(Denied::QuotaExceeded(usage));
+                    }
+                    if tightest
+                        .as_ref()
+                        .is_none_or(|tightest| usage.remaining < tightest.remaining)
+                    {
+                        tightest = Some(usage);
+                    }
+                }
+                let mut extensions = Extensions::new();
+                if let Some(usage) = tightest {
+                    extensions.insert(usage);
+                }
+                Ok(extensions)
+            })
+            .boxed()
+    }
+}
+
+impl fmt::Debug for ClientQuota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientQuota")
+            .field("claim", &self.claim)
+            .field("limits", &self.limits)
+            .field("strict", &self.strict)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{days_from_civil, ClientQuota, MemoryQuotaStore, Period, QuotaUsage};
+    use crate::{util, Denied, Gate, GateContext};
+    use http::{header::RETRY_AFTER, Request, StatusCode};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[tokio::test]
+    async fn client_quota() {
+        let gate = ClientQuota::new(MemoryQuotaStore::new())
+            .per_day(2)
+            .per_month(10);
+        let (parts, _) = Request::new(()).into_parts();
+        let token = util::token(&serde_json::json!({ "client_id": "app" }));
+
+        for remaining in [1, 0] {
+            let mut extensions = gate
+                .check(&GateContext::new(&parts, Some(&token)))
+                .await
+                .expect("Within quota");
+            let usage = extensions.remove::<QuotaUsage>().expect("Usage");
+            assert_eq!((usage.limit, usage.remaining), (2, remaining));
+        }
+        let denied = gate
+            .check(&GateContext::new(&parts, Some(&token)))
+            .await
+            .expect_err("Over quota");
+        assert!(matches!(denied, Denied::QuotaExceeded(_)));
+        let rejection = denied.rejection();
+        assert_eq!(rejection.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(rejection.headers()["ratelimit-limit"], "2");
+        assert_eq!(rejection.headers()["ratelimit-remaining"], "0");
+        assert!(rejection.headers().contains_key(RETRY_AFTER));
+
+        let anonymous = util::token(&serde_json::json!({ "sub": "user" }));
+        let outcome = gate
+            .check(&GateContext::new(&parts, Some(&anonymous)))
+            .await;
+        assert!(matches!(outcome, Err(Denied::Claim(_))));
+
+        // 2024-02-29T12:00:00Z
+        let leap = UNIX_EPOCH + Duration::from_secs(1_709_208_000);
+        let march = UNIX_EPOCH + Duration::from_secs(days_from_civil(2024, 3, 1) * 86400);
+        assert_eq!(Period::Month.window(leap).1, march);
+        assert_eq!(Period::Day.window(leap).1, march);
+        assert!(Period::Month.window(SystemTime::now()).1 > SystemTime::now());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{cache::TtlCache, BoxFuture, Denied, Gate, GateContext};
+use futures::future;
+use http::Extensions;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+enum Quota {
+    Rate { requests: u64, per: Duration },
+    Concurrency(u64),
+}
+
+#[derive(Debug)]
+struct Entry {
+    started: Instant,
+    count: u64,
+}
+
+/// [`Gate`] enforcing request-rate or concurrency quotas keyed by claims, e.g. `iss`, `client_id` or `sub`.
+///
+/// Claims needed for fair-share limiting are only available after authentication,
+/// so it's most convenient to enforce quotas right after decoding the token.
+/// Requests over quota are denied with [`Denied::RateLimited`], ones whose token lacks any of
+/// the claims with [`Denied::Claim`].
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use std::time::Duration;
+/// use tower_jwt::{Layer, RateLimit};
+///
+/// let layer = Layer::new(decoder)
+///     .gate(RateLimit::rate(["iss", "client_id"], 100, Duration::from_secs(1)))
+///     .gate(RateLimit::concurrency(["sub"], 8));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    claims: Vec<String>,
+    quota: Quota,
+    entries: TtlCache<Entry>,
+}
+
+impl RateLimit {
+    /// At most `requests` per `per` interval for every distinct combination of `claims`
+    pub fn rate<I, C>(claims: I, requests: u64, per: Duration) -> Self
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<String>,
+    {
+        Self::new(claims, Quota::Rate { requests, per })
+    }
+
+    /// At most `requests` in flight for every distinct combination of `claims`.
+    ///
+    /// Slot is held until request (with its extensions) is dropped by inner service.
+    pub fn concurrency<I, C>(claims: I, requests: u64) -> Self
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<String>,
+    {
+        Self::new(claims, Quota::Concurrency(requests))
+    }
+
+    fn new<I, C>(claims: I, quota: Quota) -> Self
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<String>,
+    {
+        Self {
+            claims: claims.into_iter().map(Into::into).collect(),
+            quota,
+            entries: Default::default(),
+        }
+    }
+
+    /// Tokens lacking any of the claims are denied rather than sharing a bucket
+    fn key(&self, cx: &GateContext<'_>) -> Result<String, Denied> {
+        let payload = cx.payload();
+        self.claims
+            .iter()
+            .map(|claim| {
+                payload
+                    .and_then(|p| p.get(claim))
+                    .map(|value| value.to_string())
+                    .ok_or_else(|| Denied::Claim(claim.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|values| values.join("\u{1f}"))
+    }
+
+    fn acquire(&self, key: String) -> Result<Extensions, Denied> {
+        let entry = || Entry {
+            started: Instant::now(),
+            count: 0,
+        };
+
+        match self.quota {
+            // window expires along with its entry
+            Quota::Rate { requests, per } => self.entries.upsert(&key, per, entry, |entry| {
+                if entry.count >= requests {
+                    let retry_after = per.saturating_sub(entry.started.elapsed());
+                    return Err(Denied::RateLimited {
+                        retry_after: Some(retry_after),
+                    });
+                }
+                entry.count += 1;
+                Ok(Extensions::new())
+            }),
+            Quota::Concurrency(requests) => {
+                let acquired = self.entries.upsert(&key, Duration::MAX, entry, |entry| {
+                    let acquired = entry.count < requests;
+                    entry.count += u64::from(acquired);
+                    acquired
+                });
+                if !acquired {
+                    return Err(Denied::RateLimited { retry_after: None });
+                }
+
+                let mut extensions = Extensions::new();
+                extensions.insert(Permit {
+                    key,
+                    entries: self.entries.clone(),
+                });
+                Ok(extensions)
+            }
+        }
+    }
+}
+
+impl Gate for RateLimit {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let outcome = self.key(cx).and_then(|key| self.acquire(key));
+        Box::pin(future::ready(outcome))
+    }
+}
+
+/// Concurrency slot, released once dropped along with request extensions
+struct Permit {
+    key: String,
+    entries: TtlCache<Entry>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.entries.remove_if(&self.key, |entry| {
+            entry.count = entry.count.saturating_sub(1);
+            entry.count == 0
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RateLimit;
+    use crate::{util, Denied, Gate, GateContext};
+    use http::Request;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn rate() {
+        let gate = RateLimit::rate(["iss"], 2, Duration::from_secs(60));
+        let (parts, _) = Request::new(()).into_parts();
+        let first = util::token(&serde_json::json!({ "iss": "first" }));
+        let second = util::token(&serde_json::json!({ "iss": "second" }));
+
+        for _ in 0..2 {
+            let outcome = gate.check(&GateContext::new(&parts, Some(&first))).await;
+            assert!(outcome.is_ok());
+        }
+        let outcome = gate.check(&GateContext::new(&parts, Some(&first))).await;
+        assert!(matches!(
+            outcome,
+            Err(Denied::RateLimited {
+                retry_after: Some(_)
+            })
+        ));
+
+        let outcome = gate.check(&GateContext::new(&parts, Some(&second))).await;
+        assert!(outcome.is_ok());
+
+        let anonymous = util::token(&serde_json::json!({ "sub": "user" }));
+        let outcome = gate
+            .check(&GateContext::new(&parts, Some(&anonymous)))
+            .await;
+        assert!(matches!(outcome, Err(Denied::Claim(claim)) if claim == "iss"));
+    }
+
+    #[tokio::test]
+    async fn concurrency() {
+        let gate = RateLimit::concurrency(["sub"], 1);
+        let (parts, _) = Request::new(()).into_parts();
+        let token = util::token(&serde_json::json!({ "sub": "user" }));
+
+        let permit = gate
+            .check(&GateContext::new(&parts, Some(&token)))
+            .await
+            .expect("First request is within quota");
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(
+            outcome,
+            Err(Denied::RateLimited { retry_after: None })
+        ));
+
+        drop(permit);
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+    }
+}
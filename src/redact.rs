@@ -0,0 +1,184 @@
+use crate::{fingerprint::hex, DecodeFailure, Decoder};
+use core::future::Future;
+use core::task::{Context, Poll};
+use futures::ready;
+use pin_project::pin_project;
+use ring::hmac;
+use serde_json::{Map, Value};
+use std::pin::Pin;
+
+/// What happens to claims listed in [`Redaction::apply`]
+#[derive(Debug, Clone)]
+pub enum Redaction {
+    /// Claim is removed
+    Strip,
+    /// Claim is replaced with hex-encoded HMAC-SHA256 of its JSON representation,
+    /// so it can still be used for correlation, but not recovered by hashing guesses
+    /// without the key
+    Hash(RedactionKey),
+}
+
+/// Secret key of [`Redaction::Hash`].
+///
+/// Same key yields same hashes, so it has to be shared by every instance whose logs are
+/// correlated, and kept out of them.
+#[derive(Debug, Clone)]
+pub struct RedactionKey(hmac::Key);
+
+impl RedactionKey {
+    pub fn new(secret: &[u8]) -> Self {
+        Self(hmac::Key::new(hmac::HMAC_SHA256, secret))
+    }
+
+    /// Hex-encoded HMAC-SHA256 of `value`
+    pub fn hash(&self, value: &str) -> String {
+        hex(hmac::sign(&self.0, value.as_bytes()).as_ref())
+    }
+}
+
+impl Redaction {
+    /// Redacts `claims` of untyped claim in place
+    pub fn apply(&self, claim: &mut Map<String, Value>, claims: &[&str]) {
+        for name in claims {
+            match self {
+                Self::Strip => {
+                    claim.remove(*name);
+                }
+                Self::Hash(key) => {
+                    if let Some(value) = claim.get_mut(*name) {
+                        *value = Value::String(key.hash(&value.to_string()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Wraps any [`Decoder`] running redaction step on decoded claim before
+/// [`Middleware`][crate::Middleware] sets it on request extensions, so that
+/// sensitive claims (email, phone, ..) can't leak through downstream logging.
+///
+/// ```rust
+/// # fn example(key: jsonwebtoken::DecodingKey, validation: jsonwebtoken::Validation, secret: &[u8]) {
+/// use serde_json::{Map, Value};
+/// use tower_jwt::{InPlace, Redact, Redaction, RedactionKey};
+///
+/// let hash = Redaction::Hash(RedactionKey::new(secret));
+/// let decoder = Redact::new(
+///     InPlace::<Map<String, Value>>::new(key, validation),
+///     move |claim: &mut Map<String, Value>| {
+///         Redaction::Strip.apply(claim, &["phone_number"]);
+///         hash.apply(claim, &["email"]);
+///     },
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Redact<D, F> {
+    decoder: D,
+    redact: F,
+}
+
+impl<D, F> Redact<D, F> {
+    pub fn new(decoder: D, redact: F) -> Self {
+        Self { decoder, redact }
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+impl<D, F> Decoder for Redact<D, F>
+where
+    D: Decoder,
+    F: Fn(&mut D::Claim) + Clone,
+{
+    type Error = D::Error;
+    type Claim = D::Claim;
+    type Future = RedactFuture<D::Future, F>;
+
+    fn decode(&self, token: &str) -> Self::Future {
+        RedactFuture {
+            inner: self.decoder.decode(token),
+            redact: self.redact.clone(),
+        }
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        D::failure(err)
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        D::error(err)
+    }
+}
+
+/// Future of [`Redact`] decoder
+#[pin_project]
+pub struct RedactFuture<Fut, F> {
+    #[pin]
+    inner: Fut,
+    redact: F,
+}
+
+impl<Fut, F, C, E> Future for RedactFuture<Fut, F>
+where
+    Fut: Future<Output = Result<C, E>>,
+    F: Fn(&mut C),
+{
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut claim = ready!(this.inner.poll(cx))?;
+        (this.redact)(&mut claim);
+        Poll::Ready(Ok(claim))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Redact, Redaction, RedactionKey};
+    use crate::{util, Decoder, InPlace};
+    use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+    use serde_json::{json, Map, Value};
+
+    #[tokio::test]
+    async fn redact_claims() {
+        let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes()).expect("Valid key");
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.required_spec_claims.clear();
+        validation.validate_exp = false;
+        let secret = RedactionKey::new(b"redaction-secret");
+        let hash = Redaction::Hash(secret.clone());
+        let decoder = Redact::new(
+            InPlace::<Map<String, Value>>::new(key, validation),
+            move |claim: &mut Map<String, Value>| {
+                Redaction::Strip.apply(claim, &["phone_number"]);
+                hash.apply(claim, &["email", "missing"]);
+            },
+        );
+
+        let token = util::token(&json!({
+            "sub": "user",
+            "email": "user@example.com",
+            "phone_number": "+100000000",
+        }));
+        let claim = decoder
+            .decode(&token)
+            .await
+            .expect("Failed to decode valid token");
+        assert_eq!(
+            Value::Object(claim),
+            json!({
+                "sub": "user",
+                "email": secret.hash("\"user@example.com\""),
+            })
+        );
+        assert_ne!(
+            secret.hash("\"user@example.com\""),
+            RedactionKey::new(b"other-secret").hash("\"user@example.com\"")
+        );
+    }
+}
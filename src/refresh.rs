@@ -0,0 +1,254 @@
+use crate::{BoxError, BoxFuture, DecodeFailure, Decoder};
+use core::future::Future;
+use core::task::{Context, Poll};
+use futures::ready;
+use pin_project::pin_project;
+use std::{
+    error::Error as StdError,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// [`Decoder`] backed by remote key source, whose keys can be fetched ahead of schedule,
+/// see [`RefreshOnMismatch`]
+pub trait RefreshKeys {
+    /// Fetches keys again as `token` failed to verify with current ones, resolving once they
+    /// arrived. `None` when that wouldn't help, e.g. key named by token's `kid` is there
+    /// already. Current keys keep verifying other tokens until new ones arrive.
+    fn refresh_keys(&self, token: &str) -> Option<BoxFuture<(), BoxError>>;
+}
+
+/// Wraps [`Decoder`] of remote keys, refreshing them and decoding once again when token
+/// signature doesn't match, as keys may simply have been rotated since last fetched.
+///
+/// Forced refreshes are rate limited, at most one per 30 seconds by default, so that forged
+/// tokens can't hammer the key source. Tokens failing while refresh isn't allowed, or
+/// couldn't help (see [`RefreshKeys::refresh_keys`]), are rejected right away.
+///
+/// ```rust
+/// # async fn fetch(url: &str) -> Result<jsonwebtoken::jwk::JwkSet, tower_jwt::BoxError> { todo!() }
+/// use std::time::Duration;
+/// use tower_jwt::{GoogleIap, RefreshOnMismatch, IAP_KEYS_URL};
+///
+/// let iap = GoogleIap::new("/projects/1/apps/example", || fetch(IAP_KEYS_URL));
+/// let decoder = RefreshOnMismatch::new(iap).at_most_every(Duration::from_secs(60));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RefreshOnMismatch<D> {
+    decoder: D,
+    limiter: Limiter,
+}
+
+impl<D> RefreshOnMismatch<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            limiter: Limiter::new(Duration::from_secs(30)),
+        }
+    }
+
+    /// Refresh keys on mismatch at most once per `every`
+    pub fn at_most_every(mut self, every: Duration) -> Self {
+        self.limiter = Limiter::new(every);
+        self
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+/// Lets an action (e.g. fetching keys) happen at most once per `every`, shared by clones
+#[derive(Debug, Clone)]
+pub(crate) struct Limiter {
+    every: Duration,
+    /// When the action last happened
+    last: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Limiter {
+    pub(crate) fn new(every: Duration) -> Self {
+        Self {
+            every,
+            last: Default::default(),
+        }
+    }
+
+    /// Whether the action is allowed now, marking it as done if so
+    pub(crate) fn acquire(&self) -> bool {
+        let mut last = self
+            .last
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match *last {
+            Some(last) if last.elapsed() < self.every => false,
+            _ => {
+                *last = Some(Instant::now());
+                true
+            }
+        }
+    }
+}
+
+impl<D> Decoder for RefreshOnMismatch<D>
+where
+    D: Decoder + RefreshKeys + Clone,
+    D::Error: StdError + 'static,
+{
+    type Error = D::Error;
+    type Claim = D::Claim;
+    type Future = RefreshOnMismatchFuture<D>;
+
+    fn decode(&self, token: &str) -> Self::Future {
+        RefreshOnMismatchFuture {
+            inner: self.decoder.decode(token),
+            retry: Some((self.clone(), token.to_owned())),
+            refresh: None,
+        }
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        D::failure(err)
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        D::error(err)
+    }
+}
+
+/// Future of [`RefreshOnMismatch`] decoder
+#[pin_project]
+pub struct RefreshOnMismatchFuture<D: Decoder> {
+    #[pin]
+    inner: D::Future,
+    /// Taken once token was decoded again
+    retry: Option<(RefreshOnMismatch<D>, String)>,
+    /// Refresh in flight, along with error it was triggered by
+    refresh: Option<(BoxFuture<(), BoxError>, D::Error)>,
+}
+
+impl<D> Future for RefreshOnMismatchFuture<D>
+where
+    D: Decoder + RefreshKeys + Clone,
+    D::Error: StdError + 'static,
+{
+    type Output = Result<D::Claim, D::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            if let Some((refresh, _)) = this.refresh.as_mut() {
+                let refreshed = ready!(refresh.as_mut().poll(cx));
+                let (_, err) = this.refresh.take().expect("Refresh in flight");
+                match (refreshed, this.retry.take()) {
+                    (Ok(()), Some((decoder, token))) => {
+                        this.inner.set(decoder.decoder.decode(&token))
+                    }
+                    _ => return Poll::Ready(Err(err)),
+                }
+            }
+            let err = match ready!(this.inner.as_mut().poll(cx)) {
+                Ok(claim) => return Poll::Ready(Ok(claim)),
+                Err(err) => err,
+            };
+            let refresh = match this.retry.as_ref() {
+                Some((decoder, token))
+                    if DecodeFailure::of(&err) == DecodeFailure::Signature
+                        && decoder.limiter.acquire() =>
+                {
+                    decoder.decoder.refresh_keys(token)
+                }
+                _ => None,
+            };
+            match refresh {
+                Some(refresh) => {
+                    tracing::debug!("RefreshOnMismatch::refreshing");
+                    *this.refresh = Some((refresh, err));
+                }
+                None => return Poll::Ready(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RefreshKeys, RefreshOnMismatch};
+    use crate::{util, BoxError, BoxFuture, Decoder};
+    use jsonwebtoken::errors::{Error, ErrorKind};
+    use std::{
+        future::{ready, Ready},
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    /// Keys gone stale until refreshed
+    #[derive(Clone, Default)]
+    struct Keys {
+        fresh: Arc<AtomicBool>,
+        /// Key named by tokens is there already, refreshing won't help
+        known: Arc<AtomicBool>,
+        refreshes: Arc<AtomicUsize>,
+    }
+
+    impl Decoder for Keys {
+        type Error = Error;
+        type Claim = util::Claim;
+        type Future = Ready<Result<util::Claim, Error>>;
+
+        fn decode(&self, token: &str) -> Self::Future {
+            match self.fresh.load(Ordering::Relaxed) {
+                true => util::in_place_decoder().decode(token),
+                false => ready(Err(Error::from(ErrorKind::InvalidSignature))),
+            }
+        }
+    }
+
+    impl RefreshKeys for Keys {
+        fn refresh_keys(&self, _: &str) -> Option<BoxFuture<(), BoxError>> {
+            if self.known.load(Ordering::Relaxed) {
+                return None;
+            }
+            self.refreshes.fetch_add(1, Ordering::Relaxed);
+            self.fresh.store(true, Ordering::Relaxed);
+            Some(Box::pin(ready(Ok(()))))
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_on_mismatch() {
+        let keys = Keys::default();
+        let decoder = RefreshOnMismatch::new(keys.clone()).at_most_every(Duration::from_secs(60));
+        let token = util::token(&util::claim(Some(100)));
+
+        assert!(decoder.decode(&token).await.is_ok());
+        assert_eq!(keys.refreshes.load(Ordering::Relaxed), 1);
+
+        // Keys rotated again, but refresh is not allowed yet
+        keys.fresh.store(false, Ordering::Relaxed);
+        let err = decoder.decode(&token).await.expect_err("Rate limited");
+        assert!(matches!(err.kind(), ErrorKind::InvalidSignature));
+        assert_eq!(keys.refreshes.load(Ordering::Relaxed), 1);
+
+        // Other failures never refresh keys
+        let expired = util::token(&util::claim(None));
+        let decoder = RefreshOnMismatch::new(keys.clone());
+        keys.fresh.store(true, Ordering::Relaxed);
+        assert!(decoder.decode(&expired).await.is_err());
+        assert_eq!(keys.refreshes.load(Ordering::Relaxed), 1);
+
+        // Neither do keys which are there already
+        keys.fresh.store(false, Ordering::Relaxed);
+        keys.known.store(true, Ordering::Relaxed);
+        let err = decoder
+            .decode(&token)
+            .await
+            .expect_err("Refresh won't help");
+        assert!(matches!(err.kind(), ErrorKind::InvalidSignature));
+        assert_eq!(keys.refreshes.load(Ordering::Relaxed), 1);
+    }
+}
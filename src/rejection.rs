@@ -0,0 +1,836 @@
+use crate::{Denied, Error};
+use core::future::Future;
+use futures::{future, ready};
+use http::{
+    header::{HeaderName, RETRY_AFTER, WWW_AUTHENTICATE},
+    request::Parts,
+    Extensions, HeaderMap, HeaderValue, Request, Response, StatusCode,
+};
+use jsonwebtoken::errors::ErrorKind;
+use pin_project::pin_project;
+use std::{
+    any::Any,
+    collections::HashMap,
+    error::Error as StdError,
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::Service;
+
+/// Response to send back for request which failed authentication or authorization.
+///
+/// Every failure of [`Middleware`][crate::Middleware] maps to a rejection through
+/// [`Error::rejection`], with defaults following
+/// [RFC 6750](https://www.rfc-editor.org/rfc/rfc6750#section-3):
+///
+/// - missing token: `401` with bare `Bearer` challenge
+/// - malformed or oversized token: `400` with `invalid_request` error
+/// - token rejected by decoder, or not matching request: `401` with `invalid_token` error
+/// - [step-up][crate::StepUp] required: `401` with `insufficient_user_authentication` error
+/// - account or tenant not allowed, or claim not accepted: `403`
+/// - [scope][crate::RequireScope] missing: `403` with `insufficient_scope` error
+/// - quota exceeded: `429` with `Retry-After` (and `RateLimit-*` for [client quotas][crate::ClientQuota])
+///
+/// Rejections carry [`FailureKind`] of the failure as extension, which ends up on rendered
+/// response.
+///
+/// Rejections can be adjusted, or built from scratch, before rendering them into response.
+///
+/// ```rust
+/// # fn example<E, D: std::error::Error + 'static>(err: tower_jwt::Error<E, D>) -> Option<http::Response<String>> {
+/// let rejection = err.rejection()?.with_param("realm", "api").with_body("Authentication required");
+/// Some(rejection.into_response())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Rejection {
+    status: StatusCode,
+    scheme: Option<String>,
+    params: Vec<(String, String)>,
+    headers: HeaderMap,
+    body: Option<String>,
+    extensions: Extensions,
+}
+
+impl Rejection {
+    /// Rejection with `status` and no challenge
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            scheme: None,
+            params: Vec::new(),
+            headers: HeaderMap::new(),
+            body: None,
+            extensions: Extensions::new(),
+        }
+    }
+
+    /// `401 Unauthorized` with `Bearer` challenge
+    pub fn unauthorized() -> Self {
+        Self::new(StatusCode::UNAUTHORIZED).with_challenge("Bearer")
+    }
+
+    /// `403 Forbidden`
+    pub fn forbidden() -> Self {
+        Self::new(StatusCode::FORBIDDEN)
+    }
+
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Authentication scheme of `WWW-Authenticate` challenge
+    pub fn with_challenge(mut self, scheme: impl Into<String>) -> Self {
+        self.scheme = Some(scheme.into());
+        self
+    }
+
+    /// Challenge parameter, replacing earlier one with the same `name`.
+    ///
+    /// Adds `Bearer` challenge if there is none yet.
+    pub fn with_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let (name, value) = (name.into(), value.into());
+        self.scheme.get_or_insert_with(|| String::from("Bearer"));
+        match self.params.iter_mut().find(|(param, _)| *param == name) {
+            Some((_, current)) => *current = value,
+            None => self.params.push((name, value)),
+        }
+        self
+    }
+
+    /// `error` challenge parameter, e.g. `invalid_token`
+    pub fn with_error(self, code: impl Into<String>) -> Self {
+        self.with_param("error", code)
+    }
+
+    /// `error_description` challenge parameter
+    pub fn with_error_description(self, description: impl Into<String>) -> Self {
+        self.with_param("error_description", description)
+    }
+
+    /// Additional response header
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Response extension
+    pub fn with_extension<T: Send + Sync + 'static>(mut self, extension: T) -> Self {
+        self.extensions.insert(extension);
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Value of challenge parameter `name`
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Rendered challenge, if any.
+    ///
+    /// Parameters which can't be sent in a header are dropped.
+    pub fn www_authenticate(&self) -> Option<HeaderValue> {
+        let mut challenge = self.scheme.clone()?;
+        let mut separator = " ";
+        for (name, value) in &self.params {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            let param = format!(r#"{}="{}""#, name, value);
+            if HeaderValue::from_str(&param).is_ok() {
+                challenge.push_str(separator);
+                challenge.push_str(&param);
+                separator = ", ";
+            }
+        }
+        HeaderValue::from_str(&challenge).ok()
+    }
+
+    pub fn into_response<B: From<String>>(self) -> Response<B> {
+        let challenge = self.www_authenticate();
+        let mut res = Response::new(B::from(self.body.unwrap_or_default()));
+        *res.status_mut() = self.status;
+        *res.headers_mut() = self.headers;
+        *res.extensions_mut() = self.extensions;
+        if let Some(challenge) = challenge {
+            res.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+        }
+        res
+    }
+}
+
+/// Whether request failed to prove identity or identity isn't allowed to make it,
+/// see [`Error::kind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FailureKind {
+    /// Token is missing, malformed or rejected, presenting another one may help, i.e. `401`
+    Authentication,
+    /// Token was accepted, but doesn't permit the request, i.e. `403`
+    Authorization,
+    /// Quota of the client was exceeded, i.e. `429`
+    RateLimited,
+}
+
+/// Why decoder rejected the token, after [`jsonwebtoken`] error found along sources of decoder
+/// error, stripped of anything derived from the token itself so it's safe to log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeFailure {
+    Malformed,
+    Expired,
+    NotYetValid,
+    Signature,
+    Algorithm,
+    Issuer,
+    Audience,
+    /// Subject or required claim
+    Claims,
+    /// Key is missing or unusable
+    Key,
+    Other,
+}
+
+impl DecodeFailure {
+    pub(crate) fn of(err: &(dyn StdError + 'static)) -> Self {
+        let mut source = Some(err);
+        while let Some(err) = source {
+            if let Some(err) = err.downcast_ref::<jsonwebtoken::errors::Error>() {
+                return Self::from(err.kind());
+            }
+            source = err.source();
+        }
+        Self::Other
+    }
+}
+
+impl From<&ErrorKind> for DecodeFailure {
+    fn from(kind: &ErrorKind) -> Self {
+        match kind {
+            ErrorKind::InvalidToken
+            | ErrorKind::Base64(_)
+            | ErrorKind::Json(_)
+            | ErrorKind::Utf8(_) => Self::Malformed,
+            ErrorKind::ExpiredSignature => Self::Expired,
+            ErrorKind::ImmatureSignature => Self::NotYetValid,
+            ErrorKind::InvalidSignature => Self::Signature,
+            ErrorKind::InvalidAlgorithm
+            | ErrorKind::InvalidAlgorithmName
+            | ErrorKind::MissingAlgorithm => Self::Algorithm,
+            ErrorKind::InvalidIssuer => Self::Issuer,
+            ErrorKind::InvalidAudience => Self::Audience,
+            ErrorKind::InvalidSubject | ErrorKind::MissingRequiredClaim(_) => Self::Claims,
+            ErrorKind::InvalidEcdsaKey
+            | ErrorKind::InvalidRsaKey(_)
+            | ErrorKind::InvalidKeyFormat => Self::Key,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for DecodeFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failure = match self {
+            Self::Malformed => "malformed",
+            Self::Expired => "expired",
+            Self::NotYetValid => "not yet valid",
+            Self::Signature => "signature",
+            Self::Algorithm => "algorithm",
+            Self::Issuer => "issuer",
+            Self::Audience => "audience",
+            Self::Claims => "claims",
+            Self::Key => "key",
+            Self::Other => "other",
+        };
+        f.write_str(failure)
+    }
+}
+
+impl Denied {
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            Denied::Audience
+            | Denied::Fingerprint
+            | Denied::Invalidated
+            | Denied::UnknownSubject
+            | Denied::IdToken(_)
+            | Denied::Signature
+            | Denied::TokenTooLarge
+            | Denied::StepUp(_) => FailureKind::Authentication,
+            Denied::Account(_)
+            | Denied::Tenant
+            | Denied::InsufficientScope(_)
+            | Denied::Claim(_)
+            | Denied::Other(_) => FailureKind::Authorization,
+            Denied::RateLimited { .. } | Denied::QuotaExceeded(_) => FailureKind::RateLimited,
+        }
+    }
+
+    /// Default [`Rejection`] for the denial
+    pub fn rejection(&self) -> Rejection {
+        match self {
+            Denied::Audience
+            | Denied::Fingerprint
+            | Denied::Invalidated
+            | Denied::UnknownSubject
+            | Denied::IdToken(_) => Rejection::unauthorized()
+                .with_error("invalid_token")
+                .with_error_description(self.to_string()),
+            Denied::Signature => Rejection::unauthorized()
+                .with_error("invalid_request")
+                .with_error_description(self.to_string()),
+            Denied::TokenTooLarge => Rejection::new(StatusCode::BAD_REQUEST)
+                .with_error("invalid_request")
+                .with_error_description(self.to_string()),
+            Denied::StepUp(challenge) => challenge.rejection(),
+            Denied::RateLimited { retry_after } => {
+                let rejection = Rejection::new(StatusCode::TOO_MANY_REQUESTS);
+                match retry_after {
+                    Some(retry_after) => {
+                        let seconds =
+                            retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                        rejection.with_header(RETRY_AFTER, HeaderValue::from(seconds))
+                    }
+                    None => rejection,
+                }
+            }
+            Denied::QuotaExceeded(usage) => {
+                let mut rejection = Rejection::new(StatusCode::TOO_MANY_REQUESTS).with_header(
+                    RETRY_AFTER,
+                    HeaderValue::from(usage.reset_after().as_secs()),
+                );
+                for (name, value) in &usage.headers() {
+                    rejection = rejection.with_header(name.clone(), value.clone());
+                }
+                rejection
+            }
+            Denied::InsufficientScope(scopes) => Rejection::forbidden()
+                .with_error("insufficient_scope")
+                .with_param("scope", scopes.join(" ")),
+            Denied::Claim(_) => Rejection::forbidden().with_error_description(self.to_string()),
+            Denied::Account(_) | Denied::Tenant | Denied::Other(_) => Rejection::forbidden(),
+        }
+    }
+}
+
+/// Default [`Rejection`] for decoder error, described after its [`DecodeFailure`]
+fn decoder_rejection(err: &(dyn StdError + 'static)) -> Rejection {
+    let invalid_token = Rejection::unauthorized().with_error("invalid_token");
+    let description = match DecodeFailure::of(err) {
+        DecodeFailure::Malformed => {
+            return Rejection::new(StatusCode::BAD_REQUEST)
+                .with_error("invalid_request")
+                .with_error_description("The access token is malformed");
+        }
+        DecodeFailure::Expired => "The access token expired",
+        DecodeFailure::NotYetValid => "The access token is not valid yet",
+        DecodeFailure::Signature => "The access token signature is invalid",
+        DecodeFailure::Algorithm => "The access token algorithm is not accepted",
+        DecodeFailure::Issuer => "The access token issuer is not trusted",
+        DecodeFailure::Audience => "The access token is not intended for this resource",
+        DecodeFailure::Claims => "The access token claims are not accepted",
+        // Key and configuration problems are none of the client's business
+        DecodeFailure::Key | DecodeFailure::Other => return invalid_token,
+    };
+    invalid_token.with_error_description(description)
+}
+
+impl<E, D> Error<E, D> {
+    /// Whether authentication or authorization failed, `None` for errors of inner service
+    pub fn kind(&self) -> Option<FailureKind> {
+        match self {
+            Error::MissingAuthorizationHeader | Error::Decoder(_) => {
+                Some(FailureKind::Authentication)
+            }
+            Error::Denied(denied) => Some(denied.kind()),
+            Error::Inner(_) => None,
+        }
+    }
+}
+
+impl<E, D: StdError + 'static> Error<E, D> {
+    /// Default [`Rejection`] for the error, `None` for errors of inner service
+    pub fn rejection(&self) -> Option<Rejection> {
+        let rejection = match self {
+            Error::MissingAuthorizationHeader => Rejection::unauthorized(),
+            Error::Decoder(err) => decoder_rejection(err),
+            Error::Denied(denied) => denied.rejection(),
+            Error::Inner(_) => return None,
+        };
+        Some(rejection.with_extension(self.kind()?))
+    }
+
+    /// Why decoder rejected the token, `None` for other errors
+    pub fn decode_failure(&self) -> Option<DecodeFailure> {
+        match self {
+            Error::Decoder(err) => Some(DecodeFailure::of(err)),
+            _ => None,
+        }
+    }
+}
+
+/// Response future of [`RejectionHandler`]
+pub type RenderFuture<B> = Pin<Box<dyn Future<Output = Response<B>> + Send + 'static>>;
+
+/// Renders authentication and authorization failures into responses, see [`Rejecting`].
+///
+/// Handler is given the failure along with its default [rejection][Rejection], so it can merely
+/// adjust the rejection or build response from scratch, e.g. in custom error envelope. It also
+/// sees request parts, so rendering can be chosen per request, e.g. HTML for browsers and JSON
+/// for API clients. Any `Fn(&Parts, &Error<E, D>, Rejection) -> Future<Output = Response<B>>`
+/// is a handler. Errors of inner service never reach handlers.
+pub trait RejectionHandler<E, D, B>: Send + Sync + 'static {
+    fn render(&self, parts: &Parts, err: &Error<E, D>, rejection: Rejection) -> RenderFuture<B>;
+}
+
+impl<F, Fut, E, D, B> RejectionHandler<E, D, B> for F
+where
+    F: Fn(&Parts, &Error<E, D>, Rejection) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response<B>> + Send + 'static,
+{
+    fn render(&self, parts: &Parts, err: &Error<E, D>, rejection: Rejection) -> RenderFuture<B> {
+        Box::pin(self(parts, err, rejection))
+    }
+}
+
+/// [`RejectionHandler`] rendering default [rejections][Rejection] as they are,
+/// see [`Layer::respond`][crate::Layer::respond]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultRejection;
+
+impl<E, D, B> RejectionHandler<E, D, B> for DefaultRejection
+where
+    B: From<String> + Send + 'static,
+{
+    fn render(&self, _: &Parts, _: &Error<E, D>, rejection: Rejection) -> RenderFuture<B> {
+        Box::pin(future::ready(rejection.into_response()))
+    }
+}
+
+/// [`RejectionHandler`] rendering default [rejections][Rejection], with status and `error` code
+/// of decoder failures replaced as configured per [`DecodeFailure`], for conventions other than
+/// RFC 6750 (e.g. `419` for expired tokens).
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use http::StatusCode;
+/// use tower_jwt::{DecodeFailure, Layer, StatusMap};
+///
+/// let statuses = StatusMap::new()
+///     .map(DecodeFailure::Expired, StatusCode::from_u16(419).unwrap(), "token_expired")
+///     .map(DecodeFailure::Audience, StatusCode::FORBIDDEN, "wrong_audience");
+/// let layer = Layer::new(decoder).reject_with(statuses);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StatusMap(HashMap<DecodeFailure, (StatusCode, String)>);
+
+impl StatusMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Respond to tokens rejected for `failure` with `status` and `error` challenge parameter
+    /// set to `code`
+    pub fn map(
+        mut self,
+        failure: DecodeFailure,
+        status: StatusCode,
+        code: impl Into<String>,
+    ) -> Self {
+        self.0.insert(failure, (status, code.into()));
+        self
+    }
+}
+
+impl<E, D, B> RejectionHandler<E, D, B> for StatusMap
+where
+    D: StdError + 'static,
+    B: From<String> + Send + 'static,
+{
+    fn render(&self, _: &Parts, err: &Error<E, D>, rejection: Rejection) -> RenderFuture<B> {
+        let mapped = err
+            .decode_failure()
+            .and_then(|failure| self.0.get(&failure));
+        let rejection = match mapped {
+            Some((status, code)) => rejection.with_status(*status).with_error(code.as_str()),
+            None => rejection,
+        };
+        Box::pin(future::ready(rejection.into_response()))
+    }
+}
+
+/// Turns authentication and authorization failures of wrapped [`Middleware`][crate::Middleware]
+/// (or of middleware produced by wrapped [`Layer`][crate::Layer]) into responses rendered by
+/// [`RejectionHandler`], so the only errors left are those of inner service.
+///
+/// Unlike [`Error`][enum@crate::Error], which most servers treat as connection-level failure, missing or invalid
+/// tokens end up as proper `401` or `403` responses. Method, URI, version and headers of every
+/// request are kept aside for the handler, extensions are not.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) where D::Error: std::error::Error + 'static {
+/// use http::{header::ACCEPT, request::Parts};
+/// use std::convert::Infallible;
+/// use tower_jwt::{Error, Layer, Rejection};
+///
+/// let layer = Layer::new(decoder).reject_with(
+///     |parts: &Parts, err: &Error<Infallible, D::Error>, rejection: Rejection| {
+///         let json = parts
+///             .headers
+///             .get(ACCEPT)
+///             .is_some_and(|accept| accept.as_bytes().starts_with(b"application/json"));
+///         let body = match json {
+///             true => format!(r#"{{"error":"{}","path":"{}"}}"#, err, parts.uri.path()),
+///             false => String::from("Please sign in"),
+///         };
+///         async move { rejection.with_body(body).into_response::<String>() }
+///     },
+/// );
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Rejecting<T, H = DefaultRejection> {
+    inner: T,
+    handler: Arc<H>,
+    // Failure of inner readiness, answered once request comes in
+    failed: Option<Box<dyn Any + Send>>,
+}
+
+impl<T: Clone, H> Clone for Rejecting<T, H> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            handler: self.handler.clone(),
+            failed: None,
+        }
+    }
+}
+
+impl<T, H> Rejecting<T, H> {
+    pub(crate) fn new(inner: T, handler: H) -> Self {
+        Self {
+            inner,
+            handler: Arc::new(handler),
+            failed: None,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<L, S, H> tower::Layer<S> for Rejecting<L, H>
+where
+    L: tower::Layer<S>,
+{
+    type Service = Rejecting<L::Service, H>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Rejecting {
+            inner: self.inner.layer(inner),
+            handler: self.handler.clone(),
+            failed: None,
+        }
+    }
+}
+
+impl<T, H, B, RB, E, D> Service<Request<B>> for Rejecting<T, H>
+where
+    T: Service<Request<B>, Response = Response<RB>, Error = Error<E, D>>,
+    H: RejectionHandler<E, D, RB>,
+    E: Send + 'static,
+    D: StdError + Send + 'static,
+{
+    type Response = Response<RB>;
+    type Error = E;
+    type Future = RejectingFuture<T::Future, H, RB>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.failed.is_some() {
+            return Poll::Ready(Ok(()));
+        }
+        match ready!(self.inner.poll_ready(cx)) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(Error::Inner(err)) => Poll::Ready(Err(err)),
+            Err(err) => {
+                self.failed = Some(Box::new(err));
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let parts = parts(&req);
+        let failed = self
+            .failed
+            .take()
+            .and_then(|err| err.downcast::<Error<E, D>>().ok());
+        let state = match failed {
+            // only failures other than those of inner service are kept aside
+            Some(err) => {
+                let rejection = err.rejection().unwrap_or_else(Rejection::unauthorized);
+                RejectingState::Rendering(self.handler.render(&parts, &err, rejection))
+            }
+            None => RejectingState::Calling(self.inner.call(req)),
+        };
+        RejectingFuture {
+            state,
+            parts,
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+/// Method, URI, version and headers of `req`
+fn parts<B>(req: &Request<B>) -> Parts {
+    let (mut parts, ()) = Request::new(()).into_parts();
+    parts.method = req.method().clone();
+    parts.uri = req.uri().clone();
+    parts.version = req.version();
+    parts.headers = req.headers().clone();
+    parts
+}
+
+#[pin_project]
+pub struct RejectingFuture<F, H, B> {
+    #[pin]
+    state: RejectingState<F, B>,
+    parts: Parts,
+    handler: Arc<H>,
+}
+
+#[pin_project(project = RejectingStateProj)]
+enum RejectingState<F, B> {
+    Calling(#[pin] F),
+    Rendering(RenderFuture<B>),
+}
+
+impl<F, H, B, E, D> Future for RejectingFuture<F, H, B>
+where
+    F: Future<Output = Result<Response<B>, Error<E, D>>>,
+    H: RejectionHandler<E, D, B>,
+    D: StdError + 'static,
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            let rendering = match this.state.as_mut().project() {
+                RejectingStateProj::Calling(future) => match ready!(future.poll(cx)) {
+                    Ok(res) => return Poll::Ready(Ok(res)),
+                    Err(Error::Inner(err)) => return Poll::Ready(Err(err)),
+                    Err(err) => {
+                        let rejection = err.rejection().unwrap_or_else(Rejection::unauthorized);
+                        this.handler.render(this.parts, &err, rejection)
+                    }
+                },
+                RejectingStateProj::Rendering(future) => {
+                    return future.as_mut().poll(cx).map(Ok);
+                }
+            };
+            this.state.set(RejectingState::Rendering(rendering));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        util, DecodeFailure, Denied, Error, FailureKind, IapError, Middleware, Rejection,
+        StatusMap, StepUpChallenge,
+    };
+    use http::{header::RETRY_AFTER, request::Parts, Request, Response, StatusCode};
+    use jsonwebtoken::errors::ErrorKind;
+    use std::{convert::Infallible, time::Duration};
+    use tower::{service_fn, ServiceExt};
+
+    type Failure = Error<(), jsonwebtoken::errors::Error>;
+
+    #[test]
+    fn default_rejections() {
+        let missing = Failure::MissingAuthorizationHeader
+            .rejection()
+            .expect("Authentication failure");
+        let res: Response<String> = missing.with_body("Sign in first").into_response();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.headers()["www-authenticate"], "Bearer");
+        assert_eq!(res.body(), "Sign in first");
+
+        let expired = Failure::Decoder(ErrorKind::ExpiredSignature.into())
+            .rejection()
+            .expect("Authentication failure");
+        assert_eq!(
+            expired.www_authenticate().expect("Valid challenge"),
+            r#"Bearer error="invalid_token", error_description="The access token expired""#
+        );
+        assert_eq!(
+            expired.extensions().get::<FailureKind>(),
+            Some(&FailureKind::Authentication)
+        );
+
+        let malformed = Error::<(), _>::Decoder(IapError::Decode(ErrorKind::InvalidToken.into()))
+            .rejection()
+            .expect("Authentication failure");
+        assert_eq!(malformed.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(malformed.param("error"), Some("invalid_request"));
+
+        let key = Failure::Decoder(ErrorKind::InvalidKeyFormat.into())
+            .rejection()
+            .expect("Authentication failure");
+        assert_eq!(
+            key.www_authenticate().expect("Valid challenge"),
+            r#"Bearer error="invalid_token""#
+        );
+
+        let step_up = Denied::StepUp(StepUpChallenge::new().with_acr_values(["loa2"])).rejection();
+        assert_eq!(step_up.param("acr_values"), Some("loa2"));
+
+        let rate_limited = Denied::RateLimited {
+            retry_after: Some(Duration::from_millis(1500)),
+        }
+        .rejection();
+        assert_eq!(rate_limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(rate_limited.headers()[RETRY_AFTER], "2");
+        assert!(rate_limited.www_authenticate().is_none());
+
+        assert!(Failure::Inner(()).rejection().is_none());
+        assert_eq!(
+            Error::<(), Infallible>::Denied(Denied::Tenant).kind(),
+            Some(FailureKind::Authorization)
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_with_handler() {
+        let svc =
+            service_fn(|_: Request<()>| async move { Ok::<_, ()>(Response::new(String::new())) });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).reject_with(
+            |parts: &Parts, _: &Error<(), jsonwebtoken::errors::Error>, rejection: Rejection| {
+                let body = format!("{} {}", parts.uri.path(), rejection.status().as_u16());
+                async move { rejection.with_body(body).into_response() }
+            },
+        );
+
+        let req = Request::builder()
+            .uri("/profile")
+            .body(())
+            .expect("Valid request");
+        let res = middleware.oneshot(req).await.expect("Rendered rejection");
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.body(), "/profile 401");
+    }
+
+    #[tokio::test]
+    async fn respond() {
+        let svc = service_fn(|req: Request<()>| async move {
+            match req.uri().path() {
+                "/broken" => Err("Inner failure"),
+                _ => Ok(Response::new(String::from("Hello"))),
+            }
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).respond();
+
+        let req = Request::builder().body(()).expect("Valid request");
+        let res = middleware.clone().oneshot(req).await.expect("Rejection");
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.headers()["www-authenticate"], "Bearer");
+
+        let token = util::token(&util::claim(Some(100)));
+        let req = Request::builder()
+            .uri("/broken")
+            .header("authorization", format!("Bearer {}", token))
+            .body(())
+            .expect("Valid request");
+        let err = middleware.oneshot(req).await.expect_err("Inner error");
+        assert_eq!(err, "Inner failure");
+    }
+
+    #[tokio::test]
+    async fn reject_with_error() {
+        let svc = service_fn(|_: Request<()>| async move {
+            Ok::<_, Infallible>(Response::new(String::new()))
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).reject_with(
+            |parts: &Parts, err: &Error<Infallible, jsonwebtoken::errors::Error>, _: Rejection| {
+                let body = format!(r#"{{"error":"{}","path":"{}"}}"#, err, parts.uri.path());
+                let res = Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .body(body)
+                    .expect("Valid response");
+                async move { res }
+            },
+        );
+
+        let req = Request::builder()
+            .uri("/profile")
+            .body(())
+            .expect("Valid request");
+        let res = middleware.oneshot(req).await.expect("Custom response");
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            res.body(),
+            r#"{"error":"Authorization header must be set","path":"/profile"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn status_map() {
+        let svc = service_fn(|_: Request<()>| async move {
+            Ok::<_, Infallible>(Response::new(String::new()))
+        });
+        let expired = StatusCode::from_u16(419).expect("Valid status");
+        let statuses = StatusMap::new().map(DecodeFailure::Expired, expired, "token_expired");
+        let middleware = Middleware::new(util::in_place_decoder(), svc).reject_with(statuses);
+        let req = |token: &str| {
+            Request::builder()
+                .header("authorization", format!("Bearer {}", token))
+                .body(())
+                .expect("Valid request")
+        };
+
+        let token = util::token(&util::claim(None));
+        let res = middleware
+            .clone()
+            .oneshot(req(&token))
+            .await
+            .expect("Mapped");
+        assert_eq!(res.status(), expired);
+        let challenge = res.headers()["www-authenticate"].to_str().expect("Valid");
+        assert!(challenge.contains(r#"error="token_expired""#));
+
+        let res = middleware
+            .oneshot(req("not.a.token"))
+            .await
+            .expect("Default");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let failure = Failure::Decoder(ErrorKind::InvalidAudience.into()).decode_failure();
+        assert_eq!(failure, Some(DecodeFailure::Audience));
+    }
+}
@@ -0,0 +1,346 @@
+use crate::{fingerprint::sha256_hex, DecodeFailure, Decoder};
+use core::future::Future;
+use core::task::{Context, Poll};
+use futures::ready;
+use pin_project::pin_project;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    marker::PhantomData,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+/// Environment variable switching [`Replay::from_env`] into recording mode
+pub const RECORD_ENV: &str = "TOWER_JWT_RECORD";
+
+/// Environment variable switching [`Replay::from_env`] into replay mode
+pub const REPLAY_ENV: &str = "TOWER_JWT_REPLAY";
+
+/// Single decode outcome, stored as one JSON line
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// Hex-encoded SHA-256 of the token, tokens themselves never reach the tape
+    token: String,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Claim(Value),
+    Error(String),
+}
+
+enum Tape {
+    Record(Recorder),
+    Replay(HashMap<String, Outcome>),
+    Live,
+}
+
+/// Entries recorded so far, written out once the last [`Replay`] clone is dropped so that
+/// decoding never blocks on file I/O
+struct Recorder {
+    file: File,
+    lines: Mutex<Vec<u8>>,
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let lines = self.lines.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Err(err) = self.file.write_all(lines) {
+            tracing::warn!("Replay::failed_to_write_tape {}", err);
+        }
+    }
+}
+
+/// Wraps any [`Decoder`] recording its outcomes to a file, or replaying them deterministically,
+/// so integration suites don't depend on live IdPs or key material. Available with `test-util`
+/// feature.
+///
+/// In recording mode every decoded token is passed to wrapped decoder and the outcome (claim or
+/// error message) is recorded against the token's SHA-256, tape is written once the last clone
+/// of the decoder is dropped. In replay mode wrapped decoder is never consulted, recorded
+/// outcomes are returned regardless of token expiry, and unknown tokens are rejected.
+///
+/// ```rust
+/// # use serde::{Deserialize, Serialize};
+/// # #[derive(Deserialize, Serialize)] struct Claim { jti: String }
+/// # fn example(key: jsonwebtoken::DecodingKey, validation: jsonwebtoken::Validation) -> std::io::Result<()> {
+/// use tower_jwt::{InPlace, Replay};
+///
+/// // records when `TOWER_JWT_RECORD` is set, replays when `TOWER_JWT_REPLAY` is, decodes otherwise
+/// let decoder = Replay::from_env("tests/tapes/auth.jsonl", || {
+///     InPlace::<Claim>::new(key, validation)
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Replay<D> {
+    decoder: Option<D>,
+    tape: Arc<Tape>,
+}
+
+impl<D> Replay<D> {
+    /// Records outcomes of `decoder`, appending them to file at `path`
+    pub fn record(decoder: D, path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            decoder: Some(decoder),
+            tape: Arc::new(Tape::Record(Recorder {
+                file,
+                lines: Mutex::default(),
+            })),
+        })
+    }
+
+    /// Replays outcomes recorded in file at `path`, later entries take precedence
+    pub fn playback(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut outcomes = HashMap::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: Entry = serde_json::from_str(&line)?;
+            outcomes.insert(entry.token, entry.outcome);
+        }
+        Ok(Self {
+            decoder: None,
+            tape: Arc::new(Tape::Replay(outcomes)),
+        })
+    }
+
+    /// Passes every token to `decoder`, neither recording nor replaying
+    pub fn live(decoder: D) -> Self {
+        Self {
+            decoder: Some(decoder),
+            tape: Arc::new(Tape::Live),
+        }
+    }
+
+    /// Records if [`RECORD_ENV`] is set, replays if [`REPLAY_ENV`] is, and decodes tokens
+    /// with `decoder` otherwise.
+    ///
+    /// `decoder` is not constructed when replaying.
+    pub fn from_env<F>(path: impl AsRef<Path>, decoder: F) -> io::Result<Self>
+    where
+        F: FnOnce() -> D,
+    {
+        if std::env::var_os(RECORD_ENV).is_some() {
+            Self::record(decoder(), path)
+        } else if std::env::var_os(REPLAY_ENV).is_some() {
+            Self::playback(path)
+        } else {
+            Ok(Self::live(decoder()))
+        }
+    }
+}
+
+impl<D: Clone> Clone for Replay<D> {
+    fn clone(&self) -> Self {
+        Self {
+            decoder: self.decoder.clone(),
+            tape: self.tape.clone(),
+        }
+    }
+}
+
+impl<D> fmt::Debug for Replay<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.tape.as_ref() {
+            Tape::Record(_) => "record",
+            Tape::Replay(_) => "replay",
+            Tape::Live => "live",
+        };
+        f.debug_struct("Replay")
+            .field("mode", &mode)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ReplayError<E> {
+    #[error(transparent)]
+    Decoder(E),
+
+    /// Error recorded by wrapped decoder, only its message survives the tape
+    #[error("{0}")]
+    Recorded(String),
+
+    #[error("Token was not recorded")]
+    Unrecorded,
+
+    #[error("Failed to (de)serialize claim")]
+    Claim(#[source] serde_json::Error),
+}
+
+impl<D> Decoder for Replay<D>
+where
+    D: Decoder,
+    D::Claim: Serialize + DeserializeOwned,
+    D::Error: fmt::Display,
+{
+    type Error = ReplayError<D::Error>;
+    type Claim = D::Claim;
+    type Future = ReplayFuture<D>;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        let state = match (self.tape.as_ref(), &self.decoder) {
+            (Tape::Record(_), Some(decoder)) => State::Recording {
+                decoding: decoder.decode(token),
+                token: sha256_hex(token.as_bytes()),
+                tape: self.tape.clone(),
+            },
+            (Tape::Live, Some(decoder)) => State::Live(decoder.decode(token)),
+            (Tape::Replay(outcomes), _) => {
+                tracing::trace!("Replay::replayed");
+                State::Replayed(Some(match outcomes.get(&sha256_hex(token.as_bytes())) {
+                    Some(Outcome::Claim(claim)) => {
+                        serde_json::from_value(claim.clone()).map_err(ReplayError::Claim)
+                    }
+                    Some(Outcome::Error(message)) => Err(ReplayError::Recorded(message.clone())),
+                    None => Err(ReplayError::Unrecorded),
+                }))
+            }
+            // only way to construct recording or live tape is via Replay::record
+            // or Replay::live, which take decoder
+            (Tape::Record(_) | Tape::Live, None) => unreachable!("Decoding without decoder"),
+        };
+        ReplayFuture {
+            state,
+            _decoder: PhantomData,
+        }
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        match err {
+            ReplayError::Decoder(err) => D::failure(err),
+            _ => None,
+        }
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        match err {
+            ReplayError::Decoder(err) => D::error(err),
+            _ => None,
+        }
+    }
+}
+
+type Decoded<D> = Result<<D as Decoder>::Claim, ReplayError<<D as Decoder>::Error>>;
+
+/// Future of [`Replay`] decoder
+#[pin_project]
+pub struct ReplayFuture<D: Decoder> {
+    #[pin]
+    state: State<D::Future, Decoded<D>>,
+    _decoder: PhantomData<fn() -> D>,
+}
+
+#[pin_project(project = StateProject)]
+enum State<F, R> {
+    Recording {
+        #[pin]
+        decoding: F,
+        token: String,
+        tape: Arc<Tape>,
+    },
+    Live(#[pin] F),
+    Replayed(Option<R>),
+}
+
+impl<D> Future for ReplayFuture<D>
+where
+    D: Decoder,
+    D::Claim: Serialize,
+    D::Error: fmt::Display,
+{
+    type Output = Decoded<D>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().state.project() {
+            StateProject::Recording {
+                decoding,
+                token,
+                tape,
+            } => {
+                let outcome = ready!(decoding.poll(cx));
+                let recorded = match &outcome {
+                    Ok(claim) => serde_json::to_value(claim)
+                        .map(Outcome::Claim)
+                        .map_err(ReplayError::Claim)?,
+                    Err(err) => Outcome::Error(err.to_string()),
+                };
+                if let Tape::Record(recorder) = tape.as_ref() {
+                    let entry = Entry {
+                        token: std::mem::take(token),
+                        outcome: recorded,
+                    };
+                    let mut lines = recorder.lines.lock().unwrap_or_else(|e| e.into_inner());
+                    serde_json::to_writer(&mut *lines, &entry).map_err(ReplayError::Claim)?;
+                    lines.push(b'\n');
+                }
+                tracing::trace!("Replay::recorded");
+                Poll::Ready(outcome.map_err(ReplayError::Decoder))
+            }
+            StateProject::Live(decoding) => decoding.poll(cx).map_err(ReplayError::Decoder),
+            StateProject::Replayed(outcome) => Poll::Ready(
+                outcome
+                    .take()
+                    .expect("ReplayFuture polled after completion"),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Replay, ReplayError};
+    use crate::{util, Decoder, InPlace};
+
+    #[tokio::test]
+    async fn record_replay() {
+        let path =
+            std::env::temp_dir().join(format!("tower-jwt-replay-{}.jsonl", std::process::id()));
+        let valid = util::claim(Some(100));
+        let expired = util::claim(None);
+        let (valid_token, expired_token) = (util::token(&valid), util::token(&expired));
+
+        let recorder =
+            Replay::record(util::in_place_decoder(), &path).expect("Failed to open tape");
+        let decoded = recorder
+            .decode(&valid_token)
+            .await
+            .expect("Failed to decode valid token");
+        assert_eq!(decoded, valid);
+        let outcome = recorder.decode(&expired_token).await;
+        assert!(matches!(outcome, Err(ReplayError::Decoder(_))));
+        drop(recorder);
+
+        let tape = std::fs::read_to_string(&path).expect("Failed to read tape");
+        assert!(!tape.contains(&valid_token), "Tokens must not be recorded");
+
+        let replay = Replay::<InPlace<util::Claim>>::playback(&path).expect("Failed to read tape");
+        std::fs::remove_file(&path).expect("Failed to remove tape");
+        let decoded = replay
+            .decode(&valid_token)
+            .await
+            .expect("Failed to replay valid token");
+        assert_eq!(decoded, valid);
+        match replay.decode(&expired_token).await {
+            Err(ReplayError::Recorded(message)) => assert_eq!(message, "ExpiredSignature"),
+            _ => unreachable!("Replayed expired token as valid"),
+        }
+        let outcome = replay.decode(&util::token(&util::claim(Some(10)))).await;
+        assert!(matches!(outcome, Err(ReplayError::Unrecorded)));
+    }
+}
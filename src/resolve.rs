@@ -0,0 +1,213 @@
+use crate::{RawToken, TokenExtractor};
+use core::future::Future;
+use futures::ready;
+use http::{request::Parts, HeaderMap, Request};
+use pin_project::pin_project;
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::Service;
+
+/// Future of [`AsyncTokenExtractor`]
+pub type ExtractFuture = Pin<Box<dyn Future<Output = Option<String>> + Send + 'static>>;
+
+/// Locates the token asynchronously, with access to the whole request head, see [`AsyncExtract`].
+///
+/// Any `Fn(&Parts) -> impl Future<Output = Option<String>>` is an extractor, resolving to
+/// `None` rejects the request as if no token was presented.
+pub trait AsyncTokenExtractor: Send + Sync + 'static {
+    fn extract(&self, parts: &Parts) -> ExtractFuture;
+}
+
+impl<F, Fut> AsyncTokenExtractor for F
+where
+    F: Fn(&Parts) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Option<String>> + Send + 'static,
+{
+    fn extract(&self, parts: &Parts) -> ExtractFuture {
+        Box::pin(self(parts))
+    }
+}
+
+/// [`TokenExtractor`] picking up token resolved by [`AsyncExtract`], as [`RawToken`]
+/// extension, ignoring headers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResolvedToken;
+
+impl<B> TokenExtractor<B> for ResolvedToken {
+    fn extract(&self, req: &Request<B>) -> Option<String> {
+        req.extensions()
+            .get::<RawToken>()
+            .map(|RawToken(token)| token.clone())
+    }
+
+    fn strip(&self, _: &mut HeaderMap) {}
+}
+
+/// Runs [`AsyncTokenExtractor`] before wrapped [`Middleware`][crate::Middleware] (or middleware
+/// produced by wrapped [`Layer`][crate::Layer]), e.g. to map session cookie onto a token kept
+/// in a session store.
+///
+/// ```rust
+/// # async fn session_token(session: String) -> Option<String> { None }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use http::{header::COOKIE, request::Parts};
+/// use tower_jwt::Layer;
+///
+/// let layer = Layer::new(decoder).async_extractor(|parts: &Parts| {
+///     let session = parts.headers.get(COOKIE).and_then(|v| v.to_str().ok()).map(String::from);
+///     async move { session_token(session?).await }
+/// });
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AsyncExtract<T, X> {
+    inner: T,
+    extractor: Arc<X>,
+}
+
+impl<T: Clone, X> Clone for AsyncExtract<T, X> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            extractor: self.extractor.clone(),
+        }
+    }
+}
+
+impl<T, X> AsyncExtract<T, X> {
+    pub(crate) fn new(inner: T, extractor: X) -> Self {
+        Self {
+            inner,
+            extractor: Arc::new(extractor),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<L, S, X> tower::Layer<S> for AsyncExtract<L, X>
+where
+    L: tower::Layer<S>,
+{
+    type Service = AsyncExtract<L::Service, X>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AsyncExtract {
+            inner: self.inner.layer(inner),
+            extractor: self.extractor.clone(),
+        }
+    }
+}
+
+impl<T, X, B> Service<Request<B>> for AsyncExtract<T, X>
+where
+    T: Service<Request<B>> + Clone,
+    X: AsyncTokenExtractor,
+{
+    type Response = T::Response;
+    type Error = T::Error;
+    type Future = AsyncExtractFuture<T, B>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let clone = self.inner.clone();
+        let service = core::mem::replace(&mut self.inner, clone);
+        let (parts, body) = req.into_parts();
+        AsyncExtractFuture {
+            service,
+            state: ExtractState::Extracting {
+                extracting: self.extractor.extract(&parts),
+                request: Some(Box::new((parts, body))),
+            },
+        }
+    }
+}
+
+#[pin_project]
+pub struct AsyncExtractFuture<T, B>
+where
+    T: Service<Request<B>>,
+{
+    service: T,
+    #[pin]
+    state: ExtractState<T::Future, B>,
+}
+
+#[pin_project(project = ExtractStateProj)]
+enum ExtractState<F, B> {
+    Extracting {
+        extracting: ExtractFuture,
+        request: Option<Box<(Parts, B)>>,
+    },
+    Calling(#[pin] F),
+}
+
+impl<T, B> Future for AsyncExtractFuture<T, B>
+where
+    T: Service<Request<B>>,
+{
+    type Output = Result<T::Response, T::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            let calling = match this.state.as_mut().project() {
+                ExtractStateProj::Extracting {
+                    extracting,
+                    request,
+                } => {
+                    let token = ready!(extracting.as_mut().poll(cx));
+                    let (mut parts, body) = *request
+                        .take()
+                        .expect("AsyncExtractFuture polled after completion");
+                    if let Some(token) = token {
+                        parts.extensions.insert(RawToken(token));
+                    }
+                    this.service.call(Request::from_parts(parts, body))
+                }
+                ExtractStateProj::Calling(future) => return future.poll(cx),
+            };
+            this.state.set(ExtractState::Calling(calling));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{util, Error, Middleware};
+    use http::{request::Parts, Request};
+    use tower::{service_fn, ServiceExt};
+
+    #[tokio::test]
+    async fn async_extractor() {
+        let svc = service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(crate::claims::<util::Claim>(&req).cloned())
+        });
+        let claim = util::claim(Some(100));
+        let token = util::token(&claim);
+        let middleware =
+            Middleware::new(util::in_place_decoder(), svc).async_extractor(move |parts: &Parts| {
+                let known = parts.uri.path() == "/session/1";
+                let token = token.clone();
+                async move { known.then_some(token) }
+            });
+
+        let req = Request::builder()
+            .uri("/session/1")
+            .body(())
+            .expect("Valid request");
+        let outcome = middleware.clone().oneshot(req).await;
+        assert_eq!(outcome.expect("Resolved token"), Some(claim));
+
+        let outcome = middleware.oneshot(Request::new(())).await;
+        assert!(matches!(outcome, Err(Error::MissingAuthorizationHeader)));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{
+    gate::Gates,
+    path::{canonical, glob, normalize},
+    Denied, Gate, GateContext, MatchAudience, Options,
+};
+use http::Extensions;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Overrides of middleware settings for requests to paths matching a pattern,
+/// see [`Layer::route`][crate::Layer::route].
+///
+/// Lets one middleware serve router subtrees with different requirements, e.g. anonymous
+/// access to public pages or another audience for admin API.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)] struct AdminClaim { sub: String, roles: Vec<String> }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, Route};
+///
+/// let layer = Layer::new(decoder)
+///     .route("/public/**", Route::new().optional())
+///     .route(
+///         "/admin/**",
+///         Route::new().audience("api://admin").claims::<AdminClaim>(),
+///     );
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Route {
+    optional: Option<bool>,
+    gates: Gates,
+}
+
+impl Route {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let requests without token through, see [`Layer::optional`][crate::Layer::optional]
+    pub fn optional(mut self) -> Self {
+        self.optional = Some(true);
+        self
+    }
+
+    /// Reject requests without token, even if middleware lets them through otherwise
+    pub fn required(mut self) -> Self {
+        self.optional = Some(false);
+        self
+    }
+
+    /// Run [`Gate`] on requests to the route, after gates of the middleware
+    pub fn gate<G: Gate>(mut self, gate: G) -> Self {
+        self.gates.push(gate);
+        self
+    }
+
+    /// Deny tokens not issued for `audience`, see [`MatchAudience`]
+    pub fn audience(self, audience: impl Into<String>) -> Self {
+        self.gate(MatchAudience::new([audience.into()]))
+    }
+
+    /// Deserialize payload of accepted token into `C` as well and set it on request extensions
+    /// next to decoded claim, denying tokens it doesn't fit.
+    ///
+    /// Meant for routes expecting richer claim than middleware [`Decoder`][crate::Decoder]
+    /// produces, handlers read it with [`claims`][crate::claims] as usual.
+    pub fn claims<C>(self) -> Self
+    where
+        C: DeserializeOwned + Send + Sync + 'static,
+    {
+        self.gate(|cx: &GateContext<'_>| {
+            let payload = cx.payload().map(|payload| payload.as_map().clone());
+            let claim = serde_json::from_value::<C>(Value::Object(payload.unwrap_or_default()))
+                .map_err(|err| Denied::Other(err.into()))?;
+            let mut extensions = Extensions::new();
+            extensions.insert(claim);
+            Ok(extensions)
+        })
+    }
+}
+
+/// Routes registered on middleware, first matching one applies
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Routes(Arc<[(String, Route)]>);
+
+impl Routes {
+    pub(crate) fn push(&mut self, pattern: String, route: Route) {
+        let mut routes = self.0.to_vec();
+        routes.push((pattern, route));
+        self.0 = routes.into();
+    }
+
+    /// `options` with overrides of route matching `path`, if any.
+    ///
+    /// Routes letting requests without token through only match canonical paths
+    /// as is, others match [normalized][normalize] path case-insensitively, so no spelling of
+    /// the path a router would resolve to the route escapes its gates.
+    pub(crate) fn apply(&self, options: &Options, path: &str) -> Option<Options> {
+        let normalized = normalize(path);
+        let (_, route) = self
+            .0
+            .iter()
+            .find(|(pattern, route)| match route.optional {
+                Some(true) => canonical(path) && glob(pattern.as_bytes(), path.as_bytes()),
+                _ => glob(
+                    pattern.to_ascii_lowercase().as_bytes(),
+                    normalized.as_bytes(),
+                ),
+            })?;
+        let mut options = options.clone();
+        if let Some(optional) = route.optional {
+            options.optional = optional;
+        }
+        options.gates.extend(&route.gates);
+        Some(options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Route;
+    use crate::{util, Error, Middleware};
+    use http::Request;
+    use serde::Deserialize;
+    use std::convert::Infallible;
+    use tower::{service_fn, ServiceExt};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Subject {
+        sub: String,
+    }
+
+    #[tokio::test]
+    async fn route() {
+        let svc = service_fn(|req: Request<()>| async move {
+            Ok::<_, Infallible>(crate::claims::<Subject>(&req).map(|claim| claim.sub.clone()))
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc)
+            .route("/public/**", Route::new().optional())
+            .route("/admin/**", Route::new().audience("admin"))
+            .route("/users/*", Route::new().claims::<Subject>());
+        let token = util::token(&util::claim(Some(100)));
+        let call = |path: &str, token: Option<&str>| {
+            let mut req = Request::builder().uri(path);
+            if let Some(token) = token {
+                req = req.header("authorization", format!("Bearer {}", token));
+            }
+            middleware
+                .clone()
+                .oneshot(req.body(()).expect("Valid request"))
+        };
+
+        assert_eq!(call("/public/index.html", None).await.ok(), Some(None));
+        let err = call("/orders", None).await.expect_err("Token required");
+        assert!(matches!(err, Error::MissingAuthorizationHeader));
+        let err = call("/admin/users", Some(&token))
+            .await
+            .expect_err("Audience");
+        assert!(matches!(err, Error::Denied(_)));
+        for path in ["//admin/users", "/Admin/users", "/public/../admin/users"] {
+            let err = call(path, Some(&token)).await.expect_err("Audience");
+            assert!(matches!(err, Error::Denied(_)), "{}", path);
+        }
+        let err = call("/public/../orders", None)
+            .await
+            .expect_err("Token required");
+        assert!(matches!(err, Error::MissingAuthorizationHeader));
+        assert_eq!(call("/orders", Some(&token)).await.ok(), Some(None));
+        let sub = call("/users/1", Some(&token)).await.expect("Accepted");
+        assert_eq!(sub.as_deref(), Some("sub"));
+    }
+}
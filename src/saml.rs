@@ -0,0 +1,218 @@
+use crate::{DecodeFailure, Decoder, ValidationProfile};
+use jsonwebtoken::{DecodingKey, Validation};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    future::{self, Ready},
+    marker::PhantomData,
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// Attribute names commonly emitted by WS-Fed / ADFS bridges, and claims they are mapped to
+const ATTRIBUTES: [(&str, &str); 9] = [
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/nameidentifier",
+        "sub",
+    ),
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/upn",
+        "upn",
+    ),
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress",
+        "email",
+    ),
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/name",
+        "name",
+    ),
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/givenname",
+        "given_name",
+    ),
+    (
+        "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/surname",
+        "family_name",
+    ),
+    (
+        "http://schemas.microsoft.com/ws/2008/06/identity/claims/role",
+        "roles",
+    ),
+    ("http://schemas.xmlsoap.org/claims/Group", "groups"),
+    (
+        "http://schemas.microsoft.com/ws/2008/06/identity/claims/authenticationmethod",
+        "amr",
+    ),
+];
+
+/// Decoder accepting SAML-bearer-style assertions wrapped as JWTs by WS-Fed / ADFS bridges,
+/// for deployments mid-migration from SAML.
+///
+/// Token is verified as any other JWT, then attribute statements (claims named by
+/// `http://schemas.xmlsoap.org/...` URIs) are renamed to their JWT counterparts before
+/// being deserialized into claim type, so the same claim type serves both kinds of tokens.
+/// Common ADFS attributes are mapped out of the box, claims which are not attribute
+/// statements are kept as is.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # fn example(key: jsonwebtoken::DecodingKey, validation: jsonwebtoken::Validation) {
+/// use tower_jwt::SamlBridge;
+///
+/// #[derive(Deserialize)]
+/// struct Claim { sub: String, email: String, roles: Vec<String> }
+///
+/// let decoder = SamlBridge::<Claim>::new(key, validation)
+///     .attribute("http://schemas.example.com/claims/department", "department");
+/// # }
+/// ```
+pub struct SamlBridge<C> {
+    validation: Validation,
+    profile: Option<ValidationProfile>,
+    key: Arc<DecodingKey>,
+    attributes: Arc<HashMap<String, String>>,
+    _claim: PhantomData<fn() -> C>,
+}
+
+impl<C> Clone for SamlBridge<C> {
+    fn clone(&self) -> Self {
+        Self {
+            validation: self.validation.clone(),
+            profile: self.profile,
+            key: self.key.clone(),
+            attributes: self.attributes.clone(),
+            _claim: PhantomData,
+        }
+    }
+}
+
+impl<C> SamlBridge<C> {
+    pub fn new(key: DecodingKey, validation: Validation) -> Self {
+        let attributes = ATTRIBUTES
+            .iter()
+            .map(|(uri, claim)| (uri.to_string(), claim.to_string()))
+            .collect();
+        Self {
+            validation,
+            profile: None,
+            key: Arc::new(key),
+            attributes: Arc::new(attributes),
+            _claim: PhantomData,
+        }
+    }
+
+    /// Map attribute named `uri` to `claim`
+    pub fn attribute(mut self, uri: impl Into<String>, claim: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.attributes).insert(uri.into(), claim.into());
+        self
+    }
+
+    /// Apply [`ValidationProfile`] to assertion validation
+    pub fn with_profile(mut self, profile: ValidationProfile) -> Self {
+        profile.apply(&mut self.validation);
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Renames attribute statements, claims already present take precedence
+    fn map(&self, assertion: Map<String, Value>) -> Map<String, Value> {
+        let (attributes, mut claims): (Map<_, _>, Map<_, _>) = assertion
+            .into_iter()
+            .partition(|(name, _)| self.attributes.contains_key(name));
+        for (name, value) in attributes {
+            if let Some(claim) = self.attributes.get(&name) {
+                claims.entry(claim.clone()).or_insert(value);
+            }
+        }
+        claims
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SamlBridgeError {
+    #[error("Failed to decode assertion")]
+    Decode(#[source] jsonwebtoken::errors::Error),
+
+    #[error("Assertion doesn't match claim")]
+    Claim(#[source] serde_json::Error),
+}
+
+impl<C> Decoder for SamlBridge<C>
+where
+    C: DeserializeOwned + 'static,
+{
+    type Error = SamlBridgeError;
+    type Claim = C;
+    type Future = Ready<Result<Self::Claim, Self::Error>>;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        tracing::trace!("SamlBridge::entered");
+        let decoded =
+            jsonwebtoken::decode::<Map<String, Value>>(token, &self.key, &self.validation)
+                .and_then(|token_data| match self.profile {
+                    Some(profile) => profile.check(token).map(|_| token_data.claims),
+                    None => Ok(token_data.claims),
+                })
+                .map_err(SamlBridgeError::Decode)
+                .and_then(|assertion| {
+                    serde_json::from_value(Value::Object(self.map(assertion)))
+                        .map_err(SamlBridgeError::Claim)
+                });
+        future::ready(decoded)
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        Some(DecodeFailure::of(err))
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SamlBridge;
+    use crate::{util, Decoder};
+    use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Claim {
+        sub: String,
+        email: String,
+        roles: Vec<String>,
+        department: String,
+    }
+
+    #[tokio::test]
+    async fn saml_bridge() {
+        let key = DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes()).expect("Valid key");
+        let decoder = SamlBridge::<Claim>::new(key, Validation::new(Algorithm::EdDSA))
+            .attribute("http://schemas.example.com/claims/department", "department");
+
+        let token = util::token(&serde_json::json!({
+            "exp": chrono::Utc::now().timestamp() + 100,
+            "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/nameidentifier": "jdoe",
+            "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress": "jdoe@corp.example",
+            "http://schemas.microsoft.com/ws/2008/06/identity/claims/role": ["admin"],
+            "http://schemas.example.com/claims/department": "finance",
+        }));
+        let claim = decoder
+            .decode(&token)
+            .await
+            .expect("Failed to decode bridged assertion");
+        assert_eq!(
+            claim,
+            Claim {
+                sub: "jdoe".into(),
+                email: "jdoe@corp.example".into(),
+                roles: vec!["admin".into()],
+                department: "finance".into(),
+            }
+        );
+    }
+}
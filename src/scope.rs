@@ -0,0 +1,93 @@
+use crate::{BoxFuture, Denied, Gate, GateContext, Payload};
+use futures::future;
+use http::Extensions;
+
+/// [`Gate`] denying tokens lacking any of required scopes with [`Denied::InsufficientScope`],
+/// rendered as `403` with `insufficient_scope` error.
+///
+/// Scopes are read from space-delimited `scope` claim of
+/// [RFC 8693](https://www.rfc-editor.org/rfc/rfc8693#section-4.2), falling back to `scp`,
+/// which some issuers send as an array.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, RequireScope};
+///
+/// let layer = Layer::new(decoder).gate(RequireScope::new(["orders:read"]));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RequireScope {
+    scopes: Vec<String>,
+}
+
+impl RequireScope {
+    pub fn new<I, S>(scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            scopes: scopes.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    fn granted(payload: &Payload) -> Vec<&str> {
+        match payload.str("scope") {
+            Some(scope) => scope.split(' ').collect(),
+            None => payload
+                .strings("scp")
+                .into_iter()
+                .flat_map(|scp| scp.split(' '))
+                .collect(),
+        }
+    }
+}
+
+impl Gate for RequireScope {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let granted = cx.payload().map(Self::granted).unwrap_or_default();
+        let outcome = match self
+            .scopes
+            .iter()
+            .all(|scope| granted.contains(&scope.as_str()))
+        {
+            true => Ok(Extensions::new()),
+            false => Err(Denied::InsufficientScope(self.scopes.clone())),
+        };
+        Box::pin(future::ready(outcome))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RequireScope;
+    use crate::{util, Denied, FailureKind, Gate, GateContext};
+    use http::{Request, StatusCode};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn require_scope() {
+        let gate = RequireScope::new(["orders:read", "orders:write"]);
+        let (parts, _) = Request::new(()).into_parts();
+
+        let token = util::token(&json!({ "scope": "orders:read orders:write profile" }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+        let token = util::token(&json!({ "scp": ["orders:read", "orders:write"] }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+
+        let token = util::token(&json!({ "scope": "orders:read" }));
+        let denied = gate
+            .check(&GateContext::new(&parts, Some(&token)))
+            .await
+            .expect_err("Missing scope");
+        assert!(matches!(denied, Denied::InsufficientScope(_)));
+        assert_eq!(denied.kind(), FailureKind::Authorization);
+        let rejection = denied.rejection();
+        assert_eq!(rejection.status(), StatusCode::FORBIDDEN);
+        assert_eq!(rejection.param("error"), Some("insufficient_scope"));
+        assert_eq!(rejection.param("scope"), Some("orders:read orders:write"));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::Decoder;
+use serde::de::DeserializeOwned;
+use tower::{util::Oneshot, Service, ServiceExt};
+
+/// Request passed to [services][tower::Service] wrapped by [`DecoderService`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyRequest {
+    token: String,
+}
+
+impl VerifyRequest {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn into_token(self) -> String {
+        self.token
+    }
+}
+
+/// Turns any [`tower::Service`] verifying tokens into [`Decoder`].
+///
+/// Service is cloned per decoded token and driven to readiness before being called,
+/// so readiness-aware middleware (buffers, concurrency limits, load shedding, ..)
+/// can sit between [`Middleware`][crate::Middleware] and remote verifiers.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)] struct Claim { jti: String }
+/// # async fn verify(token: &str) -> Result<Claim, std::io::Error> { todo!() }
+/// use tower_jwt::{DecoderService, VerifyRequest};
+///
+/// let decoder = DecoderService::new(tower::service_fn(|req: VerifyRequest| async move {
+///     verify(req.token()).await
+/// }));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecoderService<S> {
+    service: S,
+}
+
+impl<S> DecoderService<S> {
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+}
+
+impl<S> Decoder for DecoderService<S>
+where
+    S: Service<VerifyRequest> + Clone,
+    S::Response: DeserializeOwned + 'static,
+{
+    type Error = S::Error;
+    type Claim = S::Response;
+    type Future = Oneshot<S, VerifyRequest>;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        tracing::trace!("DecoderService::entered");
+        self.service.clone().oneshot(VerifyRequest::new(token))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DecoderService, VerifyRequest};
+    use crate::{util, Decoder};
+
+    #[tokio::test]
+    async fn service_decoder() {
+        let in_place = util::in_place_decoder();
+        let decoder = DecoderService::new(tower::service_fn(move |req: VerifyRequest| {
+            in_place.decode(req.token())
+        }));
+
+        let valid = util::claim(Some(100));
+        let decoded = decoder
+            .decode(&util::token(&valid))
+            .await
+            .expect("Failed to decode token with service decoder");
+        assert_eq!(decoded, valid);
+
+        let expired = util::claim(None);
+        let result = decoder.decode(&util::token(&expired)).await;
+        assert!(result.is_err(), "Decoded expired claim");
+    }
+}
@@ -0,0 +1,450 @@
+use crate::{BoxError, BoxFuture, Denied, Gate, GateContext};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use core::future::Future;
+use futures::{future, FutureExt};
+use http::{header::HOST, request::Parts, Extensions};
+use ring::{hmac, signature};
+use serde_json::Value;
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Key verifying [RFC 9421](https://www.rfc-editor.org/rfc/rfc9421) message signatures,
+/// resolved by `keyid` through [`SignatureKeys`]
+#[derive(Clone)]
+pub struct SignatureKey {
+    alg: &'static str,
+    key: Vec<u8>,
+}
+
+impl SignatureKey {
+    /// `ed25519` key from raw 32 byte public key
+    pub fn ed25519(public_key: impl Into<Vec<u8>>) -> Self {
+        Self::new("ed25519", public_key)
+    }
+
+    /// `ecdsa-p256-sha256` key from uncompressed public point
+    pub fn ecdsa_p256_sha256(public_key: impl Into<Vec<u8>>) -> Self {
+        Self::new("ecdsa-p256-sha256", public_key)
+    }
+
+    /// `rsa-pss-sha512` key from DER encoded `RSAPublicKey`
+    pub fn rsa_pss_sha512(public_key: impl Into<Vec<u8>>) -> Self {
+        Self::new("rsa-pss-sha512", public_key)
+    }
+
+    /// `rsa-v1_5-sha256` key from DER encoded `RSAPublicKey`
+    pub fn rsa_v1_5_sha256(public_key: impl Into<Vec<u8>>) -> Self {
+        Self::new("rsa-v1_5-sha256", public_key)
+    }
+
+    /// `hmac-sha256` shared secret
+    pub fn hmac_sha256(secret: impl Into<Vec<u8>>) -> Self {
+        Self::new("hmac-sha256", secret)
+    }
+
+    fn new(alg: &'static str, key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            alg,
+            key: key.into(),
+        }
+    }
+
+    /// Algorithm name as registered with IANA
+    pub fn algorithm(&self) -> &str {
+        self.alg
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        let algorithm: &dyn signature::VerificationAlgorithm = match self.alg {
+            "ed25519" => &signature::ED25519,
+            "ecdsa-p256-sha256" => &signature::ECDSA_P256_SHA256_FIXED,
+            "rsa-pss-sha512" => &signature::RSA_PSS_2048_8192_SHA512,
+            "rsa-v1_5-sha256" => &signature::RSA_PKCS1_2048_8192_SHA256,
+            _ => {
+                let key = hmac::Key::new(hmac::HMAC_SHA256, &self.key);
+                return hmac::verify(&key, message, signature).is_ok();
+            }
+        };
+        signature::UnparsedPublicKey::new(algorithm, &self.key)
+            .verify(message, signature)
+            .is_ok()
+    }
+}
+
+impl fmt::Debug for SignatureKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignatureKey")
+            .field("alg", &self.alg)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Resolves message signature keys by `keyid`, see [`MessageSignature`].
+pub trait SignatureKeys: Send + Sync + 'static {
+    fn key(&self, keyid: &str) -> BoxFuture<SignatureKey, BoxError>;
+}
+
+impl<F, Fut> SignatureKeys for F
+where
+    F: Fn(&str) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<SignatureKey, BoxError>> + Send + 'static,
+{
+    fn key(&self, keyid: &str) -> BoxFuture<SignatureKey, BoxError> {
+        Box::pin(self(keyid))
+    }
+}
+
+/// Signature parameters of verified message, set on request extensions by [`MessageSignature`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedMessage {
+    label: String,
+    keyid: String,
+    components: Vec<String>,
+    created: Option<u64>,
+}
+
+impl SignedMessage {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn keyid(&self) -> &str {
+        &self.keyid
+    }
+
+    /// Covered components, e.g. `@method` or `content-digest`
+    pub fn components(&self) -> &[String] {
+        &self.components
+    }
+
+    pub fn created(&self) -> Option<u64> {
+        self.created
+    }
+}
+
+/// [`Gate`] verifying [RFC 9421](https://www.rfc-editor.org/rfc/rfc9421) HTTP message
+/// signatures alongside the token, for APIs requiring both bearer identity and request
+/// integrity.
+///
+/// First signature of `Signature-Input` is verified with key resolved from its `keyid`.
+/// Signature must cover `@method` and `@target-uri` (so token can't be replayed against
+/// other routes) plus any [required][MessageSignature::require] components, and must not
+/// be expired. When [bound to token][MessageSignature::bind_token], `keyid` must also equal
+/// token `cnf.kid`. Verified parameters are recorded as [`SignedMessage`], failures are
+/// denied with [`Denied::Signature`].
+///
+/// Only bare components are supported, components with parameters such as `;sf` are rejected.
+/// `@target-uri` and `@scheme` are reconstructed as `https` when request URI is not absolute.
+///
+/// ```rust
+/// # async fn lookup(keyid: &str) -> Result<tower_jwt::SignatureKey, tower_jwt::BoxError> { todo!() }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use std::time::Duration;
+/// use tower_jwt::{Layer, MessageSignature};
+///
+/// let layer = Layer::new(decoder).gate(
+///     MessageSignature::new(|keyid: &str| {
+///         let keyid = keyid.to_owned();
+///         async move { lookup(&keyid).await }
+///     })
+///     .require("content-digest")
+///     .max_age(Duration::from_secs(300))
+///     .bind_token(),
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MessageSignature {
+    keys: Arc<dyn SignatureKeys>,
+    required: Vec<String>,
+    max_age: Option<Duration>,
+    bind_token: bool,
+}
+
+impl MessageSignature {
+    pub fn new<K: SignatureKeys>(keys: K) -> Self {
+        Self {
+            keys: Arc::new(keys),
+            required: vec![String::from("@method"), String::from("@target-uri")],
+            max_age: None,
+            bind_token: false,
+        }
+    }
+
+    /// Require signature to cover `component`
+    pub fn require(mut self, component: impl Into<String>) -> Self {
+        self.required.push(component.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Require `created` parameter no older than `max_age`
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Require `keyid` to equal token `cnf.kid`
+    pub fn bind_token(mut self) -> Self {
+        self.bind_token = true;
+        self
+    }
+
+    /// Parses signature, checks its parameters and builds signature base
+    fn prepare(
+        &self,
+        cx: &GateContext<'_>,
+        now: u64,
+    ) -> Option<(SignedMessage, Option<String>, Vec<u8>, String)> {
+        let headers = cx.headers();
+        let input = headers.get("signature-input")?.to_str().ok()?;
+        let signatures = headers.get("signature")?.to_str().ok()?;
+
+        let (label, params) = members(input).next()?;
+        let signature = members(signatures)
+            .find(|(other, _)| *other == label)
+            .and_then(|(_, value)| value.strip_prefix(':')?.strip_suffix(':'))
+            .and_then(|value| STANDARD.decode(value).ok())?;
+
+        let (components, parameters) = inner_list(params)?;
+        let mut keyid = None;
+        let (mut alg, mut created, mut expires) = (None, None, None);
+        for (name, value) in parameters {
+            match name {
+                "keyid" => keyid = Some(unquote(value)?.to_owned()),
+                "alg" => alg = Some(unquote(value)?.to_owned()),
+                "created" => created = Some(value.parse::<u64>().ok()?),
+                "expires" => expires = Some(value.parse::<u64>().ok()?),
+                _ => {}
+            }
+        }
+        let keyid = keyid?;
+
+        if self.required.iter().any(|r| !components.contains(r)) {
+            return None;
+        }
+        if expires.is_some_and(|expires| expires <= now) {
+            return None;
+        }
+        if let Some(max_age) = self.max_age {
+            let created = created?;
+            if created > now + 60 || now.saturating_sub(created) > max_age.as_secs() {
+                return None;
+            }
+        }
+        if self.bind_token {
+            let bound = cx
+                .payload()
+                .and_then(|payload| payload.get("cnf"))
+                .and_then(|cnf| cnf.get("kid"))
+                .and_then(Value::as_str);
+            if bound != Some(keyid.as_str()) {
+                return None;
+            }
+        }
+
+        let mut base = String::new();
+        for component in &components {
+            let value = component_value(cx.parts(), component)?;
+            base.push_str(&format!("\"{}\": {}\n", component, value));
+        }
+        base.push_str(&format!("\"@signature-params\": {}", params));
+
+        let message = SignedMessage {
+            label: label.to_owned(),
+            keyid,
+            components,
+            created,
+        };
+        Some((message, alg, signature, base))
+    }
+}
+
+impl Gate for MessageSignature {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (message, alg, signature, base) = match self.prepare(cx, now) {
+            Some(prepared) => prepared,
+            None => return Box::pin(future::ready(Err(Denied::Signature))),
+        };
+
+        self.keys
+            .key(&message.keyid)
+            .map(move |key| {
+                let key = key.map_err(Denied::Other)?;
+                let alg_matches = alg.is_none_or(|alg| alg == key.alg);
+                if !alg_matches || !key.verify(base.as_bytes(), &signature) {
+                    return Err(Denied::Signature);
+                }
+                let mut extensions = Extensions::new();
+                extensions.insert(message);
+                Ok(extensions)
+            })
+            .boxed()
+    }
+}
+
+impl fmt::Debug for MessageSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageSignature")
+            .field("required", &self.required)
+            .field("max_age", &self.max_age)
+            .field("bind_token", &self.bind_token)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Top level members of structured field dictionary, as `(key, raw value)`
+fn members(field: &str) -> impl Iterator<Item = (&str, &str)> {
+    let (mut start, mut quoted, mut depth) = (0, false, 0);
+    let mut members = Vec::new();
+    for (index, c) in field.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                members.push(&field[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    members.push(&field[start..]);
+    members.into_iter().filter_map(|member| {
+        let (key, value) = member.trim().split_once('=')?;
+        Some((key, value))
+    })
+}
+
+type Parameters<'a> = Vec<(&'a str, &'a str)>;
+
+/// Parses inner list of bare string items followed by parameters
+fn inner_list(value: &str) -> Option<(Vec<String>, Parameters<'_>)> {
+    let (items, parameters) = value.strip_prefix('(')?.split_once(')')?;
+    let components = items
+        .split_whitespace()
+        .map(|item| unquote(item).map(str::to_owned))
+        .collect::<Option<Vec<_>>>()?;
+    let parameters = parameters
+        .split(';')
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| parameter.split_once('='))
+        .collect::<Option<Vec<_>>>()?;
+    Some((components, parameters))
+}
+
+fn unquote(value: &str) -> Option<&str> {
+    value.strip_prefix('"')?.strip_suffix('"')
+}
+
+fn component_value(parts: &Parts, component: &str) -> Option<String> {
+    let uri = &parts.uri;
+    let authority = || match uri.authority() {
+        Some(authority) => Some(authority.as_str().to_ascii_lowercase()),
+        None => Some(parts.headers.get(HOST)?.to_str().ok()?.to_ascii_lowercase()),
+    };
+    let scheme = || uri.scheme_str().unwrap_or("https").to_ascii_lowercase();
+    let path_and_query = || {
+        uri.path_and_query()
+            .map_or("/", |pq| pq.as_str())
+            .to_owned()
+    };
+
+    let value = match component {
+        "@method" => parts.method.as_str().to_owned(),
+        "@authority" => authority()?,
+        "@scheme" => scheme(),
+        "@path" => uri.path().to_owned(),
+        "@query" => format!("?{}", uri.query().unwrap_or_default()),
+        "@request-target" => path_and_query(),
+        "@target-uri" => format!("{}://{}{}", scheme(), authority()?, path_and_query()),
+        component if component.starts_with('@') => return None,
+        header => {
+            let values = parts
+                .headers
+                .get_all(header)
+                .iter()
+                .map(|value| value.to_str().map(str::trim))
+                .collect::<Result<Vec<_>, _>>()
+                .ok()?;
+            if values.is_empty() {
+                return None;
+            }
+            values.join(", ")
+        }
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MessageSignature, SignatureKey, SignedMessage};
+    use crate::{util, BoxError, Denied, Gate, GateContext};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use http::Request;
+    use ring::hmac;
+
+    const SECRET: &[u8] = b"message-signature-secret";
+
+    fn signed(uri: &str, params: &str, base: &str) -> Request<()> {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, SECRET);
+        let signature = STANDARD.encode(hmac::sign(&key, base.as_bytes()));
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("host", "api.example.com")
+            .header("signature-input", format!("sig1={}", params))
+            .header("signature", format!("sig1=:{}:", signature))
+            .body(())
+            .expect("Valid request")
+    }
+
+    #[tokio::test]
+    async fn message_signature() {
+        let gate = MessageSignature::new(|keyid: &str| {
+            let key = match keyid {
+                "client" => Ok(SignatureKey::hmac_sha256(SECRET)),
+                _ => Err(BoxError::from("Unknown key")),
+            };
+            async move { key }
+        })
+        .bind_token();
+        let token = util::token(&serde_json::json!({ "cnf": { "kid": "client" } }));
+
+        let params = r#"("@method" "@target-uri");keyid="client";alg="hmac-sha256""#;
+        let base = format!(
+            "\"@method\": POST\n\"@target-uri\": https://api.example.com/orders?id=1\n\"@signature-params\": {}",
+            params
+        );
+        let (parts, _) = signed("/orders?id=1", params, &base).into_parts();
+        let extensions = gate
+            .check(&GateContext::new(&parts, Some(&token)))
+            .await
+            .expect("Signature is valid");
+        let message = extensions
+            .get::<SignedMessage>()
+            .expect("Message is signed");
+        assert_eq!(message.keyid(), "client");
+
+        // Signature doesn't cover this URI
+        let (parts, _) = signed("/orders?id=2", params, &base).into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Signature)));
+
+        // Signature doesn't cover method
+        let params = r#"("@target-uri");keyid="client""#;
+        let base = format!(
+            "\"@target-uri\": https://api.example.com/orders?id=1\n\"@signature-params\": {}",
+            params
+        );
+        let (parts, _) = signed("/orders?id=1", params, &base).into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Signature)));
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    fmt,
+    sync::{Arc, OnceLock},
+};
+
+/// Place for the claim preallocated on request extensions (e.g. by connection handler,
+/// along with other per-request state), filled by [`Middleware`][crate::Middleware] rather
+/// than inserting the claim on extensions, which allocates.
+///
+/// [`claims`][crate::claims] looks into slots as well, cloned slot keeps seeing the claim
+/// after request was handed over to inner service.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)] struct Claim { jti: String }
+/// # fn example(mut req: http::Request<()>) {
+/// use tower_jwt::{ClaimSlot, Claims};
+///
+/// // `Shared` decoders set `Claims<Claim>`
+/// let slot = ClaimSlot::<Claims<Claim>>::new();
+/// req.extensions_mut().insert(slot.clone());
+/// # }
+/// ```
+pub struct ClaimSlot<C>(Arc<OnceLock<C>>);
+
+impl<C> ClaimSlot<C> {
+    pub fn new() -> Self {
+        Self(Arc::new(OnceLock::new()))
+    }
+
+    /// Decoded claim, once token was accepted
+    pub fn get(&self) -> Option<&C> {
+        self.0.get()
+    }
+
+    /// Hands `claim` back if slot was filled already
+    pub(crate) fn set(&self, claim: C) -> Result<(), C> {
+        self.0.set(claim)
+    }
+}
+
+impl<C> Default for ClaimSlot<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> Clone for ClaimSlot<C> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<C> fmt::Debug for ClaimSlot<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClaimSlot")
+            .field("filled", &self.0.get().is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ClaimSlot;
+    use crate::{util, Cached, Claims, Middleware, RawToken, Shared};
+    use core::future::Future;
+    use core::task::{Context, Poll};
+    use futures::task::noop_waker_ref;
+    use http::Request;
+    use std::{pin::pin, time::Duration};
+    use tower::{service_fn, Service};
+
+    #[test]
+    fn no_alloc() {
+        let decoder = Cached::new(
+            Shared::new(util::in_place_decoder()),
+            Duration::from_secs(60),
+        );
+        let svc = service_fn(|req: Request<()>| {
+            std::future::ready(Ok::<_, ()>(crate::claims::<util::Claim>(&req).is_some()))
+        });
+        let mut middleware = Middleware::new(decoder, svc).no_alloc();
+        let token = util::token(&util::claim(Some(100)));
+        let req = || {
+            let mut req = Request::new(());
+            req.extensions_mut().insert(RawToken(token.clone()));
+            let slot = ClaimSlot::<Claims<util::Claim>>::new();
+            req.extensions_mut().insert(slot.clone());
+            (req, slot)
+        };
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut authenticate = |req| {
+            assert!(middleware.poll_ready(&mut cx).is_ready());
+            let fut = pin!(middleware.call(req));
+            fut.poll(&mut cx).map(Result::ok)
+        };
+
+        // Cache miss allocates
+        let (miss, _) = req();
+        assert_eq!(authenticate(miss), Poll::Ready(Some(true)));
+
+        let (hit, slot) = req();
+        let (outcome, allocations) = util::allocations(|| authenticate(hit));
+        assert_eq!(outcome, Poll::Ready(Some(true)));
+        assert_eq!(allocations, 0);
+        assert!(slot.get().is_some());
+
+        // Without slot, claim is inserted on extensions
+        let (mut unslotted, _) = req();
+        unslotted
+            .extensions_mut()
+            .remove::<ClaimSlot<Claims<util::Claim>>>();
+        let (_, allocations) = util::allocations(|| authenticate(unslotted));
+        assert!(allocations > 0);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{DecodeFailure, Error, FailureKind};
+
+/// Why token was not accepted, set on request extensions in place of the claim
+/// when [soft failing][crate::Layer::soft_fail]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AuthFailure {
+    pub kind: FailureKind,
+    /// Errors of decoders are not included as they aren't required to be printable
+    pub reason: String,
+    /// Why decoder rejected the token, if decoder classifies its errors,
+    /// see [`Decoder::failure`][crate::Decoder::failure]
+    pub failure: Option<DecodeFailure>,
+}
+
+impl AuthFailure {
+    /// `None` for errors of inner service
+    pub(crate) fn new<E, D>(err: &Error<E, D>) -> Option<Self> {
+        let reason = match err {
+            Error::MissingAuthorizationHeader => String::from("Authorization header must be set"),
+            Error::Decoder(_) => String::from("Failed to decode token"),
+            Error::Denied(denied) => denied.to_string(),
+            Error::Inner(_) => return None,
+        };
+        Some(Self {
+            kind: err.kind()?,
+            reason,
+            failure: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AuthFailure;
+    use crate::{util, DecodeFailure, FailureKind, Middleware};
+    use http::Request;
+    use tower::{service_fn, ServiceExt};
+
+    #[tokio::test]
+    async fn soft_fail() {
+        let svc = service_fn(|req: Request<()>| async move {
+            let failure = req.extensions().get::<AuthFailure>().cloned();
+            Ok::<_, ()>((crate::claims::<util::Claim>(&req).is_some(), failure))
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).soft_fail();
+        let req = |token: &str| {
+            Request::builder()
+                .header("authorization", format!("Bearer {}", token))
+                .body(())
+                .expect("Valid request")
+        };
+
+        let token = util::token(&util::claim(Some(100)));
+        let (claimed, failure) = middleware.clone().oneshot(req(&token)).await.expect("Ok");
+        assert!(claimed);
+        assert_eq!(failure, None);
+
+        let token = util::token(&util::claim(None));
+        let (_, failure) = middleware.clone().oneshot(req(&token)).await.expect("Ok");
+        let failure = failure.and_then(|failure| failure.failure);
+        assert_eq!(failure, Some(DecodeFailure::Expired));
+
+        let (claimed, failure) = middleware
+            .clone()
+            .oneshot(req("not.a.token"))
+            .await
+            .expect("Let through");
+        assert!(!claimed);
+        let failure = failure.expect("Failure recorded");
+        assert_eq!(failure.kind, FailureKind::Authentication);
+        assert_eq!(failure.reason, "Failed to decode token");
+        assert_eq!(failure.failure, Some(DecodeFailure::Malformed));
+    }
+}
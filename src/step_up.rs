@@ -0,0 +1,250 @@
+use crate::{path::under, BoxFuture, Denied, Gate, GateContext, Rejection};
+use futures::future;
+use http::{Extensions, HeaderValue};
+use std::time::Duration;
+
+/// Requirements token failed to meet, carried by [`Denied::StepUp`].
+///
+/// Renders into `WWW-Authenticate` challenge as described in
+/// [RFC 9470](https://www.rfc-editor.org/rfc/rfc9470#section-3), so clients know
+/// to re-authenticate with stronger (or fresher) credentials.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StepUpChallenge {
+    acr_values: Vec<String>,
+    max_age: Option<Duration>,
+}
+
+impl StepUpChallenge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Acceptable `acr` values, in order of preference
+    pub fn with_acr_values<I, A>(mut self, acr_values: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<String>,
+    {
+        self.acr_values = acr_values.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Maximum time elapsed since user authenticated
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn acr_values(&self) -> &[String] {
+        &self.acr_values
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// `WWW-Authenticate` value with `insufficient_user_authentication` error
+    pub fn www_authenticate(&self) -> HeaderValue {
+        // acr values come from configuration, drop them rather than fail if they aren't valid
+        self.rejection().www_authenticate().unwrap_or_else(|| {
+            HeaderValue::from_static(r#"Bearer error="insufficient_user_authentication""#)
+        })
+    }
+
+    pub(crate) fn rejection(&self) -> Rejection {
+        let mut rejection = Rejection::unauthorized()
+            .with_error("insufficient_user_authentication")
+            .with_error_description("A different authentication level is required");
+        if !self.acr_values.is_empty() {
+            rejection = rejection.with_param("acr_values", self.acr_values.join(" "));
+        }
+        if let Some(max_age) = self.max_age {
+            rejection = rejection.with_param("max_age", max_age.as_secs().to_string());
+        }
+        rejection
+    }
+}
+
+/// [`Gate`] enforcing minimum authentication assurance through `acr`/`amr` claims,
+/// so sensitive endpoints can demand step-up authentication.
+///
+/// Assurance levels are configured from lowest to highest, token `acr` must be at or above
+/// required level, and token `amr` must list every required method. Routes can raise
+/// required level by path prefix, longest prefix wins. Requests falling short are denied
+/// with [`Denied::StepUp`] carrying the [challenge][StepUpChallenge] to send back.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, StepUp};
+///
+/// let layer = Layer::new(decoder).gate(
+///     StepUp::new(["urn:loa:1", "urn:loa:2", "urn:loa:3"])
+///         .minimum("urn:loa:1")
+///         .route("/payments", "urn:loa:2")
+///         .route("/admin", "urn:loa:3")
+///         .amr(["pwd"]),
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct StepUp {
+    levels: Vec<String>,
+    minimum: Option<String>,
+    amr: Vec<String>,
+    routes: Vec<(String, String)>,
+}
+
+impl StepUp {
+    /// `levels` are `acr` values ordered from lowest to highest assurance
+    pub fn new<I, A>(levels: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<String>,
+    {
+        Self {
+            levels: levels.into_iter().map(Into::into).collect(),
+            minimum: None,
+            amr: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+
+    /// Minimum `acr` required on every route.
+    ///
+    /// Values outside of configured levels only accept exact match.
+    pub fn minimum(mut self, acr: impl Into<String>) -> Self {
+        self.minimum = Some(acr.into());
+        self
+    }
+
+    /// Minimum `acr` required on `prefix` and paths beneath it, whole segments compared once
+    /// paths are normalized, so `/admin` covers `//Admin/x` but not `/administrator`
+    pub fn route(mut self, prefix: impl Into<String>, acr: impl Into<String>) -> Self {
+        self.routes.push((prefix.into(), acr.into()));
+        self
+    }
+
+    /// Authentication methods which must all be listed in token `amr`
+    pub fn amr<I, M>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = M>,
+        M: Into<String>,
+    {
+        self.amr = methods.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn required_acr(&self, path: &str) -> Option<&str> {
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| under(prefix, path))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, acr)| acr.as_str())
+            .or(self.minimum.as_deref())
+    }
+
+    fn level(&self, acr: &str) -> Option<usize> {
+        self.levels.iter().position(|level| level == acr)
+    }
+
+    fn acr_satisfied(&self, required: &str, acr: Option<&str>) -> bool {
+        match (acr, self.level(required)) {
+            (Some(acr), Some(required)) => self.level(acr).is_some_and(|acr| acr >= required),
+            (Some(acr), None) => acr == required,
+            (None, _) => false,
+        }
+    }
+
+    /// Levels at or above `required`, which are acceptable to satisfy it
+    fn acceptable(&self, required: &str) -> Vec<String> {
+        match self.level(required) {
+            Some(level) => self.levels[level..].to_vec(),
+            None => vec![required.to_owned()],
+        }
+    }
+
+    fn verify(&self, cx: &GateContext<'_>) -> Result<(), StepUpChallenge> {
+        let payload = cx.payload();
+        let required = self.required_acr(cx.uri().path());
+
+        let acr_ok = required.is_none_or(|required| {
+            self.acr_satisfied(required, payload.and_then(|p| p.str("acr")))
+        });
+        let amr = payload.map(|p| p.strings("amr")).unwrap_or_default();
+        let amr_ok = self.amr.iter().all(|method| amr.contains(&method.as_str()));
+
+        match acr_ok && amr_ok {
+            true => Ok(()),
+            false => Err(StepUpChallenge::new()
+                .with_acr_values(required.map(|acr| self.acceptable(acr)).unwrap_or_default())),
+        }
+    }
+}
+
+impl Gate for StepUp {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let outcome = self
+            .verify(cx)
+            .map(|_| Extensions::new())
+            .map_err(Denied::StepUp);
+        Box::pin(future::ready(outcome))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StepUp, StepUpChallenge};
+    use crate::{util, Denied, Gate, GateContext};
+    use http::Request;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn step_up_per_route() {
+        let gate = StepUp::new(["loa1", "loa2", "loa3"])
+            .minimum("loa1")
+            .route("/admin", "loa3")
+            .amr(["pwd"]);
+        let token = util::token(&serde_json::json!({ "acr": "loa2", "amr": ["pwd", "otp"] }));
+
+        let (parts, _) = Request::get("/orders").body(()).unwrap().into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+
+        for path in [
+            "/admin",
+            "/admin/users",
+            "//Admin/users",
+            "/orders/../admin",
+        ] {
+            let (parts, _) = Request::get(path).body(()).unwrap().into_parts();
+            let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+            match outcome {
+                Err(Denied::StepUp(challenge)) => assert_eq!(challenge.acr_values(), ["loa3"]),
+                _ => unreachable!("Accepted token below required assurance on {}", path),
+            }
+        }
+
+        let (parts, _) = Request::get("/administrator")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+
+        let token = util::token(&serde_json::json!({ "acr": "loa3", "amr": "otp" }));
+        let (parts, _) = Request::get("/orders").body(()).unwrap().into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::StepUp(_))));
+    }
+
+    #[test]
+    fn challenge_header() {
+        let challenge = StepUpChallenge::new()
+            .with_acr_values(["loa2", "loa3"])
+            .with_max_age(Duration::from_secs(300));
+        assert_eq!(
+            challenge.www_authenticate(),
+            r#"Bearer error="insufficient_user_authentication", error_description="A different authentication level is required", acr_values="loa2 loa3", max_age="300""#
+        );
+    }
+}
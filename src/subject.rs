@@ -0,0 +1,163 @@
+use crate::{cache::TtlCache, BoxError, BoxFuture, Denied, Gate, GateContext};
+use core::future::Future;
+use futures::{future, FutureExt, TryFutureExt};
+use http::Extensions;
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Internal user id resolved from token `iss` and `sub`, set on request extensions by [`ResolveSubject`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvedSubject(String);
+
+impl ResolvedSubject {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+/// Maps subjects issued by IdP onto internal user ids, see [`ResolveSubject`].
+///
+/// Subjects are only unique within their issuer, so both are passed.
+pub trait SubjectResolver: Send + Sync + 'static {
+    /// Returns `None` for subjects without internal counterpart
+    fn resolve(&self, iss: &str, sub: &str) -> BoxFuture<Option<String>, BoxError>;
+}
+
+impl<F, Fut> SubjectResolver for F
+where
+    F: Fn(&str, &str) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Option<String>, BoxError>> + Send + 'static,
+{
+    fn resolve(&self, iss: &str, sub: &str) -> BoxFuture<Option<String>, BoxError> {
+        Box::pin(self(iss, sub))
+    }
+}
+
+/// [`Gate`] resolving pairwise (or otherwise opaque) `sub` into [`ResolvedSubject`],
+/// keeping the mapping out of every handler.
+///
+/// Resolved ids are cached per issuer and subject for configured time (five minutes by default).
+/// Tokens without `iss` or `sub`, or with subjects unknown to resolver, are denied with
+/// [`Denied::UnknownSubject`], resolver errors are propagated as [`Denied::Other`].
+///
+/// ```rust
+/// # async fn lookup(iss: &str, sub: &str) -> Result<Option<String>, tower_jwt::BoxError> { todo!() }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, ResolveSubject};
+///
+/// let layer = Layer::new(decoder).gate(ResolveSubject::new(|iss: &str, sub: &str| {
+///     let (iss, sub) = (iss.to_owned(), sub.to_owned());
+///     async move { lookup(&iss, &sub).await }
+/// }));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ResolveSubject {
+    resolver: Arc<dyn SubjectResolver>,
+    cache: TtlCache<String>,
+}
+
+impl ResolveSubject {
+    pub fn new<R: SubjectResolver>(resolver: R) -> Self {
+        Self {
+            resolver: Arc::new(resolver),
+            cache: TtlCache::new(Duration::from_secs(300)),
+        }
+    }
+
+    /// How long resolved ids are cached for, zero disables caching
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.cache = TtlCache::new(ttl);
+        self
+    }
+}
+
+fn resolved(id: String) -> Extensions {
+    let mut extensions = Extensions::new();
+    extensions.insert(ResolvedSubject(id));
+    extensions
+}
+
+impl Gate for ResolveSubject {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let payload = cx.payload();
+        let claim = |claim| payload.and_then(|payload| payload.str(claim));
+        let (iss, sub) = match (claim("iss"), claim("sub")) {
+            (Some(iss), Some(sub)) => (iss, sub),
+            _ => return Box::pin(future::ready(Err(Denied::UnknownSubject))),
+        };
+        // Length prefix keeps `iss` and `sub` apart whatever they contain
+        let key = format!("{}:{}{}", iss.len(), iss, sub);
+        if let Some(id) = self.cache.get(&key) {
+            return Box::pin(future::ready(Ok(resolved(id))));
+        }
+
+        let cache = self.cache.clone();
+        self.resolver
+            .resolve(iss, sub)
+            .map_err(Denied::Other)
+            .map(move |outcome| {
+                let id = outcome?.ok_or(Denied::UnknownSubject)?;
+                cache.insert(key, id.clone());
+                Ok(resolved(id))
+            })
+            .boxed()
+    }
+}
+
+impl fmt::Debug for ResolveSubject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResolveSubject")
+            .field("ttl", &self.cache.ttl())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ResolveSubject, ResolvedSubject};
+    use crate::{util, BoxError, Denied, Gate, GateContext};
+    use http::Request;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    async fn resolve_subject_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let gate = ResolveSubject::new(move |iss: &str, sub: &str| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            let id = (iss == "idp" && sub == "pairwise-1").then(|| String::from("user-42"));
+            async move { Ok::<_, BoxError>(id) }
+        });
+        let (parts, _) = Request::new(()).into_parts();
+        let token = util::token(&serde_json::json!({ "iss": "idp", "sub": "pairwise-1" }));
+
+        for _ in 0..2 {
+            let extensions = gate
+                .check(&GateContext::new(&parts, Some(&token)))
+                .await
+                .expect("Known subject is resolved");
+            let resolved = extensions
+                .get::<ResolvedSubject>()
+                .map(ResolvedSubject::as_str);
+            assert_eq!(resolved, Some("user-42"));
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        for claims in [
+            serde_json::json!({ "iss": "idp", "sub": "pairwise-2" }),
+            serde_json::json!({ "iss": "other", "sub": "pairwise-1" }),
+            serde_json::json!({ "sub": "pairwise-1" }),
+        ] {
+            let token = util::token(&claims);
+            let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+            assert!(matches!(outcome, Err(Denied::UnknownSubject)));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{BoxFuture, Denied, Gate, GateContext};
+use futures::future;
+use http::{header::HOST, request::Parts, Extensions};
+use std::{fmt, sync::Arc};
+
+/// Tenant request was made for, set on request extensions by [`MatchTenant`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tenant(String);
+
+impl Tenant {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+type Resolve = dyn Fn(&Parts) -> Option<String> + Send + Sync;
+
+/// [`Gate`] rejecting cross-tenant token reuse in multi-tenant services.
+///
+/// Tenant is resolved from request, either by [subdomain][MatchTenant::subdomain],
+/// [path segment][MatchTenant::path_segment] or custom function, and must equal tenant claim
+/// (`org_id` by default) of the token. Matched tenant is recorded as [`Tenant`], requests
+/// without resolvable tenant or with tokens of other tenants are denied with [`Denied::Tenant`].
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, MatchTenant};
+///
+/// // `acme.example.com` only accepts tokens with `"tid": "acme"`
+/// let layer = Layer::new(decoder).gate(MatchTenant::subdomain().claim("tid"));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MatchTenant {
+    resolve: Arc<Resolve>,
+    claim: String,
+}
+
+impl MatchTenant {
+    /// Resolves tenant from request with `resolve`
+    pub fn new<F>(resolve: F) -> Self
+    where
+        F: Fn(&Parts) -> Option<String> + Send + Sync + 'static,
+    {
+        Self {
+            resolve: Arc::new(resolve),
+            claim: String::from("org_id"),
+        }
+    }
+
+    /// Resolves tenant from leftmost label of request host, e.g. `acme` of `acme.example.com`
+    pub fn subdomain() -> Self {
+        Self::new(|parts| {
+            let host = match parts.uri.host() {
+                Some(host) => host,
+                None => parts.headers.get(HOST)?.to_str().ok()?,
+            };
+            let (label, _) = host.split_once('.')?;
+            (!label.is_empty()).then(|| label.to_ascii_lowercase())
+        })
+    }
+
+    /// Resolves tenant from zero-based path segment, e.g. `acme` of `/tenants/acme/orders` at `1`
+    pub fn path_segment(index: usize) -> Self {
+        Self::new(move |parts| {
+            let segment = parts
+                .uri
+                .path()
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .nth(index)?;
+            Some(segment.to_owned())
+        })
+    }
+
+    /// Claim carrying token tenant
+    pub fn claim(mut self, claim: impl Into<String>) -> Self {
+        self.claim = claim.into();
+        self
+    }
+
+    fn matched(&self, cx: &GateContext<'_>) -> Option<Tenant> {
+        let tenant = (self.resolve)(cx.parts())?;
+        let claimed = cx.payload()?.str(&self.claim)?;
+        (claimed == tenant).then_some(Tenant(tenant))
+    }
+}
+
+impl Gate for MatchTenant {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let outcome = match self.matched(cx) {
+            Some(tenant) => {
+                let mut extensions = Extensions::new();
+                extensions.insert(tenant);
+                Ok(extensions)
+            }
+            None => Err(Denied::Tenant),
+        };
+        Box::pin(future::ready(outcome))
+    }
+}
+
+impl fmt::Debug for MatchTenant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MatchTenant")
+            .field("claim", &self.claim)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MatchTenant, Tenant};
+    use crate::{util, Denied, Gate, GateContext};
+    use http::Request;
+
+    #[tokio::test]
+    async fn match_tenant() {
+        let gate = MatchTenant::subdomain();
+        let token = util::token(&serde_json::json!({ "org_id": "acme" }));
+
+        let (parts, _) = Request::builder()
+            .uri("https://acme.example.com/orders")
+            .body(())
+            .expect("Valid request")
+            .into_parts();
+        let extensions = gate
+            .check(&GateContext::new(&parts, Some(&token)))
+            .await
+            .expect("Token tenant matches");
+        assert_eq!(extensions.get::<Tenant>().map(Tenant::as_str), Some("acme"));
+
+        let (parts, _) = Request::builder()
+            .uri("/orders")
+            .header("host", "globex.example.com")
+            .body(())
+            .expect("Valid request")
+            .into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Tenant)));
+
+        let gate = MatchTenant::path_segment(1);
+        let (parts, _) = Request::builder()
+            .uri("/tenants/acme/orders")
+            .body(())
+            .expect("Valid request")
+            .into_parts();
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+    }
+}
@@ -0,0 +1,350 @@
+use core::future::Future;
+use core::task::{Context, Poll};
+use futures::ready;
+use http::{header::HeaderName, HeaderValue, Request, Response};
+use pin_project::pin_project;
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicU64, AtomicU8, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tower::Service;
+
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+thread_local! {
+    /// Statistics of decoder stack being built, see [`DecodeStats::current`]
+    static CURRENT: RefCell<Option<DecodeStats>> = const { RefCell::new(None) };
+}
+
+/// Time spent authenticating the request and how decoder got there, set on request extensions
+/// when timing is enabled on [`Layer`][crate::Layer] or [`Middleware`][crate::Middleware].
+///
+/// Response-side layers can fold it into `Server-Timing` headers or access logs
+/// to attribute tail latency to authentication.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthTiming {
+    pub(crate) decode: Duration,
+    pub(crate) gates: Duration,
+    pub(crate) cache: Option<CacheStatus>,
+    pub(crate) key: Option<KeySource>,
+}
+
+impl AuthTiming {
+    /// Time spent in [`Decoder`][crate::Decoder]
+    pub fn decode(&self) -> Duration {
+        self.decode
+    }
+
+    /// Time spent in [gates][crate::Gate]
+    pub fn gates(&self) -> Duration {
+        self.gates
+    }
+
+    pub fn total(&self) -> Duration {
+        self.decode + self.gates
+    }
+
+    /// Whether claims were served from decoder cache, if decoder reported it
+    pub fn cache(&self) -> Option<CacheStatus> {
+        self.cache
+    }
+
+    /// Where token verification key came from, if decoder reported it
+    pub fn key_source(&self) -> Option<KeySource> {
+        self.key
+    }
+}
+
+/// Outcome of decoder cache lookup, see [`Cached`][crate::Cached]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+}
+
+/// Where decoder took token verification key from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySource {
+    /// Key configured upfront
+    Static,
+    /// Previously fetched key set
+    Cached,
+    /// Key set fetched for this token
+    Fetched,
+}
+
+/// Collects decoder statistics reported on [`AuthTiming`].
+///
+/// Available to decoders through [`DecodeStats::current`] while their futures are built,
+/// with timing enabled on [`Layer`][crate::Layer] or [`Middleware`][crate::Middleware]:
+///
+/// ```rust
+/// use tower_jwt::{CacheStatus, DecodeStats};
+///
+/// // inside `Decoder::decode`
+/// if let Some(stats) = DecodeStats::current() {
+///     stats.cache(CacheStatus::Hit);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DecodeStats(Arc<[AtomicU8; 2]>);
+
+impl DecodeStats {
+    /// Statistics of request whose token is being decoded, if timing is enabled.
+    ///
+    /// Only set while [`Decoder::decode`][crate::Decoder::decode] is called, so decoders
+    /// learning outcomes later on keep the handle in their futures.
+    pub fn current() -> Option<Self> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    pub fn cache(&self, status: CacheStatus) {
+        let status = match status {
+            CacheStatus::Hit => 1,
+            CacheStatus::Miss => 2,
+        };
+        self.0[0].store(status, Ordering::Relaxed);
+    }
+
+    pub fn key_source(&self, source: KeySource) {
+        let source = match source {
+            KeySource::Static => 1,
+            KeySource::Cached => 2,
+            KeySource::Fetched => 3,
+        };
+        self.0[1].store(source, Ordering::Relaxed);
+    }
+
+    /// Runs `f` with `self` available through [`DecodeStats::current`]
+    pub(crate) fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let outcome = f();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+        outcome
+    }
+
+    /// Copies reported statistics onto `timing`
+    pub(crate) fn report(&self, timing: &mut AuthTiming) {
+        timing.cache = match self.0[0].load(Ordering::Relaxed) {
+            1 => Some(CacheStatus::Hit),
+            2 => Some(CacheStatus::Miss),
+            _ => None,
+        };
+        timing.key = match self.0[1].load(Ordering::Relaxed) {
+            1 => Some(KeySource::Static),
+            2 => Some(KeySource::Cached),
+            3 => Some(KeySource::Fetched),
+            _ => None,
+        };
+    }
+}
+
+/// Shared between [`ServerTiming`] and [`MiddlewareFuture`][crate::MiddlewareFuture],
+/// which reports total authentication time through request extensions.
+#[derive(Debug, Clone)]
+pub(crate) struct TimingSlot(Arc<AtomicU64>);
+
+impl Default for TimingSlot {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU64::new(u64::MAX)))
+    }
+}
+
+impl TimingSlot {
+    pub(crate) fn set(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX - 1);
+        self.0.store(nanos, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> Option<Duration> {
+        match self.0.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+}
+
+/// Appends `Server-Timing: jwt;dur=<milliseconds>` entry to responses of requests
+/// authenticated by wrapped [`Middleware`][crate::Middleware] (or [`Layer`][crate::Layer]),
+/// making authentication overhead visible in browser devtools and APM.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::Layer;
+///
+/// let layer = Layer::new(decoder).server_timing();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServerTiming<T> {
+    inner: T,
+}
+
+impl<T> ServerTiming<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<L, S> tower::Layer<S> for ServerTiming<L>
+where
+    L: tower::Layer<S>,
+{
+    type Service = ServerTiming<L::Service>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ServerTiming::new(self.inner.layer(inner))
+    }
+}
+
+impl<T, B, RB> Service<Request<B>> for ServerTiming<T>
+where
+    T: Service<Request<B>, Response = Response<RB>>,
+{
+    type Response = T::Response;
+    type Error = T::Error;
+    type Future = ServerTimingFuture<T::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let slot = TimingSlot::default();
+        req.extensions_mut().insert(slot.clone());
+        ServerTimingFuture {
+            inner: self.inner.call(req),
+            slot,
+        }
+    }
+}
+
+#[pin_project]
+pub struct ServerTimingFuture<F> {
+    #[pin]
+    inner: F,
+    slot: TimingSlot,
+}
+
+impl<F, RB, E> Future for ServerTimingFuture<F>
+where
+    F: Future<Output = Result<Response<RB>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut response = ready!(this.inner.poll(cx))?;
+        if let Some(duration) = this.slot.get() {
+            let entry = format!("jwt;dur={:.3}", duration.as_secs_f64() * 1000.0);
+            if let Ok(value) = HeaderValue::from_str(&entry) {
+                response.headers_mut().append(SERVER_TIMING, value);
+            }
+        }
+        Poll::Ready(Ok(response))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AuthTiming, CacheStatus, KeySource};
+    use crate::{util, Cached, Middleware};
+    use http::{HeaderValue, Request, Response};
+    use std::time::Duration;
+    use tower::{service_fn, ServiceExt};
+
+    #[tokio::test]
+    async fn timing_set() {
+        let svc = service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(req.extensions().get::<AuthTiming>().cloned())
+        });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).timing();
+
+        let mut req = Request::new(());
+        let token = util::token(&util::claim(Some(100)));
+        req.headers_mut().insert(
+            "Authorization",
+            format!("Bearer {}", token)
+                .parse::<HeaderValue>()
+                .expect("Failed to parse valid header"),
+        );
+
+        let timing = middleware
+            .oneshot(req)
+            .await
+            .expect("Failed to call middleware")
+            .expect("AuthTiming must be set");
+        assert!(timing.decode() > std::time::Duration::ZERO);
+        assert_eq!(timing.total(), timing.decode());
+        assert_eq!(timing.key_source(), Some(KeySource::Static));
+        assert_eq!(timing.cache(), None);
+    }
+
+    #[tokio::test]
+    async fn timing_cache() {
+        let svc = service_fn(|req: Request<()>| async move {
+            Ok::<_, ()>(req.extensions().get::<AuthTiming>().cloned())
+        });
+        let decoder = Cached::new(util::in_place_decoder(), Duration::from_secs(60));
+        let middleware = Middleware::new(decoder, svc).timing();
+        let token = util::token(&util::claim(Some(100)));
+
+        let mut statuses = Vec::new();
+        for _ in 0..2 {
+            let mut req = Request::new(());
+            req.headers_mut().insert(
+                "Authorization",
+                format!("Bearer {}", token)
+                    .parse::<HeaderValue>()
+                    .expect("Failed to parse valid header"),
+            );
+            let timing = middleware
+                .clone()
+                .oneshot(req)
+                .await
+                .expect("Failed to call middleware")
+                .expect("AuthTiming must be set");
+            statuses.push((timing.cache(), timing.key_source()));
+        }
+        assert_eq!(
+            statuses,
+            [
+                (Some(CacheStatus::Miss), Some(KeySource::Static)),
+                (Some(CacheStatus::Hit), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn server_timing_header() {
+        let svc = service_fn(|_: Request<()>| async move { Ok::<_, ()>(Response::new(())) });
+        let middleware = Middleware::new(util::in_place_decoder(), svc).server_timing();
+
+        let mut req = Request::new(());
+        let token = util::token(&util::claim(Some(100)));
+        req.headers_mut().insert(
+            "Authorization",
+            format!("Bearer {}", token)
+                .parse::<HeaderValue>()
+                .expect("Failed to parse valid header"),
+        );
+
+        let response = middleware
+            .oneshot(req)
+            .await
+            .expect("Failed to call middleware");
+        let entry = response
+            .headers()
+            .get("server-timing")
+            .and_then(|value| value.to_str().ok())
+            .expect("Server-Timing must be set");
+        assert!(entry.starts_with("jwt;dur="));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{fast, DecodeFailure, Decoder};
+use core::future::Future;
+use futures::ready;
+use pin_project::pin_project;
+use std::{
+    error::Error,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+use tracing::{field::Empty, Span};
+
+/// Wraps any [`Decoder`] running every decode in a dedicated `decode` span, so distributed
+/// traces show where authentication time goes.
+///
+/// Span carries `key_source` label, token `kid`, `elapsed_ms` and `outcome` of decoding, as
+/// well as `cache` hit or miss recorded by caching decoders, e.g. [`GoogleIap`][crate::GoogleIap].
+/// Errors are recorded as span events along with their cause chain.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder>(decoder: D) {
+/// use tower_jwt::Traced;
+///
+/// let decoder = Traced::new(decoder).key_source("jwks");
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Traced<D> {
+    decoder: D,
+    key_source: &'static str,
+}
+
+impl<D> Traced<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            key_source: "static",
+        }
+    }
+
+    /// Label of where decoder keys come from, `static` by default
+    pub fn key_source(mut self, key_source: &'static str) -> Self {
+        self.key_source = key_source;
+        self
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+impl<D> Decoder for Traced<D>
+where
+    D: Decoder,
+    D::Error: Error + 'static,
+{
+    type Error = D::Error;
+    type Claim = D::Claim;
+    type Future = TracedFuture<D::Future>;
+
+    fn decode(&self, token: &str) -> Self::Future {
+        let span = tracing::info_span!(
+            "decode",
+            key_source = self.key_source,
+            kid = Empty,
+            cache = Empty,
+            elapsed_ms = Empty,
+            outcome = Empty,
+        );
+        if let Some(kid) = fast::decode_header(token)
+            .ok()
+            .and_then(|header| header.kid)
+        {
+            span.record("kid", kid.as_str());
+        }
+        let inner = span.in_scope(|| self.decoder.decode(token));
+        TracedFuture {
+            inner,
+            span,
+            started: Instant::now(),
+        }
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        D::failure(err)
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        D::error(err)
+    }
+}
+
+/// Error along with its sources, `error: cause: root cause`
+fn chain(err: &(dyn Error + 'static)) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        chain.push_str(": ");
+        chain.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    chain
+}
+
+#[pin_project]
+pub struct TracedFuture<F> {
+    #[pin]
+    inner: F,
+    span: Span,
+    started: Instant,
+}
+
+impl<F, T, E> Future for TracedFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _entered = this.span.enter();
+        let outcome = ready!(this.inner.poll(cx));
+        let elapsed = this.started.elapsed();
+        this.span
+            .record("elapsed_ms", elapsed.as_secs_f64() * 1000.0);
+        match &outcome {
+            Ok(_) => {
+                this.span.record("outcome", "accepted");
+            }
+            Err(err) => {
+                this.span.record("outcome", "rejected");
+                tracing::warn!(error = %chain(err), "Token rejected");
+            }
+        }
+        Poll::Ready(outcome)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{chain, Traced};
+    use crate::{util, Decoder, IapError};
+
+    #[tokio::test]
+    async fn traced() {
+        let decoder = Traced::new(util::in_place_decoder()).key_source("pem");
+        let token = util::token(&util::claim(Some(100)));
+        assert!(decoder.decode(&token).await.is_ok());
+        assert!(decoder.decode("not-a-token").await.is_err());
+
+        let err = jsonwebtoken::decode_header("not-a-token").expect_err("Malformed token");
+        assert_eq!(
+            chain(&IapError::Decode(err.clone())),
+            format!("Failed to decode assertion: {}", err)
+        );
+    }
+}
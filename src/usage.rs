@@ -0,0 +1,244 @@
+use crate::{fast, DecodeFailure, Decoder};
+use core::future::Future;
+use futures::ready;
+use pin_project::pin_project;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+    time::SystemTime,
+};
+
+/// Usage of a single key, see [`KeyStats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyUsage {
+    verified: u64,
+    last_seen: SystemTime,
+}
+
+impl KeyUsage {
+    /// Number of tokens verified with the key
+    pub fn verified(&self) -> u64 {
+        self.verified
+    }
+
+    /// When last token verified with the key was seen
+    pub fn last_seen(&self) -> SystemTime {
+        self.last_seen
+    }
+}
+
+type Callback = dyn Fn(&str, &KeyUsage) + Send + Sync;
+
+#[derive(Default)]
+struct Inner {
+    usage: HashMap<String, KeyUsage>,
+    retiring: HashSet<String>,
+}
+
+/// Handle to per-`kid` verification statistics collected by [`TrackKeys`]
+#[derive(Clone, Default)]
+pub struct KeyStats {
+    inner: Arc<Mutex<Inner>>,
+    on_retiring: Option<Arc<Callback>>,
+}
+
+impl KeyStats {
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Usage of key identified by `kid`, if any token was verified with it
+    pub fn get(&self, kid: &str) -> Option<KeyUsage> {
+        self.lock().usage.get(kid).copied()
+    }
+
+    /// Usage of every key seen so far
+    pub fn snapshot(&self) -> Vec<(String, KeyUsage)> {
+        let inner = self.lock();
+        let mut usage: Vec<_> = inner
+            .usage
+            .iter()
+            .map(|(kid, usage)| (kid.clone(), *usage))
+            .collect();
+        usage.sort_by(|(a, _), (b, _)| a.cmp(b));
+        usage
+    }
+
+    /// Schedule key identified by `kid` for retirement
+    pub fn retire(&self, kid: impl Into<String>) {
+        self.lock().retiring.insert(kid.into());
+    }
+
+    fn record(&self, kid: &str) {
+        let (usage, retiring) = {
+            let mut inner = self.lock();
+            let usage = inner
+                .usage
+                .entry(kid.to_owned())
+                .and_modify(|usage| usage.verified += 1)
+                .or_insert(KeyUsage {
+                    verified: 1,
+                    last_seen: SystemTime::UNIX_EPOCH,
+                });
+            usage.last_seen = SystemTime::now();
+            let usage = *usage;
+            (usage, inner.retiring.contains(kid))
+        };
+        if retiring {
+            tracing::warn!(kid, "Token signed by key scheduled for retirement");
+            if let Some(on_retiring) = &self.on_retiring {
+                on_retiring(kid, &usage);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for KeyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyStats")
+            .field("keys", &self.lock().usage.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Wraps any [`Decoder`] counting verified tokens per `kid`, making key rotation rollouts
+/// observable.
+///
+/// Counts and last-seen timestamps are exposed through [`KeyStats`] handle. Tokens still
+/// signed by keys [scheduled for retirement][KeyStats::retire] are logged and reported
+/// to optional callback, so old keys are only dropped once traffic moved over.
+/// Only accepted tokens with `kid` are counted.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder>(decoder: D) {
+/// use tower_jwt::TrackKeys;
+///
+/// let decoder = TrackKeys::new(decoder).on_retiring(|kid, usage| {
+///     eprintln!("{} still in use, {} tokens so far", kid, usage.verified());
+/// });
+/// let stats = decoder.stats();
+/// stats.retire("2023-key");
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TrackKeys<D> {
+    decoder: D,
+    stats: KeyStats,
+}
+
+impl<D> TrackKeys<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            stats: KeyStats::default(),
+        }
+    }
+
+    /// Call `callback` whenever token signed by key scheduled for retirement is accepted
+    pub fn on_retiring<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str, &KeyUsage) + Send + Sync + 'static,
+    {
+        self.stats.on_retiring = Some(Arc::new(callback));
+        self
+    }
+
+    /// Handle to collected statistics
+    pub fn stats(&self) -> KeyStats {
+        self.stats.clone()
+    }
+
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+impl<D: Decoder> Decoder for TrackKeys<D> {
+    type Error = D::Error;
+    type Claim = D::Claim;
+    type Future = TrackKeysFuture<D::Future>;
+
+    fn decode(&self, token: &str) -> Self::Future {
+        let kid = fast::decode_header(token)
+            .ok()
+            .and_then(|header| header.kid);
+        TrackKeysFuture {
+            inner: self.decoder.decode(token),
+            kid: kid.map(|kid| (kid, self.stats.clone())),
+        }
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        D::failure(err)
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        D::error(err)
+    }
+}
+
+#[pin_project]
+pub struct TrackKeysFuture<F> {
+    #[pin]
+    inner: F,
+    kid: Option<(String, KeyStats)>,
+}
+
+impl<F, T, E> Future for TrackKeysFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let outcome = ready!(this.inner.poll(cx));
+        if let (Ok(_), Some((kid, stats))) = (&outcome, this.kid.take()) {
+            stats.record(&kid);
+        }
+        Poll::Ready(outcome)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TrackKeys;
+    use crate::{util, Decoder};
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    async fn track_keys() {
+        let reported = Arc::new(AtomicUsize::new(0));
+        let counter = reported.clone();
+        let decoder = TrackKeys::new(util::in_place_decoder()).on_retiring(move |_, _| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        let stats = decoder.stats();
+
+        let key = EncodingKey::from_ed_pem(util::PRIVATE_KEY.as_bytes()).expect("Valid key");
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(String::from("old"));
+        let token = encode(&header, &util::claim(Some(100)), &key).expect("Valid token");
+
+        decoder.decode(&token).await.expect("Valid token");
+        assert_eq!(reported.load(Ordering::Relaxed), 0);
+
+        stats.retire("old");
+        decoder.decode(&token).await.expect("Valid token");
+        assert_eq!(reported.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.get("old").map(|usage| usage.verified()), Some(2));
+
+        let expired = encode(&header, &util::claim(None), &key).expect("Valid token");
+        assert!(decoder.decode(&expired).await.is_err());
+        assert_eq!(stats.get("old").map(|usage| usage.verified()), Some(2));
+    }
+}
@@ -4,6 +4,10 @@ use crate::{InPlace, InPlaceBuilder};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{encode, DecodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
 
 // please don't use that keypair in your project. You can generate your own key with openssl:
 // openssl genpkey -algorithm ed25519 -out secret_key
@@ -60,7 +64,7 @@ pub(crate) fn claim(expiry: Option<i64>) -> Claim {
     }
 }
 
-pub(crate) fn token(claim: &Claim) -> String {
+pub(crate) fn token(claim: &impl Serialize) -> String {
     let header = Header::new(jsonwebtoken::Algorithm::EdDSA);
     let key = jsonwebtoken::EncodingKey::from_ed_pem(PRIVATE_KEY.as_bytes())
         .expect("Failed to create encoding key from valid bytes");
@@ -74,3 +78,38 @@ pub(crate) fn in_place_decoder() -> InPlace<Claim> {
     )
     .build()
 }
+
+thread_local! {
+    /// Allocations made by current thread while counted, see [`allocations`]
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// System allocator counting allocations of threads running [`allocations`]
+struct Counting;
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get().map(|n| n + 1)));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get().map(|n| n + 1)));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Runs `f`, counting heap allocations it made on current thread
+pub(crate) fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    ALLOCATIONS.with(|count| count.set(Some(0)));
+    let outcome = f();
+    let count = ALLOCATIONS.with(|count| count.take());
+    (outcome, count.unwrap_or_default())
+}
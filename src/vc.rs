@@ -0,0 +1,329 @@
+use crate::{
+    BoxError, DecodeFailure, Decoder, DidResolver, Payload, SyncBoxFuture, ValidationProfile,
+};
+use jsonwebtoken::Validation;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Value};
+use std::{collections::HashSet, marker::PhantomData, sync::Arc};
+use thiserror::Error;
+
+const CREDENTIALS_CONTEXT: &str = "https://www.w3.org/2018/credentials/v1";
+const CREDENTIAL_TYPE: &str = "VerifiableCredential";
+
+/// W3C Verifiable Credential verified by [`JwtVc`], set on request extensions in place of claim
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Credential<C> {
+    issuer: String,
+    id: Option<String>,
+    types: Vec<String>,
+    subject: C,
+}
+
+impl<C> Credential<C> {
+    /// DID of credential issuer
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Credential id, from `jti`
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Credential types, always including `VerifiableCredential`
+    pub fn types(&self) -> &[String] {
+        &self.types
+    }
+
+    pub fn subject(&self) -> &C {
+        &self.subject
+    }
+
+    pub fn into_subject(self) -> C {
+        self.subject
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VcError {
+    #[error("Credential issuer must be a DID")]
+    Issuer,
+
+    #[error("Credential issuer is not trusted: {0}")]
+    UntrustedIssuer(String),
+
+    #[error("Failed to resolve issuer key")]
+    Resolve(#[source] BoxError),
+
+    #[error("Failed to decode credential")]
+    Decode(#[source] jsonwebtoken::errors::Error),
+
+    #[error("Malformed credential: {0}")]
+    Malformed(&'static str),
+
+    #[error("Credential subject doesn't match claim")]
+    Subject(#[source] serde_json::Error),
+}
+
+/// Decoder verifying [W3C Verifiable Credentials](https://www.w3.org/TR/vc-data-model/#json-web-token)
+/// in JWT form, exposing credential subject as [`Credential`].
+///
+/// Only credentials of trusted `issuers` are accepted: resolving DID proves possession of the
+/// key, not that issuer is entitled to vouch for the subject (anyone can mint `did:key` and
+/// issue credentials to themselves). Key is resolved from DID in `iss` through
+/// [`DidResolver`], signature and expiry are verified according to `validation`, and `vc`
+/// claim must be a well-formed credential consistent with registered claims: `vc.issuer`
+/// matching `iss` and `credentialSubject.id` matching `sub`.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # async fn lookup(did: &str) -> Result<jsonwebtoken::DecodingKey, tower_jwt::BoxError> { todo!() }
+/// # fn example(validation: jsonwebtoken::Validation) {
+/// use tower_jwt::JwtVc;
+///
+/// #[derive(Deserialize)]
+/// struct Degree { id: String, degree: serde_json::Value }
+///
+/// let issuers = ["did:web:university.example"];
+/// let decoder = JwtVc::<Degree>::new(validation, issuers, |did: &str, _kid: Option<&str>| {
+///     let did = did.to_owned();
+///     async move { lookup(&did).await }
+/// });
+/// # }
+/// ```
+pub struct JwtVc<C> {
+    validation: Validation,
+    profile: Option<ValidationProfile>,
+    issuers: Arc<HashSet<String>>,
+    resolver: Arc<dyn DidResolver>,
+    _claim: PhantomData<fn() -> C>,
+}
+
+impl<C> Clone for JwtVc<C> {
+    fn clone(&self) -> Self {
+        Self {
+            validation: self.validation.clone(),
+            profile: self.profile,
+            issuers: self.issuers.clone(),
+            resolver: self.resolver.clone(),
+            _claim: PhantomData,
+        }
+    }
+}
+
+impl<C> JwtVc<C> {
+    /// Accepts credentials issued by DIDs in `issuers`, with keys resolved by `resolver`
+    pub fn new<I, R>(validation: Validation, issuers: I, resolver: R) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+        R: DidResolver,
+    {
+        Self {
+            validation,
+            profile: None,
+            issuers: Arc::new(issuers.into_iter().map(Into::into).collect()),
+            resolver: Arc::new(resolver),
+            _claim: PhantomData,
+        }
+    }
+
+    /// Apply [`ValidationProfile`] to credential validation
+    pub fn with_profile(mut self, profile: ValidationProfile) -> Self {
+        profile.apply(&mut self.validation);
+        self.profile = Some(profile);
+        self
+    }
+}
+
+impl<C: DeserializeOwned> Credential<C> {
+    fn from_claims(mut claims: Map<String, Value>) -> Result<Self, VcError> {
+        let issuer = match claims.remove("iss") {
+            Some(Value::String(issuer)) => issuer,
+            _ => return Err(VcError::Issuer),
+        };
+        let mut vc = match claims.remove("vc") {
+            Some(Value::Object(vc)) => vc,
+            _ => return Err(VcError::Malformed("`vc` claim must be an object")),
+        };
+
+        let contexts = vc.get("@context").and_then(Value::as_array);
+        if contexts.and_then(|c| c.first()).and_then(Value::as_str) != Some(CREDENTIALS_CONTEXT) {
+            return Err(VcError::Malformed(
+                "`@context` must start with credentials context",
+            ));
+        }
+
+        let types: Vec<String> = vc
+            .get("type")
+            .and_then(Value::as_array)
+            .map(|types| {
+                types
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !types.iter().any(|t| t == CREDENTIAL_TYPE) {
+            return Err(VcError::Malformed(
+                "`type` must include VerifiableCredential",
+            ));
+        }
+
+        let vc_issuer = match vc.get("issuer") {
+            Some(Value::String(id)) => Some(id.as_str()),
+            Some(Value::Object(issuer)) => issuer.get("id").and_then(Value::as_str),
+            _ => None,
+        };
+        if vc_issuer.is_some_and(|id| id != issuer) {
+            return Err(VcError::Malformed("`vc.issuer` doesn't match `iss`"));
+        }
+
+        let subject = match vc.remove("credentialSubject") {
+            Some(Value::Object(subject)) => subject,
+            _ => return Err(VcError::Malformed("`credentialSubject` must be an object")),
+        };
+        let subject_id = subject.get("id").and_then(Value::as_str);
+        if let (Some(sub), Some(id)) = (claims.get("sub").and_then(Value::as_str), subject_id) {
+            if sub != id {
+                return Err(VcError::Malformed(
+                    "`credentialSubject.id` doesn't match `sub`",
+                ));
+            }
+        }
+
+        let id = claims.get("jti").and_then(Value::as_str).map(String::from);
+        Ok(Self {
+            issuer,
+            id,
+            types,
+            subject: serde_json::from_value(Value::Object(subject)).map_err(VcError::Subject)?,
+        })
+    }
+}
+
+impl<C> Decoder for JwtVc<C>
+where
+    C: DeserializeOwned + Send + 'static,
+{
+    type Error = VcError;
+    type Claim = Credential<C>;
+    type Future = SyncBoxFuture<Self::Claim, Self::Error>;
+
+    #[tracing::instrument(skip_all)]
+    fn decode(&self, token: &str) -> Self::Future {
+        tracing::trace!("JwtVc::entered");
+        let token = token.to_owned();
+        let validation = self.validation.clone();
+        let profile = self.profile;
+        let issuers = self.issuers.clone();
+        let resolver = self.resolver.clone();
+        SyncBoxFuture::new(Box::pin(async move {
+            let header = jsonwebtoken::decode_header(&token).map_err(VcError::Decode)?;
+            let issuer = Payload::from_token(&token)
+                .and_then(|payload| payload.str("iss").map(String::from))
+                .filter(|issuer| issuer.starts_with("did:"))
+                .ok_or(VcError::Issuer)?;
+            if !issuers.contains(&issuer) {
+                return Err(VcError::UntrustedIssuer(issuer));
+            }
+            let key = resolver
+                .resolve(&issuer, header.kid.as_deref())
+                .await
+                .map_err(VcError::Resolve)?;
+            tracing::trace!("JwtVc::resolved");
+            let claims = jsonwebtoken::decode::<Map<String, Value>>(&token, &key, &validation)
+                .map_err(VcError::Decode)?
+                .claims;
+            if let Some(profile) = profile {
+                profile.check(&token).map_err(VcError::Decode)?;
+            }
+            Credential::from_claims(claims)
+        }))
+    }
+
+    fn failure(err: &Self::Error) -> Option<DecodeFailure> {
+        Some(DecodeFailure::of(err))
+    }
+
+    fn error(err: &Self::Error) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JwtVc, VcError};
+    use crate::{util, BoxError, Decoder};
+    use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Degree {
+        id: String,
+        degree: String,
+    }
+
+    fn vc_claims(issuer: &str) -> serde_json::Value {
+        json!({
+            "iss": issuer,
+            "sub": "did:example:holder",
+            "jti": "urn:uuid:3978344f",
+            "exp": chrono::Utc::now().timestamp() + 100,
+            "vc": {
+                "@context": ["https://www.w3.org/2018/credentials/v1"],
+                "type": ["VerifiableCredential", "UniversityDegreeCredential"],
+                "credentialSubject": { "id": "did:example:holder", "degree": "BSc" },
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn verify_credential() {
+        let decoder = JwtVc::<Degree>::new(
+            Validation::new(Algorithm::EdDSA),
+            ["did:example:university", "did:example:diploma-mill"],
+            |did: &str, _: Option<&str>| {
+                let key = match did {
+                    "did:example:university" => {
+                        DecodingKey::from_ed_pem(util::PUBLIC_KEY.as_bytes())
+                            .map_err(BoxError::from)
+                    }
+                    _ => Err(BoxError::from("Unknown DID")),
+                };
+                async move { key }
+            },
+        );
+
+        let credential = decoder
+            .decode(&util::token(&vc_claims("did:example:university")))
+            .await
+            .expect("Failed to verify credential");
+        assert_eq!(credential.issuer(), "did:example:university");
+        assert_eq!(credential.id(), Some("urn:uuid:3978344f"));
+        assert_eq!(credential.subject().degree, "BSc");
+
+        let outcome = decoder
+            .decode(&util::token(&vc_claims("did:example:diploma-mill")))
+            .await;
+        assert!(matches!(outcome, Err(VcError::Resolve(_))));
+
+        // resolves, but nobody vouched for it
+        let outcome = decoder
+            .decode(&util::token(&vc_claims("did:example:self-issued")))
+            .await;
+        assert!(matches!(outcome, Err(VcError::UntrustedIssuer(_))));
+
+        let outcome = decoder
+            .decode(&util::token(&vc_claims("https://university.example")))
+            .await;
+        assert!(matches!(outcome, Err(VcError::Issuer)));
+
+        let mut forged = vc_claims("did:example:university");
+        forged["vc"]["issuer"] = json!("did:example:other");
+        let outcome = decoder.decode(&util::token(&forged)).await;
+        assert!(matches!(outcome, Err(VcError::Malformed(_))));
+    }
+}
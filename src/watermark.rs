@@ -0,0 +1,132 @@
+use crate::{cache::TtlCache, BoxError, BoxFuture, Denied, Gate, GateContext};
+use core::future::Future;
+use futures::{future, FutureExt, TryFutureExt};
+use http::Extensions;
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Keeps per-subject invalidation watermarks, see [`Watermark`].
+pub trait WatermarkStore: Send + Sync + 'static {
+    /// Returns unix timestamp tokens of `sub` must be issued at or after, if any
+    fn watermark(&self, sub: &str) -> BoxFuture<Option<u64>, BoxError>;
+}
+
+impl<F, Fut> WatermarkStore for F
+where
+    F: Fn(&str) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Option<u64>, BoxError>> + Send + 'static,
+{
+    fn watermark(&self, sub: &str) -> BoxFuture<Option<u64>, BoxError> {
+        Box::pin(self(sub))
+    }
+}
+
+/// [`Gate`] implementing "tokens issued before T for subject S are invalid" pattern,
+/// so that password change or logout-everywhere can invalidate outstanding tokens
+/// instantly without maintaining full revocation lists.
+///
+/// Store is consulted with token `sub` and tokens with `iat` older than returned watermark
+/// are denied with [`Denied::Invalidated`], as are tokens missing either claim.
+/// Watermarks aren't cached unless [ttl][Watermark::ttl] is set.
+///
+/// ```rust
+/// # async fn password_changed_at(sub: &str) -> Result<Option<u64>, tower_jwt::BoxError> { todo!() }
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower_jwt::{Layer, Watermark};
+///
+/// let layer = Layer::new(decoder).gate(Watermark::new(|sub: &str| {
+///     let sub = sub.to_owned();
+///     async move { password_changed_at(&sub).await }
+/// }));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Watermark {
+    store: Arc<dyn WatermarkStore>,
+    cache: TtlCache<Option<u64>>,
+}
+
+impl Watermark {
+    pub fn new<S: WatermarkStore>(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            cache: TtlCache::new(Duration::ZERO),
+        }
+    }
+
+    /// How long watermarks are cached for, which delays invalidation by up to `ttl`
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.cache = TtlCache::new(ttl);
+        self
+    }
+}
+
+fn verdict(iat: u64, watermark: Option<u64>) -> Result<Extensions, Denied> {
+    match watermark {
+        Some(watermark) if iat < watermark => Err(Denied::Invalidated),
+        _ => Ok(Extensions::new()),
+    }
+}
+
+impl Gate for Watermark {
+    fn check(&self, cx: &GateContext<'_>) -> BoxFuture<Extensions, Denied> {
+        let payload = cx.payload();
+        let sub = payload.and_then(|payload| payload.str("sub"));
+        let iat = payload
+            .and_then(|payload| payload.i64("iat"))
+            .and_then(|iat| u64::try_from(iat).ok());
+        let (sub, iat) = match (sub, iat) {
+            (Some(sub), Some(iat)) => (sub.to_owned(), iat),
+            _ => return Box::pin(future::ready(Err(Denied::Invalidated))),
+        };
+        if let Some(watermark) = self.cache.get(&sub) {
+            return Box::pin(future::ready(verdict(iat, watermark)));
+        }
+
+        let cache = self.cache.clone();
+        self.store
+            .watermark(&sub)
+            .map_err(Denied::Other)
+            .map(move |watermark| {
+                let watermark = watermark?;
+                cache.insert(sub, watermark);
+                verdict(iat, watermark)
+            })
+            .boxed()
+    }
+}
+
+impl fmt::Debug for Watermark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watermark")
+            .field("ttl", &self.cache.ttl())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Watermark;
+    use crate::{util, BoxError, Denied, Gate, GateContext};
+    use http::Request;
+
+    #[tokio::test]
+    async fn watermark() {
+        let gate = Watermark::new(|sub: &str| {
+            let watermark = (sub == "changed-password").then_some(1_000);
+            async move { Ok::<_, BoxError>(watermark) }
+        });
+        let (parts, _) = Request::new(()).into_parts();
+
+        let token = util::token(&serde_json::json!({ "sub": "changed-password", "iat": 999 }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(matches!(outcome, Err(Denied::Invalidated)));
+
+        let token = util::token(&serde_json::json!({ "sub": "changed-password", "iat": 1_000 }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+
+        let token = util::token(&serde_json::json!({ "sub": "other", "iat": 1 }));
+        let outcome = gate.check(&GateContext::new(&parts, Some(&token))).await;
+        assert!(outcome.is_ok());
+    }
+}
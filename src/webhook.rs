@@ -0,0 +1,282 @@
+use crate::{fingerprint::sha256_hex, Decoder, Payload};
+use core::future::Future;
+use futures::ready;
+use http::{header::HeaderName, Request};
+use pin_project::pin_project;
+use ring::constant_time;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tower::Service;
+
+/// Identity of webhook sender, from `iss` of verified token
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WebhookSender(String);
+
+impl WebhookSender {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+/// [`Layer`][tower::Layer] for webhook receivers, verifying a token which signs the request body.
+///
+/// Token is read from configured header (`Webhook-Signature` by default, optionally prefixed
+/// with `Bearer `) and decoded with the decoder. Its body hash claim (`body_sha256` by default)
+/// must then equal hex-encoded SHA-256 of the body, so payloads can't be swapped under a valid
+/// token. Claim is set on request extensions, as is [`WebhookSender`] when token has `iss`.
+///
+/// Body must already be buffered, i.e. implement `AsRef<[u8]>` (`Bytes`, `Vec<u8>`, `String`, ..).
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use http::header::HeaderName;
+/// use tower_jwt::Webhook;
+///
+/// let layer = Webhook::new(decoder)
+///     .header(HeaderName::from_static("x-hub-token"))
+///     .body_hash_claim("payload_hash");
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Webhook<D> {
+    decoder: D,
+    header: HeaderName,
+    claim: String,
+}
+
+impl<D> Webhook<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            header: HeaderName::from_static("webhook-signature"),
+            claim: String::from("body_sha256"),
+        }
+    }
+
+    /// Header carrying the token
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Claim carrying hex-encoded SHA-256 of the body
+    pub fn body_hash_claim(mut self, claim: impl Into<String>) -> Self {
+        self.claim = claim.into();
+        self
+    }
+}
+
+impl<D, S> tower::Layer<S> for Webhook<D>
+where
+    D: Clone,
+{
+    type Service = WebhookService<D, S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        WebhookService {
+            webhook: self.clone(),
+            service: inner,
+        }
+    }
+}
+
+/// [`Service`] produced by [`Webhook`]
+#[derive(Debug, Clone)]
+pub struct WebhookService<D, S> {
+    webhook: Webhook<D>,
+    service: S,
+}
+
+#[derive(Error, Debug)]
+pub enum WebhookError<E, D> {
+    #[error("Webhook signature header must be set")]
+    MissingSignature,
+
+    #[error("Failed to decode webhook token")]
+    Decoder(#[source] D),
+
+    #[error("Webhook body doesn't match signed hash")]
+    BodyHash,
+
+    #[error(transparent)]
+    Inner(#[from] E),
+}
+
+impl<D, S, B> Service<Request<B>> for WebhookService<D, S>
+where
+    S: Service<Request<B>> + Clone,
+    D: Decoder,
+    D::Claim: Send + Sync + 'static,
+    B: AsRef<[u8]>,
+{
+    type Response = S::Response;
+    type Error = WebhookError<S::Error, D::Error>;
+    type Future = WebhookFuture<B, S, D>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(WebhookError::Inner)
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        tracing::trace!("WebhookService::entered");
+        let token = req
+            .headers()
+            .get(&self.webhook.header)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.strip_prefix("Bearer ").unwrap_or(value).trim());
+        let token = match token {
+            Some(token) if !token.is_empty() => token.to_owned(),
+            _ => {
+                return WebhookFuture {
+                    pending: None,
+                    state: WebhookState::Failed(Some(WebhookError::MissingSignature)),
+                }
+            }
+        };
+
+        let clone = self.service.clone();
+        let service = core::mem::replace(&mut self.service, clone);
+        WebhookFuture {
+            state: WebhookState::Decoding {
+                future: self.webhook.decoder.decode(&token),
+            },
+            pending: Some(Pending {
+                service,
+                digest: sha256_hex(req.body().as_ref()),
+                request: req,
+                token,
+                claim: self.webhook.claim.clone(),
+            }),
+        }
+    }
+}
+
+struct Pending<B, S> {
+    service: S,
+    request: Request<B>,
+    token: String,
+    digest: String,
+    claim: String,
+}
+
+impl<B, S> Pending<B, S> {
+    /// Checks body hash claim, returning sender identity
+    fn verify(&self) -> Option<Option<WebhookSender>> {
+        let payload = Payload::from_token(&self.token)?;
+        let expected = payload.str(&self.claim)?.to_ascii_lowercase();
+        constant_time::verify_slices_are_equal(self.digest.as_bytes(), expected.as_bytes()).ok()?;
+        Some(payload.str("iss").map(|iss| WebhookSender(iss.to_owned())))
+    }
+}
+
+#[pin_project]
+pub struct WebhookFuture<B, S, D>
+where
+    S: Service<Request<B>>,
+    D: Decoder,
+{
+    pending: Option<Pending<B, S>>,
+    #[pin]
+    state: WebhookState<B, S, D>,
+}
+
+#[pin_project(project = WebhookStateProj)]
+enum WebhookState<B, S, D>
+where
+    S: Service<Request<B>>,
+    D: Decoder,
+{
+    Decoding {
+        #[pin]
+        future: D::Future,
+    },
+    Calling {
+        #[pin]
+        future: S::Future,
+    },
+    Failed(Option<WebhookError<S::Error, D::Error>>),
+}
+
+impl<B, S, D> Future for WebhookFuture<B, S, D>
+where
+    S: Service<Request<B>>,
+    D: Decoder,
+    D::Claim: Send + Sync + 'static,
+{
+    type Output = Result<S::Response, WebhookError<S::Error, D::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            let next = match this.state.as_mut().project() {
+                WebhookStateProj::Decoding { future } => {
+                    let claim = ready!(future.poll(cx)).map_err(WebhookError::Decoder)?;
+                    tracing::trace!("WebhookFuture::decoded");
+                    let mut pending = this.pending.take().expect("Polled after completion");
+                    let sender = pending.verify().ok_or(WebhookError::BodyHash)?;
+                    let extensions = pending.request.extensions_mut();
+                    extensions.insert::<D::Claim>(claim);
+                    if let Some(sender) = sender {
+                        extensions.insert(sender);
+                    }
+                    WebhookState::Calling {
+                        future: pending.service.call(pending.request),
+                    }
+                }
+                WebhookStateProj::Calling { future } => {
+                    return future.poll(cx).map_err(WebhookError::Inner)
+                }
+                WebhookStateProj::Failed(err) => {
+                    return Poll::Ready(Err(err.take().expect("Polled after completion")))
+                }
+            };
+            this.state.set(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Webhook, WebhookError, WebhookSender};
+    use crate::{fingerprint::sha256_hex, util};
+    use http::Request;
+    use tower::{service_fn, Layer, ServiceExt};
+
+    #[tokio::test]
+    async fn webhook() {
+        let svc = service_fn(|req: Request<&'static str>| async move {
+            Ok::<_, ()>(req.extensions().get::<WebhookSender>().cloned())
+        });
+        let webhook = Webhook::new(util::in_place_decoder()).layer(svc);
+
+        let body = r#"{"event":"invoice.paid"}"#;
+        let mut claim = serde_json::to_value(util::claim(Some(100))).expect("Valid claim");
+        claim["body_sha256"] = sha256_hex(body.as_bytes()).into();
+        let token = util::token(&claim);
+
+        let req = Request::builder()
+            .header("webhook-signature", &token)
+            .body(body)
+            .expect("Valid request");
+        let sender = webhook.clone().oneshot(req).await.expect("Valid webhook");
+        assert_eq!(sender.as_ref().map(WebhookSender::as_str), Some("issuer"));
+
+        let req = Request::builder()
+            .header("webhook-signature", &token)
+            .body(r#"{"event":"invoice.refunded"}"#)
+            .expect("Valid request");
+        let outcome = webhook.clone().oneshot(req).await;
+        assert!(matches!(outcome, Err(WebhookError::BodyHash)));
+
+        let outcome = webhook.oneshot(Request::new(body)).await;
+        assert!(matches!(outcome, Err(WebhookError::MissingSignature)));
+    }
+}
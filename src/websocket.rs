@@ -0,0 +1,212 @@
+use crate::TokenExtractor;
+use core::future::Future;
+use futures::ready;
+use http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderMap, HeaderValue, Request, Response, StatusCode};
+use pin_project::pin_project;
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::Service;
+
+/// [`TokenExtractor`] for WebSocket upgrades, reading the token off `Sec-WebSocket-Protocol`.
+///
+/// Browsers can't set `Authorization` on upgrade requests, so clients offer the token as
+/// a subprotocol right after a marker (`access_token` by default):
+///
+/// ```text
+/// new WebSocket(url, ["access_token", token, "graphql-ws"])
+/// ```
+///
+/// Browsers fail the handshake unless server selects one of offered subprotocols, which
+/// [`echo`][WebSocketProtocol::echo] layer takes care of.
+///
+/// ```rust
+/// # fn example<D: tower_jwt::Decoder + Clone>(decoder: D) {
+/// use tower::ServiceBuilder;
+/// use tower_jwt::{Layer, WebSocketProtocol};
+///
+/// let protocol = WebSocketProtocol::new();
+/// let layers = ServiceBuilder::new()
+///     .layer(protocol.echo())
+///     .layer(Layer::new(decoder).extractor(protocol));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct WebSocketProtocol {
+    marker: Arc<str>,
+}
+
+impl Default for WebSocketProtocol {
+    fn default() -> Self {
+        Self {
+            marker: Arc::from("access_token"),
+        }
+    }
+}
+
+impl WebSocketProtocol {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subprotocol preceding the token
+    pub fn marker(mut self, marker: impl Into<String>) -> Self {
+        self.marker = Arc::from(marker.into());
+        self
+    }
+
+    /// [`Layer`][tower::Layer] selecting the first subprotocol offered besides marker and
+    /// token on successful upgrades, or the marker when client offered nothing else.
+    pub fn echo(&self) -> EchoSubprotocol {
+        EchoSubprotocol {
+            marker: self.marker.clone(),
+        }
+    }
+
+    /// Offered subprotocols, in order
+    fn offered(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+        headers
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|protocol| !protocol.is_empty())
+    }
+
+    fn token<'a>(marker: &str, headers: &'a HeaderMap) -> Option<&'a str> {
+        let mut offered = Self::offered(headers);
+        offered.find(|protocol| *protocol == marker)?;
+        offered.next()
+    }
+}
+
+impl<B> TokenExtractor<B> for WebSocketProtocol {
+    fn extract(&self, req: &Request<B>) -> Option<String> {
+        Self::token(&self.marker, req.headers()).map(String::from)
+    }
+
+    /// Subprotocols are left in place, [`echo`][WebSocketProtocol::echo] layer reads them
+    fn strip(&self, _: &mut HeaderMap) {}
+}
+
+/// [`Layer`][tower::Layer] completing subprotocol negotiation for [`WebSocketProtocol`],
+/// see [`WebSocketProtocol::echo`]
+///
+/// Responses other than `101 Switching Protocols`, or already selecting a subprotocol,
+/// are left as is.
+#[derive(Debug, Clone)]
+pub struct EchoSubprotocol {
+    marker: Arc<str>,
+}
+
+impl<S> tower::Layer<S> for EchoSubprotocol {
+    type Service = EchoSubprotocolService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        EchoSubprotocolService {
+            service,
+            marker: self.marker.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EchoSubprotocolService<S> {
+    service: S,
+    marker: Arc<str>,
+}
+
+impl<S, B, RB> Service<Request<B>> for EchoSubprotocolService<S>
+where
+    S: Service<Request<B>, Response = Response<RB>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = EchoSubprotocolFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let headers = req.headers();
+        let selected = WebSocketProtocol::token(&self.marker, headers).and_then(|token| {
+            let mut offered = WebSocketProtocol::offered(headers)
+                .filter(|protocol| *protocol != &*self.marker && *protocol != token);
+            let selected = offered.next().unwrap_or(&self.marker);
+            HeaderValue::from_str(selected).ok()
+        });
+        EchoSubprotocolFuture {
+            inner: self.service.call(req),
+            selected,
+        }
+    }
+}
+
+#[pin_project]
+pub struct EchoSubprotocolFuture<F> {
+    #[pin]
+    inner: F,
+    selected: Option<HeaderValue>,
+}
+
+impl<F, RB, E> Future for EchoSubprotocolFuture<F>
+where
+    F: Future<Output = Result<Response<RB>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut response = ready!(this.inner.poll(cx))?;
+        if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+            if let Some(selected) = this.selected.take() {
+                response
+                    .headers_mut()
+                    .entry(SEC_WEBSOCKET_PROTOCOL)
+                    .or_insert(selected);
+            }
+        }
+        Poll::Ready(Ok(response))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WebSocketProtocol;
+    use crate::{util, Layer, TokenExtractor};
+    use http::{header::SEC_WEBSOCKET_PROTOCOL, Request, Response, StatusCode};
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    #[tokio::test]
+    async fn websocket_protocol() {
+        let protocol = WebSocketProtocol::new();
+        let token = util::token(&util::claim(Some(100)));
+        let offered = format!("access_token, {}, graphql-ws", token);
+        let req = || {
+            Request::builder()
+                .header(SEC_WEBSOCKET_PROTOCOL, offered.as_str())
+                .body(())
+                .expect("Valid request")
+        };
+        assert_eq!(protocol.extract(&req()), Some(token.clone()));
+
+        let svc = service_fn(|_: Request<()>| async move {
+            let mut response = Response::new(());
+            *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+            Ok::<_, ()>(response)
+        });
+        let svc = ServiceBuilder::new()
+            .layer(protocol.echo())
+            .layer(Layer::new(util::in_place_decoder()).extractor(protocol))
+            .service(svc);
+        let response = svc.oneshot(req()).await.expect("Authenticated upgrade");
+        assert_eq!(
+            response.headers().get(SEC_WEBSOCKET_PROTOCOL),
+            Some(&"graphql-ws".parse().expect("Valid header"))
+        );
+    }
+}